@@ -11,6 +11,14 @@ declare module '../wasm-pkg/learning_letters_scoring.js' {
   export function init(): Promise<void>
   export default init
 
+  // Option and result objects, shared with the generated bindings
+  export type ScoringOptions = import('./wasm-scoring/types').ScoringOptions
+  export type Stroke = import('./wasm-scoring/types').Stroke
+  export type ScoringResult = import('./wasm-scoring/types').ScoringResult
+  export type ScoredDrawing = import('./wasm-scoring/types').ScoredDrawing
+  export type ScoreExplanation = import('./wasm-scoring/types').ScoreExplanation
+  export type StageTimings = import('./wasm-scoring/types').StageTimings
+
   export interface WasmScoringResult {
     score: number
     stars: number
@@ -19,6 +27,11 @@ declare module '../wasm-pkg/learning_letters_scoring.js' {
     accuracy: number
     similarity: number
     reference_image: Uint8Array
+    clipped: boolean
+    confidence: number
+    explanation: ScoreExplanation
+    timings: StageTimings | null
+    toJSON(): ScoringResult
   }
 
   export function score_drawing(
@@ -27,9 +40,173 @@ declare module '../wasm-pkg/learning_letters_scoring.js' {
     font_data: Uint8Array
   ): WasmScoringResult
 
+  export function score_drawing_async(
+    image_data: Uint8Array,
+    character: string,
+    font_data: Uint8Array,
+    strokes?: Stroke[] | null,
+    options?: ScoringOptions | null,
+    chunked?: boolean,
+    signal?: AbortSignal | null
+  ): Promise<WasmScoringResult>
+
   export function generate_reference_image(
     character: string,
     font_data: Uint8Array,
     size: number
   ): Uint8Array
+
+  export class WasmFont {
+    constructor(font_data: Uint8Array)
+    has_glyph(character: string): boolean
+    add_fallback(font: WasmFont): void
+    free(): void
+  }
+
+  export function score_drawing_with_font(
+    image_data: Uint8Array,
+    character: string,
+    font: WasmFont,
+    strokes?: Stroke[] | null,
+    options?: ScoringOptions | null
+  ): WasmScoringResult
+
+  export function score_drawing_js(
+    image_data: Uint8Array,
+    character: string,
+    font: WasmFont,
+    strokes?: Stroke[] | null,
+    options?: ScoringOptions | null
+  ): ScoredDrawing
+
+  export function score_drawings(
+    images: Uint8Array[],
+    characters: string,
+    font: WasmFont,
+    options?: ScoringOptions | null
+  ): Array<WasmScoringResult | string | Error>
+
+  /** Only exported by builds with the `parallel` feature */
+  export function initThreadPool(num_threads: number): Promise<void>
+
+  export class CancelToken {
+    constructor()
+    cancel(): void
+    readonly cancelled: boolean
+    free(): void
+  }
+
+  export class ScoringEngine {
+    constructor(font_data: Uint8Array, options?: ScoringOptions | null)
+    set_options(options?: ScoringOptions | null): void
+    set_random_source(next?: (() => number) | null): void
+    set_clock(now?: (() => number) | null): void
+    set_cancel_token(token: CancelToken): void
+    clear_cancel_token(): void
+    score(image_data: Uint8Array, character: string, strokes?: Stroke[] | null): WasmScoringResult
+    free(): void
+  }
+
+  export function generate_reference_image_with_font(
+    character: string,
+    font: WasmFont,
+    size: number,
+    options?: ScoringOptions | null
+  ): Uint8Array
+
+  export function generate_sized_reference_image(
+    character: string,
+    font: WasmFont,
+    width: number,
+    height: number,
+    options?: ScoringOptions | null
+  ): Uint8Array
+
+  export function generate_stroke_order_image(
+    character: string,
+    font: WasmFont,
+    size: number,
+    options?: ScoringOptions | null
+  ): Uint8Array
+
+  export function generate_stroke_animation(
+    character: string,
+    font: WasmFont,
+    size: number,
+    frame_count: number,
+    options?: ScoringOptions | null
+  ): Uint8Array[]
+
+  export function generate_stroke_animation_sheet(
+    character: string,
+    font: WasmFont,
+    size: number,
+    frame_count: number,
+    options?: ScoringOptions | null
+  ): Uint8Array
+
+  export function generate_worksheet(
+    characters: string,
+    font_data: Uint8Array,
+    rows: number,
+    cols: number,
+    options?: ScoringOptions | null
+  ): Uint8Array
+
+  export class WasmReferencePack {
+    readonly characters: string
+    contains(character: string): boolean
+    reference_image(character: string): Uint8Array | undefined
+    free(): void
+  }
+
+  export function build_reference_pack(
+    font_data: Uint8Array,
+    charset: string,
+    options?: ScoringOptions | null
+  ): Uint8Array
+
+  export function load_reference_pack(bytes: Uint8Array): WasmReferencePack
+
+  export class WasmReferenceHandle {
+    static prepare(character: string, font: WasmFont, options?: ScoringOptions | null): WasmReferenceHandle
+    static from_template(character: string, template_png: Uint8Array): WasmReferenceHandle
+    static from_svg_path(character: string, path: string, stroke_width?: number): WasmReferenceHandle
+    static deserialize(bytes: Uint8Array): WasmReferenceHandle
+    serialize(): Uint8Array
+    readonly character: string
+    reference_image(): Uint8Array
+    free(): void
+  }
+
+  export function generate_svg_path_reference_image(
+    path: string,
+    stroke_width: number | undefined,
+    size: number
+  ): Uint8Array
+
+  export function score_against_template(
+    image_data: Uint8Array,
+    character: string,
+    template_png: Uint8Array,
+    strokes?: Stroke[] | null,
+    options?: ScoringOptions | null
+  ): WasmScoringResult
+
+  export function handwriting_styles(): string[]
+
+  export function score_drawing_with_reference(
+    image_data: Uint8Array,
+    reference: WasmReferenceHandle,
+    strokes?: Stroke[] | null,
+    options?: ScoringOptions | null
+  ): WasmScoringResult
+
+  export function score_drawing_with_pack(
+    image_data: Uint8Array,
+    character: string,
+    pack: WasmReferencePack,
+    strokes?: Stroke[] | null,
+    options?: ScoringOptions | null
+  ): WasmScoringResult
 }
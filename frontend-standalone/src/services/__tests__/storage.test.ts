@@ -102,6 +102,25 @@ describe('progressStorage', () => {
       expect(saved?.stars).toBe(5)
       expect(saved?.attempts).toBe(2)
     })
+
+    it('flags a resubmitted drawing without counting it', () => {
+      progressStorage.record('A', 'Fredoka-Regular', 'freestyle', 75, 4, 'abc123')
+      const result = progressStorage.record('A', 'Fredoka-Regular', 'freestyle', 75, 4, 'abc123')
+
+      expect(result.isDuplicate).toBe(true)
+      expect(result.isNewHighScore).toBe(false)
+      expect(progressStorage.get('A', 'Fredoka-Regular', 'freestyle')?.attempts).toBe(1)
+    })
+
+    it('only remembers the most recent drawing hashes', () => {
+      for (let i = 0; i < 7; i++) {
+        progressStorage.record('A', 'Fredoka-Regular', 'freestyle', 50, 3, `hash${i}`)
+      }
+
+      const saved = progressStorage.get('A', 'Fredoka-Regular', 'freestyle')
+      expect(saved?.recentHashes).toEqual(['hash2', 'hash3', 'hash4', 'hash5', 'hash6'])
+      expect(progressStorage.record('A', 'Fredoka-Regular', 'freestyle', 50, 3, 'hash0').isDuplicate).toBe(false)
+    })
   })
 
   describe('getHighScore', () => {
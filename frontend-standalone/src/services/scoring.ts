@@ -6,10 +6,10 @@
  */
 
 // @ts-ignore - WASM module types
-import init, { score_drawing, generate_reference_image } from '../wasm-pkg/learning_letters_scoring.js'
+import init, { WasmFont, score_drawing_with_font, generate_reference_image_with_font } from '../wasm-pkg/learning_letters_scoring.js'
 
 let wasmInitialized = false
-const fontCache = new Map<string, Uint8Array>()
+const fontCache = new Map<string, WasmFont>()
 
 export interface ScoringResult {
   score: number
@@ -18,6 +18,7 @@ export interface ScoringResult {
   coverage: number
   accuracy: number
   similarity: number
+  drawingHash: string
   referenceImage: string
 }
 
@@ -32,9 +33,9 @@ export async function initScoring(): Promise<void> {
 }
 
 /**
- * Load a font file and cache it
+ * Load a font file and cache it, parsed once in the WASM module
  */
-async function loadFont(fontName: string): Promise<Uint8Array> {
+async function loadFont(fontName: string): Promise<WasmFont> {
   if (fontCache.has(fontName)) {
     return fontCache.get(fontName)!
   }
@@ -45,9 +46,9 @@ async function loadFont(fontName: string): Promise<Uint8Array> {
   }
 
   const buffer = await response.arrayBuffer()
-  const bytes = new Uint8Array(buffer)
-  fontCache.set(fontName, bytes)
-  return bytes
+  const font = new WasmFont(new Uint8Array(buffer))
+  fontCache.set(fontName, font)
+  return font
 }
 
 /**
@@ -82,9 +83,9 @@ export async function scoreDrawing(
   await initScoring()
 
   const pngBytes = await canvasToPngBytes(canvas)
-  const fontData = await loadFont(fontName)
+  const font = await loadFont(fontName)
 
-  const result = score_drawing(pngBytes, character, fontData)
+  const result = score_drawing_with_font(pngBytes, character, font, null)
 
   // Convert reference image bytes to data URL
   const refBytes = result.reference_image
@@ -98,6 +99,7 @@ export async function scoreDrawing(
     coverage: result.coverage,
     accuracy: result.accuracy,
     similarity: result.similarity,
+    drawingHash: result.drawing_hash,
     referenceImage
   }
 }
@@ -117,8 +119,8 @@ export async function getReferenceImage(
 ): Promise<string> {
   await initScoring()
 
-  const fontData = await loadFont(fontName)
-  const pngBytes = generate_reference_image(character, fontData, size)
+  const font = await loadFont(fontName)
+  const pngBytes = generate_reference_image_with_font(character, font, size)
 
   const blob = new Blob([pngBytes], { type: 'image/png' })
   return URL.createObjectURL(blob)
@@ -128,5 +130,6 @@ export async function getReferenceImage(
  * Preload fonts for faster scoring
  */
 export async function preloadFonts(fontNames: string[]): Promise<void> {
+  await initScoring()
   await Promise.all(fontNames.map(name => loadFont(name)))
 }
@@ -20,8 +20,13 @@ export interface ProgressEntry {
   stars: number
   attempts: number
   lastAttempt: number
+  /** Drawing hashes of the latest attempts, newest last */
+  recentHashes?: string[]
 }
 
+/** How many recent drawing hashes are kept per entry */
+const RECENT_HASH_LIMIT = 5
+
 export type ProgressStore = Record<string, ProgressEntry>
 
 function getProgressKey(character: string, font: string, mode: string): string {
@@ -52,30 +57,42 @@ export const progressStorage = {
 
   /**
    * Record a new attempt and update high score if necessary
+   *
+   * When a drawing hash is given and matches one of the recent attempts,
+   * the submission is a duplicate and is not counted.
    */
   record(
     character: string,
     font: string,
     mode: string,
     score: number,
-    stars: number
-  ): { isNewHighScore: boolean; previousHighScore: number | null } {
+    stars: number,
+    drawingHash?: string
+  ): { isNewHighScore: boolean; previousHighScore: number | null; isDuplicate: boolean } {
     const key = getProgressKey(character, font, mode)
     const store = this.getAll()
     const existing = store[key]
 
     const previousHighScore = existing?.highScore ?? null
+    if (drawingHash && existing?.recentHashes?.includes(drawingHash)) {
+      return { isNewHighScore: false, previousHighScore, isDuplicate: true }
+    }
+
     const isNewHighScore = !existing || score > existing.highScore
 
-    store[key] = {
+    const entry: ProgressEntry = {
       highScore: isNewHighScore ? score : existing.highScore,
       stars: isNewHighScore ? stars : existing.stars,
       attempts: (existing?.attempts || 0) + 1,
       lastAttempt: Date.now(),
     }
+    if (drawingHash) {
+      entry.recentHashes = [...(existing?.recentHashes || []), drawingHash].slice(-RECENT_HASH_LIMIT)
+    }
+    store[key] = entry
 
     localStorage.setItem(STORAGE_KEYS.PROGRESS, JSON.stringify(store))
-    return { isNewHighScore, previousHighScore }
+    return { isNewHighScore, previousHighScore, isDuplicate: false }
   },
 
   /**
@@ -0,0 +1,65 @@
+/*
+ * C interface to the Learning Letters scoring engine
+ *
+ * Build with the `ffi` feature (see the README). Options and strokes are
+ * the same JSON the JavaScript API takes; characters are Unicode scalar
+ * values. Functions returning LL_OK fill their output buffers, any other
+ * status leaves a UTF-8 error message (not NUL-terminated) in the first
+ * output buffer. Release every buffer with ll_buffer_free and every font
+ * with ll_font_free.
+ */
+
+#ifndef LEARNING_LETTERS_H
+#define LEARNING_LETTERS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define LL_OK 0
+#define LL_ERROR 1
+#define LL_GLYPH_MISSING 2
+#define LL_PANIC 3
+
+/* Bytes allocated by the engine */
+typedef struct {
+    uint8_t *data;
+    size_t len;
+} LlBuffer;
+
+/* A parsed font, kept between calls */
+typedef struct LlFont LlFont;
+
+void ll_buffer_free(LlBuffer buffer);
+
+/* TTF, OTF, WOFF or WOFF2 bytes; NULL on failure, with the message in error */
+LlFont *ll_font_load(const uint8_t *data, size_t len, LlBuffer *error);
+void ll_font_free(LlFont *font);
+
+/* Result JSON and the reference PNG; options_json and strokes_json may be NULL */
+int32_t ll_score_drawing(
+    const LlFont *font,
+    const uint8_t *image,
+    size_t image_len,
+    uint32_t character,
+    const char *options_json,
+    const char *strokes_json,
+    LlBuffer *result_json,
+    LlBuffer *reference_png);
+
+/* Reference PNG size x size pixels; options_json may be NULL */
+int32_t ll_generate_reference_image(
+    const LlFont *font,
+    uint32_t character,
+    uint32_t size,
+    const char *options_json,
+    LlBuffer *png);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
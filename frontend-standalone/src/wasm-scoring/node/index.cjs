@@ -0,0 +1,58 @@
+/**
+ * Node.js entry point for the scoring engine
+ *
+ * Wraps the `wasm-pack build --target nodejs` package (the `wasm:build:node`
+ * script) with helpers that take drawings and fonts as files or Buffers, so
+ * a server can rescore submissions with the same engine the app runs. Every
+ * export of the package is passed through as well.
+ */
+'use strict'
+
+const fs = require('node:fs/promises')
+const wasm = require('../../wasm-pkg-node/learning_letters_scoring.js')
+
+const fontCache = new Map()
+
+/**
+ * Load a font file once, parsed in the WASM module
+ *
+ * @param {string} path - TTF, OTF, WOFF or WOFF2 file
+ * @returns {Promise<wasm.WasmFont>}
+ */
+async function loadFont(path) {
+  if (!fontCache.has(path)) {
+    fontCache.set(path, new wasm.WasmFont(await fs.readFile(path)))
+  }
+  return fontCache.get(path)
+}
+
+/**
+ * Score drawing bytes against a character
+ *
+ * @param {Uint8Array} image - PNG bytes (a Buffer works as is)
+ * @param {string} character - The character that was drawn
+ * @param {wasm.WasmFont} font - Font from `loadFont` or `new WasmFont(bytes)`
+ * @param {object} [options] - Scoring options, as for `score_drawing_with_font`
+ * @param {object[]} [strokes] - Strokes the drawing was made with, if known
+ * @returns {wasm.WasmScoringResult}
+ */
+function scoreBuffer(image, character, font, options, strokes) {
+  return wasm.score_drawing_with_font(image, character, font, strokes ?? null, options ?? null)
+}
+
+/**
+ * Score a drawing file against a character
+ *
+ * @param {string} imagePath - PNG file of the drawing
+ * @param {string} character - The character that was drawn
+ * @param {string | wasm.WasmFont} font - Font file path, or a loaded font
+ * @param {object} [options] - Scoring options
+ * @param {object[]} [strokes] - Strokes the drawing was made with, if known
+ * @returns {Promise<wasm.WasmScoringResult>}
+ */
+async function scoreFile(imagePath, character, font, options, strokes) {
+  const loaded = typeof font === 'string' ? await loadFont(font) : font
+  return scoreBuffer(await fs.readFile(imagePath), character, loaded, options, strokes)
+}
+
+module.exports = { ...wasm, loadFont, scoreBuffer, scoreFile }
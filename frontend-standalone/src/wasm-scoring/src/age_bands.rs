@@ -0,0 +1,119 @@
+//! Age-banded scoring bundles
+//!
+//! Built-in option sets for young writers, selected with a single
+//! `age_band`. Younger bands get wider tolerances, lower star thresholds
+//! and more slack on stroke counts. Bundles carry their rationale so the
+//! app can show which leniency is applied.
+
+use crate::options::{ScoringOptions, ScoreWeights, StarThresholds, TimeOptions};
+use serde::{Serialize, Deserialize};
+
+/// Age band of the learner
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AgeBand {
+    #[serde(rename = "3-4")]
+    Ages3To4,
+    #[serde(rename = "5-6")]
+    Ages5To6,
+    #[serde(rename = "7-8")]
+    Ages7To8,
+}
+
+/// A bundle's options along with what they are for
+#[derive(Debug, Clone, Serialize)]
+pub struct AgeBandBundle {
+    pub age_band: AgeBand,
+    /// Short description for display
+    pub label: &'static str,
+    /// Why the values differ from the defaults
+    pub rationale: &'static str,
+    pub options: ScoringOptions,
+}
+
+impl AgeBand {
+    pub const ALL: [AgeBand; 3] = [AgeBand::Ages3To4, AgeBand::Ages5To6, AgeBand::Ages7To8];
+
+    /// Scoring options for the band
+    pub fn options(self) -> ScoringOptions {
+        let options = match self {
+            AgeBand::Ages3To4 => ScoringOptions {
+                accuracy_tolerance: 5.0,
+                accuracy_falloff: 10.0,
+                weights: ScoreWeights {
+                    coverage: 0.35,
+                    accuracy: 0.20,
+                    similarity: 0.30,
+                    structure: 0.15,
+                    legibility: 0.02,
+                },
+                min_skeleton_coverage: 0.3,
+                stars: StarThresholds { five: 70, four: 55, three: 40, two: 20 },
+                extra_strokes_allowed: 2,
+                time: TimeOptions { max_penalty: 0.0, ..TimeOptions::default() },
+                ..ScoringOptions::default()
+            },
+            AgeBand::Ages5To6 => ScoringOptions {
+                accuracy_tolerance: 4.0,
+                accuracy_falloff: 8.0,
+                min_skeleton_coverage: 0.35,
+                stars: StarThresholds { five: 75, four: 60, three: 45, two: 25 },
+                extra_strokes_allowed: 1,
+                ..ScoringOptions::default()
+            },
+            AgeBand::Ages7To8 => ScoringOptions::default(),
+        };
+        ScoringOptions { age_band: Some(self), ..options }
+    }
+
+    /// The band's options with their description
+    pub fn bundle(self) -> AgeBandBundle {
+        let (label, rationale) = match self {
+            AgeBand::Ages3To4 => (
+                "Ages 3-4",
+                "Pre-writers are still developing pencil control: strokes may wander \
+                 well off the letter, overall shape counts more than precise placement, \
+                 extra strokes are expected and drawing speed is not penalized.",
+            ),
+            AgeBand::Ages5To6 => (
+                "Ages 5-6",
+                "Early writers form most letters but with uneven lines: tolerances and \
+                 star thresholds sit between the preschool and school-age bundles, and \
+                 one extra stroke is allowed.",
+            ),
+            AgeBand::Ages7To8 => (
+                "Ages 7-8",
+                "Fluent writers are scored with the standard options.",
+            ),
+        };
+        AgeBandBundle {
+            age_band: self,
+            label,
+            rationale,
+            options: self.options(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundles_get_stricter_with_age() {
+        let options: Vec<ScoringOptions> = AgeBand::ALL.iter().map(|band| band.options()).collect();
+
+        for pair in options.windows(2) {
+            assert!(pair[0].accuracy_tolerance >= pair[1].accuracy_tolerance);
+            assert!(pair[0].stars.five <= pair[1].stars.five);
+            assert!(pair[0].extra_strokes_allowed >= pair[1].extra_strokes_allowed);
+        }
+        assert!(options.iter().zip(AgeBand::ALL).all(|(o, band)| o.age_band == Some(band)));
+    }
+
+    #[test]
+    fn test_age_band_names() {
+        assert_eq!(serde_json::to_value(AgeBand::Ages5To6).unwrap(), "5-6");
+        assert_eq!(serde_json::from_value::<AgeBand>("3-4".into()).unwrap(), AgeBand::Ages3To4);
+        assert_eq!(AgeBand::Ages7To8.bundle().label, "Ages 7-8");
+    }
+}
@@ -0,0 +1,139 @@
+//! Score drawings from the command line
+//!
+//! Scores image files against a character and font and prints one JSON
+//! line per file, so exported drawings can be evaluated in a shell or a CI
+//! job without a browser:
+//!
+//! ```text
+//! score-drawing --font Fredoka-Regular.ttf --character a drawings/*.png
+//! ```
+//!
+//! Each line is `{"file": ..., "result": {...}}`, or `{"file": ..., "error": ...}`
+//! when a file could not be scored; the exit status is 1 if any failed.
+
+use learning_letters_scoring::{Font, ScoringOptions, score_drawing};
+use serde_json::json;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: score-drawing --font FONT --character CHAR [--options FILE] IMAGE...
+
+  --font FONT         TTF, OTF, WOFF or WOFF2 font to render the reference with
+  --character CHAR    Character every drawing should be
+  --options FILE      JSON scoring options, as passed to score_drawing in JavaScript
+  IMAGE...            Drawings to score (PNG, or JPEG and WebP with those features)";
+
+/// Parsed command line
+#[derive(Debug, PartialEq)]
+struct Args {
+    font: String,
+    character: char,
+    options: Option<String>,
+    images: Vec<String>,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let (mut font, mut character, mut options) = (None, None, None);
+    let mut images = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--font" => font = Some(value("--font")?),
+            "--character" => {
+                let text = value("--character")?;
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => character = Some(c),
+                    _ => return Err(format!("--character must be one character, not \"{}\"", text)),
+                }
+            }
+            "--options" => options = Some(value("--options")?),
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            _ => images.push(arg),
+        }
+    }
+
+    if images.is_empty() {
+        return Err("No images to score".to_string());
+    }
+    Ok(Args {
+        font: font.ok_or("--font is required")?,
+        character: character.ok_or("--character is required")?,
+        options,
+        images,
+    })
+}
+
+/// The options file, or the defaults without one
+fn load_options(path: Option<&str>) -> Result<ScoringOptions, String> {
+    let Some(path) = path else {
+        return Ok(ScoringOptions::default());
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let value = serde_json::from_str(&text).map_err(|e| format!("Invalid JSON in {}: {}", path, e))?;
+    ScoringOptions::from_json(value)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let setup = std::fs::read(&args.font)
+        .map_err(|e| format!("Failed to read {}: {}", args.font, e))
+        .and_then(|data| Font::from_bytes(&data).map_err(|e| e.to_string()))
+        .and_then(|font| Ok((font, load_options(args.options.as_deref())?)));
+    let (font, options) = match setup {
+        Ok(setup) => setup,
+        Err(message) => {
+            eprintln!("{}", message);
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut failed = false;
+    for image in &args.images {
+        let scored = std::fs::read(image)
+            .map_err(|e| format!("Failed to read {}: {}", image, e))
+            .and_then(|data| score_drawing(&data, args.character, &font, None, &options).map_err(|e| e.to_string()));
+        let line = match scored {
+            Ok(scored) => json!({ "file": image, "result": scored.result }),
+            Err(error) => {
+                failed = true;
+                json!({ "file": image, "error": error })
+            }
+        };
+        println!("{}", line);
+    }
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&["--font", "f.ttf", "a.png", "--character", "é", "b.png"]).unwrap();
+        assert_eq!(args, Args {
+            font: "f.ttf".to_string(),
+            character: 'é',
+            options: None,
+            images: vec!["a.png".to_string(), "b.png".to_string()],
+        });
+
+        assert!(parse(&["--font", "f.ttf", "--character", "ab", "a.png"]).is_err());
+        assert!(parse(&["--font", "f.ttf", "--character", "a"]).is_err());
+        assert!(parse(&["--character", "a", "a.png"]).is_err());
+        assert!(parse(&["--font"]).is_err());
+        assert!(parse(&["--size", "3", "a.png"]).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+//! Bit-packed binary images
+//!
+//! Ink masks, skeletons and thickness-normalized strokes are stored one bit
+//! per pixel in 64-bit words, each row starting on a fresh word. That is an
+//! eighth of the memory of a `Vec<bool>`, and dilation and erosion work on
+//! 64 pixels at a time. Bits past the right edge of a row are always clear.
+
+use crate::simd;
+
+/// Writes one mask row's horizontal pass
+type RowPass = fn(&mut [u64], &[u64]);
+/// Writes the combination of three rows
+type RowCombine = fn(&mut [u64], &[u64], &[u64], &[u64]);
+
+/// A binary image, one bit per pixel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitImage {
+    width: usize,
+    height: usize,
+    /// Words per row
+    stride: usize,
+    words: Vec<u64>,
+}
+
+impl BitImage {
+    /// An image with no pixels set
+    pub fn new(width: usize, height: usize) -> Self {
+        let stride = width.div_ceil(64);
+        Self { width, height, stride, words: vec![0; stride * height] }
+    }
+
+    /// An image with the pixels set where `f(x, y)` is true
+    pub fn from_fn(width: usize, height: usize, mut f: impl FnMut(usize, usize) -> bool) -> Self {
+        let mut image = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                if f(x, y) {
+                    image.set(x, y, true);
+                }
+            }
+        }
+        image
+    }
+
+    /// An image from one bool per pixel, in row order
+    #[cfg(test)]
+    pub fn from_bools(bits: &[bool], width: usize, height: usize) -> Self {
+        Self::from_fn(width, height, |x, y| bits[y * width + x])
+    }
+
+    /// Ink mask of intensities in row order (0 = ink, 1 = paper): the
+    /// pixels darker than mid-gray
+    pub fn ink(values: &[f32], width: usize, height: usize) -> Self {
+        Self::from_fn(width, height, |x, y| values[y * width + x] < 0.5)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.words[y * self.stride + x / 64] >> (x % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, value: bool) {
+        let word = &mut self.words[y * self.stride + x / 64];
+        if value {
+            *word |= 1 << (x % 64);
+        } else {
+            *word &= !(1 << (x % 64));
+        }
+    }
+
+    /// Pixel at a row-order index (`y * width + x`)
+    #[cfg(test)]
+    pub fn at(&self, index: usize) -> bool {
+        self.get(index % self.width, index / self.width)
+    }
+
+    /// Set the pixel at a row-order index (`y * width + x`)
+    pub fn set_at(&mut self, index: usize, value: bool) {
+        self.set(index % self.width, index / self.width, value);
+    }
+
+    /// Whether the pixel at a signed position is set; outside is clear
+    pub fn get_signed(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height && self.get(x as usize, y as usize)
+    }
+
+    /// Number of set pixels
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Whether no pixel is set
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+
+    /// Number of pixels set in both images
+    pub fn count_and(&self, other: &BitImage) -> usize {
+        self.words.iter().zip(&other.words).map(|(a, b)| (a & b).count_ones() as usize).sum()
+    }
+
+    /// Number of pixels set in either image
+    pub fn count_or(&self, other: &BitImage) -> usize {
+        self.words.iter().zip(&other.words).map(|(a, b)| (a | b).count_ones() as usize).sum()
+    }
+
+    /// Set pixels as `(x, y)`, in row order
+    pub fn ones(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.words.iter().enumerate().flat_map(move |(i, &word)| {
+            let (y, base) = (i / self.stride, (i % self.stride) * 64);
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some((base + bit, y))
+            })
+        })
+    }
+
+    /// Row-order indices (`y * width + x`) of the set pixels
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ones().map(move |(x, y)| y * self.width + x)
+    }
+
+    /// Every pixel as a bool, in row order
+    #[cfg(test)]
+    pub fn to_bools(&self) -> Vec<bool> {
+        (0..self.width * self.height).map(|i| self.at(i)).collect()
+    }
+
+    /// The image with every pixel flipped
+    pub fn inverted(&self) -> Self {
+        let mut image = self.clone();
+        for word in &mut image.words {
+            *word = !*word;
+        }
+        image.clear_padding();
+        image
+    }
+
+    /// Grow the set pixels by a 3x3 square, `iterations` times
+    pub fn dilate(&self, iterations: u32) -> Self {
+        let mut current = self.clone();
+        for _ in 0..iterations {
+            current = current.step(simd::spread, simd::or3);
+        }
+        current
+    }
+
+    /// Keep only pixels whose whole 3x3 neighbourhood is set, `iterations`
+    /// times; pixels beyond the border count as clear
+    pub fn erode(&self, iterations: u32) -> Self {
+        let mut current = self.clone();
+        for _ in 0..iterations {
+            current = current.step(simd::shrink, simd::and3);
+        }
+        current
+    }
+
+    /// Combine each row's horizontal pass with the rows above and below;
+    /// rows beyond the border are clear
+    fn step(&self, horizontal: RowPass, vertical: RowCombine) -> Self {
+        let stride = self.stride;
+        if stride == 0 {
+            return self.clone();
+        }
+        let mut rows = vec![0; self.words.len()];
+        for (out, row) in rows.chunks_mut(stride).zip(self.words.chunks(stride)) {
+            horizontal(out, row);
+        }
+        let clear = vec![0; stride];
+        let mut result = Self::new(self.width, self.height);
+        for (y, out) in result.words.chunks_mut(stride).enumerate() {
+            let above = if y > 0 { &rows[(y - 1) * stride..y * stride] } else { &clear[..] };
+            let below = if y + 1 < self.height { &rows[(y + 1) * stride..(y + 2) * stride] } else { &clear[..] };
+            vertical(out, above, &rows[y * stride..(y + 1) * stride], below);
+        }
+        result.clear_padding();
+        result
+    }
+
+    fn clear_padding(&mut self) {
+        let used = self.width % 64;
+        if used == 0 || self.stride == 0 {
+            return;
+        }
+        let mask = (1u64 << used) - 1;
+        for y in 0..self.height {
+            self.words[y * self.stride + self.stride - 1] &= mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference 3x3 morphology on plain bools
+    fn naive(image: &BitImage, all: bool) -> BitImage {
+        BitImage::from_fn(image.width(), image.height(), |x, y| {
+            let mut neighbours = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (x as i32 + dx, y as i32 + dy)));
+            if all {
+                neighbours.all(|(nx, ny)| image.get_signed(nx, ny))
+            } else {
+                neighbours.any(|(nx, ny)| image.get_signed(nx, ny))
+            }
+        })
+    }
+
+    #[test]
+    fn test_pixels_and_counts() {
+        let mut image = BitImage::new(70, 3);
+        image.set(0, 0, true);
+        image.set(63, 1, true);
+        image.set(64, 1, true);
+        image.set_at(2 * 70 + 69, true);
+        assert!(image.get(64, 1) && image.at(70 + 63) && !image.get(1, 0));
+        assert_eq!(image.count(), 4);
+        assert_eq!(image.ones().collect::<Vec<_>>(), vec![(0, 0), (63, 1), (64, 1), (69, 2)]);
+        assert_eq!(image.inverted().count(), 70 * 3 - 4);
+        image.set(63, 1, false);
+        assert_eq!(image.count(), 3);
+        assert!(BitImage::new(70, 3).is_empty());
+    }
+
+    #[test]
+    fn test_word_morphology_matches_naive() {
+        // Spans a word boundary and touches every border
+        let image = BitImage::from_fn(130, 9, |x, y| (x * 31 + y * 17) % 11 < 4 || x == 63 || x == 129);
+        for all in [false, true] {
+            let mut expected = image.clone();
+            for _ in 0..3 {
+                expected = naive(&expected, all);
+            }
+            let actual = if all { image.erode(3) } else { image.dilate(3) };
+            assert_eq!(actual, expected, "{}", if all { "erode" } else { "dilate" });
+        }
+    }
+}
@@ -0,0 +1,190 @@
+//! Reusable scoring engine
+//!
+//! Every scoring call fills a few hundred kilobytes of pixel buffers: the
+//! registered drawing, its distance fields and stroke orientations. A
+//! `ScoringEngine` owns its font, options and those buffers, so scoring
+//! attempt after attempt reuses the same memory instead of allocating
+//! afresh and growing the WASM heap. References still come from the shared
+//! per-thread cache.
+//!
+//! An engine can also be given its own random source for practice variation
+//! and clock for `with_timings`, e.g. to replay a recorded session, and a
+//! `CancelToken` to stop an attempt that is no longer wanted.
+
+use crate::error::ScoringError;
+use crate::font::Font;
+use crate::image_ops::{CancelToken, ThinningScope, check_cancelled};
+use crate::options::ScoringOptions;
+use crate::pipeline::ScoringBuffers;
+use crate::practice::{RandomSource, ReferenceVariation};
+use crate::reference::cached_reference;
+use crate::scoring::{REFERENCE_SIZE, score_against_reference_with_buffers};
+use crate::strokes::Stroke;
+use crate::timing::{self, Clock, Recording, Stage};
+use crate::ScoredDrawing;
+use std::rc::Rc;
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Stateful scorer for back-to-back attempts with one font and options
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct ScoringEngine {
+    font: Font,
+    options: ScoringOptions,
+    buffers: ScoringBuffers,
+    random_source: Option<Box<dyn RandomSource>>,
+    clock: Option<Rc<dyn Clock>>,
+    cancel: Option<CancelToken>,
+}
+
+impl ScoringEngine {
+    pub fn with_options(font: Font, options: ScoringOptions) -> Self {
+        Self { font, options, buffers: ScoringBuffers::default(), random_source: None, clock: None, cancel: None }
+    }
+
+    pub fn options(&self) -> &ScoringOptions {
+        &self.options
+    }
+
+    /// Replace the options used for the following attempts
+    pub fn set_options(&mut self, options: ScoringOptions) {
+        self.options = options;
+    }
+
+    /// Draw each following attempt's practice variation from `source`, in
+    /// turn, instead of from `practice.seed`; `None` restores the seed.
+    /// Without `practice` in the options nothing is drawn.
+    pub fn set_random_source(&mut self, source: Option<Box<dyn RandomSource>>) {
+        self.random_source = source;
+    }
+
+    /// Time the stages of `with_timings` with `clock` instead of the
+    /// platform's monotonic clock; `None` restores it
+    pub fn set_clock(&mut self, clock: Option<Box<dyn Clock>>) {
+        self.clock = clock.map(Rc::from);
+    }
+
+    /// Stop attempts with `ScoringError::Cancelled` once `token` is
+    /// cancelled, between thinning passes and scoring stages; the token
+    /// stays cancelled, so set a fresh one for the next attempt
+    pub fn set_cancel_token(&mut self, token: Option<CancelToken>) {
+        self.cancel = token;
+    }
+
+    /// Score a drawing as `score_drawing` does, reusing the engine's buffers
+    pub fn score(
+        &mut self,
+        image_data: &[u8],
+        character: char,
+        strokes: Option<&[Stroke]>,
+    ) -> Result<ScoredDrawing, ScoringError> {
+        let recording = self.options.with_timings.then(|| Recording::start(self.clock.clone())).flatten();
+        let _thinning = ThinningScope::enter(self.options.thinning, self.options.input_limits.thinning, self.cancel.clone());
+        check_cancelled()?;
+        let variation = self.variation(character);
+        let reference = timing::time(Stage::Reference, || {
+            cached_reference(&variation, &self.font, &self.options, REFERENCE_SIZE)
+        })?;
+
+        let mut scored = score_against_reference_with_buffers(
+            image_data, character, &variation, &reference, strokes, &self.options, &mut self.buffers,
+        )?;
+        if let Some(recording) = recording {
+            scored.result.timings = Some(recording.finish());
+        }
+        Ok(scored)
+    }
+
+    fn variation(&mut self, character: char) -> ReferenceVariation {
+        match (&self.options.practice, &mut self.random_source) {
+            (Some(practice), Some(source)) => ReferenceVariation::choose(character, practice, source.as_mut()),
+            _ => self.options.reference_variation(character),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::{generate_reference_image_internal, score_drawing_internal};
+
+    fn test_font() -> Font {
+        Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap()
+    }
+
+    #[test]
+    fn test_engine_matches_one_off_scoring() {
+        let font = test_font();
+        let options = ScoringOptions::default();
+        let mut engine = ScoringEngine::with_options(font.clone(), options.clone());
+
+        // Different letters back to back, so each call reuses buffers the
+        // previous one filled
+        for character in ['a', 'T', 'a', 'e'] {
+            let png = generate_reference_image_internal(character, &font, 300, &options).unwrap();
+            let expected = score_drawing_internal(&png, character, &font, None, &options).unwrap();
+            let actual = engine.score(&png, character, None).unwrap();
+            assert_eq!(
+                serde_json::to_string(&actual.result).unwrap(),
+                serde_json::to_string(&expected.result).unwrap(),
+                "{}", character,
+            );
+        }
+    }
+
+    #[test]
+    fn test_engine_random_source_and_clock() {
+        // Draws always at the top of their range, and a clock moving 1 ms
+        // each time it is read
+        struct Highest;
+        impl RandomSource for Highest {
+            fn next_u64(&mut self) -> u64 {
+                u64::MAX
+            }
+        }
+        struct Ticking(std::cell::Cell<f64>);
+        impl Clock for Ticking {
+            fn now_ms(&self) -> f64 {
+                self.0.set(self.0.get() + 1.0);
+                self.0.get()
+            }
+        }
+
+        let font = test_font();
+        let png = generate_reference_image_internal('k', &font, 300, &ScoringOptions::default()).unwrap();
+        let practice = |seed| ScoringOptions {
+            practice: Some(crate::PracticeOptions { seed, ..Default::default() }),
+            with_timings: true,
+            ..Default::default()
+        };
+        let mut engine = ScoringEngine::with_options(font, practice(1));
+        let score = |engine: &mut ScoringEngine, seed| {
+            engine.set_options(practice(seed));
+            engine.score(&png, 'k', None).unwrap()
+        };
+        assert_ne!(score(&mut engine, 1).reference_image, score(&mut engine, 2).reference_image);
+
+        engine.set_random_source(Some(Box::new(Highest)));
+        engine.set_clock(Some(Box::new(Ticking(std::cell::Cell::new(0.0)))));
+        let drawn = [score(&mut engine, 1), score(&mut engine, 2)];
+        assert_eq!(drawn[0].reference_image, drawn[1].reference_image);
+        // Whole milliseconds, one per clock read
+        let total = drawn[1].result.timings.unwrap().total_ms;
+        assert!(total > 0.0 && total.fract() == 0.0, "total {}", total);
+    }
+
+    #[test]
+    fn test_engine_cancel_token() {
+        let font = test_font();
+        let png = generate_reference_image_internal('a', &font, 300, &ScoringOptions::default()).unwrap();
+        let mut engine = ScoringEngine::with_options(font, ScoringOptions::default());
+        let token = CancelToken::new();
+        engine.set_cancel_token(Some(token.clone()));
+        assert!(engine.score(&png, 'a', None).is_ok());
+
+        token.cancel();
+        assert_eq!(engine.score(&png, 'a', None).err(), Some(ScoringError::Cancelled));
+        engine.set_cancel_token(None);
+        assert!(engine.score(&png, 'a', None).is_ok());
+    }
+}
@@ -0,0 +1,39 @@
+//! Errors returned by the Rust API
+
+use std::fmt;
+
+/// Why a drawing could not be scored or a reference rendered
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScoringError {
+    /// The font has no glyph for the character, so the reference would be
+    /// the blank `.notdef` box and every drawing would score zero
+    GlyphMissing(char),
+    /// The call's `CancelToken` was cancelled before it finished
+    Cancelled,
+    /// Any other failure: undecodable images, unparsable fonts, bad input
+    Invalid(String),
+}
+
+impl fmt::Display for ScoringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScoringError::GlyphMissing(character) => write!(f, "Font has no glyph for '{}'", character),
+            ScoringError::Cancelled => f.write_str("Scoring was cancelled"),
+            ScoringError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for ScoringError {}
+
+impl From<String> for ScoringError {
+    fn from(message: String) -> Self {
+        ScoringError::Invalid(message)
+    }
+}
+
+impl From<&str> for ScoringError {
+    fn from(message: &str) -> Self {
+        ScoringError::Invalid(message.to_string())
+    }
+}
@@ -0,0 +1,209 @@
+//! Critical letter details
+//!
+//! Small parts that barely move the pixel metrics but make a letter wrong
+//! when left out: the dot on i and j, the crossbar on t and f, and the
+//! tail on Q. Each check runs on both the drawing and the reference, and a
+//! detail is only reported when the reference glyph has it.
+
+use crate::bit_image::BitImage;
+use crate::image_ops::connected_components;
+use crate::scoring::{frame_area, frame_pixels, frame_side, normalize_line_thickness};
+use serde::{Serialize, Deserialize};
+
+// Parts smaller than this (normalized pixels) are specks, not dots
+const DOT_MIN_AREA: usize = 6;
+
+// Crossbar search: rows merged on each side so a slanted bar still forms a
+// run, the band of the letter's height searched, and how far past the stem
+// center the bar must reach on both sides, as a fraction of the height
+const CROSSBAR_BAND: u32 = 3;
+const CROSSBAR_ROWS: (f32, f32) = (0.1, 0.7);
+const CROSSBAR_MIN_REACH: f32 = 0.08;
+
+// Tail: ink must reach this much further down-right of the centroid than
+// it does in a typical direction
+const TAIL_SECTORS: usize = 16;
+const TAIL_MIN_EXTENT_RATIO: f32 = 1.2;
+
+/// A detail a letter is not complete without
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CriticalFeature {
+    /// The dot above i and j
+    Dot,
+    /// The horizontal bar crossing the stem of t and f
+    Crossbar,
+    /// The tail crossing the bowl of Q
+    Tail,
+}
+
+impl CriticalFeature {
+    /// The critical detail of a character, if it has one
+    pub fn for_character(character: char) -> Option<Self> {
+        match character {
+            'i' | 'j' => Some(CriticalFeature::Dot),
+            't' | 'f' => Some(CriticalFeature::Crossbar),
+            'Q' => Some(CriticalFeature::Tail),
+            _ => None,
+        }
+    }
+
+    /// Feedback code used when the detail is missing
+    pub fn missing_code(self) -> &'static str {
+        match self {
+            CriticalFeature::Dot => "missing_dot",
+            CriticalFeature::Crossbar => "missing_crossbar",
+            CriticalFeature::Tail => "missing_tail",
+        }
+    }
+
+    /// Feedback for the learner when the detail is missing
+    pub fn reminder(self, character: char) -> String {
+        match self {
+            CriticalFeature::Dot => format!("Don't forget the dot on the {}!", character),
+            CriticalFeature::Crossbar => format!("Don't forget to cross the {}!", character),
+            CriticalFeature::Tail => format!("Don't forget the little tail on the {}!", character),
+        }
+    }
+
+    /// Whether a normalized image shows the detail
+    fn present(self, image: &[f32]) -> bool {
+        let size = frame_side(image);
+        let binary = BitImage::ink(image, size, size);
+        match self {
+            CriticalFeature::Dot => has_dot(&binary),
+            CriticalFeature::Crossbar => has_crossbar(&binary),
+            CriticalFeature::Tail => has_tail(&binary),
+        }
+    }
+}
+
+/// Check a character's critical detail in the drawing
+///
+/// Both images are normalized drawings (0 = ink). Returns `None` when the
+/// character has no such detail or the reference glyph doesn't show it.
+pub fn check_critical_feature(character: char, drawn: &[f32], reference: &[f32]) -> Option<(CriticalFeature, bool)> {
+    let feature = CriticalFeature::for_character(character)?;
+    if !feature.present(reference) {
+        return None;
+    }
+    Some((feature, feature.present(drawn)))
+}
+
+/// A separate part centered above the top of the main stroke
+fn has_dot(binary: &BitImage) -> bool {
+    let mut parts = connected_components(binary);
+    parts.retain(|part| part.area >= frame_area(DOT_MIN_AREA, binary.width()));
+    let Some((main, rest)) = parts.split_first() else {
+        return false;
+    };
+    rest.iter().any(|part| (part.min_y + part.max_y) / 2 < main.min_y)
+}
+
+/// A horizontal run through the stem reaching well past it on both sides
+fn has_crossbar(binary: &BitImage) -> bool {
+    let size = binary.width();
+    let ink = normalize_line_thickness(binary, frame_pixels(5, size), true);
+    let (mut min_y, mut max_y) = (size, 0);
+    let mut column_ink = vec![0usize; size];
+    for (x, y) in ink.ones() {
+        min_y = min_y.min(y);
+        max_y = max_y.max(y);
+        column_ink[x] += 1;
+    }
+    if min_y > max_y {
+        return false;
+    }
+
+    // Stem center: middle of the columns carrying nearly the most ink
+    let most = *column_ink.iter().max().unwrap();
+    let stem_columns: Vec<usize> = (0..size).filter(|&x| column_ink[x] * 5 >= most * 4).collect();
+    let stem_x = (stem_columns[0] + stem_columns[stem_columns.len() - 1]) / 2;
+
+    let height = (max_y - min_y + 1) as f32;
+    let min_reach = (CROSSBAR_MIN_REACH * height).ceil() as usize;
+    let first_row = min_y + (CROSSBAR_ROWS.0 * height) as usize;
+    let last_row = min_y + (CROSSBAR_ROWS.1 * height) as usize;
+
+    let band = frame_pixels(CROSSBAR_BAND, size) as usize;
+    (first_row..=last_row).any(|y| {
+        let rows = y.saturating_sub(band)..=(y + band).min(size - 1);
+        let inked = |x: usize| rows.clone().any(|row| ink.get(x, row));
+        if !inked(stem_x) {
+            return false;
+        }
+        let left = (0..stem_x).rev().take_while(|&x| inked(x)).count();
+        let right = (stem_x + 1..size).take_while(|&x| inked(x)).count();
+        left >= min_reach && right >= min_reach
+    })
+}
+
+/// Ink reaching unusually far down-right of the centroid
+fn has_tail(binary: &BitImage) -> bool {
+    let points: Vec<(f32, f32)> = binary.ones().map(|(x, y)| (x as f32, y as f32)).collect();
+    if points.is_empty() {
+        return false;
+    }
+    let cx = points.iter().map(|p| p.0).sum::<f32>() / points.len() as f32;
+    let cy = points.iter().map(|p| p.1).sum::<f32>() / points.len() as f32;
+
+    // Farthest ink in each direction (y grows downwards)
+    let mut extent = [0.0f32; TAIL_SECTORS];
+    for &(x, y) in &points {
+        let angle = (y - cy).atan2(x - cx).rem_euclid(std::f32::consts::TAU);
+        let sector = ((angle / std::f32::consts::TAU * TAIL_SECTORS as f32) as usize).min(TAIL_SECTORS - 1);
+        extent[sector] = extent[sector].max(((x - cx).powi(2) + (y - cy).powi(2)).sqrt());
+    }
+
+    let mut sorted = extent;
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let typical = sorted[TAIL_SECTORS / 2];
+    // Sectors 1 and 2 span 22.5-67.5 degrees below the horizontal, to the right
+    let tail = extent[1].max(extent[2]);
+    typical > 0.0 && tail >= TAIL_MIN_EXTENT_RATIO * typical
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_frames::{blank, fill, ring};
+
+    #[test]
+    fn test_dot_on_i() {
+        let mut stem = blank();
+        fill(&mut stem, 60..68, 40..115);
+        let mut dotted = stem.clone();
+        fill(&mut dotted, 60..68, 15..24);
+
+        assert_eq!(check_critical_feature('i', &dotted, &dotted), Some((CriticalFeature::Dot, true)));
+        assert_eq!(check_critical_feature('i', &stem, &dotted), Some((CriticalFeature::Dot, false)));
+        // No dot in the reference: nothing to check
+        assert_eq!(check_critical_feature('i', &stem, &stem), None);
+        assert_eq!(check_critical_feature('l', &stem, &dotted), None);
+    }
+
+    #[test]
+    fn test_crossbar_on_t() {
+        let mut stem = blank();
+        fill(&mut stem, 60..68, 14..114);
+        // A foot to the right only, like the hook of a t
+        fill(&mut stem, 60..90, 106..114);
+        let mut crossed = stem.clone();
+        fill(&mut crossed, 40..90, 40..47);
+
+        assert_eq!(check_critical_feature('t', &crossed, &crossed), Some((CriticalFeature::Crossbar, true)));
+        assert_eq!(check_critical_feature('t', &stem, &crossed), Some((CriticalFeature::Crossbar, false)));
+    }
+
+    #[test]
+    fn test_tail_on_q() {
+        let ring = ring(34.0..42.0);
+        let mut q = ring.clone();
+        for i in 0..30 {
+            fill(&mut q, 84 + i..90 + i, 84 + i..90 + i);
+        }
+
+        assert_eq!(check_critical_feature('Q', &q, &q), Some((CriticalFeature::Tail, true)));
+        assert_eq!(check_critical_feature('Q', &ring, &q), Some((CriticalFeature::Tail, false)));
+    }
+}
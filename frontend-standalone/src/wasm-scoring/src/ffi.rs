@@ -0,0 +1,251 @@
+//! C interface for embedding the engine in native apps
+//!
+//! The mobile apps' Swift and Kotlin layers call these through
+//! `include/learning_letters.h` to score without a WebView. Options and
+//! strokes go in as JSON strings, the same JSON the JavaScript API takes,
+//! and results come back as JSON and PNG in buffers the engine allocates.
+//!
+//! Functions returning `LL_OK` (0) fill their output buffers; any other
+//! status puts a UTF-8 error message in the first output buffer instead.
+//! Every buffer handed out must be released with `ll_buffer_free`, and
+//! every font with `ll_font_free`. Panics are caught and reported as
+//! `LL_PANIC` rather than unwinding into the caller.
+
+use crate::{Font, ScoringError, ScoringOptions, Stroke, generate_reference_image, score_drawing};
+use std::ffi::{CStr, c_char};
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+/// Success
+pub const LL_OK: i32 = 0;
+/// Bad arguments, undecodable input, or the engine rejected the request
+pub const LL_ERROR: i32 = 1;
+/// The font has no glyph for the character
+pub const LL_GLYPH_MISSING: i32 = 2;
+/// The engine panicked; the message says where
+pub const LL_PANIC: i32 = 3;
+
+/// Bytes allocated by the engine
+#[repr(C)]
+pub struct LlBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl LlBuffer {
+    #[cfg(test)]
+    fn empty() -> Self {
+        Self { data: std::ptr::null_mut(), len: 0 }
+    }
+
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let mut bytes = bytes.into_boxed_slice();
+        let buffer = Self { data: bytes.as_mut_ptr(), len: bytes.len() };
+        std::mem::forget(bytes);
+        buffer
+    }
+}
+
+/// A parsed font, kept between calls
+pub struct LlFont(Font);
+
+/// Release a buffer the engine returned; empty buffers are ignored
+///
+/// # Safety
+/// `buffer` must come from this library and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ll_buffer_free(buffer: LlBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(buffer.data, buffer.len)));
+    }
+}
+
+/// Parse TTF, OTF, WOFF or WOFF2 font bytes
+///
+/// Returns the font, or null with the error message in `error`.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes and `error` to a writable
+/// buffer (or be null).
+#[no_mangle]
+pub unsafe extern "C" fn ll_font_load(data: *const u8, len: usize, error: *mut LlBuffer) -> *mut LlFont {
+    let mut font = std::ptr::null_mut();
+    let status = guarded(error, || {
+        font = Box::into_raw(Box::new(LlFont(Font::from_bytes(bytes(data, len)?)?)));
+        Ok(())
+    });
+    if status != LL_OK {
+        return std::ptr::null_mut();
+    }
+    font
+}
+
+/// Release a font from `ll_font_load`; null is ignored
+///
+/// # Safety
+/// `font` must come from `ll_font_load` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ll_font_free(font: *mut LlFont) {
+    if !font.is_null() {
+        drop(Box::from_raw(font));
+    }
+}
+
+/// Score a drawing against a character
+///
+/// `character` is a Unicode scalar value. `options_json` and `strokes_json`
+/// are optional (null); strokes are in the image's pixel coordinates. On
+/// success `result_json` holds the scoring result as JSON and
+/// `reference_png` the reference the drawing was compared against.
+///
+/// # Safety
+/// `font` must come from `ll_font_load`, `image` must point to `image_len`
+/// readable bytes, the strings must be null or NUL-terminated, and the
+/// output pointers must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn ll_score_drawing(
+    font: *const LlFont,
+    image: *const u8,
+    image_len: usize,
+    character: u32,
+    options_json: *const c_char,
+    strokes_json: *const c_char,
+    result_json: *mut LlBuffer,
+    reference_png: *mut LlBuffer,
+) -> i32 {
+    guarded(result_json, || {
+        let font = font.as_ref().ok_or("No font")?;
+        let character = char::from_u32(character).ok_or("Not a Unicode character")?;
+        let options = options(options_json)?;
+        let strokes: Option<Vec<Stroke>> = match string(strokes_json)? {
+            Some(text) => Some(serde_json::from_str(text).map_err(|e| format!("Invalid stroke data: {}", e))?),
+            None => None,
+        };
+
+        let scored = score_drawing(bytes(image, image_len)?, character, &font.0, strokes.as_deref(), &options)?;
+        let result = serde_json::to_vec(&scored.result).map_err(|e| e.to_string())?;
+        write(result_json, result);
+        write(reference_png, scored.reference_image);
+        Ok(())
+    })
+}
+
+/// Render the reference image for a character as PNG bytes into `png`
+///
+/// `options_json` is optional (null).
+///
+/// # Safety
+/// `font` must come from `ll_font_load`, `options_json` must be null or
+/// NUL-terminated and `png` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn ll_generate_reference_image(
+    font: *const LlFont,
+    character: u32,
+    size: u32,
+    options_json: *const c_char,
+    png: *mut LlBuffer,
+) -> i32 {
+    guarded(png, || {
+        let font = font.as_ref().ok_or("No font")?;
+        let character = char::from_u32(character).ok_or("Not a Unicode character")?;
+        let image = generate_reference_image(character, &font.0, size, &options(options_json)?)?;
+        write(png, image);
+        Ok(())
+    })
+}
+
+/// Run `body`, turning errors and panics into a status and a message in
+/// `error`
+unsafe fn guarded(error: *mut LlBuffer, body: impl FnOnce() -> Result<(), ScoringError>) -> i32 {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => return LL_OK,
+        Ok(Err(ScoringError::GlyphMissing(character))) => {
+            (LL_GLYPH_MISSING, ScoringError::GlyphMissing(character).to_string())
+        }
+        Ok(Err(error)) => (LL_ERROR, error.to_string()),
+        Err(panic) => {
+            let message = panic.downcast_ref::<&str>().map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            (LL_PANIC, format!("Scoring panicked: {}", message))
+        }
+    };
+    write(error, message.into_bytes());
+    status
+}
+
+unsafe fn write(buffer: *mut LlBuffer, bytes: Vec<u8>) {
+    if !buffer.is_null() {
+        *buffer = LlBuffer::from_vec(bytes);
+    }
+}
+
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Result<&'a [u8], ScoringError> {
+    if data.is_null() {
+        return if len == 0 { Ok(&[]) } else { Err("Null data pointer".into()) };
+    }
+    Ok(std::slice::from_raw_parts(data, len))
+}
+
+unsafe fn string<'a>(text: *const c_char) -> Result<Option<&'a str>, ScoringError> {
+    if text.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(text).to_str().map(Some).map_err(|_| "Strings must be UTF-8".into())
+}
+
+unsafe fn options(options_json: *const c_char) -> Result<ScoringOptions, ScoringError> {
+    match string(options_json)? {
+        Some(text) => {
+            let value = serde_json::from_str(text).map_err(|e| format!("Invalid scoring options: {}", e))?;
+            Ok(ScoringOptions::from_json(value)?)
+        }
+        None => Ok(ScoringOptions::default()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_through_the_c_interface() {
+        let data = include_bytes!("../../../public/fonts/Fredoka-Regular.ttf");
+        unsafe {
+            let mut error = LlBuffer::empty();
+            let font = ll_font_load(data.as_ptr(), data.len(), &mut error);
+            assert!(!font.is_null());
+
+            let mut png = LlBuffer::empty();
+            assert_eq!(ll_generate_reference_image(font, 'a' as u32, 300, std::ptr::null(), &mut png), LL_OK);
+
+            let (mut result, mut reference) = (LlBuffer::empty(), LlBuffer::empty());
+            let options = c"{\"mode\": \"freehand\"}";
+            let status = ll_score_drawing(
+                font, png.data, png.len, 'a' as u32, options.as_ptr(), std::ptr::null(), &mut result, &mut reference,
+            );
+            assert_eq!(status, LL_OK);
+            let json: serde_json::Value = serde_json::from_slice(std::slice::from_raw_parts(result.data, result.len)).unwrap();
+            assert!(json["score"].as_u64().unwrap() >= 90);
+            assert!(reference.len > 0);
+            ll_buffer_free(result);
+            ll_buffer_free(reference);
+
+            // Errors leave a message in the first output
+            let mut message = LlBuffer::empty();
+            let status = ll_score_drawing(
+                font, png.data, png.len, '\u{e000}' as u32, std::ptr::null(), std::ptr::null(), &mut message, std::ptr::null_mut(),
+            );
+            assert_eq!(status, LL_GLYPH_MISSING);
+            assert!(std::str::from_utf8(std::slice::from_raw_parts(message.data, message.len)).unwrap().contains("glyph"));
+            ll_buffer_free(message);
+
+            let bad = [0u8; 4];
+            assert!(ll_font_load(bad.as_ptr(), bad.len(), &mut error).is_null());
+            assert!(error.len > 0);
+            ll_buffer_free(error);
+
+            ll_buffer_free(png);
+            ll_font_free(font);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Recorded stroke-session fixtures
+//!
+//! Each JSON file in `fixtures/strokes/` is a recorded drawing session:
+//!
+//! ```json
+//! {
+//!   "description": "T drawn bar first, then the stem",
+//!   "character": "T",
+//!   "font": "Fredoka-Regular",
+//!   "canvas": { "width": 400, "height": 400 },
+//!   "options": { "similarity_method": "ssim" },
+//!   "events": [{ "type": "stroke", "points": [{ "x": 100, "y": 80, "t": 0 }] }],
+//!   "expected": { "min_score": 60, "actual_strokes": 2, "min_order_score": 90 }
+//! }
+//! ```
+//!
+//! `font` defaults to Fredoka-Regular and `options` to the defaults. The
+//! events are replayed, rasterized onto the canvas and scored through the
+//! stroke pipeline twice; both runs must agree exactly and satisfy every
+//! field given in `expected`.
+
+use crate::options::ScoringOptions;
+use crate::font::Font;
+use crate::scoring::{encode_grayscale_to_png, score_drawing_internal};
+use crate::strokes::{StrokeEvent, replay_events, rasterize_strokes};
+use crate::ScoringResult;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Deserialize)]
+struct Fixture {
+    description: String,
+    character: char,
+    #[serde(default = "default_font")]
+    font: String,
+    canvas: Canvas,
+    #[serde(default)]
+    options: ScoringOptions,
+    events: Vec<StrokeEvent>,
+    expected: Expected,
+}
+
+#[derive(Deserialize)]
+struct Canvas {
+    width: u32,
+    height: u32,
+}
+
+/// Checks on the result; absent fields are not checked
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct Expected {
+    min_score: Option<u8>,
+    max_score: Option<u8>,
+    stars: Option<u8>,
+    expected_strokes: Option<u32>,
+    actual_strokes: Option<u32>,
+    min_order_score: Option<f32>,
+    max_order_score: Option<f32>,
+    /// Per-stroke `direction_ok` flags, in drawn order
+    directions_ok: Option<Vec<bool>>,
+    feedback_code: Option<Option<String>>,
+}
+
+fn default_font() -> String {
+    "Fredoka-Regular".to_string()
+}
+
+fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/strokes")
+}
+
+fn score_fixture(fixture: &Fixture) -> Result<ScoringResult, String> {
+    let font_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../../public/fonts")
+        .join(format!("{}.ttf", fixture.font));
+    let font = std::fs::read(&font_path)
+        .map_err(|e| format!("Failed to read font {}: {}", font_path.display(), e))?;
+    let font = Font::from_bytes(&font).map_err(|e| e.to_string())?;
+
+    let mut options = fixture.options.clone();
+    options.rasterize_strokes = true;
+
+    let strokes = replay_events(&fixture.events);
+    let canvas = rasterize_strokes(&strokes, fixture.canvas.width, fixture.canvas.height, options.stroke_width);
+    let png = encode_grayscale_to_png(&canvas)?;
+
+    score_drawing_internal(&png, fixture.character, &font, Some(&strokes), &options)
+        .map(|scored| scored.result)
+        .map_err(|e| e.to_string())
+}
+
+fn check_expected(result: &ScoringResult, expected: &Expected) -> Vec<String> {
+    let mut failures = Vec::new();
+    let mut check = |ok: bool, what: String| {
+        if !ok {
+            failures.push(what);
+        }
+    };
+
+    if let Some(min) = expected.min_score {
+        check(result.score >= min, format!("score {} < min_score {}", result.score, min));
+    }
+    if let Some(max) = expected.max_score {
+        check(result.score <= max, format!("score {} > max_score {}", result.score, max));
+    }
+    if let Some(stars) = expected.stars {
+        check(result.stars == stars, format!("stars {} != {}", result.stars, stars));
+    }
+    if let Some(n) = expected.expected_strokes {
+        check(result.expected_strokes == Some(n), format!("expected_strokes {:?} != {}", result.expected_strokes, n));
+    }
+    if let Some(n) = expected.actual_strokes {
+        check(result.actual_strokes == Some(n), format!("actual_strokes {:?} != {}", result.actual_strokes, n));
+    }
+    if let Some(min) = expected.min_order_score {
+        check(result.order_score.is_some_and(|o| o >= min), format!("order_score {:?} < {}", result.order_score, min));
+    }
+    if let Some(max) = expected.max_order_score {
+        check(result.order_score.is_some_and(|o| o <= max), format!("order_score {:?} > {}", result.order_score, max));
+    }
+    if let Some(flags) = &expected.directions_ok {
+        let actual: Vec<bool> = result.stroke_directions.iter().map(|d| d.direction_ok).collect();
+        check(&actual == flags, format!("directions_ok {:?} != {:?}", actual, flags));
+    }
+    if let Some(code) = &expected.feedback_code {
+        check(&result.feedback_code == code, format!("feedback_code {:?} != {:?}", result.feedback_code, code));
+    }
+
+    failures
+}
+
+#[test]
+fn test_stroke_fixtures() {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(fixture_dir())
+        .expect("fixtures/strokes exists")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty(), "no stroke fixtures found");
+
+    let mut failures = Vec::new();
+    for path in &paths {
+        let name = path.file_stem().unwrap().to_string_lossy();
+        let text = std::fs::read_to_string(path).unwrap();
+        let fixture: Fixture = match serde_json::from_str(&text) {
+            Ok(fixture) => fixture,
+            Err(e) => {
+                failures.push(format!("{}: invalid fixture: {}", name, e));
+                continue;
+            }
+        };
+
+        let first = score_fixture(&fixture);
+        let second = score_fixture(&fixture);
+        let (first, second) = match (first, second) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(e), _) | (_, Err(e)) => {
+                failures.push(format!("{}: scoring failed: {}", name, e));
+                continue;
+            }
+        };
+
+        // Replays must be bit-for-bit deterministic
+        let first_json = serde_json::to_string(&first).unwrap();
+        if first_json != serde_json::to_string(&second).unwrap() {
+            failures.push(format!("{}: replay is not deterministic", name));
+        }
+
+        for failure in check_expected(&first, &fixture.expected) {
+            failures.push(format!("{} ({}): {}", name, fixture.description, failure));
+        }
+    }
+
+    assert!(failures.is_empty(), "stroke fixture failures:\n{}", failures.join("\n"));
+}
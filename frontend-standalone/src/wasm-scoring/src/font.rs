@@ -0,0 +1,129 @@
+//! Loaded font handle
+//!
+//! Unpacking a WOFF2 font and validating its tables costs far more than
+//! rendering one glyph, and from JavaScript every call would also copy the
+//! whole font into wasm memory. A [`Font`] holds the plain sfnt data, so a
+//! session loads its font once and passes the handle to every call.
+//!
+//! A font can carry fallbacks for mixed-script exercises: every glyph is
+//! looked up in the first font of the chain that has it.
+
+use crate::error::ScoringError;
+use crate::woff::sfnt_data;
+use ab_glyph::{Font as _, FontRef, VariableFont};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// A font loaded from TTF, OTF, WOFF or WOFF2 bytes
+#[derive(Debug, Clone)]
+pub struct Font {
+    /// Plain sfnt data, already checked to parse
+    data: Vec<u8>,
+    /// Hash of `data`, identifying the font in the reference cache
+    fingerprint: u64,
+    /// Fonts tried in order for characters this one lacks
+    fallbacks: Vec<Font>,
+}
+
+impl Font {
+    /// Load a font, unpacking WOFF and WOFF2 data
+    pub fn from_bytes(font_data: &[u8]) -> Result<Self, ScoringError> {
+        let data = sfnt_data(font_data)?.into_owned();
+        FontRef::try_from_slice(&data).map_err(|_| "Failed to parse font data")?;
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        Ok(Self { fingerprint: hasher.finish(), data, fallbacks: Vec::new() })
+    }
+
+    /// The font with `fonts` to fall back on, in order, for characters it
+    /// lacks (after any fallbacks it already has)
+    pub fn with_fallbacks(mut self, fonts: impl IntoIterator<Item = Font>) -> Self {
+        for mut font in fonts {
+            let nested = std::mem::take(&mut font.fallbacks);
+            self.fallbacks.push(font);
+            self.fallbacks.extend(nested);
+        }
+        self
+    }
+
+    /// Whether the font or one of its fallbacks has a glyph for the character
+    pub fn has_glyph(&self, character: char) -> bool {
+        self.for_glyph(character).has_own_glyph(character)
+    }
+
+    /// The first font in the chain with a glyph for the character, or this
+    /// one when none has it
+    pub(crate) fn for_glyph(&self, character: char) -> &Font {
+        std::iter::once(self)
+            .chain(&self.fallbacks)
+            .find(|font| font.has_own_glyph(character))
+            .unwrap_or(self)
+    }
+
+    fn has_own_glyph(&self, character: char) -> bool {
+        self.face().glyph_id(character).0 != 0
+    }
+
+    /// The font with variation axes set by tag, e.g. `wght`
+    pub(crate) fn with_axes(&self, axes: &HashMap<String, f32>) -> Result<FontRef<'_>, ScoringError> {
+        let mut face = self.face();
+        for (tag, &value) in axes {
+            let tag: &[u8; 4] = tag.as_bytes().try_into()
+                .map_err(|_| format!("Invalid font axis tag '{}'", tag))?;
+            face.set_variation(tag, value);
+        }
+        Ok(face)
+    }
+
+    pub(crate) fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    fn face(&self) -> FontRef<'_> {
+        // Only reads the table directory; the data parsed in from_bytes
+        FontRef::try_from_slice(&self.data).expect("font data was validated on load")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_FONT: &[u8] = include_bytes!("../../../public/fonts/Fredoka-Regular.ttf");
+
+    #[test]
+    fn test_font_from_bytes() {
+        let font = Font::from_bytes(TEST_FONT).unwrap();
+        assert!(font.has_glyph('a'));
+        assert!(!font.has_glyph('Ω'));
+
+        // CFF2-flavored OTF
+        let otf = Font::from_bytes(include_bytes!("../fixtures/fonts/Cantarell-VF.otf")).unwrap();
+        assert!(otf.has_glyph('a') && !otf.has_glyph('字'));
+
+        assert!(Font::from_bytes(b"not a font").is_err());
+        assert!(Font::from_bytes(&[]).is_err());
+    }
+
+    #[test]
+    fn test_font_fallbacks() {
+        let fredoka = Font::from_bytes(TEST_FONT).unwrap();
+        let nunito = Font::from_bytes(include_bytes!("../../../public/fonts/Nunito-Regular.ttf")).unwrap();
+        let chain = fredoka.clone().with_fallbacks([nunito.clone()]);
+        assert!(chain.has_glyph('a') && chain.has_glyph('Ω') && !chain.has_glyph('α'));
+        assert_eq!(chain.for_glyph('a').fingerprint(), fredoka.fingerprint());
+        assert_eq!(chain.for_glyph('Ω').fingerprint(), nunito.fingerprint());
+        assert_eq!(chain.for_glyph('α').fingerprint(), fredoka.fingerprint());
+
+        // A chain added as a fallback brings its own fallbacks along
+        let nested = fredoka.clone().with_fallbacks([fredoka.clone().with_fallbacks([nunito])]);
+        assert!(nested.has_glyph('Ω'));
+    }
+
+    #[test]
+    fn test_font_axes() {
+        let font = Font::from_bytes(TEST_FONT).unwrap();
+        assert!(font.with_axes(&HashMap::from([("wght".to_string(), 600.0)])).is_ok());
+        assert!(font.with_axes(&HashMap::from([("weight".to_string(), 600.0)])).is_err());
+    }
+}
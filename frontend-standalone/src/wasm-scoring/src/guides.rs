@@ -0,0 +1,800 @@
+//! Tracing guides
+//!
+//! Trace-over worksheets show the letter as a row of dots or dashes for the
+//! child to join up, rather than a solid glyph. Marks follow the glyph's
+//! outline, or the centerline for handwriting styles, whose letterforms are
+//! pen paths. They are spread evenly: a closed contour gets a whole number
+//! of dots or dashes, and an open path has one at each end.
+//!
+//! Stroke-order guides draw the letter in light gray with each template
+//! stroke over it as an arrow from a numbered start dot. The template is
+//! fitted to the glyph's skeleton the way scoring fits it to a drawing, so
+//! no per-letter artwork is needed. Animation frames use the same fit to
+//! ink the letter stroke by stroke: each glyph pixel appears once the pen
+//! passes its nearest template point.
+//!
+//! Worksheets put these together for printing: one row per character on
+//! topline, dashed midline and baseline rules, each row starting with a
+//! model letter followed by faded (or dotted) copies to trace. The same
+//! rules can be drawn behind a single reference image, in color.
+
+use crate::bit_image::BitImage;
+use crate::error::ScoringError;
+use crate::font::Font;
+use crate::options::{GuideLine, RulingOptions, ScoringOptions};
+use crate::practice::ReferenceVariation;
+use crate::scoring::{glyph_lines, glyph_outline, normalize_line_thickness, render_reference, sized_variation};
+use crate::strokes::{Stroke, StrokePoint, rasterize_strokes};
+use crate::styles::{style_lines, style_paths, styled_template};
+use crate::templates::CharacterTemplate;
+use ab_glyph::{Font as _, FontRef, PxScale, ScaleFont as _, point};
+use image::{GrayImage, ImageBuffer, Luma, Pixel, Rgb, RgbImage};
+
+// Stroke-order guide proportions, as fractions of the image size
+const ORDER_DOT_RADIUS: f32 = 0.045;
+const ORDER_LINE_WIDTH: f32 = 0.015;
+const ORDER_ARROW_HEAD: f32 = 0.05;
+// Closest two start dots may be, in dot radii
+const ORDER_DOT_SEPARATION: f32 = 1.7;
+// Half-angle of an arrowhead, in radians
+const ORDER_ARROW_SPREAD: f32 = 0.5;
+// Gray the letter is drawn in under the arrows
+const ORDER_GLYPH_GRAY: u8 = 200;
+// Closest marks in a guide, as a share of the dot size and never under a
+// pixel; closer ones would only merge into a solid line
+const MIN_GUIDE_STEP: f32 = 0.25;
+// Most frames in one stroke animation
+const MAX_ANIMATION_FRAMES: u32 = 240;
+// Worksheet cell side in pixels, about 2.7 cm printed at 150 dpi; page
+// margins and the gaps between rows are a quarter of it
+const WORKSHEET_CELL: u32 = 160;
+const MAX_WORKSHEET_ROWS: u32 = 20;
+const MAX_WORKSHEET_COLS: u32 = 16;
+// Gray of the letters to trace and of the ruled lines
+const WORKSHEET_TRACE_GRAY: u8 = 210;
+const WORKSHEET_LINE_GRAY: u8 = 150;
+
+/// Render a dotted or dashed guide for a variation, black on white
+pub(crate) fn render_guide(
+    variation: &ReferenceVariation,
+    font: &Font,
+    options: &ScoringOptions,
+    size: u32,
+) -> Result<GrayImage, ScoringError> {
+    let variation = &sized_variation(variation, options)?;
+    let guide = &options.guide;
+    let positive = |value: f32| value.is_finite() && value > 0.0;
+    if !(positive(guide.size) && positive(guide.spacing) && positive(guide.dash_length)) {
+        return Err("Guide size, spacing and dash length must be positive".into());
+    }
+    let min_step = (guide.size * MIN_GUIDE_STEP).max(1.0);
+    let (spacing, dash_length) = (guide.spacing.max(min_step), guide.dash_length.max(min_step));
+
+    let styled = match &options.style {
+        Some(style) => style_paths(style, variation, size)?,
+        None => None,
+    };
+    let paths = match styled {
+        Some(paths) => paths,
+        None => glyph_outline(variation, font, &options.font_axes, size)?,
+    };
+
+    let marks: Vec<Vec<(f32, f32)>> = paths.iter()
+        .flat_map(|path| match guide.line {
+            GuideLine::Dashed => dashes(path, dash_length, spacing),
+            _ => dots(path, spacing),
+        })
+        .collect();
+    let strokes: Vec<Stroke> = marks.into_iter()
+        .map(|mark| Stroke {
+            points: mark.into_iter().map(|(x, y)| StrokePoint { x, y, t: None, pressure: None }).collect(),
+            ..Default::default()
+        })
+        .collect();
+    Ok(rasterize_strokes(&strokes, size, size, guide.size))
+}
+
+/// Render the letter with numbered, arrowed strokes from its stroke
+/// template, black on white
+pub(crate) fn render_stroke_order(
+    character: char,
+    font: &Font,
+    options: &ScoringOptions,
+    size: u32,
+) -> Result<GrayImage, ScoringError> {
+    let template = styled_template(character, options.style.as_deref())
+        .ok_or_else(|| format!("No stroke template for '{}'", character))?;
+    let variation = options.reference_variation(character);
+    let glyph = render_reference(&variation, font, options, size)?;
+    let strokes = fit_template(template, &glyph)
+        .ok_or_else(|| format!("Glyph for '{}' is empty", variation.glyph))?;
+
+    let scale = size as f32;
+    let radius = scale * ORDER_DOT_RADIUS;
+    let head = scale * ORDER_ARROW_HEAD;
+    let mut dots: Vec<(f32, f32)> = Vec::new();
+    let mut arrows: Vec<Vec<(f32, f32)>> = Vec::new();
+    for path in &strokes {
+        let lengths = arc_lengths(path);
+        let length = *lengths.last().unwrap_or(&0.0);
+
+        // A stroke starting where an earlier one did (the apex of an 'A')
+        // gets its dot a little way along itself; dots may overlap slightly
+        let mut start = 0.0;
+        while start < length && dots.iter().any(|&dot| distance(dot, point_at(path, &lengths, start)) < ORDER_DOT_SEPARATION * radius) {
+            start += radius / 4.0;
+        }
+        dots.push(point_at(path, &lengths, start.min(length)));
+
+        // Short strokes, like the dot of an 'i', are shown by the dot alone
+        let tail = start + radius * 1.5;
+        if length - tail < head {
+            continue;
+        }
+        arrows.push(sub_path(path, &lengths, tail, length));
+        let tip = path[path.len() - 1];
+        let back = point_at(path, &lengths, length - head);
+        let angle = (tip.1 - back.1).atan2(tip.0 - back.0);
+        let wing = |turn: f32| (
+            tip.0 - head * (angle + turn).cos(),
+            tip.1 - head * (angle + turn).sin(),
+        );
+        arrows.push(vec![wing(ORDER_ARROW_SPREAD), tip, wing(-ORDER_ARROW_SPREAD)]);
+    }
+
+    // Light letter, dark arrows and dots, white numbers
+    let mut image = fade(&glyph, ORDER_GLYPH_GRAY);
+    let to_strokes = |marks: Vec<Vec<(f32, f32)>>| -> Vec<Stroke> {
+        marks.into_iter()
+            .map(|mark| Stroke {
+                points: mark.into_iter().map(|(x, y)| StrokePoint { x, y, t: None, pressure: None }).collect(),
+                ..Default::default()
+            })
+            .collect()
+    };
+    let arrows = rasterize_strokes(&to_strokes(arrows), size, size, scale * ORDER_LINE_WIDTH);
+    let dot_marks = dots.iter().map(|&dot| vec![dot]).collect();
+    let dot_image = rasterize_strokes(&to_strokes(dot_marks), size, size, 2.0 * radius);
+    for ((pixel, arrow), dot) in image.pixels_mut().zip(arrows.pixels()).zip(dot_image.pixels()) {
+        pixel.0[0] = pixel.0[0].min(arrow.0[0]).min(dot.0[0]);
+    }
+    // Labels are digits, from the first font in the chain that has them
+    let font = font.for_glyph('1').with_axes(&options.font_axes)?;
+    for (i, &dot) in dots.iter().enumerate() {
+        draw_label(&mut image, &font, &(i + 1).to_string(), dot, radius * 1.4);
+    }
+
+    Ok(image)
+}
+
+/// Frames inking the letter stroke by stroke over a light copy of itself,
+/// from the light letter alone to the finished letter
+pub(crate) fn render_stroke_animation(
+    character: char,
+    font: &Font,
+    options: &ScoringOptions,
+    size: u32,
+    frame_count: u32,
+) -> Result<Vec<GrayImage>, ScoringError> {
+    if !(2..=MAX_ANIMATION_FRAMES).contains(&frame_count) {
+        return Err(format!("Frame count must be between 2 and {}", MAX_ANIMATION_FRAMES).into());
+    }
+    let template = styled_template(character, options.style.as_deref())
+        .ok_or_else(|| format!("No stroke template for '{}'", character))?;
+    let variation = options.reference_variation(character);
+    let glyph = render_reference(&variation, font, options, size)?;
+    let strokes = fit_template(template, &glyph)
+        .ok_or_else(|| format!("Glyph for '{}' is empty", variation.glyph))?;
+
+    // Template points about a pixel apart, with how far the pen has gone
+    // in all strokes when it reaches each
+    let mut samples: Vec<((f32, f32), f32)> = Vec::new();
+    let mut drawn = 0.0;
+    for path in &strokes {
+        let lengths = arc_lengths(path);
+        let length = *lengths.last().unwrap_or(&0.0);
+        let steps = (length.ceil() as usize).max(1);
+        for i in 0..=steps {
+            let along = length * i as f32 / steps as f32;
+            samples.push((point_at(path, &lengths, along), drawn + along));
+        }
+        drawn += length;
+    }
+
+    // How far the pen has gone when each inked pixel appears
+    let inked_at: Vec<Option<f32>> = glyph.enumerate_pixels()
+        .map(|(x, y, pixel)| {
+            let center = (x as f32 + 0.5, y as f32 + 0.5);
+            (pixel.0[0] < 255).then(|| {
+                samples.iter()
+                    .min_by(|a, b| distance(a.0, center).total_cmp(&distance(b.0, center)))
+                    .map_or(0.0, |&(_, along)| along)
+            })
+        })
+        .collect();
+
+    let light = fade(&glyph, ORDER_GLYPH_GRAY);
+    Ok((0..frame_count)
+        .map(|k| {
+            let pen = if k + 1 == frame_count { f32::INFINITY } else { drawn * k as f32 / (frame_count - 1) as f32 };
+            let mut frame = light.clone();
+            for ((pixel, inked), &at) in frame.pixels_mut().zip(glyph.pixels()).zip(&inked_at) {
+                if at.is_some_and(|at| at < pen) {
+                    *pixel = *inked;
+                }
+            }
+            frame
+        })
+        .collect())
+}
+
+/// The frames of `render_stroke_animation` side by side, no wider than
+/// `options.input_limits` accepts a drawing
+pub(crate) fn render_stroke_animation_sheet(
+    character: char,
+    font: &Font,
+    options: &ScoringOptions,
+    size: u32,
+    frame_count: u32,
+) -> Result<GrayImage, ScoringError> {
+    let limits = &options.input_limits;
+    let width = size.checked_mul(frame_count).filter(|&width| width <= limits.max_width && size <= limits.max_height)
+        .ok_or_else(|| format!("Animation sheet would be over the {}x{} limit", limits.max_width, limits.max_height))?;
+    let frames = render_stroke_animation(character, font, options, size, frame_count)?;
+    let mut sheet = GrayImage::new(width, size);
+    for (i, frame) in frames.iter().enumerate() {
+        image::imageops::overlay(&mut sheet, frame, i as i64 * size as i64, 0);
+    }
+    Ok(sheet)
+}
+
+/// A practice worksheet: one row per character (cycling through
+/// `characters`), each a model letter followed by copies to trace
+pub(crate) fn render_worksheet(
+    characters: &str,
+    font: &Font,
+    rows: u32,
+    cols: u32,
+    options: &ScoringOptions,
+) -> Result<GrayImage, ScoringError> {
+    let characters: Vec<char> = characters.chars().collect();
+    if characters.is_empty() {
+        return Err("Worksheet needs at least one character".into());
+    }
+    if !(1..=MAX_WORKSHEET_ROWS).contains(&rows) || !(2..=MAX_WORKSHEET_COLS).contains(&cols) {
+        return Err(format!(
+            "Worksheet must have 1 to {} rows and 2 to {} columns",
+            MAX_WORKSHEET_ROWS, MAX_WORKSHEET_COLS
+        ).into());
+    }
+
+    let cell = WORKSHEET_CELL;
+    let margin = cell / 4;
+    let mut page = GrayImage::from_pixel(cols * cell + 2 * margin, rows * cell + (rows + 1) * margin, Luma([255]));
+    for row in 0..rows {
+        let character = characters[row as usize % characters.len()];
+        let variation = options.reference_variation(character);
+        let model = render_reference(&variation, font, options, cell)?;
+        let trace = match options.guide.line {
+            GuideLine::Solid => fade(&model, WORKSHEET_TRACE_GRAY),
+            _ => render_guide(&variation, font, options, cell)?,
+        };
+        let top = margin + row * (cell + margin);
+
+        if let Some(lines) = writing_lines(&variation, font, options, cell)? {
+            let rules = Rules {
+                colors: [Luma([WORKSHEET_LINE_GRAY]); 3],
+                thickness: (cell / 80).max(1),
+                dash: cell / 16,
+                dashed_midline: true,
+            };
+            let right = page.width() - margin;
+            rules.draw(&mut page, lines, margin..right, top);
+        }
+
+        for col in 0..cols {
+            let letter = if col == 0 { &model } else { &trace };
+            let left = margin + col * cell;
+            for (x, y, pixel) in letter.enumerate_pixels() {
+                let target = page.get_pixel_mut(left + x, top + y);
+                target.0[0] = target.0[0].min(pixel.0[0]);
+            }
+        }
+    }
+
+    Ok(page)
+}
+
+/// A reference image over writing lines in the ruling's colors; the letter
+/// was drawn in a `side`-pixel square `top` pixels down the image
+pub(crate) fn rule_reference(
+    glyph: &GrayImage,
+    variation: &ReferenceVariation,
+    font: &Font,
+    options: &ScoringOptions,
+    ruling: &RulingOptions,
+    (side, top): (u32, u32),
+) -> Result<RgbImage, ScoringError> {
+    if !(ruling.thickness.is_finite() && ruling.thickness > 0.0) {
+        return Err("Ruling thickness must be positive".into());
+    }
+    let rules = Rules {
+        colors: [
+            parse_color(&ruling.topline_color)?,
+            parse_color(&ruling.midline_color)?,
+            parse_color(&ruling.baseline_color)?,
+        ],
+        thickness: (ruling.thickness.round() as u32).max(1),
+        dash: (side / 16).max(1),
+        dashed_midline: ruling.dashed_midline,
+    };
+
+    let (width, height) = glyph.dimensions();
+    let mut image = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+    if let Some(lines) = writing_lines(variation, font, options, side)? {
+        rules.draw(&mut image, lines, 0..width, top);
+    }
+    // Ink darkens the lines it crosses
+    for (pixel, ink) in image.pixels_mut().zip(glyph.pixels()) {
+        for channel in &mut pixel.0 {
+            *channel = (*channel as u32 * ink.0[0] as u32 / 255) as u8;
+        }
+    }
+    Ok(image)
+}
+
+/// `#rgb` or `#rrggbb`
+fn parse_color(color: &str) -> Result<Rgb<u8>, ScoringError> {
+    let invalid = || format!("Invalid color '{}'; expected #rgb or #rrggbb", color);
+    let digits = color.strip_prefix('#').filter(|d| d.is_ascii()).ok_or_else(invalid)?;
+    let channel = |hex: &str| u8::from_str_radix(hex, 16).map_err(|_| invalid());
+    match digits.len() {
+        3 => {
+            let mut rgb = [0u8; 3];
+            for (i, value) in rgb.iter_mut().enumerate() {
+                *value = channel(&digits[i..i + 1])? * 17;
+            }
+            Ok(Rgb(rgb))
+        }
+        6 => Ok(Rgb([channel(&digits[0..2])?, channel(&digits[2..4])?, channel(&digits[4..6])?])),
+        _ => Err(invalid().into()),
+    }
+}
+
+/// How topline, midline and baseline rules are drawn
+struct Rules<P> {
+    colors: [P; 3],
+    thickness: u32,
+    dash: u32,
+    dashed_midline: bool,
+}
+
+impl<P: Pixel> Rules<P> {
+    /// Draw the lines, given as heights below `top`, across the columns
+    fn draw(&self, image: &mut ImageBuffer<P, Vec<P::Subpixel>>, lines: [f32; 3], columns: std::ops::Range<u32>, top: u32) {
+        for (i, (y, color)) in lines.into_iter().zip(self.colors).enumerate() {
+            let first = (top as f32 + y - self.thickness as f32 / 2.0).round().max(0.0) as u32;
+            for x in columns.clone() {
+                if i == 1 && self.dashed_midline && (x - columns.start) / self.dash % 2 == 1 {
+                    continue;
+                }
+                for y in first..(first + self.thickness).min(image.height()) {
+                    image.put_pixel(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+/// Topline, midline and baseline heights in image pixels for a variation,
+/// from the handwriting style's box or the font's metrics
+fn writing_lines(
+    variation: &ReferenceVariation,
+    font: &Font,
+    options: &ScoringOptions,
+    size: u32,
+) -> Result<Option<[f32; 3]>, ScoringError> {
+    let variation = &sized_variation(variation, options)?;
+    if let Some(style) = &options.style {
+        if let Some(lines) = style_lines(style, variation, size)? {
+            return Ok(Some(lines));
+        }
+    }
+    glyph_lines(variation, font, &options.font_axes, size)
+}
+
+/// The glyph redrawn with its ink lightened to `gray`
+fn fade(glyph: &GrayImage, gray: u8) -> GrayImage {
+    let mut light = glyph.clone();
+    for pixel in light.pixels_mut() {
+        pixel.0[0] = 255 - ((255 - pixel.0[0] as u32) * (255 - gray as u32) / 255) as u8;
+    }
+    light
+}
+
+/// Template strokes in image pixels, fitted to the glyph's skeleton as
+/// scoring fits them to a drawing: joint bounding boxes matched on their
+/// larger side and centered. `None` for a blank glyph
+fn fit_template(template: &CharacterTemplate, glyph: &GrayImage) -> Option<Vec<Vec<(f32, f32)>>> {
+    let ink = BitImage::from_fn(glyph.width() as usize, glyph.height() as usize, |x, y| glyph.get_pixel(x as u32, y as u32).0[0] < 128);
+    let skeleton = normalize_line_thickness(&ink, 1, false);
+    let skeleton_points = skeleton.ones().map(|(x, y)| (x as f32 + 0.5, y as f32 + 0.5));
+    let (skeleton_min, skeleton_max) = point_bounds(skeleton_points)?;
+    let (template_min, template_max) = point_bounds(template.strokes.iter().flat_map(|s| s.points.iter().copied()))?;
+
+    let extent = |min: (f32, f32), max: (f32, f32)| (max.0 - min.0).max(max.1 - min.1).max(f32::EPSILON);
+    let scale = extent(skeleton_min, skeleton_max) / extent(template_min, template_max);
+    let center = |min: (f32, f32), max: (f32, f32)| ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+    let (from, to) = (center(template_min, template_max), center(skeleton_min, skeleton_max));
+    Some(template.strokes.iter()
+        .map(|stroke| stroke.points.iter()
+            .map(|&(x, y)| ((x - from.0) * scale + to.0, (y - from.1) * scale + to.1))
+            .collect())
+        .collect())
+}
+
+fn point_bounds(points: impl Iterator<Item = (f32, f32)>) -> Option<((f32, f32), (f32, f32))> {
+    points.fold(None, |acc, (x, y)| match acc {
+        None => Some(((x, y), (x, y))),
+        Some((min, max)) => Some(((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))),
+    })
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+/// Draw text in white, centered on a point; characters the font lacks are
+/// left out
+fn draw_label(image: &mut GrayImage, font: &FontRef, text: &str, center: (f32, f32), height: f32) {
+    let scaled = font.as_scaled(PxScale::from(height));
+    let mut x = 0.0;
+    let outlines: Vec<_> = text.chars()
+        .map(|c| font.glyph_id(c))
+        .filter(|id| id.0 != 0)
+        .filter_map(|id| {
+            let glyph = id.with_scale_and_position(scaled.scale(), point(x, 0.0));
+            x += scaled.h_advance(id);
+            font.outline_glyph(glyph)
+        })
+        .collect();
+    let Some((min, max)) = point_bounds(outlines.iter()
+        .flat_map(|outline| {
+            let bb = outline.px_bounds();
+            [(bb.min.x, bb.min.y), (bb.max.x, bb.max.y)]
+        })) else {
+        return;
+    };
+    let offset_x = (center.0 - (min.0 + max.0) / 2.0).round() as i32;
+    let offset_y = (center.1 - (min.1 + max.1) / 2.0).round() as i32;
+    for outline in &outlines {
+        let bb = outline.px_bounds();
+        outline.draw(|x, y, v| {
+            let px = x as i32 + bb.min.x as i32 + offset_x;
+            let py = y as i32 + bb.min.y as i32 + offset_y;
+            if px >= 0 && py >= 0 && (px as u32) < image.width() && (py as u32) < image.height() {
+                let pixel = image.get_pixel(px as u32, py as u32).0[0] as f32;
+                let lit = pixel + (255.0 - pixel) * v.min(1.0);
+                image.put_pixel(px as u32, py as u32, Luma([lit as u8]));
+            }
+        });
+    }
+}
+
+/// Dots about `spacing` apart along a path, each a single-point mark
+fn dots(path: &[(f32, f32)], spacing: f32) -> Vec<Vec<(f32, f32)>> {
+    let lengths = arc_lengths(path);
+    let length = *lengths.last().unwrap_or(&0.0);
+    if path.is_empty() {
+        return Vec::new();
+    }
+    let count = ((length / spacing).round() as usize).max(1);
+    // A closed path's last dot would land on its first
+    let last = if is_closed(path) { count - 1 } else { count };
+    (0..=last)
+        .map(|i| vec![point_at(path, &lengths, length * i as f32 / count as f32)])
+        .collect()
+}
+
+/// Dashes about `dash_length` long with gaps about `gap` wide along a path
+fn dashes(path: &[(f32, f32)], dash_length: f32, gap: f32) -> Vec<Vec<(f32, f32)>> {
+    let lengths = arc_lengths(path);
+    let length = *lengths.last().unwrap_or(&0.0);
+    if path.is_empty() {
+        return Vec::new();
+    }
+    let period = dash_length + gap;
+    // Closed paths have a gap after every dash, open ones end on a dash
+    let (count, stretch) = if is_closed(path) {
+        let count = ((length / period).round() as usize).max(1);
+        (count, length / (count as f32 * period))
+    } else {
+        let count = (((length + gap) / period).round() as usize).max(1);
+        (count, length / (count as f32 * period - gap))
+    };
+    (0..count)
+        .map(|i| {
+            let start = i as f32 * period * stretch;
+            sub_path(path, &lengths, start, (start + dash_length * stretch).min(length))
+        })
+        .collect()
+}
+
+fn is_closed(path: &[(f32, f32)]) -> bool {
+    path.len() > 2 && path.first() == path.last()
+}
+
+/// Distance along the path to each vertex
+fn arc_lengths(path: &[(f32, f32)]) -> Vec<f32> {
+    let mut total = 0.0;
+    let mut lengths = Vec::with_capacity(path.len());
+    for (i, p) in path.iter().enumerate() {
+        if i > 0 {
+            let q = path[i - 1];
+            total += ((p.0 - q.0).powi(2) + (p.1 - q.1).powi(2)).sqrt();
+        }
+        lengths.push(total);
+    }
+    lengths
+}
+
+/// Point at a distance along the path
+fn point_at(path: &[(f32, f32)], lengths: &[f32], distance: f32) -> (f32, f32) {
+    let i = lengths.partition_point(|&l| l < distance);
+    if i == 0 {
+        return path[0];
+    }
+    if i == path.len() {
+        return path[path.len() - 1];
+    }
+    let (a, b) = (path[i - 1], path[i]);
+    let span = lengths[i] - lengths[i - 1];
+    let t = if span > 0.0 { (distance - lengths[i - 1]) / span } else { 0.0 };
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+/// The part of the path between two distances along it
+fn sub_path(path: &[(f32, f32)], lengths: &[f32], from: f32, to: f32) -> Vec<(f32, f32)> {
+    let mut points = vec![point_at(path, lengths, from)];
+    points.extend(path.iter().zip(lengths).filter(|&(_, &l)| l > from && l < to).map(|(&p, _)| p));
+    points.push(point_at(path, lengths, to));
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::connected_components;
+    use crate::options::GuideOptions;
+    use crate::scoring::{REFERENCE_SIZE, generate_reference_gray};
+    use std::collections::HashMap;
+
+    fn font() -> Font {
+        Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap()
+    }
+
+    fn ink(image: &GrayImage) -> BitImage {
+        BitImage::from_fn(image.width() as usize, image.height() as usize, |x, y| image.get_pixel(x as u32, y as u32).0[0] < 128)
+    }
+
+    #[test]
+    fn test_marks_along_path() {
+        let square = [(0.0, 0.0), (40.0, 0.0), (40.0, 40.0), (0.0, 40.0), (0.0, 0.0)];
+        assert_eq!(dots(&square, 10.0).len(), 16);
+        assert_eq!(dots(&square[..2], 10.0).len(), 5);
+        assert_eq!(dots(&square[..2], 10.0)[4], vec![(40.0, 0.0)]);
+
+        // Dashes stretch to fit: an open 40 px line holds two 12 px dashes
+        // with a 10 px gap, scaled to end on the endpoint
+        let line = dashes(&square[..2], 12.0, 10.0);
+        assert_eq!(line.len(), 2);
+        assert_eq!(line[1].last(), Some(&(40.0, 0.0)));
+        // Dashes turn corners
+        let closed = dashes(&square, 12.0, 6.0);
+        assert_eq!(closed.len(), 9);
+        assert!(closed.iter().any(|dash| dash.len() > 2));
+    }
+
+    #[test]
+    fn test_dotted_and_dashed_guides() {
+        let font = font();
+        let variation = ReferenceVariation::identity('O');
+        let dotted = ScoringOptions { guide: GuideOptions { line: GuideLine::Dotted, ..Default::default() }, ..Default::default() };
+        let image = render_guide(&variation, &font, &dotted, REFERENCE_SIZE).unwrap();
+
+        // One dot per spacing along both contours of the 'O'
+        let outline = glyph_outline(&variation, &font, &HashMap::new(), REFERENCE_SIZE).unwrap();
+        assert_eq!(outline.len(), 2);
+        let expected: usize = outline.iter()
+            .map(|contour| (arc_lengths(contour).last().unwrap() / dotted.guide.spacing).round() as usize)
+            .sum();
+        let size = REFERENCE_SIZE as usize;
+        assert_eq!(connected_components(&ink(&image)).len(), expected);
+
+        // Dots sit on the edge of the solid glyph
+        let solid = generate_reference_gray(&variation, &font, &HashMap::new(), REFERENCE_SIZE).unwrap();
+        let solid_ink = ink(&solid);
+        let near_edge = |i: usize| {
+            let (x, y) = ((i % size) as i32, (i / size) as i32);
+            let mut seen = [false; 2];
+            for dy in -3..=3 {
+                for dx in -3..=3 {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx >= 0 && ny >= 0 && (nx as usize) < size && (ny as usize) < size {
+                        seen[solid_ink.get(nx as usize, ny as usize) as usize] = true;
+                    }
+                }
+            }
+            seen[0] && seen[1]
+        };
+        assert!(ink(&image).indices().all(near_edge));
+
+        let dashed = ScoringOptions { guide: GuideOptions { line: GuideLine::Dashed, ..Default::default() }, ..Default::default() };
+        let dashes = render_guide(&variation, &font, &dashed, REFERENCE_SIZE).unwrap();
+        assert!(connected_components(&ink(&dashes)).len() < expected);
+
+        let bad = ScoringOptions { guide: GuideOptions { spacing: 0.0, ..dotted.guide.clone() }, ..Default::default() };
+        assert!(render_guide(&variation, &font, &bad, REFERENCE_SIZE).is_err());
+
+        // Tiny spacings and dashes are raised to a pixel rather than placing
+        // billions of marks
+        for line in [GuideLine::Dotted, GuideLine::Dashed] {
+            let guide = GuideOptions { line, size: 1.0, spacing: 1e-6, dash_length: 1e-6, ..Default::default() };
+            let tiny = ScoringOptions { guide, ..Default::default() };
+            let floor = ScoringOptions { guide: GuideOptions { spacing: 1.0, dash_length: 1.0, ..tiny.guide.clone() }, ..Default::default() };
+            assert_eq!(render_guide(&variation, &font, &tiny, REFERENCE_SIZE).unwrap(),
+                render_guide(&variation, &font, &floor, REFERENCE_SIZE).unwrap());
+        }
+    }
+
+    #[cfg(feature = "stroke-templates")]
+    #[test]
+    fn test_stroke_order_guide() {
+        let font = font();
+        let options = ScoringOptions::default();
+        let solid = generate_reference_gray(&ReferenceVariation::identity('T'), &font, &HashMap::new(), REFERENCE_SIZE).unwrap();
+
+        // The fitted template lies on the glyph
+        let template = crate::templates::template_for('T').unwrap();
+        let strokes = fit_template(template, &solid).unwrap();
+        for &(x, y) in strokes.iter().flatten() {
+            assert!(solid.get_pixel(x as u32, y as u32).0[0] < 128, "({}, {}) is off the glyph", x, y);
+        }
+
+        // Letter in gray, black marks, and a white number on each dot
+        let image = render_stroke_order('T', &font, &options, REFERENCE_SIZE).unwrap();
+        assert!(image.pixels().any(|p| p.0[0] == ORDER_GLYPH_GRAY));
+        assert!(image.pixels().any(|p| p.0[0] == 0));
+        let start = strokes[0][0];
+        let radius = REFERENCE_SIZE as f32 * ORDER_DOT_RADIUS;
+        let on_dot = image.enumerate_pixels()
+            .filter(|(x, y, _)| distance((*x as f32 + 0.5, *y as f32 + 0.5), start) < radius * 0.8);
+        assert!(on_dot.clone().any(|(_, _, p)| p.0[0] > 200));
+        assert!(on_dot.clone().any(|(_, _, p)| p.0[0] == 0));
+
+        assert!(render_stroke_order('#', &font, &options, REFERENCE_SIZE).is_err());
+    }
+
+    #[cfg(feature = "stroke-templates")]
+    #[test]
+    fn test_stroke_animation() {
+        let font = font();
+        let options = ScoringOptions::default();
+        let frames = render_stroke_animation('T', &font, &options, REFERENCE_SIZE, 5).unwrap();
+        assert_eq!(frames.len(), 5);
+
+        // Ink only grows, from none to the whole letter
+        let black = |frame: &GrayImage| frame.pixels().filter(|p| p.0[0] < 128).count();
+        assert_eq!(black(&frames[0]), 0);
+        for pair in frames.windows(2) {
+            assert!(black(&pair[1]) > black(&pair[0]));
+        }
+        let solid = render_reference(&ReferenceVariation::identity('T'), &font, &options, REFERENCE_SIZE).unwrap();
+        assert_eq!(frames[4], solid);
+
+        // The bar comes first: a quarter of the way, all ink is on the bar
+        let template = crate::templates::template_for('T').unwrap();
+        let bar_y = fit_template(template, &solid).unwrap()[0][0].1;
+        assert!(frames[1].enumerate_pixels().filter(|(_, _, p)| p.0[0] < 128).all(|(_, y, _)| (y as f32 - bar_y).abs() < 20.0));
+
+        assert!(render_stroke_animation('T', &font, &options, REFERENCE_SIZE, 1).is_err());
+
+        let sheet = render_stroke_animation_sheet('T', &font, &options, 64, 5).unwrap();
+        assert_eq!(sheet.dimensions(), (320, 64));
+        // Too wide for the input limits, or for a u32 at all
+        assert!(render_stroke_animation_sheet('T', &font, &options, 1000, 5).is_err());
+        assert!(render_stroke_animation_sheet('T', &font, &options, u32::MAX / 2, 3).is_err());
+    }
+
+    #[test]
+    fn test_writing_lines_follow_font_metrics() {
+        let font = font();
+        let options = ScoringOptions::default();
+        // An 'x' sits on the baseline and reaches the midline
+        let variation = ReferenceVariation::identity('x');
+        let [topline, midline, baseline] = writing_lines(&variation, &font, &options, REFERENCE_SIZE).unwrap().unwrap();
+        let solid = generate_reference_gray(&variation, &font, &HashMap::new(), REFERENCE_SIZE).unwrap();
+        let rows: Vec<u32> = solid.enumerate_pixels().filter(|(_, _, p)| p.0[0] < 128).map(|(_, y, _)| y).collect();
+        let (ink_top, ink_bottom) = (*rows.iter().min().unwrap() as f32, *rows.iter().max().unwrap() as f32);
+        assert!((midline - ink_top).abs() <= 2.0, "{} vs {}", midline, ink_top);
+        assert!((baseline - ink_bottom).abs() <= 2.0, "{} vs {}", baseline, ink_bottom);
+        assert!(topline < midline - 10.0);
+    }
+
+    #[test]
+    fn test_worksheet() {
+        let font = font();
+        let options = ScoringOptions::default();
+        let page = render_worksheet("ab", &font, 3, 4, &options).unwrap();
+        let margin = WORKSHEET_CELL / 4;
+        assert_eq!(page.dimensions(), (4 * WORKSHEET_CELL + 2 * margin, 3 * WORKSHEET_CELL + 4 * margin));
+
+        // Black model, faded copies, gray rules; rows cycle through the characters
+        let cell = |row: u32, col: u32| {
+            let mut image = GrayImage::new(WORKSHEET_CELL, WORKSHEET_CELL);
+            image::imageops::overlay(&mut image, &image::imageops::crop_imm(
+                &page, margin + col * WORKSHEET_CELL, margin + row * (WORKSHEET_CELL + margin), WORKSHEET_CELL, WORKSHEET_CELL,
+            ).to_image(), 0, 0);
+            image
+        };
+        let darkest = |image: &GrayImage| image.pixels().map(|p| p.0[0]).min().unwrap();
+        assert_eq!(darkest(&cell(0, 0)), 0);
+        assert_eq!(darkest(&cell(0, 1)), WORKSHEET_LINE_GRAY);
+        assert!(cell(0, 1).pixels().any(|p| p.0[0] == WORKSHEET_TRACE_GRAY));
+        assert_eq!(cell(2, 0), cell(0, 0));
+        assert_ne!(cell(1, 0), cell(0, 0));
+
+        assert!(render_worksheet("", &font, 1, 2, &options).is_err());
+        assert!(render_worksheet("a", &font, 1, 1, &options).is_err());
+    }
+
+    #[test]
+    fn test_ruled_reference() {
+        use crate::scoring::generate_reference_image_internal;
+
+        let font = font();
+        let ruling = RulingOptions { thickness: 3.0, ..Default::default() };
+        let options = ScoringOptions {
+            guide: GuideOptions { ruling: Some(ruling.clone()), ..Default::default() },
+            ..Default::default()
+        };
+        let png = generate_reference_image_internal('x', &font, REFERENCE_SIZE, &options).unwrap();
+        let image = image::load_from_memory(&png).unwrap().to_rgb8();
+
+        // Solid blue topline, dashed blue midline, red baseline beside the
+        // letter; black letter
+        let [topline, midline, baseline] = writing_lines(&ReferenceVariation::identity('x'), &font, &options, REFERENCE_SIZE).unwrap().unwrap();
+        let row = |y: f32| (0..REFERENCE_SIZE).map(|x| *image.get_pixel(x, y.round() as u32)).collect::<Vec<_>>();
+        let (blue, red) = (Rgb([0x33, 0x66, 0xcc]), Rgb([0xcc, 0x33, 0x33]));
+        assert!(row(topline).iter().all(|&p| p == blue));
+        assert_eq!(row(baseline)[0], red);
+        let dashes = row(midline)[..40].iter().filter(|&&p| p == blue).count();
+        assert!(dashes > 10 && dashes < 30);
+        let center = REFERENCE_SIZE / 2;
+        assert_eq!(*image.get_pixel(center, ((midline + baseline) / 2.0) as u32), Rgb([0, 0, 0]));
+
+        assert_eq!(parse_color("#fff").unwrap(), Rgb([255, 255, 255]));
+        assert!(parse_color("3366cc").is_err());
+        assert!(parse_color("#33zz66").is_err());
+        let bad = ScoringOptions {
+            guide: GuideOptions { ruling: Some(RulingOptions { midline_color: "blue".to_string(), ..ruling }), ..Default::default() },
+            ..Default::default()
+        };
+        assert!(generate_reference_image_internal('x', &font, REFERENCE_SIZE, &bad).is_err());
+    }
+
+    #[cfg(feature = "shapes")]
+    #[test]
+    fn test_styled_guide_follows_centerline() {
+        // Ball-and-stick '1' is a single line: dots from end to end
+        let options = ScoringOptions {
+            style: Some("ball_and_stick".to_string()),
+            guide: GuideOptions { line: GuideLine::Dotted, ..Default::default() },
+            ..Default::default()
+        };
+        let image = render_guide(&ReferenceVariation::identity('1'), &font(), &options, REFERENCE_SIZE).unwrap();
+        let components = connected_components(&ink(&image));
+        assert!(components.len() > 5);
+        assert!(components.iter().all(|c| c.max_x - c.min_x < 8));
+    }
+}
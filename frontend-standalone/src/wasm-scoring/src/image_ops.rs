@@ -2,16 +2,149 @@
 //!
 //! Implements distance transforms, morphological operations, and skeleton extraction.
 
-/// Euclidean Distance Transform using the Meijster algorithm
-/// O(n) per dimension, very efficient for image processing
-pub fn distance_transform_edt(binary: &[bool], width: usize, height: usize) -> Vec<f32> {
+use crate::bit_image::BitImage;
+use crate::error::ScoringError;
+use crate::logging::log;
+use crate::parallel;
+use crate::simd;
+use crate::timing::{self, Stage};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+// Squared distance standing in for "no ink in this row or column"
+const EDT_UNREACHED: f32 = f32::INFINITY;
+// Each thinning iteration peels at most one pixel off each side of a
+// stroke, so this is far more than any letter needs; noisy input that keeps
+// changing stops here
+const MAX_THINNING_ITERATIONS: u32 = 256;
+// Pixels examined across all thinning passes: every iteration over the
+// largest (512-pixel) comparison frame, so it only binds on images larger
+// than any frame scoring thins
+const MAX_THINNING_PIXEL_VISITS: u64 = MAX_THINNING_ITERATIONS as u64 * 2 * 512 * 512;
+
+thread_local! {
+    // Algorithm, limits and cancellation of the scoring call running on
+    // this thread
+    static THINNING: RefCell<Option<Thinning>> = const { RefCell::new(None) };
+}
+
+/// Exact Euclidean distance transform (Felzenszwalb and Huttenlocher)
+///
+/// Distance from each pixel to the nearest `true` pixel, computed as a scan
+/// down each column and then a lower envelope of parabolas along each row;
+/// O(n) in the pixel count. An image with no ink is `f32::MAX` everywhere.
+/// With the `chamfer-edt` feature this falls back to the older chamfer
+/// approximation, to compare scores against earlier builds.
+pub fn distance_transform_edt(binary: &BitImage) -> Vec<f32> {
+    let mut distances = Vec::new();
+    distance_transform_edt_into(binary, &mut distances);
+    distances
+}
+
+/// `distance_transform_edt` into a buffer kept between calls
+pub fn distance_transform_edt_into(binary: &BitImage, squared: &mut Vec<f32>) {
+    let lap = timing::lap();
+    if cfg!(feature = "chamfer-edt") {
+        *squared = distance_transform_chamfer(binary);
+    } else {
+        exact_distance_transform(binary, squared);
+    }
+    lap.record(Stage::DistanceTransform);
+    log!(Trace, "distance transform of {}x{}, {} ink pixels", binary.width(), binary.height(), binary.count());
+}
+
+fn exact_distance_transform(binary: &BitImage, squared: &mut Vec<f32>) {
+    let (width, height) = (binary.width(), binary.height());
+    squared.clear();
+    if width == 0 || height == 0 {
+        return;
+    }
+    squared.resize(width * height, EDT_UNREACHED);
+    for index in binary.indices() {
+        squared[index] = 0.0;
+    }
+
+    // Down each column: the distance to the nearest ink in that column, as
+    // a scan down and a scan up over whole rows at a time
+    for y in 1..height {
+        let (done, rest) = squared.split_at_mut(y * width);
+        simd::relax(&mut rest[..width], &done[(y - 1) * width..]);
+    }
+    for y in (0..height - 1).rev() {
+        let (head, tail) = squared.split_at_mut((y + 1) * width);
+        simd::relax(&mut head[y * width..], &tail[..width]);
+    }
+    simd::square(squared);
+
+    // Along each row, independently of the others
+    parallel::for_each_chunk(
+        squared,
+        width,
+        || (vec![0.0; width], Vec::new(), Vec::new()),
+        |(line, sites, bounds), row| {
+            line.copy_from_slice(row);
+            squared_distance_1d(line, row, sites, bounds);
+        },
+    );
+
+    simd::sqrt_clamped(squared);
+}
+
+/// One-dimensional squared distance transform of the sampled function `f`:
+/// `out[i]` is the least `(i - q)^2 + f[q]`. `sites` and `bounds` are
+/// scratch space for the envelope's parabolas and where each takes over.
+fn squared_distance_1d(f: &[f32], out: &mut [f32], sites: &mut Vec<usize>, bounds: &mut Vec<f32>) {
+    sites.clear();
+    bounds.clear();
+    let lifted = |q: usize| f[q] + (q * q) as f32;
+    for q in (0..f.len()).filter(|&q| f[q] != EDT_UNREACHED) {
+        // Drop parabolas the new one hides entirely
+        while let Some(&v) = sites.last() {
+            let crossing = (lifted(q) - lifted(v)) / (2 * (q - v)) as f32;
+            if sites.len() > 1 && crossing <= bounds[bounds.len() - 1] {
+                sites.pop();
+                bounds.pop();
+            } else {
+                bounds.push(crossing);
+                break;
+            }
+        }
+        if sites.is_empty() {
+            bounds.push(f32::NEG_INFINITY);
+        }
+        sites.push(q);
+    }
+
+    if sites.is_empty() {
+        out.fill(EDT_UNREACHED);
+        return;
+    }
+    let mut k = 0;
+    for (i, value) in out.iter_mut().enumerate() {
+        while k + 1 < sites.len() && bounds[k + 1] < i as f32 {
+            k += 1;
+        }
+        let d = i.abs_diff(sites[k]);
+        *value = (d * d) as f32 + f[sites[k]];
+    }
+}
+
+/// Two-pass 3x3 chamfer approximation of the distance transform, the
+/// engine's transform before the exact one; overestimates off-axis
+/// distances by up to about 8%
+fn distance_transform_chamfer(binary: &BitImage) -> Vec<f32> {
+    let (width, height) = (binary.width(), binary.height());
     let mut result = vec![f32::MAX; width * height];
 
     // First pass: forward scan
     for y in 0..height {
         for x in 0..width {
             let idx = y * width + x;
-            if binary[idx] {
+            if binary.get(x, y) {
                 result[idx] = 0.0;
             } else {
                 let mut min_dist = f32::MAX;
@@ -58,125 +191,188 @@ pub fn distance_transform_edt(binary: &[bool], width: usize, height: usize) -> V
     result
 }
 
-/// Binary dilation with a 3x3 structuring element
-pub fn binary_dilation(binary: &[bool], width: usize, height: usize, iterations: u32) -> Vec<bool> {
-    let mut current = binary.to_vec();
-    let mut next = vec![false; width * height];
-
-    for _ in 0..iterations {
-        for y in 0..height {
-            for x in 0..width {
-                let idx = y * width + x;
-
-                // Check 3x3 neighborhood
-                let mut has_neighbor = false;
-                for dy in -1i32..=1 {
-                    for dx in -1i32..=1 {
-                        let ny = y as i32 + dy;
-                        let nx = x as i32 + dx;
-
-                        if ny >= 0 && ny < height as i32 && nx >= 0 && nx < width as i32 {
-                            let nidx = ny as usize * width + nx as usize;
-                            if current[nidx] {
-                                has_neighbor = true;
-                                break;
-                            }
-                        }
-                    }
-                    if has_neighbor {
-                        break;
-                    }
-                }
-
-                next[idx] = has_neighbor;
-            }
-        }
+/// Bounds on the work thinning may do
+///
+/// Reaching either returns the image thinned as far as it got.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThinningLimits {
+    pub max_iterations: u32,
+    /// Pixels examined, summed over every pass
+    pub max_pixel_visits: u64,
+}
 
-        std::mem::swap(&mut current, &mut next);
+impl Default for ThinningLimits {
+    fn default() -> Self {
+        Self { max_iterations: MAX_THINNING_ITERATIONS, max_pixel_visits: MAX_THINNING_PIXEL_VISITS }
     }
-
-    current
 }
 
-/// Binary erosion with a 3x3 structuring element
-pub fn binary_erosion(binary: &[bool], width: usize, height: usize, iterations: u32) -> Vec<bool> {
-    let mut current = binary.to_vec();
-    let mut next = vec![false; width * height];
+/// Cooperative cancellation of a scoring call
+///
+/// Clones share one flag, so a token kept by the caller can stop a call
+/// given its clone, e.g. from another thread or a JavaScript callback that
+/// runs while `score_drawing_async` yields. Thinning checks it between
+/// passes and scoring between stages.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-    for _ in 0..iterations {
-        for y in 0..height {
-            for x in 0..width {
-                let idx = y * width + x;
+    /// Ask calls holding this token to stop
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
 
-                // Check if all 3x3 neighbors are set
-                let mut all_neighbors = true;
-                for dy in -1i32..=1 {
-                    for dx in -1i32..=1 {
-                        let ny = y as i32 + dy;
-                        let nx = x as i32 + dx;
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
 
-                        if ny >= 0 && ny < height as i32 && nx >= 0 && nx < width as i32 {
-                            let nidx = ny as usize * width + nx as usize;
-                            if !current[nidx] {
-                                all_neighbors = false;
-                                break;
-                            }
-                        } else {
-                            all_neighbors = false;
-                            break;
-                        }
-                    }
-                    if !all_neighbors {
-                        break;
-                    }
-                }
+/// Thinning settings of the scoring call on this thread
+#[derive(Clone, Default)]
+struct Thinning {
+    algorithm: ThinningAlgorithm,
+    limits: ThinningLimits,
+    cancel: Option<CancelToken>,
+}
 
-                next[idx] = all_neighbors;
+/// Thinning algorithm, limits and cancellation for one scoring call, in
+/// effect on this thread until dropped
+pub(crate) struct ThinningScope(());
+
+impl ThinningScope {
+    /// Thin with `algorithm` within `limits` and stop on `cancel` until the
+    /// scope drops, unless a caller already set a scope
+    pub(crate) fn enter(algorithm: ThinningAlgorithm, limits: ThinningLimits, cancel: Option<CancelToken>) -> Option<ThinningScope> {
+        THINNING.with(|thinning| {
+            let mut thinning = thinning.borrow_mut();
+            if thinning.is_some() {
+                return None;
             }
-        }
+            *thinning = Some(Thinning { algorithm, limits, cancel });
+            Some(ThinningScope(()))
+        })
+    }
+}
 
-        std::mem::swap(&mut current, &mut next);
+impl Drop for ThinningScope {
+    fn drop(&mut self) {
+        THINNING.with(|thinning| thinning.borrow_mut().take());
     }
+}
 
-    current
+/// Whether the scoring call on this thread was cancelled
+pub(crate) fn cancelled() -> bool {
+    THINNING.with(|thinning| thinning.borrow().as_ref().and_then(|t| t.cancel.as_ref()).is_some_and(CancelToken::is_cancelled))
 }
 
-/// Zhang-Suen thinning algorithm for skeleton extraction
-pub fn skeletonize(binary: &[bool], width: usize, height: usize) -> Vec<bool> {
-    let mut current = binary.to_vec();
+/// `Err(ScoringError::Cancelled)` once the scoring call on this thread was
+/// cancelled, for checks between stages
+pub(crate) fn check_cancelled() -> Result<(), ScoringError> {
+    if cancelled() { Err(ScoringError::Cancelled) } else { Ok(()) }
+}
 
-    loop {
+/// Run `f` thinning with `algorithm`, within the default limits and with
+/// no cancellation
+///
+/// Prepared references are cached and shared by calls with other limits
+/// and tokens, so they are always thinned to the end.
+pub(crate) fn thin_fully<T>(algorithm: ThinningAlgorithm, f: impl FnOnce() -> T) -> T {
+    let outer = THINNING.with(|thinning| thinning.replace(Some(Thinning { algorithm, ..Default::default() })));
+    let result = f();
+    THINNING.with(|thinning| thinning.replace(outer));
+    result
+}
+
+/// Rule deciding which border pixels each thinning pass peels off
+///
+/// Set per call by `ScoringOptions::thinning`. A drawing is always thinned
+/// like its reference, so packs and serialized handles record the
+/// algorithm they were built with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThinningAlgorithm {
+    /// Zhang-Suen; leaves staircase steps two pixels thick on diagonals
+    ZhangSuen,
+    /// Guo-Hall; thins diagonals to single pixels
+    GuoHall,
+}
+
+impl Default for ThinningAlgorithm {
+    /// Zhang-Suen, or Guo-Hall with the `guo-hall` feature
+    fn default() -> Self {
+        if cfg!(feature = "guo-hall") { ThinningAlgorithm::GuoHall } else { ThinningAlgorithm::ZhangSuen }
+    }
+}
+
+/// Thin a mask to a one-pixel skeleton
+///
+/// With the algorithm and within the limits of the scoring call on this
+/// thread, if any, else the defaults; a cancelled call gets the mask back
+/// unthinned, and its next stage check returns the error.
+pub fn skeletonize(binary: &BitImage) -> BitImage {
+    let Thinning { algorithm, limits, cancel } = THINNING.with(|thinning| thinning.borrow().clone()).unwrap_or_default();
+    skeletonize_with(binary, algorithm, &limits, cancel.as_ref()).unwrap_or_else(|_| binary.clone())
+}
+
+/// `skeletonize` with the given algorithm within `limits`, checking
+/// `cancel` between passes
+pub fn skeletonize_with(
+    binary: &BitImage,
+    algorithm: ThinningAlgorithm,
+    limits: &ThinningLimits,
+    cancel: Option<&CancelToken>,
+) -> Result<BitImage, ScoringError> {
+    let lap = timing::lap();
+    let (width, height) = (binary.width(), binary.height());
+    let mut current = binary.clone();
+    let pass_visits = (width.saturating_sub(2) * height.saturating_sub(2)) as u64 * 2;
+    let mut visits = 0u64;
+
+    let mut iterations = 0;
+    for _ in 0..limits.max_iterations {
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            return Err(ScoringError::Cancelled);
+        }
+        iterations += 1;
+        visits += pass_visits;
+        if visits > limits.max_pixel_visits {
+            break;
+        }
         let mut changed = false;
 
         // Sub-iteration 1
         let mut to_remove = Vec::new();
-        for y in 1..height - 1 {
-            for x in 1..width - 1 {
-                let idx = y * width + x;
-                if current[idx] && should_remove_subiteration1(&current, x, y, width) {
-                    to_remove.push(idx);
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                if current.get(x, y) && removable(algorithm, 0, &current, x, y) {
+                    to_remove.push((x, y));
                 }
             }
         }
 
-        for idx in &to_remove {
-            current[*idx] = false;
+        for &(x, y) in &to_remove {
+            current.set(x, y, false);
             changed = true;
         }
 
         // Sub-iteration 2
         to_remove.clear();
-        for y in 1..height - 1 {
-            for x in 1..width - 1 {
-                let idx = y * width + x;
-                if current[idx] && should_remove_subiteration2(&current, x, y, width) {
-                    to_remove.push(idx);
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                if current.get(x, y) && removable(algorithm, 1, &current, x, y) {
+                    to_remove.push((x, y));
                 }
             }
         }
 
-        for idx in &to_remove {
-            current[*idx] = false;
+        for &(x, y) in &to_remove {
+            current.set(x, y, false);
             changed = true;
         }
 
@@ -185,20 +381,31 @@ pub fn skeletonize(binary: &[bool], width: usize, height: usize) -> Vec<bool> {
         }
     }
 
-    current
+    lap.record(Stage::Skeleton);
+    log!(Trace, "thinned {}x{} in {} iterations, {} to {} pixels", width, height, iterations, binary.count(), current.count());
+    Ok(current)
 }
 
-fn get_neighbors(binary: &[bool], x: usize, y: usize, width: usize) -> [bool; 8] {
+/// Whether `algorithm` peels the pixel at (x, y) off in sub-iteration `pass`
+fn removable(algorithm: ThinningAlgorithm, pass: usize, binary: &BitImage, x: usize, y: usize) -> bool {
+    match (algorithm, pass) {
+        (ThinningAlgorithm::ZhangSuen, 0) => should_remove_subiteration1(binary, x, y),
+        (ThinningAlgorithm::ZhangSuen, _) => should_remove_subiteration2(binary, x, y),
+        (ThinningAlgorithm::GuoHall, _) => guo_hall_removable(&get_neighbors(binary, x, y), pass),
+    }
+}
+
+fn get_neighbors(binary: &BitImage, x: usize, y: usize) -> [bool; 8] {
     // P2, P3, P4, P5, P6, P7, P8, P9 in clockwise order starting from top
     [
-        binary[(y - 1) * width + x],     // P2 (top)
-        binary[(y - 1) * width + x + 1], // P3 (top-right)
-        binary[y * width + x + 1],       // P4 (right)
-        binary[(y + 1) * width + x + 1], // P5 (bottom-right)
-        binary[(y + 1) * width + x],     // P6 (bottom)
-        binary[(y + 1) * width + x - 1], // P7 (bottom-left)
-        binary[y * width + x - 1],       // P8 (left)
-        binary[(y - 1) * width + x - 1], // P9 (top-left)
+        binary.get(x, y - 1),     // P2 (top)
+        binary.get(x + 1, y - 1), // P3 (top-right)
+        binary.get(x + 1, y),     // P4 (right)
+        binary.get(x + 1, y + 1), // P5 (bottom-right)
+        binary.get(x, y + 1),     // P6 (bottom)
+        binary.get(x - 1, y + 1), // P7 (bottom-left)
+        binary.get(x - 1, y),     // P8 (left)
+        binary.get(x - 1, y - 1), // P9 (top-left)
     ]
 }
 
@@ -216,58 +423,72 @@ fn count_neighbors(neighbors: &[bool; 8]) -> u32 {
     neighbors.iter().filter(|&&x| x).count() as u32
 }
 
-fn should_remove_subiteration1(binary: &[bool], x: usize, y: usize, width: usize) -> bool {
-    let neighbors = get_neighbors(binary, x, y, width);
+fn should_remove_subiteration1(binary: &BitImage, x: usize, y: usize) -> bool {
+    let neighbors = get_neighbors(binary, x, y);
     let n = count_neighbors(&neighbors);
     let t = count_transitions(&neighbors);
 
     // Conditions for sub-iteration 1
-    n >= 2 && n <= 6 &&
+    (2..=6).contains(&n) &&
     t == 1 &&
     !(neighbors[0] && neighbors[2] && neighbors[4]) && // P2 * P4 * P6
     !(neighbors[2] && neighbors[4] && neighbors[6])    // P4 * P6 * P8
 }
 
-fn should_remove_subiteration2(binary: &[bool], x: usize, y: usize, width: usize) -> bool {
-    let neighbors = get_neighbors(binary, x, y, width);
+fn should_remove_subiteration2(binary: &BitImage, x: usize, y: usize) -> bool {
+    let neighbors = get_neighbors(binary, x, y);
     let n = count_neighbors(&neighbors);
     let t = count_transitions(&neighbors);
 
     // Conditions for sub-iteration 2
-    n >= 2 && n <= 6 &&
+    (2..=6).contains(&n) &&
     t == 1 &&
     !(neighbors[0] && neighbors[2] && neighbors[6]) && // P2 * P4 * P8
     !(neighbors[0] && neighbors[4] && neighbors[6])    // P2 * P6 * P8
 }
 
+/// Guo-Hall deletion test
+///
+/// A pixel goes if it joins exactly one run of background (`c`), has two or
+/// three neighbour pairs holding ink (`n`, so endpoints and thick interiors
+/// stay), and isn't on the side the other pass handles (`m`).
+fn guo_hall_removable(neighbors: &[bool; 8], pass: usize) -> bool {
+    let [p2, p3, p4, p5, p6, p7, p8, p9] = *neighbors;
+    let c = [(!p2 && (p3 || p4)), (!p4 && (p5 || p6)), (!p6 && (p7 || p8)), (!p8 && (p9 || p2))]
+        .iter().filter(|&&b| b).count();
+    let n1 = [p9 || p2, p3 || p4, p5 || p6, p7 || p8].iter().filter(|&&b| b).count();
+    let n2 = [p2 || p3, p4 || p5, p6 || p7, p8 || p9].iter().filter(|&&b| b).count();
+    let m = if pass == 0 { (p6 || p7 || !p9) && p8 } else { (p2 || p3 || !p5) && p4 };
+
+    c == 1 && (2..=3).contains(&n1.min(n2)) && !m
+}
+
 /// Find endpoints in a skeleton (pixels with exactly 1 neighbor)
-pub fn find_endpoints(skeleton: &[bool], width: usize, height: usize) -> Vec<(usize, usize)> {
+pub fn find_endpoints(skeleton: &BitImage) -> Vec<(usize, usize)> {
+    let (width, height) = (skeleton.width(), skeleton.height());
     let mut endpoints = Vec::new();
 
-    for y in 1..height - 1 {
-        for x in 1..width - 1 {
-            let idx = y * width + x;
-            if !skeleton[idx] {
-                continue;
-            }
+    for (x, y) in skeleton.ones() {
+        if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+            continue;
+        }
 
-            let mut neighbor_count = 0;
-            for dy in -1i32..=1 {
-                for dx in -1i32..=1 {
-                    if dy == 0 && dx == 0 {
-                        continue;
-                    }
-                    let ny = (y as i32 + dy) as usize;
-                    let nx = (x as i32 + dx) as usize;
-                    if skeleton[ny * width + nx] {
-                        neighbor_count += 1;
-                    }
+        let mut neighbor_count = 0;
+        for dy in -1i32..=1 {
+            for dx in -1i32..=1 {
+                if dy == 0 && dx == 0 {
+                    continue;
+                }
+                let ny = (y as i32 + dy) as usize;
+                let nx = (x as i32 + dx) as usize;
+                if skeleton.get(nx, ny) {
+                    neighbor_count += 1;
                 }
             }
+        }
 
-            if neighbor_count == 1 {
-                endpoints.push((x, y));
-            }
+        if neighbor_count == 1 {
+            endpoints.push((x, y));
         }
     }
 
@@ -275,10 +496,17 @@ pub fn find_endpoints(skeleton: &[bool], width: usize, height: usize) -> Vec<(us
 }
 
 /// Bridge small gaps between endpoints
-pub fn bridge_gaps(skeleton: &mut Vec<bool>, width: usize, height: usize, max_gap: u32) {
-    let endpoints = find_endpoints(skeleton, width, height);
+///
+/// An endpoint is linked to the nearest skeleton pixel within `max_gap`
+/// that isn't on its own stroke close by, i.e. within twice `max_gap`
+/// along the skeleton; linking back onto its own stroke would only draw a
+/// spur.
+pub fn bridge_gaps(skeleton: &mut BitImage, max_gap: u32) {
+    let (width, height) = (skeleton.width(), skeleton.height());
+    let endpoints = find_endpoints(skeleton);
 
     for (ex, ey) in &endpoints {
+        let own_stroke = skeleton_within(skeleton, (*ex, *ey), 2 * max_gap);
         let mut best_target: Option<(usize, usize)> = None;
         let mut best_dist = max_gap as f32 + 1.0;
 
@@ -300,12 +528,7 @@ pub fn bridge_gaps(skeleton: &mut Vec<bool>, width: usize, height: usize, max_ga
                 let ty = ty as usize;
                 let tx = tx as usize;
 
-                if !skeleton[ty * width + tx] {
-                    continue;
-                }
-
-                // Skip direct neighbors
-                if dy.abs() <= 1 && dx.abs() <= 1 {
+                if !skeleton.get(tx, ty) || own_stroke.get(tx, ty) {
                     continue;
                 }
 
@@ -319,13 +542,35 @@ pub fn bridge_gaps(skeleton: &mut Vec<bool>, width: usize, height: usize, max_ga
 
         // Draw line to connect
         if let Some((tx, ty)) = best_target {
-            draw_line(skeleton, width, *ex, *ey, tx, ty);
+            draw_line(skeleton, *ex, *ey, tx, ty);
+        }
+    }
+}
+
+/// Skeleton pixels at most `steps` 8-connected steps from `start` along
+/// the skeleton
+fn skeleton_within(skeleton: &BitImage, start: (usize, usize), steps: u32) -> BitImage {
+    let mut reached = BitImage::new(skeleton.width(), skeleton.height());
+    reached.set(start.0, start.1, true);
+    let mut frontier = vec![start];
+    for _ in 0..steps {
+        let mut next = Vec::new();
+        for (x, y) in frontier {
+            for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if skeleton.get_signed(nx, ny) && !reached.get(nx as usize, ny as usize) {
+                    reached.set(nx as usize, ny as usize, true);
+                    next.push((nx as usize, ny as usize));
+                }
+            }
         }
+        frontier = next;
     }
+    reached
 }
 
 /// Bresenham's line algorithm
-fn draw_line(image: &mut Vec<bool>, width: usize, x0: usize, y0: usize, x1: usize, y1: usize) {
+fn draw_line(image: &mut BitImage, x0: usize, y0: usize, x1: usize, y1: usize) {
     let dx = (x1 as i32 - x0 as i32).abs();
     let dy = -(y1 as i32 - y0 as i32).abs();
     let sx = if x0 < x1 { 1i32 } else { -1i32 };
@@ -336,11 +581,8 @@ fn draw_line(image: &mut Vec<bool>, width: usize, x0: usize, y0: usize, x1: usiz
     let mut y = y0 as i32;
 
     loop {
-        if x >= 0 && y >= 0 {
-            let idx = y as usize * width + x as usize;
-            if idx < image.len() {
-                image[idx] = true;
-            }
+        if x >= 0 && y >= 0 && (x as usize) < image.width() && (y as usize) < image.height() {
+            image.set(x as usize, y as usize, true);
         }
 
         if x == x1 as i32 && y == y1 as i32 {
@@ -360,8 +602,12 @@ fn draw_line(image: &mut Vec<bool>, width: usize, x0: usize, y0: usize, x1: usiz
 }
 
 /// Prune short branches from a skeleton
-pub fn prune_branches(skeleton: &mut Vec<bool>, width: usize, height: usize, prune_length: u32, max_removal_percent: f32) {
-    let initial_pixels: u32 = skeleton.iter().filter(|&&x| x).count() as u32;
+///
+/// Endpoints are peeled one pixel per round. A piece is never pruned away
+/// entirely: the dot of an i can thin to two pixels, each the other's only
+/// neighbour, and only the first of them goes.
+pub fn prune_branches(skeleton: &mut BitImage, prune_length: u32, max_removal_percent: f32) {
+    let initial_pixels = skeleton.count() as u32;
     let max_removal = (initial_pixels as f32 * max_removal_percent) as u32;
     let mut total_removed: u32 = 0;
 
@@ -370,23 +616,499 @@ pub fn prune_branches(skeleton: &mut Vec<bool>, width: usize, height: usize, pru
             break;
         }
 
-        let endpoints = find_endpoints(skeleton, width, height);
+        let endpoints = find_endpoints(skeleton);
         if endpoints.is_empty() {
             break;
         }
 
-        let to_remove: Vec<_> = endpoints.iter()
-            .take((max_removal - total_removed) as usize)
-            .map(|(x, y)| y * width + x)
-            .collect();
-
-        for idx in &to_remove {
-            skeleton[*idx] = false;
+        for &(x, y) in endpoints.iter().take((max_removal - total_removed) as usize) {
+            if !has_neighbor(skeleton, x, y) {
+                continue;
+            }
+            skeleton.set(x, y, false);
             total_removed += 1;
         }
     }
 }
 
+fn has_neighbor(skeleton: &BitImage, x: usize, y: usize) -> bool {
+    (-1i32..=1).any(|dy| (-1i32..=1).any(|dx| (dx, dy) != (0, 0) && skeleton.get_signed(x as i32 + dx, y as i32 + dy)))
+}
+
+/// Estimate the local stroke tangent orientation of a binary image
+///
+/// Uses Sobel gradients accumulated into a structure tensor over a
+/// `(2 * radius + 1)` square window. Returns angles in radians in
+/// `[0, pi)`; orientation is only meaningful on or near ink pixels.
+pub fn orientation_field(binary: &BitImage, radius: usize) -> Vec<f32> {
+    let mut result = Vec::new();
+    orientation_field_into(binary, radius, None, &mut result);
+    result
+}
+
+/// `orientation_field` into a buffer kept between calls, only at the
+/// pixels set in `at` when given (0 elsewhere)
+pub fn orientation_field_into(binary: &BitImage, radius: usize, at: Option<&BitImage>, result: &mut Vec<f32>) {
+    let (width, height) = (binary.width(), binary.height());
+    let value = |x: i32, y: i32| -> f32 {
+        if binary.get_signed(x, y) {
+            1.0
+        } else {
+            0.0
+        }
+    };
+
+    // Sobel gradients
+    let mut gxx = vec![0.0f32; width * height];
+    let mut gyy = vec![0.0f32; width * height];
+    let mut gxy = vec![0.0f32; width * height];
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let gx = (value(x + 1, y - 1) + 2.0 * value(x + 1, y) + value(x + 1, y + 1))
+                - (value(x - 1, y - 1) + 2.0 * value(x - 1, y) + value(x - 1, y + 1));
+            let gy = (value(x - 1, y + 1) + 2.0 * value(x, y + 1) + value(x + 1, y + 1))
+                - (value(x - 1, y - 1) + 2.0 * value(x, y - 1) + value(x + 1, y - 1));
+            let idx = y as usize * width + x as usize;
+            gxx[idx] = gx * gx;
+            gyy[idx] = gy * gy;
+            gxy[idx] = gx * gy;
+        }
+    }
+
+    // Accumulate the structure tensor over the window
+    let r = radius as i32;
+    result.clear();
+    result.resize(width * height, 0.0);
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            if at.is_some_and(|at| !at.get(x as usize, y as usize)) {
+                continue;
+            }
+            let mut jxx = 0.0f32;
+            let mut jyy = 0.0f32;
+            let mut jxy = 0.0f32;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let nx = x + dx;
+                    let ny = y + dy;
+                    if nx >= 0 && ny >= 0 && nx < width as i32 && ny < height as i32 {
+                        let nidx = ny as usize * width + nx as usize;
+                        jxx += gxx[nidx];
+                        jyy += gyy[nidx];
+                        jxy += gxy[nidx];
+                    }
+                }
+            }
+
+            // Dominant gradient direction is normal to the stroke
+            let normal = 0.5 * (2.0 * jxy).atan2(jxx - jyy);
+            let mut tangent = normal + std::f32::consts::FRAC_PI_2;
+            if tangent >= std::f32::consts::PI {
+                tangent -= std::f32::consts::PI;
+            }
+            if tangent < 0.0 {
+                tangent += std::f32::consts::PI;
+            }
+            result[y as usize * width + x as usize] = tangent;
+        }
+    }
+}
+
+/// Raw and central image moments up to second order
+#[derive(Debug, Clone, Copy)]
+pub struct ImageMoments {
+    pub mass: f32,
+    pub cx: f32,
+    pub cy: f32,
+    pub mu20: f32,
+    pub mu02: f32,
+    pub mu11: f32,
+}
+
+impl ImageMoments {
+    /// Radius of gyration around the centroid
+    pub fn spread(&self) -> f32 {
+        ((self.mu20 + self.mu02) / self.mass).sqrt()
+    }
+
+    /// Angle of the principal axis in radians, in (-pi/2, pi/2]
+    pub fn orientation(&self) -> f32 {
+        0.5 * (2.0 * self.mu11).atan2(self.mu20 - self.mu02)
+    }
+
+    /// Eccentricity of the equivalent ellipse (0 = isotropic, 1 = a line)
+    pub fn eccentricity(&self) -> f32 {
+        let common = ((self.mu20 - self.mu02).powi(2) + 4.0 * self.mu11 * self.mu11).sqrt();
+        let major = self.mu20 + self.mu02 + common;
+        let minor = self.mu20 + self.mu02 - common;
+        if major <= 0.0 {
+            return 0.0;
+        }
+        (1.0 - minor.max(0.0) / major).sqrt()
+    }
+}
+
+/// Compute image moments of a weight map (e.g. ink intensity per pixel)
+///
+/// Returns `None` when the image carries no weight.
+pub fn image_moments(weights: &[f32], width: usize, height: usize) -> Option<ImageMoments> {
+    let mut mass = 0.0f32;
+    let mut sum_x = 0.0f32;
+    let mut sum_y = 0.0f32;
+
+    for y in 0..height {
+        for x in 0..width {
+            let w = weights[y * width + x];
+            mass += w;
+            sum_x += w * x as f32;
+            sum_y += w * y as f32;
+        }
+    }
+
+    if mass <= f32::EPSILON {
+        return None;
+    }
+
+    let cx = sum_x / mass;
+    let cy = sum_y / mass;
+
+    let mut mu20 = 0.0f32;
+    let mut mu02 = 0.0f32;
+    let mut mu11 = 0.0f32;
+    for y in 0..height {
+        for x in 0..width {
+            let w = weights[y * width + x];
+            let dx = x as f32 - cx;
+            let dy = y as f32 - cy;
+            mu20 += w * dx * dx;
+            mu02 += w * dy * dy;
+            mu11 += w * dx * dy;
+        }
+    }
+
+    Some(ImageMoments { mass, cx, cy, mu20, mu02, mu11 })
+}
+
+/// Hu's seven moment invariants of a weight map
+///
+/// Invariant to translation, scale and rotation. Returns `None` when the
+/// image carries no weight.
+pub fn hu_moments(weights: &[f32], width: usize, height: usize) -> Option<[f64; 7]> {
+    let m = image_moments(weights, width, height)?;
+
+    // Central moments up to third order (f64 to keep precision on 3rd order)
+    let mut mu = [[0.0f64; 4]; 4];
+    for y in 0..height {
+        for x in 0..width {
+            let w = weights[y * width + x] as f64;
+            if w == 0.0 {
+                continue;
+            }
+            let dx = x as f64 - m.cx as f64;
+            let dy = y as f64 - m.cy as f64;
+            for (p, row) in mu.iter_mut().enumerate() {
+                for (q, value) in row.iter_mut().enumerate() {
+                    if p + q >= 2 && p + q <= 3 {
+                        *value += w * dx.powi(p as i32) * dy.powi(q as i32);
+                    }
+                }
+            }
+        }
+    }
+
+    // Scale-normalized central moments
+    let mass = m.mass as f64;
+    let eta = |p: usize, q: usize| -> f64 {
+        mu[p][q] / mass.powf(1.0 + (p + q) as f64 / 2.0)
+    };
+    let (n20, n02, n11) = (eta(2, 0), eta(0, 2), eta(1, 1));
+    let (n30, n03, n21, n12) = (eta(3, 0), eta(0, 3), eta(2, 1), eta(1, 2));
+
+    let a = n30 + n12;
+    let b = n21 + n03;
+
+    Some([
+        n20 + n02,
+        (n20 - n02).powi(2) + 4.0 * n11 * n11,
+        (n30 - 3.0 * n12).powi(2) + (3.0 * n21 - n03).powi(2),
+        a * a + b * b,
+        (n30 - 3.0 * n12) * a * (a * a - 3.0 * b * b)
+            + (3.0 * n21 - n03) * b * (3.0 * a * a - b * b),
+        (n20 - n02) * (a * a - b * b) + 4.0 * n11 * a * b,
+        (3.0 * n21 - n03) * a * (a * a - 3.0 * b * b)
+            - (n30 - 3.0 * n12) * b * (3.0 * a * a - b * b),
+    ])
+}
+
+/// A connected region of set pixels
+#[derive(Debug, Clone)]
+pub struct Component {
+    /// Number of pixels
+    pub area: usize,
+    /// Inclusive bounding box
+    pub min_x: usize,
+    pub min_y: usize,
+    pub max_x: usize,
+    pub max_y: usize,
+    /// Pixel indices (`y * width + x`) belonging to the region
+    pub pixels: Vec<usize>,
+}
+
+impl Component {
+    /// Gap between two bounding boxes (0 when they touch or overlap)
+    pub fn box_distance(&self, other: &Component) -> f32 {
+        let gap = |a0: usize, a1: usize, b0: usize, b1: usize| b0.saturating_sub(a1).max(a0.saturating_sub(b1));
+        let dx = gap(self.min_x, self.max_x, other.min_x, other.max_x) as f32;
+        let dy = gap(self.min_y, self.max_y, other.min_y, other.max_y) as f32;
+        (dx * dx + dy * dy).sqrt()
+    }
+}
+
+/// Which neighbours join pixels into one region
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Edge neighbours only
+    Four,
+    /// Edge and corner neighbours
+    Eight,
+}
+
+/// Connected regions of a binary image and the region of each pixel
+#[derive(Debug, Clone)]
+pub struct ComponentLabels {
+    /// Per pixel: 0 where unset, `i + 1` for a pixel of `components[i]`
+    pub labels: Vec<u32>,
+    /// Regions, largest first
+    pub components: Vec<Component>,
+}
+
+/// Label 8-connected regions of a binary image, largest first
+pub fn connected_components(binary: &BitImage) -> Vec<Component> {
+    label_components(binary, Connectivity::Eight).components
+}
+
+/// Label connected regions in two raster passes
+///
+/// The first pass gives each pixel the smallest label among its already
+/// visited neighbours and records labels that meet in a union-find forest;
+/// the second resolves each pixel to its region. Regions of equal area keep
+/// the order of their first pixel.
+pub fn label_components(binary: &BitImage, connectivity: Connectivity) -> ComponentLabels {
+    let (width, height) = (binary.width(), binary.height());
+    let mut labels = vec![0u32; width * height];
+    // parent[label]; label 0 is the background
+    let mut parent = vec![0u32];
+
+    fn root(parent: &mut [u32], mut label: u32) -> u32 {
+        while parent[label as usize] != label {
+            let grandparent = parent[parent[label as usize] as usize];
+            parent[label as usize] = grandparent;
+            label = grandparent;
+        }
+        label
+    }
+
+    for index in binary.indices() {
+        let (x, y) = (index % width, index / width);
+        let mut neighbours = [0u32; 4];
+        if x > 0 {
+            neighbours[0] = labels[index - 1];
+        }
+        if y > 0 {
+            neighbours[1] = labels[index - width];
+            if connectivity == Connectivity::Eight {
+                if x > 0 {
+                    neighbours[2] = labels[index - width - 1];
+                }
+                if x + 1 < width {
+                    neighbours[3] = labels[index - width + 1];
+                }
+            }
+        }
+
+        let label = match neighbours.iter().copied().filter(|&l| l != 0).map(|l| root(&mut parent, l)).min() {
+            Some(smallest) => {
+                for &neighbour in neighbours.iter().filter(|&&l| l != 0) {
+                    let other = root(&mut parent, neighbour);
+                    parent[other as usize] = smallest;
+                }
+                smallest
+            }
+            None => {
+                let label = parent.len() as u32;
+                parent.push(label);
+                label
+            }
+        };
+        labels[index] = label;
+    }
+
+    // Number regions by first pixel and gather their extents
+    let mut region_of = vec![u32::MAX; parent.len()];
+    let mut components: Vec<Component> = Vec::new();
+    for index in binary.indices() {
+        let label = root(&mut parent, labels[index]) as usize;
+        if region_of[label] == u32::MAX {
+            region_of[label] = components.len() as u32;
+            components.push(Component {
+                area: 0,
+                min_x: usize::MAX,
+                min_y: usize::MAX,
+                max_x: 0,
+                max_y: 0,
+                pixels: Vec::new(),
+            });
+        }
+        let (x, y) = (index % width, index / width);
+        let component = &mut components[region_of[label] as usize];
+        component.area += 1;
+        component.min_x = component.min_x.min(x);
+        component.min_y = component.min_y.min(y);
+        component.max_x = component.max_x.max(x);
+        component.max_y = component.max_y.max(y);
+        component.pixels.push(index);
+        labels[index] = region_of[label];
+    }
+
+    let mut order: Vec<usize> = (0..components.len()).collect();
+    order.sort_by_key(|&region| std::cmp::Reverse(components[region].area));
+    let mut rank = vec![0u32; components.len()];
+    for (position, &region) in order.iter().enumerate() {
+        rank[region] = position as u32 + 1;
+    }
+    for index in binary.indices() {
+        labels[index] = rank[labels[index] as usize];
+    }
+    let mut slots: Vec<Option<Component>> = components.into_iter().map(Some).collect();
+    let components = order.iter().map(|&region| slots[region].take().unwrap()).collect();
+
+    ComponentLabels { labels, components }
+}
+
+/// Background regions fully enclosed by set pixels (the holes of a shape)
+///
+/// Background is joined through edge neighbours only, so a diagonal step in
+/// an 8-connected outline still closes it.
+pub fn enclosed_regions(binary: &BitImage) -> Vec<Component> {
+    let (width, height) = (binary.width(), binary.height());
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+    let ComponentLabels { labels, components } = label_components(&binary.inverted(), Connectivity::Four);
+
+    let mut open = vec![false; components.len() + 1];
+    for x in 0..width {
+        open[labels[x] as usize] = true;
+        open[labels[(height - 1) * width + x] as usize] = true;
+    }
+    for y in 0..height {
+        open[labels[y * width] as usize] = true;
+        open[labels[y * width + width - 1] as usize] = true;
+    }
+
+    components.into_iter()
+        .enumerate()
+        .filter(|&(i, _)| !open[i + 1])
+        .map(|(_, region)| region)
+        .collect()
+}
+
+/// The mask with enclosed background regions of at most `max_area` pixels
+/// filled in
+///
+/// Patchy colouring-in leaves pinholes that thinning would turn into tiny
+/// loops; the counters of letters are far larger and stay open.
+pub fn fill_holes(binary: &BitImage, max_area: usize) -> BitImage {
+    let mut filled = binary.clone();
+    let width = binary.width();
+    for hole in enclosed_regions(binary).into_iter().filter(|hole| hole.area <= max_area) {
+        for index in hole.pixels {
+            filled.set(index % width, index / width, true);
+        }
+    }
+    filled
+}
+
+/// The mask with 8-connected regions of at most `max_area` pixels cleared
+///
+/// Palm-rejection glitches on touchscreens leave isolated dots that
+/// thinning would keep as stroke ends to be bridged to the letter.
+pub fn remove_specks(binary: &BitImage, max_area: usize) -> BitImage {
+    let mut cleaned = binary.clone();
+    let width = binary.width();
+    for speck in connected_components(binary).into_iter().filter(|speck| speck.area <= max_area) {
+        for index in speck.pixels {
+            cleaned.set(index % width, index / width, false);
+        }
+    }
+    cleaned
+}
+
+/// Otsu's threshold of a 256-level histogram
+///
+/// The level splitting the histogram into two classes with the greatest
+/// between-class variance, returned with the means of the dark (below the
+/// level) and light classes. `None` when every pixel has one level.
+pub fn otsu_threshold(histogram: &[usize; 256]) -> Option<(u8, f32, f32)> {
+    let total: usize = histogram.iter().sum();
+    let weighted_total: f64 = histogram.iter().enumerate().map(|(level, &count)| (level * count) as f64).sum();
+
+    let mut best: Option<(u8, f32, f32)> = None;
+    let mut best_variance = 0.0f64;
+    let (mut dark, mut dark_sum) = (0usize, 0.0f64);
+    for level in 1..256 {
+        dark += histogram[level - 1];
+        dark_sum += ((level - 1) * histogram[level - 1]) as f64;
+        let light = total - dark;
+        if dark == 0 || light == 0 {
+            continue;
+        }
+        let dark_mean = dark_sum / dark as f64;
+        let light_mean = (weighted_total - dark_sum) / light as f64;
+        let variance = dark as f64 * light as f64 * (dark_mean - light_mean).powi(2);
+        if variance > best_variance {
+            best_variance = variance;
+            best = Some((level as u8, dark_mean as f32, light_mean as f32));
+        }
+    }
+    best
+}
+
+/// Difference hash of a grayscale image (values in [0, 1])
+///
+/// The image is area-averaged onto a `(size + 1) x size` grid and each bit
+/// records whether a cell is brighter than its right neighbour, giving
+/// `size * size` bits as lowercase hex. Identical images always hash the
+/// same; visually similar ones differ in few bits.
+pub fn difference_hash(image: &[f32], width: usize, height: usize, size: usize) -> String {
+    let columns = size + 1;
+    let mut cells = vec![0.0f32; columns * size];
+    for row in 0..size {
+        let (y0, y1) = (row * height / size, ((row + 1) * height / size).max(row * height / size + 1));
+        for column in 0..columns {
+            let (x0, x1) = (column * width / columns, ((column + 1) * width / columns).max(column * width / columns + 1));
+            let mut sum = 0.0;
+            for y in y0..y1.min(height) {
+                for x in x0..x1.min(width) {
+                    sum += image[y * width + x];
+                }
+            }
+            cells[row * columns + column] = sum / ((y1 - y0) * (x1 - x0)) as f32;
+        }
+    }
+
+    let bits: Vec<bool> = (0..size)
+        .flat_map(|row| (0..size).map(move |column| (row, column)))
+        .map(|(row, column)| cells[row * columns + column] > cells[row * columns + column + 1])
+        .collect();
+    bits.chunks(4)
+        .map(|nibble| {
+            let value = nibble.iter().fold(0u32, |acc, &bit| (acc << 1) | bit as u32);
+            char::from_digit(value, 16).unwrap()
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -397,7 +1119,7 @@ mod tests {
         let mut binary = vec![false; 25];
         binary[12] = true; // center point (2, 2)
 
-        let result = distance_transform_edt(&binary, 5, 5);
+        let result = distance_transform_edt(&BitImage::from_bools(&binary, 5, 5));
 
         // Center should be 0
         assert_eq!(result[12], 0.0);
@@ -415,10 +1137,33 @@ mod tests {
         assert!((result[18] - 1.414).abs() < 0.01); // bottom-right
     }
 
+    #[cfg(not(feature = "chamfer-edt"))]
+    #[test]
+    fn test_distance_transform_is_exact() {
+        // Every pixel's distance matches a brute-force search, including
+        // off-axis ones the chamfer approximation overestimates
+        let (width, height) = (23, 17);
+        let binary: Vec<bool> = (0..width * height).map(|i| (i * 7919) % 61 == 0).collect();
+        let ink: Vec<(usize, usize)> = (0..width * height).filter(|&i| binary[i]).map(|i| (i % width, i / width)).collect();
+        let result = distance_transform_edt(&BitImage::from_bools(&binary, width, height));
+        for (i, &d) in result.iter().enumerate() {
+            let (x, y) = ((i % width) as f32, (i / width) as f32);
+            let expected = ink.iter()
+                .map(|&(ix, iy)| ((x - ix as f32).powi(2) + (y - iy as f32).powi(2)).sqrt())
+                .fold(f32::MAX, f32::min);
+            assert!((d - expected).abs() < 1e-4, "pixel {}: {} vs {}", i, d, expected);
+        }
+
+        let mut point = vec![false; 100];
+        point[0] = true;
+        assert_eq!(distance_transform_edt(&BitImage::from_bools(&point, 10, 10))[4 * 10 + 3], 5.0);
+        assert!(distance_transform_chamfer(&BitImage::from_bools(&point, 10, 10))[4 * 10 + 3] > 5.2);
+    }
+
     #[test]
     fn test_distance_transform_empty_image() {
         let binary = vec![false; 25];
-        let result = distance_transform_edt(&binary, 5, 5);
+        let result = distance_transform_edt(&BitImage::from_bools(&binary, 5, 5));
 
         // All distances should be very large (MAX)
         for val in result {
@@ -429,7 +1174,7 @@ mod tests {
     #[test]
     fn test_distance_transform_full_image() {
         let binary = vec![true; 25];
-        let result = distance_transform_edt(&binary, 5, 5);
+        let result = distance_transform_edt(&BitImage::from_bools(&binary, 5, 5));
 
         // All distances should be 0
         for val in result {
@@ -442,7 +1187,7 @@ mod tests {
         let mut binary = vec![false; 25];
         binary[12] = true; // center point (2, 2)
 
-        let result = binary_dilation(&binary, 5, 5, 1);
+        let result = BitImage::from_bools(&binary, 5, 5).dilate(1).to_bools();
 
         // Center and all neighbors should be true
         assert!(result[12]); // center
@@ -467,7 +1212,7 @@ mod tests {
         let mut binary = vec![false; 49]; // 7x7
         binary[24] = true; // center point (3, 3)
 
-        let result = binary_dilation(&binary, 7, 7, 2);
+        let result = BitImage::from_bools(&binary, 7, 7).dilate(2).to_bools();
 
         // After 2 iterations, should expand by 2 pixels in all directions
         // Check that center 5x5 area is mostly true
@@ -480,7 +1225,7 @@ mod tests {
         let mut binary = vec![false; 25];
         binary[12] = true; // single center pixel
 
-        let result = binary_erosion(&binary, 5, 5, 1);
+        let result = BitImage::from_bools(&binary, 5, 5).erode(1).to_bools();
 
         // Single pixel should be eroded away
         assert!(!result[12]);
@@ -496,7 +1241,7 @@ mod tests {
             }
         }
 
-        let result = binary_erosion(&binary, 5, 5, 1);
+        let result = BitImage::from_bools(&binary, 5, 5).erode(1).to_bools();
 
         // Center should still be true after 1 erosion
         assert!(result[12]);
@@ -512,7 +1257,7 @@ mod tests {
             }
         }
 
-        let result = skeletonize(&binary, 15, 5);
+        let result = skeletonize(&BitImage::from_bools(&binary, 15, 5)).to_bools();
 
         // Should produce a thin horizontal line
         let true_count: usize = result.iter().filter(|&&x| x).count();
@@ -523,12 +1268,74 @@ mod tests {
     #[test]
     fn test_skeletonize_empty_image() {
         let binary = vec![false; 25];
-        let result = skeletonize(&binary, 5, 5);
+        let result = skeletonize(&BitImage::from_bools(&binary, 5, 5)).to_bools();
 
         // Should remain empty
         assert!(result.iter().all(|&x| !x));
     }
 
+    #[test]
+    fn test_skeletonize_stops_at_limits() {
+        let block = BitImage::from_fn(40, 40, |x, y| (5..35).contains(&x) && (5..35).contains(&y));
+        let thin = |limits: &ThinningLimits| skeletonize_with(&block, ThinningAlgorithm::ZhangSuen, limits, None).unwrap();
+        let full = thin(&ThinningLimits::default());
+
+        let capped = thin(&ThinningLimits { max_iterations: 2, ..Default::default() });
+        assert!(capped.count() > full.count() && capped.count() < block.count());
+        let unbudgeted = ThinningLimits { max_pixel_visits: 0, ..Default::default() };
+        assert_eq!(thin(&unbudgeted), block);
+    }
+
+    #[test]
+    fn test_cancelled_token_stops_thinning() {
+        let block = BitImage::from_fn(40, 40, |x, y| (5..35).contains(&x) && (5..35).contains(&y));
+        let token = CancelToken::new();
+        let shared = token.clone();
+        assert!(skeletonize_with(&block, ThinningAlgorithm::ZhangSuen, &ThinningLimits::default(), Some(&shared)).is_ok());
+
+        token.cancel();
+        let result = skeletonize_with(&block, ThinningAlgorithm::ZhangSuen, &ThinningLimits::default(), Some(&shared));
+        assert_eq!(result, Err(ScoringError::Cancelled));
+
+        // Within a call's scope, skeletonize stops at once and the next
+        // stage check reports it; references are still thinned in full
+        let _scope = ThinningScope::enter(ThinningAlgorithm::ZhangSuen, ThinningLimits::default(), Some(token)).unwrap();
+        assert_eq!(skeletonize(&block), block);
+        assert_eq!(check_cancelled(), Err(ScoringError::Cancelled));
+        assert!(thin_fully(ThinningAlgorithm::ZhangSuen, || skeletonize(&block)).count() < block.count());
+        assert!(ThinningScope::enter(ThinningAlgorithm::ZhangSuen, ThinningLimits::default(), None).is_none());
+    }
+
+    #[test]
+    fn test_skeletonize_largest_frame() {
+        // A bar 60 pixels thick in the 512-pixel frame thins to a line
+        // within the default limits
+        let bar = BitImage::from_fn(512, 512, |x, y| (100..412).contains(&x) && (226..286).contains(&y));
+        let skeleton = skeletonize(&bar);
+        assert!(skeleton.count() < 400, "{} pixels", skeleton.count());
+    }
+
+    #[test]
+    fn test_guo_hall_thins_diagonals_to_one_pixel() {
+        // Diagonal bands an even number of pixels across, where Zhang-Suen
+        // leaves a staircase or wears the band away
+        let band = |lo: i32, hi: i32| {
+            BitImage::from_fn(40, 40, |x, y| (lo..=hi).contains(&(x as i32 - y as i32)) && (4..36).contains(&x))
+        };
+        let thin = |band: &BitImage, algorithm| skeletonize_with(band, algorithm, &ThinningLimits::default(), None).unwrap();
+        let stairs = |skeleton: &BitImage| skeleton.ones().filter(|&(x, y)| skeleton.get(x + 1, y)).count();
+
+        let wide = band(-2, 3);
+        assert!(stairs(&thin(&wide, ThinningAlgorithm::ZhangSuen)) > 20);
+        let guo_hall = thin(&wide, ThinningAlgorithm::GuoHall);
+        assert_eq!(stairs(&guo_hall), 0);
+        assert!(guo_hall.count() >= 30);
+
+        let narrow = band(0, 1);
+        assert!(thin(&narrow, ThinningAlgorithm::ZhangSuen).count() < 5);
+        assert!(thin(&narrow, ThinningAlgorithm::GuoHall).count() >= 30);
+    }
+
     #[test]
     fn test_find_endpoints_line() {
         // Create a simple horizontal line
@@ -537,7 +1344,7 @@ mod tests {
         skeleton[12] = true; // (2, 2)
         skeleton[13] = true; // (3, 2)
 
-        let endpoints = find_endpoints(&skeleton, 5, 5);
+        let endpoints = find_endpoints(&BitImage::from_bools(&skeleton, 5, 5));
 
         // Should find 2 endpoints
         assert_eq!(endpoints.len(), 2);
@@ -559,7 +1366,7 @@ mod tests {
         skeleton[30] = true; // (2, 4)
         skeleton[31] = true; // (3, 4)
 
-        let endpoints = find_endpoints(&skeleton, 7, 7);
+        let endpoints = find_endpoints(&BitImage::from_bools(&skeleton, 7, 7));
 
         // Closed loop should have no endpoints
         assert_eq!(endpoints.len(), 0);
@@ -605,10 +1412,11 @@ mod tests {
         skeleton[12] = true; // (5, 1)
         skeleton[13] = true; // (6, 1)
 
-        bridge_gaps(&mut skeleton, 7, 7, 5);
+        let mut skeleton = BitImage::from_bools(&skeleton, 7, 7);
+        bridge_gaps(&mut skeleton, 5);
 
         // Gap should be bridged, total true count should increase
-        let true_count: usize = skeleton.iter().filter(|&&x| x).count();
+        let true_count = skeleton.count();
         assert!(true_count > 4);
     }
 
@@ -626,13 +1434,262 @@ mod tests {
         skeleton[17] = true; // (3, 2)
         skeleton[10] = true; // (3, 1)
 
-        let initial_count: usize = skeleton.iter().filter(|&&x| x).count();
+        let mut skeleton = BitImage::from_bools(&skeleton, 7, 7);
+        let initial_count = skeleton.count();
 
-        prune_branches(&mut skeleton, 7, 7, 2, 0.5);
+        prune_branches(&mut skeleton, 2, 0.5);
 
-        let final_count: usize = skeleton.iter().filter(|&&x| x).count();
+        let final_count = skeleton.count();
 
         // Should have removed some pixels
         assert!(final_count <= initial_count);
     }
+
+    #[test]
+    fn test_small_pieces_survive_thinning_and_pruning() {
+        // Every blob that fits in 4x4 pixels keeps its pieces through
+        // Guo-Hall thinning and pruning, as the dot of an i must
+        for mask in 1u32..1 << 16 {
+            let blob = BitImage::from_fn(8, 8, |x, y| {
+                (2..6).contains(&x) && (2..6).contains(&y) && mask >> ((y - 2) * 4 + x - 2) & 1 == 1
+            });
+            let mut skeleton = skeletonize_with(&blob, ThinningAlgorithm::GuoHall, &ThinningLimits::default(), None).unwrap();
+            prune_branches(&mut skeleton, 8, 1.0);
+            assert_eq!(connected_components(&skeleton).len(), connected_components(&blob).len(), "{:016b}", mask);
+        }
+
+        let mut pair = BitImage::from_fn(6, 6, |x, y| y == 2 && (2..4).contains(&x));
+        prune_branches(&mut pair, 8, 1.0);
+        assert_eq!(pair.count(), 1);
+    }
+
+    #[test]
+    fn test_image_moments_centered_block() {
+        // 3x3 block centered at (2, 2) in a 5x5 grid
+        let mut weights = vec![0.0f32; 25];
+        for y in 1..4 {
+            for x in 1..4 {
+                weights[y * 5 + x] = 1.0;
+            }
+        }
+
+        let m = image_moments(&weights, 5, 5).unwrap();
+
+        assert_eq!(m.mass, 9.0);
+        assert!((m.cx - 2.0).abs() < 1e-5);
+        assert!((m.cy - 2.0).abs() < 1e-5);
+        assert!(m.mu11.abs() < 1e-5);
+        assert!(m.eccentricity() < 0.01);
+    }
+
+    #[test]
+    fn test_image_moments_orientation() {
+        // Horizontal line has orientation 0, vertical line pi/2
+        let mut horizontal = vec![0.0f32; 49];
+        let mut vertical = vec![0.0f32; 49];
+        for i in 1..6 {
+            horizontal[3 * 7 + i] = 1.0;
+            vertical[i * 7 + 3] = 1.0;
+        }
+
+        let h = image_moments(&horizontal, 7, 7).unwrap();
+        let v = image_moments(&vertical, 7, 7).unwrap();
+
+        assert!(h.orientation().abs() < 1e-5);
+        assert!((v.orientation().abs() - std::f32::consts::FRAC_PI_2).abs() < 1e-5);
+        assert!(h.eccentricity() > 0.99);
+    }
+
+    #[test]
+    fn test_image_moments_empty() {
+        let weights = vec![0.0f32; 25];
+        assert!(image_moments(&weights, 5, 5).is_none());
+    }
+
+    #[test]
+    fn test_orientation_field_lines() {
+        // Thick horizontal and vertical bars in separate images
+        let mut horizontal = vec![false; 21 * 21];
+        let mut vertical = vec![false; 21 * 21];
+        for a in 3..18 {
+            for b in 9..12 {
+                horizontal[b * 21 + a] = true;
+                vertical[a * 21 + b] = true;
+            }
+        }
+
+        let h = orientation_field(&BitImage::from_bools(&horizontal, 21, 21), 2);
+        let v = orientation_field(&BitImage::from_bools(&vertical, 21, 21), 2);
+
+        // Horizontal stroke tangent is ~0 (or ~pi), vertical is ~pi/2
+        let angle_h = h[10 * 21 + 10];
+        assert!(angle_h.min(std::f32::consts::PI - angle_h) < 0.05);
+        assert!((v[10 * 21 + 10] - std::f32::consts::FRAC_PI_2).abs() < 0.05);
+
+        // Restricted to some pixels, those come out the same
+        let mut at = BitImage::new(21, 21);
+        at.set(10, 10, true);
+        let mut restricted = Vec::new();
+        orientation_field_into(&BitImage::from_bools(&vertical, 21, 21), 2, Some(&at), &mut restricted);
+        assert_eq!(restricted[10 * 21 + 10], v[10 * 21 + 10]);
+        assert_eq!(restricted.iter().filter(|&&angle| angle != 0.0).count(), 1);
+    }
+
+    #[test]
+    fn test_hu_moments_rotation_invariant() {
+        // An L shape and the same shape rotated by 90 degrees
+        let size = 21;
+        let mut shape = vec![0.0f32; size * size];
+        let mut rotated = vec![0.0f32; size * size];
+        for i in 4..16 {
+            shape[i * size + 4] = 1.0;
+            shape[15 * size + i] = 1.0;
+        }
+        for y in 0..size {
+            for x in 0..size {
+                rotated[x * size + (size - 1 - y)] = shape[y * size + x];
+            }
+        }
+
+        let a = hu_moments(&shape, size, size).unwrap();
+        let b = hu_moments(&rotated, size, size).unwrap();
+
+        for i in 0..4 {
+            assert!((a[i] - b[i]).abs() <= 1e-6 + a[i].abs() * 1e-3, "hu[{}] differs", i);
+        }
+    }
+
+    #[test]
+    fn test_hu_moments_empty() {
+        let weights = vec![0.0f32; 25];
+        assert!(hu_moments(&weights, 5, 5).is_none());
+    }
+
+    #[test]
+    fn test_connected_components() {
+        // A bar, a diagonal pair (8-connected) and a single dot
+        let (w, h) = (10, 6);
+        let mut binary = vec![false; w * h];
+        for x in 1..6 {
+            binary[w + x] = true;
+        }
+        binary[4 * w + 1] = true;
+        binary[5 * w + 2] = true;
+        binary[4 * w + 8] = true;
+
+        let components = connected_components(&BitImage::from_bools(&binary, w, h));
+        let areas: Vec<usize> = components.iter().map(|c| c.area).collect();
+        assert_eq!(areas, vec![5, 2, 1]);
+        assert_eq!((components[0].min_x, components[0].max_x, components[0].min_y), (1, 5, 1));
+        assert_eq!(components[0].box_distance(&components[2]), (9.0f32 + 9.0).sqrt());
+        assert!(connected_components(&BitImage::new(2, 2)).is_empty());
+    }
+
+    #[test]
+    fn test_label_components() {
+        // A U whose arms meet only at the bottom, a diagonal pair and a dot
+        let (w, h) = (12, 6);
+        let mut binary = vec![false; w * h];
+        for y in 0..5 {
+            binary[y * w] = true;
+            binary[y * w + 4] = true;
+        }
+        for x in 0..5 {
+            binary[4 * w + x] = true;
+        }
+        binary[w + 7] = true;
+        binary[2 * w + 8] = true;
+        binary[5 * w + 11] = true;
+        let image = BitImage::from_bools(&binary, w, h);
+
+        let eight = label_components(&image, Connectivity::Eight);
+        let areas: Vec<usize> = eight.components.iter().map(|c| c.area).collect();
+        assert_eq!(areas, vec![13, 2, 1]);
+        assert_eq!((eight.labels[0], eight.labels[4], eight.labels[w + 7], eight.labels[2 * w + 8]), (1, 1, 2, 2));
+        assert_eq!(eight.labels[5 * w + 11], 3);
+        assert_eq!(eight.labels.iter().filter(|&&l| l != 0).count(), 16);
+        for (i, component) in eight.components.iter().enumerate() {
+            assert!(component.pixels.iter().all(|&p| eight.labels[p] == i as u32 + 1));
+        }
+
+        // The diagonal pair comes apart with edge neighbours only
+        let four = label_components(&image, Connectivity::Four);
+        assert_eq!(four.components.iter().map(|c| c.area).collect::<Vec<_>>(), vec![13, 1, 1, 1]);
+        assert_ne!(four.labels[w + 7], four.labels[2 * w + 8]);
+    }
+
+    #[test]
+    fn test_otsu_threshold() {
+        let mut histogram = [0usize; 256];
+        histogram[160] = 50;
+        histogram[170] = 50;
+        histogram[250] = 900;
+        let (level, dark, light) = otsu_threshold(&histogram).unwrap();
+        assert!((171..=250).contains(&level), "{}", level);
+        assert_eq!((dark, light), (165.0, 250.0));
+
+        let mut flat = [0usize; 256];
+        flat[255] = 100;
+        assert!(otsu_threshold(&flat).is_none());
+    }
+
+    #[test]
+    fn test_difference_hash() {
+        use crate::test_frames::{bar, blank};
+        let size = crate::scoring::TARGET_SIZE as usize;
+        let stroke = bar(48..72, 16..112);
+        let shifted = bar(80..104, 16..112);
+
+        let hash = difference_hash(&stroke, size, size, 8);
+        assert_eq!(hash.len(), 16);
+        assert_eq!(hash, difference_hash(&stroke.clone(), size, size, 8));
+        assert_ne!(hash, difference_hash(&shifted, size, size, 8));
+        assert_eq!(difference_hash(&blank(), size, size, 8), "0".repeat(16));
+    }
+
+    #[test]
+    fn test_enclosed_regions() {
+        // A ring with a 3x3 hole, beside an open U
+        let (w, h) = (14, 7);
+        let mut binary = vec![false; w * h];
+        for y in 1..6 {
+            for x in 1..6 {
+                binary[y * w + x] = !(2..5).contains(&x) || !(2..5).contains(&y);
+            }
+            binary[y * w + 8] = true;
+            binary[y * w + 12] = true;
+        }
+        for x in 8..13 {
+            binary[5 * w + x] = true;
+        }
+
+        let holes = enclosed_regions(&BitImage::from_bools(&binary, w, h));
+        assert_eq!(holes.len(), 1);
+        assert_eq!(holes[0].area, 9);
+        assert_eq!((holes[0].min_x, holes[0].min_y), (2, 2));
+
+        let ring = BitImage::from_bools(&binary, w, h);
+        let filled = fill_holes(&ring, 9);
+        assert_eq!(filled.count(), ring.count() + 9);
+        assert!(enclosed_regions(&filled).is_empty());
+        assert_eq!(fill_holes(&ring, 8), ring);
+
+        // A diamond outlined in diagonal steps still has a hole
+        let diamond = BitImage::from_fn(9, 9, |x, y| x.abs_diff(4) + y.abs_diff(4) == 3);
+        let holes = enclosed_regions(&diamond);
+        assert_eq!(holes.len(), 1);
+        assert_eq!(holes[0].area, 13);
+    }
+
+    #[test]
+    fn test_remove_specks() {
+        // A bar, a lone pixel and a diagonal pair
+        let binary = BitImage::from_fn(12, 6, |x, y| {
+            (y == 2 && (1..9).contains(&x)) || (x, y) == (10, 5) || (x, y) == (1, 4) || (x, y) == (2, 5)
+        });
+        let bar = BitImage::from_fn(12, 6, |x, y| y == 2 && (1..9).contains(&x));
+        assert_eq!(remove_specks(&binary, 2), bar);
+        assert_eq!(remove_specks(&binary, 1).count(), bar.count() + 2);
+        assert_eq!(remove_specks(&binary, 0), binary);
+    }
 }
@@ -2,116 +2,559 @@
 //!
 //! This module provides image-based scoring for handwritten characters,
 //! comparing user drawings against reference images generated from fonts.
+//!
+//! The functions and types exported here are the Rust API: plain Rust
+//! types, errors as [`ScoringError`]. The JavaScript API in [`wasm`] (behind the
+//! `wasm` feature) is a thin adapter over it, so servers, CLIs and native
+//! apps can depend on the engine without the wasm-bindgen glue.
 
 mod scoring;
+mod age_bands;
+mod bit_image;
+mod engine;
+mod error;
+mod features;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod font;
+#[cfg(feature = "guides")]
+mod guides;
 mod image_ops;
+mod logging;
+#[cfg(feature = "coaching")]
+mod live;
+mod options;
+mod pack;
+mod parallel;
+mod photo;
+mod pipeline;
+mod practice;
+#[cfg(feature = "python")]
+mod python;
+mod reference;
+mod scaffold;
+#[cfg(feature = "coaching")]
+mod segments;
+#[cfg(feature = "session-analytics")]
+mod session;
+#[cfg(feature = "shapes")]
+mod shape;
+mod shape_context;
+mod simd;
+mod skeleton_graph;
+mod strokes;
+mod styles;
+mod templates;
+mod timing;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod woff;
+
+#[cfg(all(test, feature = "stroke-templates"))]
+mod fixtures;
+#[cfg(test)]
+mod test_frames;
 
-use wasm_bindgen::prelude::*;
 use serde::{Serialize, Deserialize};
 
-#[wasm_bindgen(start)]
-pub fn init() {
-    #[cfg(feature = "console_error_panic_hook")]
-    console_error_panic_hook::set_once();
-}
+#[cfg(feature = "coaching")]
+pub use live::LiveScorer;
+pub use age_bands::{AgeBand, AgeBandBundle};
+pub use engine::ScoringEngine;
+pub use error::ScoringError;
+pub use font::Font;
+pub use image_ops::{CancelToken, ThinningAlgorithm, ThinningLimits};
+#[cfg(feature = "tracing")]
+pub use logging::{LogLevel, set_log_level};
+pub use options::{GuideLine, GuideOptions, InkColor, InputLimits, ReferenceAnchor, RulingOptions, ScoringOptions, ScoreWeights, ScoringMode, SimilarityMethod, StarThresholds, ThresholdMethod};
+pub use pack::{ReferenceHandle, ReferencePack, build_reference_pack, load_reference_pack};
+pub use practice::{PracticeOptions, RandomSource, ReferenceVariation, SplitMix64};
+pub use reference::clear_reference_cache;
+pub use scaffold::{ScaffoldLevel, ScaffoldOptions};
+pub use scoring::StrokeGap;
+pub use skeleton_graph::NodeKind;
+pub use timing::Clock;
+#[cfg(feature = "shapes")]
+pub use shape::ReferenceShape;
+#[cfg(feature = "session-analytics")]
+pub use session::{SessionAttempt, SessionSummary, Handedness, HandednessEstimate, FatigueEstimate, Trend};
+#[cfg(feature = "coaching")]
+pub use segments::NextStroke;
+pub use strokes::{Stroke, StrokePoint, StrokeDirection, StrokeEvent, StrokeTransform};
+pub use styles::handwriting_styles;
+#[cfg(feature = "session-analytics")]
+pub use session::summarize_session;
 
 /// Result of scoring a drawing
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScoringResult {
     pub score: u8,
     pub stars: u8,
     pub feedback: String,
+    /// Machine-readable reason when the feedback overrides the star message
+    pub feedback_code: Option<String>,
+    /// The drawing is a solid filled region rather than strokes
+    pub scribble: bool,
+    /// Near pixel-perfect match to the rendered reference, suggesting the
+    /// drawing was traced or copied; for competitive modes to act on
+    pub suspected_trace: bool,
+    /// The ink runs along an edge of the image, so the letter was probably
+    /// cut off there
+    pub clipped: bool,
     pub coverage: f32,
     pub accuracy: f32,
     pub similarity: f32,
+    /// Fraction of the reference skeleton covered by the drawing
+    pub skeleton_coverage: f32,
+    /// Structural similarity of the skeleton graphs
+    pub structure: f32,
+    /// Reference-free legibility of the drawing itself
+    pub legibility: f32,
+    /// Global shape similarity from Hu moment invariants
+    pub moment_similarity: f32,
+    /// 95th-percentile symmetric distance between drawing and reference,
+    /// in pixels of the default 128-pixel frame
+    pub hausdorff_95: f32,
+    /// Grid region where the drawing deviates most (e.g. "top-right")
+    pub worst_region: Option<String>,
+    /// Breaks between parts of the drawing that should be connected
+    pub gaps: Vec<StrokeGap>,
+    /// Whether each loop of the letter was closed, top to bottom
+    pub loop_closed: Vec<bool>,
+    /// Whether the dot of an i or j was drawn (those letters only)
+    pub dot_present: Option<bool>,
+    /// Whether the crossbar of a t or f was drawn (those letters only)
+    pub crossbar_present: Option<bool>,
+    /// Whether the tail of a Q was drawn (Q only)
+    pub tail_present: Option<bool>,
+    /// How well stroke order and direction follow the template (stroke input only)
+    pub order_score: Option<f32>,
+    /// Number of strokes the character is conventionally written with
+    pub expected_strokes: Option<u32>,
+    /// Number of strokes used (stroke input only)
+    pub actual_strokes: Option<u32>,
+    /// Start-point and direction flags per drawn stroke (stroke input only)
+    pub stroke_directions: Vec<StrokeDirection>,
+    /// Drawing time, from the options or stroke timestamps
+    pub duration_ms: Option<f64>,
+    /// Percentage of the combined score removed for drawing too fast
+    pub time_penalty: f32,
+    /// Steadiness of pen pressure (stroke input with pressure only)
+    pub pressure_consistency: Option<f32>,
+    /// Glyph the drawing was scored against (practice mode only)
+    pub reference_glyph: Option<String>,
+    /// Contrast gain applied to faint ink before thresholding (1 = none)
+    pub contrast_gain: f32,
+    /// Small marks found away from the letter
+    pub stray_marks: u32,
+    /// Perceptual hash of the normalized drawing, for spotting resubmissions
+    pub drawing_hash: String,
+    /// Targeted suggestions for the learner
+    pub hints: Vec<String>,
+    /// Help to show on the next attempt at this letter
+    pub scaffold: ScaffoldLevel,
+    /// Consecutive low scores on the letter, ending with this one
+    pub low_score_streak: u32,
+    /// How far the score can be trusted (0-100): low when the drawing has
+    /// very little ink, stray marks or an odd shape for the letter, or the
+    /// reference has almost no ink, so the app can ask for another try
+    pub confidence: f32,
+    /// How the score was put together
+    pub explanation: ScoreExplanation,
+    /// Time spent in each stage, with `ScoringOptions::with_timings`
+    pub timings: Option<StageTimings>,
 }
 
-#[wasm_bindgen]
-pub struct WasmScoringResult {
-    inner: ScoringResult,
-    reference_image: Vec<u8>,
+/// Result of `check_drawing_quality`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrawingQuality {
+    /// No issues were found
+    pub scoreable: bool,
+    /// What is wrong with the drawing, most serious first
+    pub issues: Vec<DrawingIssue>,
+    /// Prompt for the learner about the first issue
+    pub message: Option<String>,
+    /// Ink pixels found, after faint ink is stretched and specks erased
+    pub ink_pixels: u32,
 }
 
-#[wasm_bindgen]
-impl WasmScoringResult {
-    #[wasm_bindgen(getter)]
-    pub fn score(&self) -> u8 {
-        self.inner.score
-    }
+/// Something that makes a drawing not worth scoring
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawingIssue {
+    /// Nothing was drawn
+    Empty,
+    /// Too little ink to tell what it is
+    TooLittleInk,
+    /// There are marks, but too light to tell from the paper
+    Faint,
+    /// The ink runs along the edge of the image, so part of the letter is missing
+    CutOff,
+}
 
-    #[wasm_bindgen(getter)]
-    pub fn stars(&self) -> u8 {
-        self.inner.stars
+impl DrawingIssue {
+    /// Prompt for the learner
+    pub fn message(self) -> &'static str {
+        match self {
+            DrawingIssue::Empty => "Draw something first!",
+            DrawingIssue::TooLittleInk => "Draw the whole letter!",
+            DrawingIssue::Faint => "Press a little harder so your lines show!",
+            DrawingIssue::CutOff => "Keep your letter inside the box!",
+        }
     }
+}
 
-    #[wasm_bindgen(getter)]
-    pub fn feedback(&self) -> String {
-        self.inner.feedback.clone()
-    }
+/// Centerline of a drawing as a graph, from `extract_skeleton_graph`
+///
+/// Coordinates and lengths are in the image's pixels.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DrawingGraph {
+    /// Stroke ends and the points where strokes meet or cross
+    pub nodes: Vec<GraphNode>,
+    /// The centerline between nodes
+    pub edges: Vec<GraphEdge>,
+    /// Separate pieces of the drawing
+    pub components: u32,
+    /// Closed loops, e.g. one for o and two for B
+    pub loops: u32,
+}
 
-    #[wasm_bindgen(getter)]
-    pub fn coverage(&self) -> f32 {
-        self.inner.coverage
-    }
+/// A node of a `DrawingGraph`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub x: f32,
+    pub y: f32,
+    /// `Loop` nodes are anchors placed on closed strokes with no other node
+    pub kind: NodeKind,
+}
 
-    #[wasm_bindgen(getter)]
-    pub fn accuracy(&self) -> f32 {
-        self.inner.accuracy
-    }
+/// A piece of centerline between two nodes of a `DrawingGraph`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GraphEdge {
+    /// Indices into `nodes`; the path runs from `from` to `to`, which says
+    /// nothing about the direction it was drawn in
+    pub from: u32,
+    pub to: u32,
+    /// Polyline along the centerline, `[x, y]` per point
+    pub points: Vec<[f32; 2]>,
+    pub length: f32,
+}
 
-    #[wasm_bindgen(getter)]
-    pub fn similarity(&self) -> f32 {
-        self.inner.similarity
-    }
+/// How a score was put together, for explaining a rating
+///
+/// The component points add up to `weighted_score`. Taking off the
+/// penalties gives the score before it is rounded down and capped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreExplanation {
+    pub coverage: ScoreContribution,
+    pub accuracy: ScoreContribution,
+    pub similarity: ScoreContribution,
+    pub structure: ScoreContribution,
+    pub legibility: ScoreContribution,
+    /// Sum of the component points
+    pub weighted_score: f32,
+    /// Points taken off for drawing too fast
+    pub time_penalty: f32,
+    /// Points taken off for stray marks
+    pub stray_mark_penalty: f32,
+    /// Points taken off for a missing dot, crossbar or tail
+    pub missing_feature_penalty: f32,
+    /// Limits that apply to the score, which win over the points
+    pub caps: Vec<ScoreCap>,
+    /// Star thresholds the score was rated against
+    pub stars: StarThresholds,
+    /// Skeleton coverage (percent) needed for more than one star
+    pub min_skeleton_coverage: f32,
+}
+
+/// One component's part in the combined score
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreContribution {
+    /// The component score (0-100), as in the result
+    pub value: f32,
+    /// Its weight as a fraction of all the weights
+    pub weight: f32,
+    /// Points it added to the score
+    pub points: f32,
+}
+
+/// A limit on the score, whatever the points add up to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreCap {
+    /// Why: "min_skeleton_coverage", "canvas_filled" or "scribble"
+    pub reason: String,
+    pub max_score: u8,
+}
 
-    #[wasm_bindgen(getter)]
-    pub fn reference_image(&self) -> Vec<u8> {
-        self.reference_image.clone()
+impl ScoreCap {
+    pub(crate) fn new(reason: &str, max_score: u8) -> Self {
+        Self { reason: reason.to_string(), max_score }
     }
 }
 
-/// Score a user's drawing against a reference character
+/// Milliseconds spent in each stage of scoring a drawing
 ///
-/// # Arguments
-/// * `image_data` - PNG image bytes of the user's drawing
-/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
-/// * `font_data` - TTF font bytes to use for generating the reference
+/// Skeletonization and distance transforms count wherever they run, so
+/// they overlap the reference and metrics stages. The reference stage is
+/// close to zero when the reference was already cached.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct StageTimings {
+    /// Decoding the image and flattening its transparency
+    pub decode_ms: f32,
+    /// Rendering and preparing the reference
+    pub reference_ms: f32,
+    /// Thinning drawings and references to skeletons
+    pub skeleton_ms: f32,
+    /// Euclidean distance transforms
+    pub distance_transform_ms: f32,
+    /// Registration, the metrics and combining them into the score
+    pub metrics_ms: f32,
+    /// The whole call
+    pub total_ms: f32,
+}
+
+/// A scoring result with the reference the drawing was compared against
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoredDrawing {
+    pub result: ScoringResult,
+    /// PNG image bytes of the rendered reference
+    pub reference_image: Vec<u8>,
+}
+
+/// Score a drawing against a reference character
 ///
-/// # Returns
-/// A ScoringResult containing the score, stars, and detailed metrics
-#[wasm_bindgen]
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing (JPEG and WebP with the
+///   `jpeg` and `webp` features)
+/// * `character` - The character that was drawn
+/// * `font` - Font to render the reference with
+/// * `strokes` - The strokes the drawing was made with, if known, in the
+///   image's pixel coordinates (or mapped there by `options.stroke_transform`);
+///   they enable stroke order, direction and timing metrics
+/// * `options` - Scoring options
 pub fn score_drawing(
     image_data: &[u8],
-    character: &str,
-    font_data: &[u8],
-) -> Result<WasmScoringResult, JsValue> {
-    let char = character.chars().next()
-        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+    character: char,
+    font: &Font,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    scoring::score_drawing_internal(image_data, character, font, strokes, options)
+}
 
-    let result = scoring::score_drawing_internal(image_data, char, font_data)
-        .map_err(|e| JsValue::from_str(&e))?;
+/// Score several drawings with one font and options
+///
+/// Each entry is a drawing's PNG bytes and the character it should be;
+/// results come back in the same order. With the `parallel` feature the
+/// drawings are scored on rayon's thread pool.
+pub fn score_drawings(
+    drawings: &[(&[u8], char)],
+    font: &Font,
+    options: &ScoringOptions,
+) -> Vec<Result<ScoredDrawing, ScoringError>> {
+    parallel::map(drawings, |&(image_data, character)| {
+        scoring::score_drawing_internal(image_data, character, font, None, options)
+    })
+}
 
-    Ok(result)
+/// Score a drawing from its event log, counting only the ink left visible
+///
+/// Replays strokes, undos, redos and clears, then scores the remaining
+/// strokes re-rendered on a canvas the size of the image.
+pub fn score_drawing_events(
+    image_data: &[u8],
+    character: char,
+    font: &Font,
+    events: &[StrokeEvent],
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    let options = ScoringOptions { rasterize_strokes: true, ..options.clone() };
+    let strokes = strokes::replay_events(events);
+    scoring::score_drawing_internal(image_data, character, font, Some(&strokes), &options)
 }
 
-/// Generate a reference image for a character
+/// Score a drawing against a reference from a pack instead of a font
 ///
-/// # Arguments
-/// * `character` - The character to render
-/// * `font_data` - TTF font bytes
-/// * `size` - Output image size (width and height)
+/// Packs hold unvaried glyphs, so `options.practice` is rejected and
+/// `options.font_axes` has no effect (the axes were applied when building).
+pub fn score_drawing_with_pack(
+    image_data: &[u8],
+    character: char,
+    pack: &ReferencePack,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    pack::score_drawing_with_pack_internal(image_data, character, pack, strokes, options)
+}
+
+/// Score a drawing against a prepared (or restored) reference
+///
+/// The reference was fixed when it was prepared, so `options.practice` and
+/// `options.font_axes` don't change it.
+pub fn score_drawing_with_reference(
+    image_data: &[u8],
+    reference: &ReferenceHandle,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    pack::score_drawing_with_reference_internal(image_data, reference, strokes, options)
+}
+
+/// Score a drawing against an image of the letter instead of a font glyph
 ///
-/// # Returns
-/// PNG image bytes
-#[wasm_bindgen]
+/// For repeated attempts, prepare the template once with
+/// [`ReferenceHandle::from_template`] and use [`score_drawing_with_reference`].
+pub fn score_against_template(
+    image_data: &[u8],
+    character: char,
+    template_png: &[u8],
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    let reference = ReferenceHandle::from_template(character, template_png)?;
+    pack::score_drawing_with_reference_internal(image_data, &reference, strokes, options)
+}
+
+/// Render the reference a drawing will be scored against, as PNG bytes
+///
+/// `options.guide` draws it as a dotted or dashed tracing guide instead.
 pub fn generate_reference_image(
-    character: &str,
-    font_data: &[u8],
+    character: char,
+    font: &Font,
+    size: u32,
+    options: &ScoringOptions,
+) -> Result<Vec<u8>, ScoringError> {
+    scoring::generate_reference_image_internal(character, font, size, options)
+}
+
+/// Render the letter with each stroke of its stroke template drawn over it
+/// as an arrow from a numbered start dot, as PNG bytes
+///
+/// Needs a stroke template for the character, from the embedded templates
+/// or the handwriting style.
+#[cfg(feature = "guides")]
+pub fn generate_stroke_order_image(
+    character: char,
+    font: &Font,
     size: u32,
-) -> Result<Vec<u8>, JsValue> {
-    let char = character.chars().next()
-        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+    options: &ScoringOptions,
+) -> Result<Vec<u8>, ScoringError> {
+    Ok(scoring::encode_grayscale_to_png(&guides::render_stroke_order(character, font, options, size)?)?)
+}
+
+/// Render frames inking the letter stroke by stroke, following its stroke
+/// template, as PNG bytes each
+///
+/// The first frame is the letter in light gray, the last the finished
+/// letter; `frame_count` is 2 to 240.
+#[cfg(feature = "guides")]
+pub fn generate_stroke_animation(
+    character: char,
+    font: &Font,
+    size: u32,
+    frame_count: u32,
+    options: &ScoringOptions,
+) -> Result<Vec<Vec<u8>>, ScoringError> {
+    guides::render_stroke_animation(character, font, options, size, frame_count)?
+        .iter()
+        .map(|frame| Ok(scoring::encode_grayscale_to_png(frame)?))
+        .collect()
+}
+
+/// The frames of `generate_stroke_animation` side by side in one PNG,
+/// `size * frame_count` pixels wide, within `options.input_limits`
+#[cfg(feature = "guides")]
+pub fn generate_stroke_animation_sheet(
+    character: char,
+    font: &Font,
+    size: u32,
+    frame_count: u32,
+    options: &ScoringOptions,
+) -> Result<Vec<u8>, ScoringError> {
+    let sheet = guides::render_stroke_animation_sheet(character, font, options, size, frame_count)?;
+    Ok(scoring::encode_grayscale_to_png(&sheet)?)
+}
+
+/// Render the reference at any width and height, as PNG bytes
+///
+/// The letter is drawn in a square as wide as the shorter side and placed
+/// by `options.guide.anchor`, for wide word canvases or tall ones with room
+/// for descenders.
+pub fn generate_sized_reference_image(
+    character: char,
+    font: &Font,
+    width: u32,
+    height: u32,
+    options: &ScoringOptions,
+) -> Result<Vec<u8>, ScoringError> {
+    scoring::generate_sized_reference_image_internal(character, font, width, height, options)
+}
+
+/// Render a printable practice worksheet as PNG bytes
+///
+/// Row `i` practices the `i`th character of `characters` (cycling when
+/// there are more rows): a model letter, then `cols - 1` copies to trace,
+/// faded, or dotted or dashed per `options.guide`, on writing lines.
+#[cfg(feature = "guides")]
+pub fn generate_worksheet(
+    characters: &str,
+    font: &Font,
+    rows: u32,
+    cols: u32,
+    options: &ScoringOptions,
+) -> Result<Vec<u8>, ScoringError> {
+    Ok(scoring::encode_grayscale_to_png(&guides::render_worksheet(characters, font, rows, cols, options)?)?)
+}
+
+/// Render a shape reference as PNG bytes
+#[cfg(feature = "shapes")]
+pub fn generate_shape_reference_image(shape: &ReferenceShape, size: u32) -> Result<Vec<u8>, ScoringError> {
+    Ok(scoring::encode_grayscale_to_png(&shape.render(size))?)
+}
+
+/// Index of the first font in a fallback chain with a glyph for the character
+pub fn select_font(fonts: &[&Font], character: char, options: &ScoringOptions) -> Result<usize, ScoringError> {
+    scoring::select_font_internal(fonts, character, options)
+}
+
+/// Suggest which part of the letter to draw next on a partial drawing,
+/// or `None` once the letter is complete
+#[cfg(feature = "coaching")]
+pub fn suggest_next_stroke(
+    image_data: &[u8],
+    character: char,
+    font: &Font,
+    options: &ScoringOptions,
+) -> Result<Option<NextStroke>, ScoringError> {
+    segments::suggest_next_stroke_internal(image_data, character, font, options)
+}
+
+/// Whether a drawing can be scored, from a quick look at its ink
+///
+/// Much cheaper than scoring, so the app can ask for a drawing (or a
+/// darker or uncut one) before submitting it.
+pub fn check_drawing_quality(image_data: &[u8]) -> Result<DrawingQuality, ScoringError> {
+    Ok(scoring::check_drawing_quality_internal(image_data)?)
+}
+
+/// Centerline graph of a drawing: stroke ends and junctions as nodes,
+/// and the polylines between them as edges, in the image's pixels
+pub fn extract_skeleton_graph(image_data: &[u8]) -> Result<DrawingGraph, ScoringError> {
+    Ok(scoring::extract_skeleton_graph_internal(image_data)?)
+}
+
+/// Legibility of a drawing without any reference, from 0 to 100
+pub fn estimate_legibility(image_data: &[u8]) -> Result<f32, ScoringError> {
+    Ok(scoring::estimate_legibility_internal(image_data)?)
+}
+
+/// Perceptual hash of a drawing as 64 hex digits, matching `drawing_hash`
+/// in scoring results
+pub fn hash_drawing(image_data: &[u8]) -> Result<String, ScoringError> {
+    Ok(scoring::hash_drawing_internal(image_data)?)
+}
 
-    scoring::generate_reference_image_internal(char, font_data, size)
-        .map_err(|e| JsValue::from_str(&e))
+/// Number of differing bits between two drawing hashes
+pub fn hash_distance(a: &str, b: &str) -> Result<u32, ScoringError> {
+    Ok(scoring::hash_distance_internal(a, b)?)
 }
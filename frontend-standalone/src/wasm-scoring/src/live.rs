@@ -0,0 +1,256 @@
+//! Incremental live scoring
+//!
+//! A stateful scorer for use while the learner is drawing. The reference is
+//! prepared once; each stroke is rasterized straight into the normalized
+//! frame, so polling the provisional score only costs a distance transform
+//! instead of the full scoring pipeline.
+
+use crate::bit_image::BitImage;
+use crate::error::ScoringError;
+use crate::font::Font;
+use crate::image_ops::distance_transform_edt;
+use crate::options::ScoringOptions;
+use crate::scoring::{distance_credit, frame_scale};
+use crate::segments::{NextStroke, ReferenceFrame};
+use crate::strokes::{Stroke, StrokePoint, rasterize_strokes};
+#[cfg(feature = "wasm")]
+use wasm_bindgen::prelude::*;
+
+/// Distance (in normalized pixels) within which ink covers the skeleton
+const LIVE_COVERAGE_TOLERANCE: f32 = 4.0;
+
+/// Stateful scorer fed with strokes as they are drawn
+///
+/// Stroke coordinates are canvas pixels on a canvas showing the reference
+/// (as from `generate_reference_image`) scaled to the full canvas size.
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+pub struct LiveScorer {
+    frame: ReferenceFrame,
+    options: ScoringOptions,
+    canvas_width: f32,
+    canvas_height: f32,
+    /// Drawn ink in the normalized frame
+    drawn: BitImage,
+    strokes: Vec<Stroke>,
+}
+
+impl LiveScorer {
+    pub fn with_options(
+        character: char,
+        font: &Font,
+        canvas_width: u32,
+        canvas_height: u32,
+        options: ScoringOptions,
+    ) -> Result<Self, ScoringError> {
+        if canvas_width == 0 || canvas_height == 0 {
+            return Err("Canvas size must be non-zero".into());
+        }
+
+        let frame = ReferenceFrame::new(character, font, &options)?;
+        let size = frame.size();
+
+        Ok(Self {
+            drawn: BitImage::new(size, size),
+            frame,
+            options,
+            canvas_width: canvas_width as f32,
+            canvas_height: canvas_height as f32,
+            strokes: Vec::new(),
+        })
+    }
+
+    /// Rasterize a stroke into the normalized frame and keep it
+    pub fn push_stroke(&mut self, stroke: Stroke) {
+        let stroke = match &self.options.stroke_transform {
+            Some(transform) => transform.apply(&stroke),
+            None => stroke,
+        };
+        let size = self.frame.size();
+        let scale = self.frame.canvas_scale(self.canvas_width);
+        let mapped = Stroke {
+            points: stroke.points.iter()
+                .map(|p| {
+                    let (x, y) = self.frame.canvas_to_frame(p.x, p.y, self.canvas_width, self.canvas_height);
+                    StrokePoint { x, y, ..*p }
+                })
+                .collect(),
+            erase: false,
+            width: stroke.width.map(|w| w * scale),
+        };
+        let marks = rasterize_strokes(&[mapped], size as u32, size as u32, self.options.stroke_width * scale);
+
+        // Eraser strokes are rendered as ink here, then cleared from the drawing
+        for (x, y, pixel) in marks.enumerate_pixels() {
+            if pixel.0[0] < 128 {
+                self.drawn.set(x as usize, y as usize, !stroke.erase);
+            }
+        }
+        self.strokes.push(stroke);
+    }
+
+    /// Fraction of the reference skeleton covered so far, in [0, 1]
+    pub fn coverage_fraction(&self) -> f32 {
+        let skeleton = &self.frame.prepared().skeleton;
+        let skeleton_pixels = skeleton.count();
+        if skeleton_pixels == 0 || self.drawn.is_empty() {
+            return 0.0;
+        }
+
+        let drawn_distance = distance_transform_edt(&self.drawn);
+        let tolerance = LIVE_COVERAGE_TOLERANCE * frame_scale(self.frame.size());
+        let covered = skeleton.indices()
+            .filter(|&index| drawn_distance[index] <= tolerance)
+            .count();
+        covered as f32 / skeleton_pixels as f32
+    }
+
+    /// Mean distance credit of the drawn ink against the reference, in [0, 1]
+    pub fn precision_fraction(&self) -> f32 {
+        let distance = &self.frame.prepared().binary_distance;
+        let scale = frame_scale(self.frame.size());
+        let (tolerance, falloff) = (self.options.accuracy_tolerance * scale, self.options.accuracy_falloff * scale);
+        let (total, count) = self.drawn.indices()
+            .fold((0.0, 0usize), |(total, count), index| {
+                let credit = distance_credit(distance[index], tolerance, falloff);
+                (total + credit, count + 1)
+            });
+        if count == 0 { 0.0 } else { total / count as f32 }
+    }
+
+    /// The largest part of the letter not drawn yet, for pulsing that
+    /// region; `None` once every part is drawn
+    pub fn next_hint(&self) -> Option<NextStroke> {
+        self.frame.largest_uncovered(&self.drawn)
+    }
+
+    /// Reference segments as polylines in fractions (0-1) of the reference
+    /// image, for lighting up parts of the letter
+    pub fn segments(&self) -> Vec<Vec<(f32, f32)>> {
+        self.frame.prepared().graph.edges.iter()
+            .map(|e| e.points.iter().map(|&(x, y)| self.frame.frame_to_fraction(x, y)).collect())
+            .collect()
+    }
+}
+
+#[cfg_attr(feature = "wasm", wasm_bindgen)]
+impl LiveScorer {
+    /// Remove all strokes
+    pub fn clear(&mut self) {
+        let size = self.frame.size();
+        self.drawn = BitImage::new(size, size);
+        self.strokes.clear();
+    }
+
+    #[cfg_attr(feature = "wasm", wasm_bindgen(getter))]
+    pub fn stroke_count(&self) -> u32 {
+        self.strokes.len() as u32
+    }
+
+    /// Provisional coverage of the letter, 0-100
+    pub fn coverage(&self) -> f32 {
+        (self.coverage_fraction() * 100.0).round()
+    }
+
+    /// Provisional score, 0-100, from coverage and how well ink stays on the letter
+    ///
+    /// A cheap estimate for live feedback; use `score_drawing` for the final score.
+    pub fn provisional_score(&self) -> u8 {
+        let score = (self.coverage_fraction() + self.precision_fraction()) / 2.0;
+        (score * 100.0).clamp(0.0, 100.0) as u8
+    }
+
+    /// Coverage of each reference segment, 0-100, in the order of `segments`
+    pub fn segment_coverage(&self) -> Vec<f32> {
+        self.frame.segment_coverage(&self.drawn)
+            .iter()
+            .map(|c| (c * 100.0).round())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_font() -> Font {
+        Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap()
+    }
+
+    fn stroke(points: &[(f32, f32)]) -> Stroke {
+        Stroke {
+            points: points.iter().map(|&(x, y)| StrokePoint { x, y, t: None, pressure: None }).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Bar and stem of the 'T' reference on a 400px canvas
+    fn t_strokes() -> (Stroke, Stroke) {
+        let scorer = LiveScorer::with_options('T', &test_font(), 400, 400, ScoringOptions::default()).unwrap();
+        let segments: Vec<Vec<(f32, f32)>> = scorer.frame.prepared().graph.edges.iter()
+            .map(|e| e.points.iter().map(|&(x, y)| scorer.frame.frame_to_fraction(x, y)).collect())
+            .collect();
+        let to_canvas = |p: (f32, f32)| (p.0 * 400.0, p.1 * 400.0);
+
+        // The stem is the most vertical segment; the bar spans the rest
+        let stem = segments.iter()
+            .max_by(|a, b| {
+                let dy = |s: &Vec<(f32, f32)>| (s[0].1 - s[s.len() - 1].1).abs();
+                dy(a).total_cmp(&dy(b))
+            })
+            .unwrap();
+        let top = stem.iter().map(|p| p.1).fold(f32::MAX, f32::min);
+        let left = segments.iter().flatten().map(|p| p.0).fold(f32::MAX, f32::min);
+        let right = segments.iter().flatten().map(|p| p.0).fold(f32::MIN, f32::max);
+
+        (
+            stroke(&[to_canvas((left, top)), to_canvas((right, top))]),
+            stroke(&stem.iter().map(|&p| to_canvas(p)).collect::<Vec<_>>()),
+        )
+    }
+
+    #[test]
+    fn test_live_coverage_grows_with_strokes() {
+        let mut scorer = LiveScorer::with_options('T', &test_font(), 400, 400, ScoringOptions::default()).unwrap();
+        assert_eq!(scorer.coverage(), 0.0);
+        assert_eq!(scorer.provisional_score(), 0);
+
+        let (bar, stem) = t_strokes();
+        scorer.push_stroke(bar);
+        let partial = scorer.coverage();
+        assert!(partial > 20.0 && partial < 80.0, "partial coverage {}", partial);
+
+        let hint = scorer.frame.largest_uncovered(&scorer.drawn).unwrap();
+        assert!(hint.points.iter().any(|p| p.1 > 0.5), "stem should be left: {:?}", hint.start);
+
+        scorer.push_stroke(stem);
+        assert!(scorer.frame.largest_uncovered(&scorer.drawn).is_none());
+        assert!(scorer.coverage() > 90.0, "coverage {}", scorer.coverage());
+        assert!(scorer.provisional_score() > 80);
+        assert!(scorer.segment_coverage().iter().all(|&c| c > 50.0));
+
+        scorer.clear();
+        assert_eq!(scorer.stroke_count(), 0);
+        assert_eq!(scorer.coverage(), 0.0);
+    }
+
+    #[test]
+    fn test_live_eraser_removes_coverage() {
+        let mut scorer = LiveScorer::with_options('T', &test_font(), 400, 400, ScoringOptions::default()).unwrap();
+        let (bar, stem) = t_strokes();
+        scorer.push_stroke(bar.clone());
+        scorer.push_stroke(stem);
+        let full = scorer.coverage();
+
+        scorer.push_stroke(Stroke { erase: true, width: Some(60.0), ..bar });
+        assert!(scorer.coverage() < full - 20.0, "coverage {} after erasing from {}", scorer.coverage(), full);
+    }
+
+    #[test]
+    fn test_live_precision_penalizes_stray_ink() {
+        let mut scorer = LiveScorer::with_options('T', &test_font(), 400, 400, ScoringOptions::default()).unwrap();
+        scorer.push_stroke(stroke(&[(20.0, 380.0), (120.0, 380.0)]));
+
+        assert!(scorer.precision_fraction() < 0.1);
+        assert!(scorer.provisional_score() < 10);
+    }
+}
@@ -0,0 +1,102 @@
+//! Console logging of the scoring pipeline
+//!
+//! With the `tracing` feature, scoring logs its stages and intermediate
+//! statistics at the level given to `set_log_level`: to the browser console
+//! in WASM builds and to stderr elsewhere. Nothing is logged until a level
+//! is set. Without the feature `log!` compiles to nothing and its arguments
+//! are never evaluated.
+
+#[cfg(feature = "tracing")]
+use serde::{Serialize, Deserialize};
+#[cfg(feature = "tracing")]
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much the `tracing` feature logs, each level including the ones above
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Off,
+    /// Each call's stages and outcome: drawing size, score, caps
+    Info,
+    /// Intermediate statistics: ink pixel counts, stray marks, registration,
+    /// skeleton sizes, component scores and reference cache hits
+    Debug,
+    /// Every skeletonization and distance transform
+    Trace,
+}
+
+#[cfg(feature = "tracing")]
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Off as u8);
+
+/// Set how much scoring logs, for all threads; `Off` by default
+#[cfg(feature = "tracing")]
+pub fn set_log_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn enabled(level: LogLevel) -> bool {
+    level != LogLevel::Off && level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn write(level: LogLevel, message: std::fmt::Arguments) {
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    {
+        #[wasm_bindgen::prelude::wasm_bindgen]
+        extern "C" {
+            #[wasm_bindgen(js_namespace = console, js_name = info)]
+            fn console_info(message: &str);
+            #[wasm_bindgen(js_namespace = console, js_name = debug)]
+            fn console_debug(message: &str);
+        }
+        let message = format!("[scoring] {}", message);
+        match level {
+            LogLevel::Info => console_info(&message),
+            _ => console_debug(&message),
+        }
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    eprintln!("[scoring] {:?}: {}", level, message);
+    // A wasm32 build without JavaScript has nowhere to write
+    #[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+    let _ = (level, message);
+}
+
+/// Log a message at a `LogLevel` (`Info`, `Debug` or `Trace`), formatted
+/// like `format!` only when that level is on
+#[cfg(feature = "tracing")]
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::$level) {
+            $crate::logging::write($crate::logging::LogLevel::$level, format_args!($($arg)*));
+        }
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use log;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_levels() {
+        assert!(!enabled(LogLevel::Info));
+        set_log_level(LogLevel::Debug);
+        assert!(enabled(LogLevel::Info) && enabled(LogLevel::Debug));
+        assert!(!enabled(LogLevel::Trace) && !enabled(LogLevel::Off));
+        set_log_level(LogLevel::Off);
+        assert!(!enabled(LogLevel::Info));
+    }
+}
@@ -0,0 +1,577 @@
+//! Scoring options
+//!
+//! Tunable parameters for the scoring pipeline. Every field has a default,
+//! so callers only need to pass the values they want to change.
+
+use crate::age_bands::AgeBand;
+use crate::image_ops::{ThinningAlgorithm, ThinningLimits};
+use crate::practice::{PracticeOptions, ReferenceVariation};
+use crate::scoring::{WHITE, TARGET_SIZE, NORMALIZE_PADDING, REFERENCE_FONT_SIZE, REFERENCE_SUPERSAMPLING};
+use crate::scaffold::ScaffoldOptions;
+use crate::strokes::StrokeTransform;
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+/// Options controlling how a drawing is scored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoringOptions {
+    /// Distance (in pixels of the default 128-pixel normalized frame) from
+    /// the reference within which drawn pixels get full accuracy credit
+    pub accuracy_tolerance: f32,
+    /// Distance beyond the tolerance over which accuracy credit falls
+    /// linearly to zero
+    pub accuracy_falloff: f32,
+    /// Kind of exercise being scored
+    pub mode: ScoringMode,
+    /// Which similarity metric feeds the similarity component
+    pub similarity_method: SimilarityMethod,
+    /// How drawing and reference are thinned to skeletons; packs and
+    /// handles keep the algorithm they were built with
+    pub thinning: ThinningAlgorithm,
+    /// Relative weights of the score components
+    pub weights: ScoreWeights,
+    /// Minimum fraction of the reference skeleton that must be covered
+    /// before more than one star can be awarded
+    pub min_skeleton_coverage: f32,
+    /// Per-character overrides of `min_skeleton_coverage`
+    pub min_skeleton_coverage_overrides: HashMap<char, f32>,
+    /// Mapping from raw stroke coordinates to image pixels, if they differ
+    pub stroke_transform: Option<StrokeTransform>,
+    /// Nominal pen width (in canvas units) used when rasterizing strokes
+    pub stroke_width: f32,
+    /// Score the supplied strokes re-rendered instead of the image, so ink
+    /// that is no longer visible (undone strokes, faint erasures in a
+    /// flattened export) is ignored. Strokes with pressure are always
+    /// re-rendered.
+    pub rasterize_strokes: bool,
+    /// Average stroke points over this many canvas units along each stroke
+    /// before rendering, so a shaky hand isn't scored as inaccuracy; above
+    /// 0 the supplied strokes are always re-rendered
+    pub stroke_smoothing: f32,
+    /// For drawings scored from the image, round off ink edges with a
+    /// closing and an opening of this many pixels (at most 8); 0 leaves
+    /// them, and ink thinner than twice this plus one is erased
+    pub edge_smoothing: u32,
+    /// Stretch faint ink (e.g. light-gray passive stylus strokes) to full
+    /// contrast before thresholding
+    pub auto_contrast: bool,
+    /// Ink blobs of at most this many pixels in the submitted image are
+    /// erased before anything else looks at the ink; 0 keeps them all
+    pub speck_area: u32,
+    /// Leave small marks far from the letter out of centering and the
+    /// metrics; they are still counted and penalized
+    pub exclude_stray_marks: bool,
+    /// How ink is told from paper when the drawing is cropped and scaled
+    pub threshold: ThresholdMethod,
+    /// RGB color showing through transparent parts of the drawing
+    pub background: [u8; 3],
+    /// Which colors in the drawing count as ink
+    pub ink: InkColor,
+    /// The drawing is a camera photo of paper: even out its lighting,
+    /// threshold it locally and level it before scoring
+    pub photo: bool,
+    /// With `photo`, the corners of the worksheet's box in image pixels,
+    /// clockwise from the top left; the box is warped square-on and scored
+    /// alone
+    pub photo_corners: Option<[[f32; 2]; 4]>,
+    /// Time the learner spent drawing, if known; otherwise it is taken
+    /// from stroke timestamps when available
+    pub duration_ms: Option<f64>,
+    /// How drawing time affects the score
+    pub time: TimeOptions,
+    /// Practice variation mode: vary the reference glyph, size and position
+    pub practice: Option<PracticeOptions>,
+    /// Minimum scores for each star rating
+    pub stars: StarThresholds,
+    /// Strokes beyond the template's count allowed before the learner is
+    /// hinted to use fewer
+    pub extra_strokes_allowed: u32,
+    /// Age bundle these options were built from, if any. When options are
+    /// parsed with an `age_band`, the bundle supplies every field not given.
+    pub age_band: Option<AgeBand>,
+    /// Recent scores on the letter and how they escalate the scaffold level
+    pub scaffold: ScaffoldOptions,
+    /// Variable font axis values by tag (e.g. "wght": 600); axes the font
+    /// doesn't have are ignored
+    pub font_axes: HashMap<String, f32>,
+    /// Handwriting style (e.g. "ball_and_stick" or "continental") whose
+    /// letterforms and stroke order replace the font's for the characters
+    /// it defines
+    pub style: Option<String>,
+    /// How `generate_reference_image` draws the letter
+    pub guide: GuideOptions,
+    /// Font size of the reference glyph as a fraction of the image size
+    /// (handwriting style letters scale with it)
+    pub reference_font_size: f32,
+    /// Redraw the reference's strokes with a round pen this wide, as a
+    /// fraction of the image size, to match the canvas brush; `None` keeps
+    /// the font's own stroke weight
+    pub reference_stroke_width: Option<f32>,
+    /// Margin around the letter in the normalized frame, as a fraction of
+    /// the frame (clamped to 0-0.4)
+    pub normalize_padding: f32,
+    /// Render the reference this many times larger (1-4) and average it
+    /// back down, smoothing the edges of small or pen-drawn references
+    pub reference_supersampling: u32,
+    /// Side in pixels (64-512) of the square frame drawings and references
+    /// are compared in; larger is finer and slower. Prepared references and
+    /// packs keep the size they were built with.
+    pub frame_size: u32,
+    /// Find the alignment, check the letter's gross shape (legibility,
+    /// moments, loops, gaps, scribbles) and compare skeletons on a frame half
+    /// the size, keeping the full frame for coverage, accuracy, SSIM and
+    /// structure; faster, with scores within a few points. Frames under 128
+    /// use one level.
+    pub pyramid: bool,
+    /// Largest drawing accepted, checked before it is decoded
+    pub input_limits: InputLimits,
+    /// Record how long each stage of scoring takes in the result's
+    /// `timings`, for profiling on real devices
+    pub with_timings: bool,
+}
+
+impl ScoringOptions {
+    /// Preset for trace-over-the-letter exercises
+    ///
+    /// Strokes must stay inside the glyph outline with little slack, and
+    /// coverage counts for more since the learner can see what to cover.
+    pub fn tracing() -> Self {
+        Self {
+            mode: ScoringMode::Tracing,
+            accuracy_tolerance: 1.0,
+            accuracy_falloff: 3.0,
+            weights: ScoreWeights {
+                coverage: 0.40,
+                accuracy: 0.30,
+                similarity: 0.15,
+                structure: 0.15,
+                legibility: 0.02,
+            },
+            ..Self::default()
+        }
+    }
+
+    /// Options preset by name ("freehand" or "tracing")
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "freehand" => Some(Self::default()),
+            "tracing" => Some(Self::tracing()),
+            _ => None,
+        }
+    }
+
+    /// Parse options from JSON, starting from the age bundle when an
+    /// `age_band` is given and from the defaults otherwise
+    pub fn from_json(value: serde_json::Value) -> Result<Self, String> {
+        let base = match value.get("age_band") {
+            Some(band) if !band.is_null() => {
+                let band: AgeBand = serde_json::from_value(band.clone())
+                    .map_err(|e| format!("Invalid age band: {}", e))?;
+                band.options()
+            }
+            _ => Self::default(),
+        };
+
+        let mut merged = serde_json::to_value(base)
+            .map_err(|e| format!("Failed to serialize options: {}", e))?;
+        merge_json(&mut merged, value);
+        serde_json::from_value(merged).map_err(|e| format!("Invalid scoring options: {}", e))
+    }
+
+    /// Minimum skeleton coverage that applies to a character
+    pub fn min_skeleton_coverage_for(&self, character: char) -> f32 {
+        self.min_skeleton_coverage_overrides
+            .get(&character)
+            .copied()
+            .unwrap_or(self.min_skeleton_coverage)
+    }
+
+    /// Reference variation to render for a character
+    pub fn reference_variation(&self, character: char) -> ReferenceVariation {
+        match &self.practice {
+            Some(practice) => ReferenceVariation::choose(character, practice, &mut practice.random_source(character)),
+            None => ReferenceVariation::identity(character),
+        }
+    }
+}
+
+/// Overlay `patch` onto `base`, recursing into objects present in both
+fn merge_json(base: &mut serde_json::Value, patch: serde_json::Value) {
+    match (base, patch) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(patch)) => {
+            for (key, value) in patch {
+                match base.get_mut(&key) {
+                    Some(existing) if existing.is_object() && value.is_object() => merge_json(existing, value),
+                    _ => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, patch) => *base = patch,
+    }
+}
+
+/// Minimum combined score for each star rating; below `two` is one star
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StarThresholds {
+    pub five: u8,
+    pub four: u8,
+    pub three: u8,
+    pub two: u8,
+}
+
+impl Default for StarThresholds {
+    fn default() -> Self {
+        Self { five: 80, four: 65, three: 50, two: 30 }
+    }
+}
+
+impl StarThresholds {
+    /// Highest score that still rates one star
+    pub fn one_star_max(&self) -> u8 {
+        self.two.saturating_sub(1)
+    }
+}
+
+/// Relative weights of the components in the combined score
+///
+/// Weights are normalized by their sum, so they need not add up to 1.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoreWeights {
+    pub coverage: f32,
+    pub accuracy: f32,
+    pub similarity: f32,
+    pub structure: f32,
+    /// Reference-free legibility; kept small so it acts as a tiebreaker
+    pub legibility: f32,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            coverage: 0.30,
+            accuracy: 0.30,
+            similarity: 0.25,
+            structure: 0.15,
+            legibility: 0.02,
+        }
+    }
+}
+
+/// Time component of the score
+///
+/// Drawings finished faster than `min_duration_ms` lose up to
+/// `max_penalty` of the combined score, scaling linearly with how far
+/// under the minimum they were. Slower drawings are never penalized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeOptions {
+    pub min_duration_ms: f64,
+    /// Largest fraction of the combined score removed (0 disables)
+    pub max_penalty: f32,
+}
+
+impl Default for TimeOptions {
+    fn default() -> Self {
+        Self {
+            min_duration_ms: 1000.0,
+            max_penalty: 0.15,
+        }
+    }
+}
+
+impl TimeOptions {
+    /// Fraction of the combined score removed for a drawing duration
+    pub fn penalty_for(&self, duration_ms: f64) -> f32 {
+        if self.min_duration_ms <= 0.0 || duration_ms >= self.min_duration_ms {
+            return 0.0;
+        }
+        let shortfall = (1.0 - duration_ms.max(0.0) / self.min_duration_ms) as f32;
+        (shortfall * self.max_penalty).clamp(0.0, 1.0)
+    }
+}
+
+/// Size limits on submitted images
+///
+/// A decoded image takes width × height × 4 bytes however well it
+/// compresses, so a full-resolution screenshot can exhaust wasm memory.
+/// Larger images are rejected before decoding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputLimits {
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Largest encoded file, in bytes
+    pub max_bytes: usize,
+    /// Work thinning the drawing may do; references are always thinned
+    /// within the defaults
+    pub thinning: ThinningLimits,
+}
+
+impl Default for InputLimits {
+    fn default() -> Self {
+        Self {
+            max_width: 4096,
+            max_height: 4096,
+            max_bytes: 16 * 1024 * 1024,
+            thinning: ThinningLimits::default(),
+        }
+    }
+}
+
+/// Look of the reference image, for showing the letter as a tracing guide
+///
+/// Sizes are in pixels of the generated image. Scoring always compares
+/// against the solid letter.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuideOptions {
+    pub line: GuideLine,
+    /// Dot diameter or dash thickness
+    pub size: f32,
+    /// Distance between dot centers, or the gap between dashes; like
+    /// `dash_length`, at least a quarter of `size` and a pixel
+    pub spacing: f32,
+    pub dash_length: f32,
+    /// Writing lines behind the letter; the image is RGB when set
+    pub ruling: Option<RulingOptions>,
+    /// Where the letter sits in an image that isn't square
+    pub anchor: ReferenceAnchor,
+}
+
+impl Default for GuideOptions {
+    fn default() -> Self {
+        Self {
+            line: GuideLine::Solid,
+            size: 4.0,
+            spacing: 10.0,
+            dash_length: 12.0,
+            ruling: None,
+            anchor: ReferenceAnchor::Center,
+        }
+    }
+}
+
+/// Topline, midline and baseline rules, as on primary writing paper
+///
+/// Colors are CSS hex (`#rgb` or `#rrggbb`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RulingOptions {
+    pub topline_color: String,
+    pub midline_color: String,
+    pub baseline_color: String,
+    pub dashed_midline: bool,
+    /// Line thickness in pixels
+    pub thickness: f32,
+}
+
+impl Default for RulingOptions {
+    fn default() -> Self {
+        Self {
+            topline_color: "#3366cc".to_string(),
+            midline_color: "#3366cc".to_string(),
+            baseline_color: "#cc3333".to_string(),
+            dashed_midline: true,
+            thickness: 2.0,
+        }
+    }
+}
+
+/// Placement of the letter's square in a wider or taller image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceAnchor {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl ReferenceAnchor {
+    /// Offset of the letter's square, given the spare width and height
+    pub fn offset(self, spare_width: u32, spare_height: u32) -> (u32, u32) {
+        use ReferenceAnchor::*;
+        let x = match self {
+            Left | TopLeft | BottomLeft => 0,
+            Right | TopRight | BottomRight => spare_width,
+            Center | Top | Bottom => spare_width / 2,
+        };
+        let y = match self {
+            Top | TopLeft | TopRight => 0,
+            Bottom | BottomLeft | BottomRight => spare_height,
+            Center | Left | Right => spare_height / 2,
+        };
+        (x, y)
+    }
+}
+
+/// How the letter is drawn in a guide
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuideLine {
+    /// The filled letter
+    Solid,
+    /// Dots along the outline (along the centerline for handwriting styles)
+    Dotted,
+    /// Dashes along the outline (along the centerline for handwriting styles)
+    Dashed,
+}
+
+/// Kind of exercise, which changes what accuracy is measured against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringMode {
+    /// Writing without a guide: accuracy is distance from the letter's strokes
+    Freehand,
+    /// Tracing over a visible letter: accuracy is how well the drawn
+    /// centerline stays inside the glyph outline
+    Tracing,
+}
+
+/// How the drawing is split into ink and paper
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdMethod {
+    /// Otsu's method on the drawing's own histogram, so light or pastel ink
+    /// counts; falls back to `Fixed` when ink and paper aren't distinct
+    #[default]
+    Otsu,
+    /// Everything darker than a fixed gray level
+    Fixed,
+}
+
+/// Which colors in a drawing are ink
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InkColor {
+    /// Any color that stands out from the paper, taken to be the drawing's
+    /// median color
+    #[default]
+    Any,
+    /// Dark ink on light paper, by brightness alone
+    Dark,
+}
+
+/// Similarity metric used for the similarity component of the score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimilarityMethod {
+    /// IoU and directional chamfer distance on thickness-normalized skeletons
+    Skeleton,
+    /// Grayscale structural similarity (SSIM) on the normalized images
+    Ssim,
+    /// Average of the skeleton and SSIM metrics
+    Blended,
+    /// Shape context descriptor matching along both skeletons (slower)
+    ShapeContext,
+}
+
+impl Default for ScoringOptions {
+    fn default() -> Self {
+        Self {
+            accuracy_tolerance: 3.0,
+            accuracy_falloff: 6.0,
+            mode: ScoringMode::Freehand,
+            similarity_method: SimilarityMethod::Skeleton,
+            thinning: ThinningAlgorithm::default(),
+            weights: ScoreWeights::default(),
+            min_skeleton_coverage: 0.4,
+            min_skeleton_coverage_overrides: HashMap::new(),
+            stroke_transform: None,
+            stroke_width: 12.0,
+            rasterize_strokes: false,
+            stroke_smoothing: 0.0,
+            edge_smoothing: 0,
+            auto_contrast: true,
+            speck_area: 4,
+            exclude_stray_marks: true,
+            threshold: ThresholdMethod::default(),
+            background: WHITE,
+            ink: InkColor::default(),
+            photo: false,
+            photo_corners: None,
+            duration_ms: None,
+            time: TimeOptions::default(),
+            practice: None,
+            stars: StarThresholds::default(),
+            extra_strokes_allowed: 0,
+            age_band: None,
+            scaffold: ScaffoldOptions::default(),
+            font_axes: HashMap::new(),
+            style: None,
+            guide: GuideOptions::default(),
+            reference_font_size: REFERENCE_FONT_SIZE,
+            reference_stroke_width: None,
+            normalize_padding: NORMALIZE_PADDING,
+            reference_supersampling: REFERENCE_SUPERSAMPLING,
+            frame_size: TARGET_SIZE,
+            pyramid: false,
+            input_limits: InputLimits::default(),
+            with_timings: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_min_skeleton_coverage_override() {
+        let mut options = ScoringOptions::default();
+        options.min_skeleton_coverage_overrides.insert('l', 0.7);
+
+        assert_eq!(options.min_skeleton_coverage_for('l'), 0.7);
+        assert_eq!(options.min_skeleton_coverage_for('A'), 0.4);
+    }
+
+    #[test]
+    fn test_presets() {
+        let tracing = ScoringOptions::preset("tracing").unwrap();
+        assert_eq!(tracing.mode, ScoringMode::Tracing);
+        assert!(tracing.accuracy_tolerance < ScoringOptions::default().accuracy_tolerance);
+        assert!(tracing.weights.coverage > ScoreWeights::default().coverage);
+
+        assert_eq!(ScoringOptions::preset("freehand").unwrap().mode, ScoringMode::Freehand);
+        assert!(ScoringOptions::preset("cursive").is_none());
+    }
+
+    #[test]
+    fn test_time_penalty() {
+        let time = TimeOptions::default();
+
+        assert_eq!(time.penalty_for(20_000.0), 0.0);
+        assert_eq!(time.penalty_for(1000.0), 0.0);
+        assert!((time.penalty_for(200.0) - 0.12).abs() < 1e-5);
+        assert!((time.penalty_for(0.0) - 0.15).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_from_json_overlays_age_band() {
+        let options = ScoringOptions::from_json(serde_json::json!({
+            "age_band": "3-4",
+            "weights": { "coverage": 0.5 },
+        })).unwrap();
+        let bundle = AgeBand::Ages3To4.options();
+
+        assert_eq!(options.age_band, Some(AgeBand::Ages3To4));
+        assert_eq!(options.accuracy_tolerance, bundle.accuracy_tolerance);
+        assert_eq!(options.weights.coverage, 0.5);
+        assert_eq!(options.weights.accuracy, bundle.weights.accuracy);
+
+        let plain = ScoringOptions::from_json(serde_json::json!({ "accuracy_tolerance": 2.0 })).unwrap();
+        assert_eq!(plain.accuracy_tolerance, 2.0);
+        assert_eq!(plain.stars.five, 80);
+        assert!(ScoringOptions::from_json(serde_json::json!({ "age_band": "9-10" })).is_err());
+    }
+}
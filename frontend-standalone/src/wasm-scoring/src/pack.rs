@@ -0,0 +1,552 @@
+//! Precompiled reference packs and serialized references
+//!
+//! A pack holds the prepared references for a whole character set, built
+//! once from a font (at build time or on a teacher's machine) and shipped
+//! instead of the font. A [`ReferenceHandle`] is a single prepared
+//! reference, which the app can serialize into IndexedDB and restore on the
+//! next page load.
+//!
+//! Both store each reference as the rendered glyph (PNG) plus the expensive
+//! parts to compute: the thickness-normalized mask and skeleton, bit-packed,
+//! and the stroke orientation at each mask pixel. Distance fields and the
+//! skeleton graph are rebuilt on load; they are cheap to derive and would
+//! take over 30 times the space.
+//!
+//! Layout, little-endian:
+//!
+//! ```text
+//! header:    magic  u16 version  u16 frame size  u32 reference size
+//!            u16 thinning (0 Zhang-Suen, 1 Guo-Hall)
+//! reference: u32 PNG length  PNG  f32 padding  mask bits  skeleton bits
+//!            f32 orientation per set mask bit
+//!
+//! pack:      "LLRP" header  u32 entry count
+//!            per entry: u32 character  reference
+//! handle:    "LLRH" header  u32 character
+//!            u32 glyph  f32 scale  f32 offset x  f32 offset y  reference
+//! ```
+
+use crate::bit_image::BitImage;
+use crate::error::ScoringError;
+use crate::font::Font;
+use crate::image_ops::ThinningAlgorithm;
+use crate::options::{InputLimits, ScoringOptions};
+use crate::practice::ReferenceVariation;
+use crate::reference::{Reference, cached_reference};
+#[cfg(feature = "shapes")]
+use crate::shape::ReferenceShape;
+use crate::scoring::{
+    TARGET_SIZE, REFERENCE_SIZE, NORMALIZE_PADDING, WHITE, checked_frame_size, decode_image, encode_grayscale_to_png, flatten_alpha, render_reference, score_against_reference
+};
+use crate::strokes::Stroke;
+use crate::ScoredDrawing;
+use image::{GrayImage, ImageBuffer, Luma};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+const PACK_MAGIC: &[u8; 4] = b"LLRP";
+const HANDLE_MAGIC: &[u8; 4] = b"LLRH";
+const FORMAT_VERSION: u16 = 3;
+
+/// Prepared references for a set of characters, loaded from a pack
+pub struct ReferencePack {
+    references: BTreeMap<char, Arc<Reference>>,
+}
+
+impl ReferencePack {
+    /// Characters the pack has references for, in code point order
+    pub fn characters(&self) -> impl Iterator<Item = char> + '_ {
+        self.references.keys().copied()
+    }
+
+    /// Whether the pack has a reference for the character
+    pub fn contains(&self, character: char) -> bool {
+        self.references.contains_key(&character)
+    }
+
+    /// PNG bytes of the reference for a character, as scoring returns it
+    pub fn reference_image(&self, character: char) -> Option<&[u8]> {
+        self.references.get(&character).map(|reference| reference.png.as_slice())
+    }
+
+    pub(crate) fn reference(&self, character: char) -> Result<&Arc<Reference>, ScoringError> {
+        self.references.get(&character).ok_or(ScoringError::GlyphMissing(character))
+    }
+}
+
+/// A prepared reference for one character, ready to score against
+pub struct ReferenceHandle {
+    character: char,
+    variation: ReferenceVariation,
+    reference: Arc<Reference>,
+}
+
+impl ReferenceHandle {
+    /// Render and prepare the reference for a character
+    ///
+    /// `options.font_axes` and `options.practice` choose the reference, as
+    /// they would when scoring with the font.
+    pub fn prepare(character: char, font: &Font, options: &ScoringOptions) -> Result<Self, ScoringError> {
+        let variation = options.reference_variation(character);
+        let reference = cached_reference(&variation, font, options, REFERENCE_SIZE)?;
+        Ok(Self { character, variation, reference })
+    }
+
+    /// Prepare a shape as the reference for a character
+    ///
+    /// The character picks the stroke template and letter-specific checks.
+    #[cfg(feature = "shapes")]
+    pub fn from_shape(character: char, shape: &ReferenceShape) -> Result<Self, ScoringError> {
+        let image = shape.render(REFERENCE_SIZE);
+        let png = encode_grayscale_to_png(&image)?;
+        let reference = Arc::new(Reference::from_image(image, png, NORMALIZE_PADDING, TARGET_SIZE, ThinningAlgorithm::default(), None));
+        Ok(Self { character, variation: ReferenceVariation::identity(character), reference })
+    }
+
+    /// Prepare an image, such as a scanned handwriting model, as the
+    /// reference for a character
+    ///
+    /// The image is fitted onto a white square the size of a rendered
+    /// reference, keeping its aspect ratio.
+    pub fn from_template(character: char, template_png: &[u8]) -> Result<Self, ScoringError> {
+        let template = decode_image(template_png, &InputLimits::default())
+            .map_err(|e| format!("Invalid template image: {}", e))?;
+        let template = flatten_alpha(template, WHITE).to_luma8();
+        let (width, height) = template.dimensions();
+        let scale = REFERENCE_SIZE as f32 / width.max(height) as f32;
+        let fitted_width = ((width as f32 * scale).round() as u32).clamp(1, REFERENCE_SIZE);
+        let fitted_height = ((height as f32 * scale).round() as u32).clamp(1, REFERENCE_SIZE);
+        let fitted = image::imageops::resize(&template, fitted_width, fitted_height, image::imageops::FilterType::Triangle);
+
+        let mut image: GrayImage = ImageBuffer::from_pixel(REFERENCE_SIZE, REFERENCE_SIZE, Luma([255u8]));
+        image::imageops::overlay(
+            &mut image,
+            &fitted,
+            ((REFERENCE_SIZE - fitted_width) / 2) as i64,
+            ((REFERENCE_SIZE - fitted_height) / 2) as i64,
+        );
+        let png = encode_grayscale_to_png(&image)?;
+        let reference = Arc::new(Reference::from_image(image, png, NORMALIZE_PADDING, TARGET_SIZE, ThinningAlgorithm::default(), None));
+        Ok(Self { character, variation: ReferenceVariation::identity(character), reference })
+    }
+
+    /// The character this reference is for
+    pub fn character(&self) -> char {
+        self.character
+    }
+
+    /// PNG bytes of the reference, as scoring returns it
+    pub fn reference_image(&self) -> &[u8] {
+        &self.reference.png
+    }
+
+    /// Encode the prepared reference for storage
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let prepared = &self.reference.prepared;
+        write_header(&mut bytes, HANDLE_MAGIC, prepared.size as u32, prepared.algorithm);
+        bytes.extend_from_slice(&(self.character as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.variation.glyph as u32).to_le_bytes());
+        for value in [self.variation.scale, self.variation.offset_x, self.variation.offset_y] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        write_reference(&mut bytes, &self.reference);
+        bytes
+    }
+
+    /// Restore a reference encoded by `serialize`
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, ScoringError> {
+        let mut reader = PackReader { data: bytes };
+        let (frame, algorithm) = read_header(&mut reader, HANDLE_MAGIC, "serialized reference")?;
+        let character = reader.character()?;
+        let variation = ReferenceVariation {
+            glyph: reader.character()?,
+            scale: reader.f32()?,
+            offset_x: reader.f32()?,
+            offset_y: reader.f32()?,
+        };
+        let reference = read_reference(&mut reader, character, frame, algorithm)?;
+        Ok(Self { character, variation, reference: Arc::new(reference) })
+    }
+}
+
+/// Score a drawing against a prepared reference
+///
+/// The reference's glyph, variation and axes were fixed when it was
+/// prepared, so `options.practice` and `options.font_axes` don't change it.
+pub fn score_drawing_with_reference_internal(
+    image_data: &[u8],
+    reference: &ReferenceHandle,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    score_against_reference(image_data, reference.character, &reference.variation, &reference.reference, strokes, options)
+}
+
+/// Score a drawing against the pack's reference for the character
+///
+/// Packs hold unvaried glyphs, so practice variation needs the font.
+pub fn score_drawing_with_pack_internal(
+    image_data: &[u8],
+    character: char,
+    pack: &ReferencePack,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    if options.practice.is_some() {
+        return Err("Practice variation needs the font; reference packs hold unvaried glyphs".into());
+    }
+    let reference = pack.reference(character)?;
+    score_against_reference(image_data, character, &ReferenceVariation::identity(character), reference, strokes, options)
+}
+
+/// Render and prepare the references for every character in `charset`
+///
+/// Uses `options.font_axes`, `options.style`, the reference sizing options,
+/// `options.frame_size` and `options.thinning`; other options don't affect
+/// the references.
+pub fn build_reference_pack(font: &Font, charset: &str, options: &ScoringOptions) -> Result<Vec<u8>, ScoringError> {
+    let characters: Vec<char> = charset.chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<std::collections::BTreeSet<char>>()
+        .into_iter()
+        .collect();
+
+    let frame = checked_frame_size(options.frame_size)?;
+    let mut pack = Vec::new();
+    write_header(&mut pack, PACK_MAGIC, frame, options.thinning);
+    pack.extend_from_slice(&(characters.len() as u32).to_le_bytes());
+
+    for character in characters {
+        let image = render_reference(&ReferenceVariation::identity(character), font, options, REFERENCE_SIZE)?;
+        let png = encode_grayscale_to_png(&image)?;
+        let reference = Reference::from_image(image, png, options.normalize_padding, frame, options.thinning, None);
+
+        pack.extend_from_slice(&(character as u32).to_le_bytes());
+        write_reference(&mut pack, &reference);
+    }
+
+    Ok(pack)
+}
+
+/// Load a pack made by `build_reference_pack`
+pub fn load_reference_pack(bytes: &[u8]) -> Result<ReferencePack, ScoringError> {
+    let mut reader = PackReader { data: bytes };
+    let (frame, algorithm) = read_header(&mut reader, PACK_MAGIC, "reference pack")?;
+
+    let count = reader.u32()?;
+    let mut references = BTreeMap::new();
+    for _ in 0..count {
+        let character = reader.character()?;
+        let reference = read_reference(&mut reader, character, frame, algorithm)?;
+        references.insert(character, Arc::new(reference));
+    }
+
+    Ok(ReferencePack { references })
+}
+
+fn write_header(bytes: &mut Vec<u8>, magic: &[u8; 4], frame: u32, algorithm: ThinningAlgorithm) {
+    bytes.extend_from_slice(magic);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(frame as u16).to_le_bytes());
+    bytes.extend_from_slice(&REFERENCE_SIZE.to_le_bytes());
+    let thinning: u16 = match algorithm {
+        ThinningAlgorithm::ZhangSuen => 0,
+        ThinningAlgorithm::GuoHall => 1,
+    };
+    bytes.extend_from_slice(&thinning.to_le_bytes());
+}
+
+/// Check the header, returning the frame size and thinning algorithm the
+/// references were built with
+fn read_header(reader: &mut PackReader, magic: &[u8; 4], kind: &str) -> Result<(u32, ThinningAlgorithm), ScoringError> {
+    if reader.take(4)? != magic {
+        return Err(format!("Not a {}", kind).into());
+    }
+    let version = reader.u16()?;
+    if version != FORMAT_VERSION {
+        return Err(format!("Unsupported {} version {}", kind, version).into());
+    }
+    let frame_size = reader.u16()? as u32;
+    let reference_size = reader.u32()?;
+    if reference_size != REFERENCE_SIZE {
+        return Err(format!(
+            "The {} was built from {}px references, not {}px", kind, reference_size, REFERENCE_SIZE
+        ).into());
+    }
+    let algorithm = match reader.u16()? {
+        0 => ThinningAlgorithm::ZhangSuen,
+        1 => ThinningAlgorithm::GuoHall,
+        other => return Err(format!("Unknown thinning algorithm {} in the {}", other, kind).into()),
+    };
+    Ok((checked_frame_size(frame_size)?, algorithm))
+}
+
+fn write_reference(bytes: &mut Vec<u8>, reference: &Reference) {
+    bytes.extend_from_slice(&(reference.png.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&reference.png);
+    bytes.extend_from_slice(&reference.padding.to_le_bytes());
+    let prepared = &reference.prepared;
+    bytes.extend(pack_bits(&prepared.norm));
+    bytes.extend(pack_bits(&prepared.skeleton));
+    for index in prepared.norm.indices() {
+        bytes.extend_from_slice(&prepared.norm_orientation[index].to_le_bytes());
+    }
+}
+
+fn read_reference(reader: &mut PackReader, character: char, frame: u32, algorithm: ThinningAlgorithm) -> Result<Reference, ScoringError> {
+    let size = frame as usize;
+    let pixels = size * size;
+    let png_length = reader.u32()? as usize;
+    let png = reader.take(png_length)?.to_vec();
+    let padding = reader.f32()?;
+    let norm = unpack_bits(reader.take(pixels.div_ceil(8))?, size, size);
+    let skeleton = unpack_bits(reader.take(pixels.div_ceil(8))?, size, size);
+    // Orientations are only read at mask pixels
+    let mut orientation = vec![0.0f32; pixels];
+    for index in norm.indices() {
+        orientation[index] = reader.f32()?;
+    }
+
+    let image = decode_image(&png, &InputLimits::default())
+        .map_err(|e| format!("Invalid reference image for '{}': {}", character, e))?
+        .to_luma8();
+    if image.dimensions() != (REFERENCE_SIZE, REFERENCE_SIZE) {
+        return Err(format!("Reference image for '{}' has the wrong size", character).into());
+    }
+    Ok(Reference::from_image(image, png, padding, frame, algorithm, Some((norm, skeleton, orientation))))
+}
+
+// Masks are stored in row order, eight pixels to a byte, lowest bit first
+fn pack_bits(mask: &BitImage) -> Vec<u8> {
+    let mut bytes = vec![0u8; (mask.width() * mask.height()).div_ceil(8)];
+    for index in mask.indices() {
+        bytes[index / 8] |= 1 << (index % 8);
+    }
+    bytes
+}
+
+fn unpack_bits(bytes: &[u8], width: usize, height: usize) -> BitImage {
+    BitImage::from_fn(width, height, |x, y| {
+        let index = y * width + x;
+        bytes[index / 8] & (1 << (index % 8)) != 0
+    })
+}
+
+struct PackReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> PackReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ScoringError> {
+        if self.data.len() < len {
+            return Err("Reference data is truncated".into());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16, ScoringError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ScoringError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, ScoringError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn character(&mut self) -> Result<char, ScoringError> {
+        char::from_u32(self.u32()?).ok_or_else(|| "Invalid character in reference data".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::practice::PracticeOptions;
+    use crate::scoring::{generate_reference_gray, generate_reference_image_internal, score_drawing_internal};
+    use std::collections::HashMap;
+
+    fn test_font() -> Font {
+        Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap()
+    }
+
+    #[test]
+    fn test_pack_bits_round_trip() {
+        let mask = BitImage::from_fn(7, 3, |x, y| (y * 7 + x) % 3 == 0 || (x, y) == (6, 2));
+        let bytes = pack_bits(&mask);
+        assert_eq!(bytes.len(), 3);
+        assert_eq!(bytes[0], 0b0100_1001);
+        assert_eq!(unpack_bits(&bytes, 7, 3), mask);
+    }
+
+    #[test]
+    fn test_reference_pack_round_trip() {
+        let font = test_font();
+        let bytes = build_reference_pack(&font, "ba a\n", &ScoringOptions::default()).unwrap();
+        let pack = load_reference_pack(&bytes).unwrap();
+        assert_eq!(pack.characters().collect::<String>(), "ab");
+        assert!(!pack.contains('c'));
+        assert_eq!(pack.reference('c').err(), Some(ScoringError::GlyphMissing('c')));
+
+        // A loaded reference matches one prepared from the font
+        let fresh = cached_reference(&ReferenceVariation::identity('a'), &font, &ScoringOptions::default(), REFERENCE_SIZE).unwrap();
+        let loaded = pack.reference('a').unwrap();
+        assert_eq!(loaded.image, fresh.image);
+        assert_eq!(loaded.prepared.processed, fresh.prepared.processed);
+        assert_eq!(loaded.prepared.norm, fresh.prepared.norm);
+        assert_eq!(loaded.prepared.skeleton, fresh.prepared.skeleton);
+        assert_eq!(loaded.prepared.norm_distance, fresh.prepared.norm_distance);
+        for i in fresh.prepared.norm.indices() {
+            assert_eq!(loaded.prepared.norm_orientation[i], fresh.prepared.norm_orientation[i]);
+        }
+        assert_eq!(pack.reference_image('a'), Some(fresh.png.as_slice()));
+
+        // So scoring against the pack gives the font's result
+        let options = ScoringOptions::default();
+        let png = generate_reference_image_internal('a', &font, 300, &options).unwrap();
+        let from_font = score_drawing_internal(&png, 'a', &font, None, &options).unwrap();
+        let from_pack = score_drawing_with_pack_internal(&png, 'a', &pack, None, &options).unwrap();
+        assert_eq!(serde_json::to_string(&from_pack.result).unwrap(), serde_json::to_string(&from_font.result).unwrap());
+        assert_eq!(from_pack.reference_image, from_font.reference_image);
+
+        let practice = ScoringOptions { practice: Some(Default::default()), ..Default::default() };
+        assert!(score_drawing_with_pack_internal(&png, 'a', &pack, None, &practice).is_err());
+        assert!(matches!(
+            score_drawing_with_pack_internal(&png, 'c', &pack, None, &options),
+            Err(ScoringError::GlyphMissing('c'))
+        ));
+    }
+
+    #[test]
+    fn test_load_reference_pack_rejects_bad_data() {
+        let bytes = build_reference_pack(&test_font(), "a", &ScoringOptions::default()).unwrap();
+        assert!(load_reference_pack(b"not a pack").is_err());
+        assert!(load_reference_pack(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut other_version = bytes.clone();
+        other_version[4] = 9;
+        assert!(load_reference_pack(&other_version).is_err());
+
+        // A reference image over the input limits is refused before decoding
+        let start = bytes.windows(8).position(|w| w == b"\x89PNG\r\n\x1a\n").unwrap();
+        let length = u32::from_le_bytes(bytes[start - 4..start].try_into().unwrap()) as usize;
+        let wide = encode_grayscale_to_png(&GrayImage::new(5000, 1)).unwrap();
+        let mut oversized = bytes[..start - 4].to_vec();
+        oversized.extend_from_slice(&(wide.len() as u32).to_le_bytes());
+        oversized.extend_from_slice(&wide);
+        oversized.extend_from_slice(&bytes[start + length..]);
+        let error = load_reference_pack(&oversized).err().unwrap().to_string();
+        assert!(error.contains("over the 4096x4096 limit"), "{}", error);
+    }
+
+    #[test]
+    fn test_reference_handle_round_trip() {
+        let font = test_font();
+        let options = ScoringOptions {
+            practice: Some(PracticeOptions { seed: 7, ..Default::default() }),
+            ..Default::default()
+        };
+        let handle = ReferenceHandle::prepare('e', &font, &options).unwrap();
+        let bytes = handle.serialize();
+        let restored = ReferenceHandle::deserialize(&bytes).unwrap();
+        assert_eq!(restored.character(), 'e');
+        assert_eq!(restored.variation, handle.variation);
+        assert_eq!(restored.reference_image(), handle.reference_image());
+        assert_eq!(restored.reference.prepared.skeleton, handle.reference.prepared.skeleton);
+        assert_eq!(restored.reference.prepared.binary_distance, handle.reference.prepared.binary_distance);
+
+        // Scores match scoring with the font, practice variation included
+        let png = generate_reference_image_internal('e', &font, 300, &options).unwrap();
+        let from_font = score_drawing_internal(&png, 'e', &font, None, &options).unwrap();
+        let from_handle = score_drawing_with_reference_internal(&png, &restored, None, &options).unwrap();
+        assert_eq!(serde_json::to_string(&from_handle.result).unwrap(), serde_json::to_string(&from_font.result).unwrap());
+
+        // A pack is not a serialized reference, nor the other way round
+        let pack = build_reference_pack(&font, "e", &ScoringOptions::default()).unwrap();
+        assert!(ReferenceHandle::deserialize(&pack).is_err());
+        assert!(load_reference_pack(&bytes).is_err());
+        assert!(ReferenceHandle::deserialize(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn test_pack_keeps_thinning_algorithm() {
+        let font = test_font();
+        let guo_hall = ScoringOptions { thinning: ThinningAlgorithm::GuoHall, ..Default::default() };
+        let zhang_suen = ScoringOptions { thinning: ThinningAlgorithm::ZhangSuen, ..Default::default() };
+        let bytes = build_reference_pack(&font, "x", &guo_hall).unwrap();
+        let pack = load_reference_pack(&bytes).unwrap();
+        let loaded = pack.reference('x').unwrap();
+        assert_eq!(loaded.prepared.algorithm, ThinningAlgorithm::GuoHall);
+
+        // The cache keeps one reference per algorithm, and the pack's matches its own
+        let fresh = cached_reference(&ReferenceVariation::identity('x'), &font, &guo_hall, REFERENCE_SIZE).unwrap();
+        let other = cached_reference(&ReferenceVariation::identity('x'), &font, &zhang_suen, REFERENCE_SIZE).unwrap();
+        assert_eq!(loaded.prepared.skeleton, fresh.prepared.skeleton);
+        assert_ne!(fresh.prepared.skeleton, other.prepared.skeleton);
+
+        // Drawings are thinned like the pack, whatever the options say
+        let png = generate_reference_image_internal('x', &font, 300, &guo_hall).unwrap();
+        let from_font = score_drawing_internal(&png, 'x', &font, None, &guo_hall).unwrap();
+        let from_pack = score_drawing_with_pack_internal(&png, 'x', &pack, None, &zhang_suen).unwrap();
+        assert_eq!(serde_json::to_string(&from_pack.result).unwrap(), serde_json::to_string(&from_font.result).unwrap());
+
+        let handle = ReferenceHandle::prepare('x', &font, &guo_hall).unwrap();
+        let restored = ReferenceHandle::deserialize(&handle.serialize()).unwrap();
+        assert_eq!(restored.reference.prepared.algorithm, ThinningAlgorithm::GuoHall);
+
+        let mut unknown = bytes.clone();
+        unknown[12] = 7;
+        assert!(load_reference_pack(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_reference_handle_from_template() {
+        // A wide template is fitted, centered, onto a reference-sized square
+        let font = test_font();
+        let options = ScoringOptions::default();
+        let glyph = generate_reference_gray(&ReferenceVariation::identity('m'), &font, &HashMap::new(), 300).unwrap();
+        let mut wide: GrayImage = ImageBuffer::from_pixel(600, 300, Luma([255u8]));
+        image::imageops::overlay(&mut wide, &glyph, 150, 0);
+        let template = encode_grayscale_to_png(&wide).unwrap();
+        let handle = ReferenceHandle::from_template('m', &template).unwrap();
+        assert_eq!(handle.reference.image.dimensions(), (REFERENCE_SIZE, REFERENCE_SIZE));
+        assert!(handle.reference.image.get_pixel(REFERENCE_SIZE / 2, 10).0[0] == 255);
+
+        // Scores against the template follow scoring against the font
+        let drawing = generate_reference_image_internal('m', &font, 300, &options).unwrap();
+        let from_template = score_drawing_with_reference_internal(&drawing, &handle, None, &options).unwrap();
+        let from_font = score_drawing_internal(&drawing, 'm', &font, None, &options).unwrap();
+        assert!(from_template.result.score.abs_diff(from_font.result.score) <= 5);
+        let other = generate_reference_image_internal('o', &font, 300, &options).unwrap();
+        let mismatch = score_drawing_with_reference_internal(&other, &handle, None, &options).unwrap();
+        assert!(mismatch.result.score < from_template.result.score);
+
+        assert!(ReferenceHandle::from_template('m', b"not an image").is_err());
+    }
+
+    #[cfg(feature = "shapes")]
+    #[test]
+    fn test_reference_handle_from_shape() {
+        // A stroked "L" scores its own rendering highly and another letter poorly
+        let shape = ReferenceShape::from_svg_path("M10 0 V100 H70", Some(14.0)).unwrap();
+        let handle = ReferenceHandle::from_shape('L', &shape).unwrap();
+        let options = ScoringOptions::default();
+        let own = crate::generate_shape_reference_image(&shape, 300).unwrap();
+        let scored = score_drawing_with_reference_internal(&own, &handle, None, &options).unwrap();
+        assert!(scored.result.score >= 90, "score {}", scored.result.score);
+
+        let other = ReferenceShape::from_svg_path("M50 0 A50 50 0 1 1 49.9 0", Some(14.0)).unwrap();
+        let other = crate::generate_shape_reference_image(&other, 300).unwrap();
+        let scored = score_drawing_with_reference_internal(&other, &handle, None, &options).unwrap();
+        assert!(scored.result.score < 60, "score {}", scored.result.score);
+
+        // Shape references serialize like font ones
+        let restored = ReferenceHandle::deserialize(&handle.serialize()).unwrap();
+        assert_eq!(restored.reference_image(), handle.reference_image());
+    }
+}
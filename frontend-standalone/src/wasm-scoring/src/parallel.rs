@@ -0,0 +1,85 @@
+//! Optional multithreading
+//!
+//! With the `parallel` feature these helpers run on rayon's thread pool
+//! (in the browser, Web Workers started by `initThreadPool`); without it
+//! they are plain loops, so callers don't need their own `cfg`s.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+// Chunks handed to one task at a time; a 128-pixel row is too little work
+// to be worth scheduling on its own
+#[cfg(feature = "parallel")]
+const MIN_CHUNKS_PER_TASK: usize = 16;
+
+/// `f` applied to each item, in order
+pub(crate) fn map<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync + Send) -> Vec<R> {
+    #[cfg(feature = "parallel")]
+    return items.par_iter().map(f).collect();
+    #[cfg(not(feature = "parallel"))]
+    return items.iter().map(f).collect();
+}
+
+/// `f` applied to each `chunk`-sized piece of `values`, with per-task
+/// scratch state from `init`
+pub(crate) fn for_each_chunk<T: Send, S>(
+    values: &mut [T],
+    chunk: usize,
+    init: impl Fn() -> S + Sync + Send,
+    f: impl Fn(&mut S, &mut [T]) + Sync + Send,
+) {
+    #[cfg(feature = "parallel")]
+    values.par_chunks_mut(chunk).with_min_len(MIN_CHUNKS_PER_TASK).for_each_init(init, f);
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut state = init();
+        for piece in values.chunks_mut(chunk) {
+            f(&mut state, piece);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_helpers_cover_every_item_in_order() {
+        let items: Vec<u32> = (0..100).collect();
+        assert_eq!(map(&items, |i| i * 2), items.iter().map(|i| i * 2).collect::<Vec<_>>());
+
+        let mut values = vec![1u32; 1000];
+        for_each_chunk(&mut values, 7, || 0u32, |_, piece| {
+            for (i, v) in piece.iter_mut().enumerate() {
+                *v += i as u32;
+            }
+        });
+        assert!(values.chunks(7).all(|piece| piece.iter().enumerate().all(|(i, &v)| v == i as u32 + 1)));
+    }
+
+    #[test]
+    fn test_batch_scoring_matches_one_at_a_time() {
+        use crate::scoring::{generate_reference_image_internal, score_drawing_internal};
+        use crate::{Font, ScoringOptions};
+
+        let font = Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap();
+        let options = ScoringOptions::default();
+        let pngs: Vec<(Vec<u8>, char)> = ['a', 'T', 'e', 'a']
+            .into_iter()
+            .map(|c| (generate_reference_image_internal(c, &font, 300, &options).unwrap(), c))
+            .collect();
+        let mut drawings: Vec<(&[u8], char)> = pngs.iter().map(|(png, c)| (png.as_slice(), *c)).collect();
+        drawings.push((b"not a png", 'a'));
+
+        let results = crate::score_drawings(&drawings, &font, &options);
+        assert_eq!(results.len(), drawings.len());
+        for (result, &(png, c)) in results.iter().zip(&drawings[..4]) {
+            let expected = score_drawing_internal(png, c, &font, None, &options).unwrap();
+            assert_eq!(
+                serde_json::to_string(&result.as_ref().unwrap().result).unwrap(),
+                serde_json::to_string(&expected.result).unwrap(),
+            );
+        }
+        assert!(results[4].is_err());
+    }
+}
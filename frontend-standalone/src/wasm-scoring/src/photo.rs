@@ -0,0 +1,321 @@
+//! Camera photos of paper worksheets
+//!
+//! A photographed page is unevenly lit, its paper is gray rather than white
+//! and it is rarely held square to the camera. `clean_photo` turns it into
+//! what the rest of the pipeline expects from a canvas: black ink on white,
+//! level with the image edges.
+
+use image::{GrayImage, Luma};
+
+// Long side photos are shrunk to first; more detail than this only slows
+// the steps below
+const PHOTO_MAX_SIDE: u32 = 1024;
+// Tiles per long side when estimating the paper brightness
+const ILLUMINATION_TILES: u32 = 16;
+// Rank of the paper level within a tile, so glare and ink are both ignored
+const PAPER_PERCENTILE: f32 = 0.9;
+// Adaptive threshold window, as a fraction of the long side, and how much
+// darker than the local mean a pixel must be to count as ink
+const THRESHOLD_WINDOW: f32 = 1.0 / 24.0;
+const THRESHOLD_DARKER_BY: f32 = 0.15;
+// Skew angles searched, in degrees either way, and the step between them
+const MAX_SKEW_DEGREES: f32 = 10.0;
+const SKEW_STEP_DEGREES: f32 = 0.5;
+// How much sharper the best row profile must be than the unrotated one
+// before the photo is rotated
+const MIN_SKEW_GAIN: f32 = 1.1;
+
+/// Even out the lighting of a photographed page, threshold it into black
+/// ink on white and rotate it level
+pub(crate) fn clean_photo(gray: &GrayImage) -> GrayImage {
+    if gray.width() == 0 || gray.height() == 0 {
+        return gray.clone();
+    }
+    let (width, height) = gray.dimensions();
+    let scale = (PHOTO_MAX_SIDE as f32 / width.max(height) as f32).min(1.0);
+    let small = if scale < 1.0 {
+        let (w, h) = (((width as f32 * scale) as u32).max(1), ((height as f32 * scale) as u32).max(1));
+        image::imageops::thumbnail(gray, w, h)
+    } else {
+        gray.clone()
+    };
+
+    let even = normalize_illumination(&small);
+    let binary = adaptive_threshold(&even);
+    let angle = estimate_skew(&binary);
+    if angle == 0.0 { binary } else { rotate(&binary, -angle) }
+}
+
+/// Warp the quadrilateral with `corners` (image pixels, clockwise from the
+/// top left) onto an upright rectangle, undoing the perspective of a page
+/// photographed at an angle
+///
+/// The rectangle's sides are the mean lengths of the opposite edges, scaled
+/// down to at most `PHOTO_MAX_SIDE`. Area outside the photo is paper.
+pub(crate) fn warp_to_rectangle(gray: &GrayImage, corners: &[[f32; 2]; 4]) -> Result<GrayImage, String> {
+    let length = |a: [f32; 2], b: [f32; 2]| ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt();
+    let [top_left, top_right, bottom_right, bottom_left] = *corners;
+    let width = (length(top_left, top_right) + length(bottom_left, bottom_right)) / 2.0;
+    let height = (length(top_left, bottom_left) + length(top_right, bottom_right)) / 2.0;
+    if !(width >= 1.0 && height >= 1.0) {
+        return Err("Photo corners enclose no area".to_string());
+    }
+    let scale = (PHOTO_MAX_SIDE as f32 / width.max(height)).min(1.0);
+    let (out_width, out_height) = (((width * scale) as u32).max(1), ((height * scale) as u32).max(1));
+
+    let target = [[0.0, 0.0], [out_width as f32, 0.0], [out_width as f32, out_height as f32], [0.0, out_height as f32]];
+    let h = homography(&target, corners).ok_or("Photo corners don't form a quadrilateral")?;
+
+    let (width, height) = gray.dimensions();
+    let sample = |x: f32, y: f32| {
+        if x < 0.0 || y < 0.0 || x > (width - 1) as f32 || y > (height - 1) as f32 {
+            return 255.0;
+        }
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+        let pixel = |x, y| gray.get_pixel(x, y).0[0] as f32;
+        let top = pixel(x0, y0) * (1.0 - fx) + pixel(x1, y0) * fx;
+        let bottom = pixel(x0, y1) * (1.0 - fx) + pixel(x1, y1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    };
+
+    Ok(GrayImage::from_fn(out_width, out_height, |u, v| {
+        let (u, v) = (u as f32 + 0.5, v as f32 + 0.5);
+        let w = h[6] * u + h[7] * v + 1.0;
+        let x = (h[0] * u + h[1] * v + h[2]) / w;
+        let y = (h[3] * u + h[4] * v + h[5]) / w;
+        Luma([sample(x - 0.5, y - 0.5).round() as u8])
+    }))
+}
+
+/// The projective map taking each `from` point to the matching `to` point,
+/// as the first eight entries of its 3×3 matrix (the ninth is 1)
+fn homography(from: &[[f32; 2]; 4], to: &[[f32; 2]; 4]) -> Option<[f32; 8]> {
+    // Two equations per point pair, solved by Gaussian elimination
+    let mut rows = [[0.0f64; 9]; 8];
+    for (i, (&[u, v], &[x, y])) in from.iter().zip(to).enumerate() {
+        let (u, v, x, y) = (u as f64, v as f64, x as f64, y as f64);
+        rows[2 * i] = [u, v, 1.0, 0.0, 0.0, 0.0, -u * x, -v * x, x];
+        rows[2 * i + 1] = [0.0, 0.0, 0.0, u, v, 1.0, -u * y, -v * y, y];
+    }
+    for column in 0..8 {
+        let pivot = (column..8).max_by(|&a, &b| rows[a][column].abs().total_cmp(&rows[b][column].abs()))?;
+        if rows[pivot][column].abs() < 1e-9 {
+            return None;
+        }
+        rows.swap(column, pivot);
+        let pivot_row = rows[column];
+        for (r, row) in rows.iter_mut().enumerate() {
+            if r != column {
+                let factor = row[column] / pivot_row[column];
+                for (value, pivot_value) in row.iter_mut().zip(&pivot_row).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+    Some(std::array::from_fn(|i| (rows[i][8] / rows[i][i]) as f32))
+}
+
+/// Divide out the paper brightness, estimated per tile and interpolated
+/// between tile centers
+fn normalize_illumination(gray: &GrayImage) -> GrayImage {
+    let (width, height) = gray.dimensions();
+    let tile = (width.max(height) / ILLUMINATION_TILES).max(1);
+    let (columns, rows) = (width.div_ceil(tile), height.div_ceil(tile));
+
+    let mut paper = vec![0.0f32; (columns * rows) as usize];
+    let mut levels = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            levels.clear();
+            for y in row * tile..((row + 1) * tile).min(height) {
+                for x in column * tile..((column + 1) * tile).min(width) {
+                    levels.push(gray.get_pixel(x, y).0[0]);
+                }
+            }
+            levels.sort_unstable();
+            let rank = ((levels.len() - 1) as f32 * PAPER_PERCENTILE) as usize;
+            paper[(row * columns + column) as usize] = levels[rank].max(1) as f32;
+        }
+    }
+
+    let at = |column: f32, row: f32| {
+        let (c0, r0) = (column.floor().clamp(0.0, (columns - 1) as f32), row.floor().clamp(0.0, (rows - 1) as f32));
+        let (c1, r1) = ((c0 + 1.0).min((columns - 1) as f32), (r0 + 1.0).min((rows - 1) as f32));
+        let (fc, fr) = ((column - c0).clamp(0.0, 1.0), (row - r0).clamp(0.0, 1.0));
+        let value = |c: f32, r: f32| paper[(r as u32 * columns + c as u32) as usize];
+        let top = value(c0, r0) * (1.0 - fc) + value(c1, r0) * fc;
+        let bottom = value(c0, r1) * (1.0 - fc) + value(c1, r1) * fc;
+        top * (1.0 - fr) + bottom * fr
+    };
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let background = at((x as f32 + 0.5) / tile as f32 - 0.5, (y as f32 + 0.5) / tile as f32 - 0.5);
+        Luma([(gray.get_pixel(x, y).0[0] as f32 * 255.0 / background).min(255.0) as u8])
+    })
+}
+
+/// Ink (0) where a pixel is clearly darker than the mean of its window,
+/// paper (255) elsewhere (Bradley and Roth)
+fn adaptive_threshold(gray: &GrayImage) -> GrayImage {
+    let (width, height) = (gray.width() as usize, gray.height() as usize);
+    let radius = ((width.max(height) as f32 * THRESHOLD_WINDOW) as usize / 2).max(3);
+
+    // Summed-area table with a zero row and column in front
+    let mut sums = vec![0u64; (width + 1) * (height + 1)];
+    for y in 0..height {
+        let mut row = 0u64;
+        for x in 0..width {
+            row += gray.get_pixel(x as u32, y as u32).0[0] as u64;
+            sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row;
+        }
+    }
+
+    GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let (x0, y0) = (x.saturating_sub(radius), y.saturating_sub(radius));
+        let (x1, y1) = ((x + radius + 1).min(width), (y + radius + 1).min(height));
+        let sum = sums[y1 * (width + 1) + x1] + sums[y0 * (width + 1) + x0]
+            - sums[y0 * (width + 1) + x1] - sums[y1 * (width + 1) + x0];
+        let mean = sum as f32 / ((x1 - x0) * (y1 - y0)) as f32;
+        let ink = (gray.get_pixel(x as u32, y as u32).0[0] as f32) < mean * (1.0 - THRESHOLD_DARKER_BY);
+        Luma([if ink { 0 } else { 255 }])
+    })
+}
+
+/// Angle, in degrees clockwise, that the ink's rows are tilted by
+///
+/// Writing and ruled lines pack ink into rows, so the angle whose rotated
+/// row counts are most uneven is taken as the page's tilt. Zero unless it
+/// beats the unrotated profile clearly.
+fn estimate_skew(binary: &GrayImage) -> f32 {
+    let ink: Vec<(f32, f32)> = binary.enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel.0[0] == 0)
+        .map(|(x, y, _)| (x as f32, y as f32))
+        .collect();
+    if ink.is_empty() {
+        return 0.0;
+    }
+    let span = (binary.width() + binary.height()) as usize;
+
+    let sharpness = |degrees: f32| {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let mut rows = vec![0u32; 2 * span + 1];
+        for &(x, y) in &ink {
+            let row = (y * cos - x * sin).round() as isize + span as isize;
+            rows[row.clamp(0, 2 * span as isize) as usize] += 1;
+        }
+        rows.iter().map(|&count| (count as f32).powi(2)).sum::<f32>()
+    };
+
+    let level = sharpness(0.0);
+    let steps = (MAX_SKEW_DEGREES / SKEW_STEP_DEGREES) as i32;
+    let (best, best_sharpness) = (-steps..=steps)
+        .map(|step| step as f32 * SKEW_STEP_DEGREES)
+        .map(|degrees| (degrees, sharpness(degrees)))
+        .fold((0.0, level), |best, candidate| if candidate.1 > best.1 { candidate } else { best });
+    if best_sharpness > level * MIN_SKEW_GAIN { best } else { 0.0 }
+}
+
+/// Rotate about the center by `degrees` clockwise, filling with paper
+fn rotate(binary: &GrayImage, degrees: f32) -> GrayImage {
+    let (width, height) = binary.dimensions();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    GrayImage::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - cx, y as f32 + 0.5 - cy);
+        // Inverse mapping: where this output pixel comes from
+        let sx = dx * cos + dy * sin + cx;
+        let sy = -dx * sin + dy * cos + cy;
+        if sx >= 0.0 && sy >= 0.0 && (sx as u32) < width && (sy as u32) < height {
+            *binary.get_pixel(sx as u32, sy as u32)
+        } else {
+            Luma([255])
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A page lit from the left, with dark writing lines tilted by `degrees`
+    fn photo(degrees: f32) -> GrayImage {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        GrayImage::from_fn(400, 300, |x, y| {
+            let paper = 120.0 + 100.0 * x as f32 / 400.0;
+            let (dx, dy) = (x as f32 - 200.0, y as f32 - 150.0);
+            let row = dy * cos - dx * sin;
+            let on_line = (-100.0..100.0).contains(&row) && (row + 100.0).rem_euclid(50.0) < 4.0;
+            Luma([if on_line { (paper * 0.4) as u8 } else { paper as u8 }])
+        })
+    }
+
+    #[test]
+    fn test_uneven_lighting_becomes_white_paper() {
+        let cleaned = clean_photo(&photo(0.0));
+        let ink = cleaned.pixels().filter(|p| p.0[0] == 0).count();
+        // Four lines 4 pixels thick across the page, and nothing else
+        assert!((4 * 4 * 300..4 * 4 * 440).contains(&ink), "{}", ink);
+        assert_eq!(*cleaned.get_pixel(10, 10), Luma([255]));
+        assert_eq!(*cleaned.get_pixel(390, 290), Luma([255]));
+    }
+
+    #[test]
+    fn test_tilted_page_is_leveled() {
+        let binary = adaptive_threshold(&normalize_illumination(&photo(6.0)));
+        assert!((estimate_skew(&binary) - 6.0).abs() <= SKEW_STEP_DEGREES);
+        assert_eq!(estimate_skew(&adaptive_threshold(&normalize_illumination(&photo(0.0)))), 0.0);
+
+        // After leveling, each line's ink sits in a few rows
+        let level = clean_photo(&photo(6.0));
+        let rows_with_ink = (0..level.height())
+            .filter(|&y| (0..level.width()).filter(|&x| level.get_pixel(x, y).0[0] == 0).count() > 100)
+            .count();
+        assert!((12..=24).contains(&rows_with_ink), "{}", rows_with_ink);
+    }
+
+    #[test]
+    fn test_perspective_is_undone() {
+        // A 200x100 box with a dark 20-pixel square at its center, seen as
+        // a trapezoid narrowing towards the top
+        let corners = [[150.0, 50.0], [350.0, 50.0], [400.0, 250.0], [100.0, 250.0]];
+        let h = homography(&[[0.0, 0.0], [200.0, 0.0], [200.0, 100.0], [0.0, 100.0]], &corners).unwrap();
+        let project = |u: f32, v: f32| {
+            let w = h[6] * u + h[7] * v + 1.0;
+            ((h[0] * u + h[1] * v + h[2]) / w, (h[3] * u + h[4] * v + h[5]) / w)
+        };
+        assert!((project(200.0, 100.0).0 - 400.0).abs() < 1e-3 && (project(200.0, 100.0).1 - 250.0).abs() < 1e-3);
+
+        let photo = GrayImage::from_fn(500, 300, |x, y| {
+            let inside = (0..20).any(|i| (0..20).any(|j| {
+                let (px, py) = project(90.0 + i as f32, 40.0 + j as f32);
+                px as u32 == x && py as u32 == y
+            }));
+            Luma([if inside { 0 } else { 230 }])
+        });
+        let flat = warp_to_rectangle(&photo, &corners).unwrap();
+        let ink: Vec<(u32, u32)> = flat.enumerate_pixels().filter(|(_, _, p)| p.0[0] < 128).map(|(x, y, _)| (x, y)).collect();
+        let (min_x, max_x) = (ink.iter().map(|p| p.0).min().unwrap(), ink.iter().map(|p| p.0).max().unwrap());
+        let (min_y, max_y) = (ink.iter().map(|p| p.1).min().unwrap(), ink.iter().map(|p| p.1).max().unwrap());
+        // The square comes back at the same place in the box, whose sides
+        // average the trapezoid's
+        assert_eq!(flat.dimensions(), (250, 206));
+        let fraction = |value: u32, side: u32| value as f32 / side as f32;
+        let close = |value: f32, expected: f32| (value - expected).abs() < 0.02;
+        assert!(close(fraction(min_x, 250), 0.45) && close(fraction(max_x + 1, 250), 0.55), "{:?}", (min_x, max_x));
+        assert!(close(fraction(min_y, 206), 0.4) && close(fraction(max_y + 1, 206), 0.6), "{:?}", (min_y, max_y));
+
+        let collinear = [[0.0, 0.0], [10.0, 0.0], [20.0, 0.0], [30.0, 0.0]];
+        assert!(warp_to_rectangle(&photo, &collinear).is_err());
+    }
+
+    #[test]
+    fn test_large_photos_are_shrunk() {
+        let big = GrayImage::from_pixel(3000, 1500, Luma([200]));
+        assert_eq!(clean_photo(&big).dimensions(), (1024, 512));
+        assert_eq!(clean_photo(&GrayImage::new(0, 0)).dimensions(), (0, 0));
+    }
+}
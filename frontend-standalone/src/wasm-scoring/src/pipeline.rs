@@ -0,0 +1,184 @@
+//! The drawing side of scoring, prepared once for all metrics
+//!
+//! Coverage, accuracy, similarity, structure and the gates all look at the
+//! same thickness-normalized mask, centerline and distance field of the
+//! registered drawing. `PreparedDrawing` derives them once, mirroring
+//! `PreparedReference`, and `ScoringPipeline` hands both sides to each
+//! metric so none of them re-binarizes, re-skeletonizes or re-runs a
+//! distance transform. Its pixel buffers can be handed back as
+//! `ScoringBuffers` and reused for the next drawing.
+//!
+//! Pyramid scoring adds a coarse level: the same pair at half the frame
+//! size, for the metrics that only look at the letter's gross shape and
+//! for skeleton similarity, whose windows grow with the frame. The full
+//! level then never computes its stroke orientations.
+
+use crate::bit_image::BitImage;
+use crate::image_ops::{distance_transform_edt_into, fill_holes, orientation_field_into};
+use crate::reference::PreparedReference;
+use crate::scoring::{
+    MIN_SPUR_LENGTH, ORIENTATION_SEARCH_RADIUS, frame_area, frame_pixels, frame_scale, frame_side, halve_frame,
+    normalize_line_thickness, redraw_at_thickness_with
+};
+use crate::skeleton_graph::SkeletonGraph;
+use std::cell::{Cell, OnceCell};
+
+// Largest gap in the ink filled before thinning, in normalized pixels; gaps
+// left when colouring a letter in are a few pixels, letter counters hundreds
+const MAX_PINHOLE_AREA: usize = 12;
+
+/// Pixel buffers a `PreparedDrawing` fills, kept between scoring calls
+#[derive(Default)]
+pub(crate) struct ScoringBuffers {
+    pub(crate) processed: Vec<f32>,
+    norm_distance: Vec<f32>,
+    skeleton_distance: Vec<f32>,
+    orientation: Vec<f32>,
+}
+
+/// Drawing data in the normalized frame, shared by all metrics
+pub(crate) struct PreparedDrawing {
+    /// Registered intensities (0 = ink, 1 = paper)
+    pub(crate) processed: Vec<f32>,
+    /// Raw ink mask
+    pub(crate) binary: BitImage,
+    /// Sanded one-pixel centerline (pinholes filled) and its graph
+    pub(crate) centerline: BitImage,
+    pub(crate) graph: SkeletonGraph,
+    /// Centerline redrawn at the comparison thickness and the distance from
+    /// each pixel to it
+    pub(crate) norm: BitImage,
+    pub(crate) norm_distance: Vec<f32>,
+    /// Stroke orientations of `norm` where skeleton similarity reads them,
+    /// and the buffer they will be computed into
+    norm_orientation: OnceCell<Vec<f32>>,
+    orientation_buffer: Cell<Vec<f32>>,
+    skeleton_distance: Vec<f32>,
+}
+
+impl PreparedDrawing {
+    pub(crate) fn new(processed: &[f32]) -> Self {
+        Self::with_buffers(processed.to_vec(), ScoringBuffers::default())
+    }
+
+    /// Prepare `processed`, filling recycled buffers; `buffers.processed`
+    /// is ignored
+    pub(crate) fn with_buffers(processed: Vec<f32>, buffers: ScoringBuffers) -> Self {
+        let size = frame_side(&processed);
+        let ScoringBuffers { mut norm_distance, mut skeleton_distance, orientation, .. } = buffers;
+
+        let binary = BitImage::ink(&processed, size, size);
+        let centerline = normalize_line_thickness(&fill_holes(&binary, frame_area(MAX_PINHOLE_AREA, size)), 1, true);
+        let norm = redraw_at_thickness_with(&binary, &centerline, frame_pixels(5, size), &mut skeleton_distance);
+        distance_transform_edt_into(&norm, &mut norm_distance);
+        let graph = SkeletonGraph::from_skeleton(&centerline, MIN_SPUR_LENGTH * frame_scale(size));
+
+        Self {
+            processed,
+            binary,
+            centerline,
+            graph,
+            norm,
+            norm_distance,
+            norm_orientation: OnceCell::new(),
+            orientation_buffer: Cell::new(orientation),
+            skeleton_distance,
+        }
+    }
+
+    /// Stroke orientations of `norm`, computed on first use at the pixels
+    /// set in `at`
+    pub(crate) fn norm_orientation(&self, at: &BitImage) -> &[f32] {
+        self.norm_orientation.get_or_init(|| {
+            let mut orientation = self.orientation_buffer.take();
+            orientation_field_into(&self.norm, frame_pixels(3, self.norm.width()) as usize, Some(at), &mut orientation);
+            orientation
+        })
+    }
+
+    /// Give the pixel buffers back for the next drawing
+    pub(crate) fn into_buffers(self) -> ScoringBuffers {
+        let orientation = self.norm_orientation.into_inner().unwrap_or_else(|| self.orientation_buffer.into_inner());
+        ScoringBuffers {
+            processed: self.processed,
+            norm_distance: self.norm_distance,
+            skeleton_distance: self.skeleton_distance,
+            orientation,
+        }
+    }
+}
+
+/// A prepared drawing and the prepared reference it is scored against
+pub(crate) struct ScoringPipeline<'a> {
+    pub(crate) drawn: PreparedDrawing,
+    pub(crate) reference: &'a PreparedReference,
+}
+
+impl<'a> ScoringPipeline<'a> {
+    #[cfg(test)]
+    pub(crate) fn new(processed: &[f32], reference: &'a PreparedReference) -> Self {
+        Self { drawn: PreparedDrawing::new(processed), reference }
+    }
+
+    pub(crate) fn with_buffers(processed: Vec<f32>, reference: &'a PreparedReference, buffers: ScoringBuffers) -> Self {
+        Self { drawn: PreparedDrawing::with_buffers(processed, buffers), reference }
+    }
+
+    /// The same drawing and reference at half the frame size
+    pub(crate) fn coarse(&self) -> ScoringPipeline<'a> {
+        Self { drawn: PreparedDrawing::new(&halve_frame(&self.drawn.processed)), reference: self.reference.coarse() }
+    }
+
+    /// Stroke orientations of the drawing's `norm`, on its ink within the
+    /// directional chamfer's search window of the reference's; the chamfer
+    /// reads no others
+    pub(crate) fn drawn_orientation(&self) -> &[f32] {
+        let size = self.drawn.norm.width();
+        let reach = frame_pixels(ORIENTATION_SEARCH_RADIUS, size) as f32 * std::f32::consts::SQRT_2;
+        let distance = &self.reference.norm_distance;
+        let at = BitImage::from_fn(size, size, |x, y| self.drawn.norm.get(x, y) && distance[y * size + x] <= reach);
+        self.drawn.norm_orientation(&at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image_ops::distance_transform_edt;
+    use crate::scoring::TARGET_SIZE;
+    use crate::test_frames::{bar, blank};
+
+    #[test]
+    fn test_prepared_drawing_matches_direct_normalization() {
+        let size = TARGET_SIZE as usize;
+        let image = bar(40..52, 20..100);
+
+        let drawing = PreparedDrawing::new(&image);
+        let binary = BitImage::ink(&image, size, size);
+        assert_eq!(drawing.norm, normalize_line_thickness(&binary, 5, true));
+        assert_eq!(drawing.centerline, normalize_line_thickness(&binary, 1, true));
+
+        // A pinhole in the ink doesn't become a loop in the centerline
+        let mut speckled = image.clone();
+        speckled[60 * size + 46] = 1.0;
+        assert_eq!(PreparedDrawing::new(&speckled).centerline, drawing.centerline);
+        assert_eq!(drawing.norm_distance, distance_transform_edt(&drawing.norm));
+
+        let blank = PreparedDrawing::new(&blank());
+        assert!(blank.norm.is_empty() && blank.centerline.is_empty());
+    }
+
+    #[test]
+    fn test_recycled_buffers_give_the_same_drawing() {
+        let bar_at = |x0: usize| bar(x0..x0 + 10, 20..100);
+
+        let first = PreparedDrawing::new(&bar_at(30));
+        first.norm_orientation(&first.norm);
+        let buffers = first.into_buffers();
+        let recycled = PreparedDrawing::with_buffers(bar_at(70), buffers);
+        let fresh = PreparedDrawing::new(&bar_at(70));
+        assert_eq!(recycled.norm, fresh.norm);
+        assert_eq!(recycled.norm_distance, fresh.norm_distance);
+        assert_eq!(recycled.norm_orientation(&fresh.norm), fresh.norm_orientation(&fresh.norm));
+    }
+}
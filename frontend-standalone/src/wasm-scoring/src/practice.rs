@@ -0,0 +1,179 @@
+//! Practice variation mode
+//!
+//! Varies the reference a learner traces and is scored against, so they
+//! learn the letterform rather than a fixed overlay position. All choices
+//! are derived from a seed, so the reference shown before drawing and the
+//! one scored afterwards match when the same seed is used.
+//!
+//! The seed drives a `SplitMix64` per character. `ReferenceVariation::choose`
+//! takes any other `RandomSource`, e.g. one replaying recorded choices, and
+//! `ScoringEngine::set_random_source` scores with one.
+
+use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+
+/// A stream of random numbers for practice variation
+pub trait RandomSource {
+    fn next_u64(&mut self) -> u64;
+}
+
+/// Options for practice variation mode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PracticeOptions {
+    /// Seed for all random choices, typically fixed for a session
+    pub seed: u64,
+    /// Maximum relative change in glyph size (0.1 = up to ±10%)
+    pub size_jitter: f32,
+    /// Maximum glyph offset as a fraction of the image size
+    pub offset_jitter: f32,
+    /// Acceptable alternative glyphs per character (e.g. 'a' -> ['ɑ']),
+    /// picked from alongside the character itself
+    pub variants: HashMap<char, Vec<char>>,
+}
+
+impl Default for PracticeOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            size_jitter: 0.1,
+            offset_jitter: 0.05,
+            variants: HashMap::new(),
+        }
+    }
+}
+
+impl PracticeOptions {
+    /// The seeded generator for a character
+    ///
+    /// The seed is mixed with the character so each letter in a session
+    /// gets its own variation.
+    pub fn random_source(&self, character: char) -> SplitMix64 {
+        SplitMix64::new(self.seed ^ (character as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+}
+
+/// The concrete reference chosen for one character
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReferenceVariation {
+    /// Glyph rendered as the reference
+    pub glyph: char,
+    /// Multiplier on the default glyph size
+    pub scale: f32,
+    /// Glyph offset as a fraction of the image size
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+impl ReferenceVariation {
+    /// The unvaried reference for a character
+    pub fn identity(character: char) -> Self {
+        Self { glyph: character, scale: 1.0, offset_x: 0.0, offset_y: 0.0 }
+    }
+
+    /// Pick a variant glyph and size/position jitter for a character,
+    /// drawing from `rng` (usually `practice.random_source(character)`)
+    pub fn choose(character: char, practice: &PracticeOptions, rng: &mut dyn RandomSource) -> Self {
+        let glyph = match practice.variants.get(&character) {
+            Some(alternatives) if !alternatives.is_empty() => {
+                let index = (rng.next_u64() % (alternatives.len() as u64 + 1)) as usize;
+                if index == 0 { character } else { alternatives[index - 1] }
+            }
+            _ => character,
+        };
+
+        let size_jitter = practice.size_jitter.clamp(0.0, 0.5);
+        let offset_jitter = practice.offset_jitter.clamp(0.0, 0.25);
+
+        Self {
+            glyph,
+            scale: 1.0 + next_signed(rng) * size_jitter,
+            offset_x: next_signed(rng) * offset_jitter,
+            offset_y: next_signed(rng) * offset_jitter,
+        }
+    }
+}
+
+/// Uniform value in [-1, 1)
+fn next_signed(rng: &mut dyn RandomSource) -> f32 {
+    let unit = (rng.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+    unit * 2.0 - 1.0
+}
+
+/// Small deterministic generator; quality is ample for picking variations
+pub struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+}
+
+impl RandomSource for SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choose(character: char, practice: &PracticeOptions) -> ReferenceVariation {
+        ReferenceVariation::choose(character, practice, &mut practice.random_source(character))
+    }
+
+    #[test]
+    fn test_choose_is_deterministic_and_bounded() {
+        let practice = PracticeOptions { seed: 42, ..Default::default() };
+
+        let a = choose('A', &practice);
+        assert_eq!(a, choose('A', &practice));
+        assert_eq!(a.glyph, 'A');
+        assert!((a.scale - 1.0).abs() <= 0.1);
+        assert!(a.offset_x.abs() <= 0.05 && a.offset_y.abs() <= 0.05);
+
+        let other_seed = PracticeOptions { seed: 43, ..Default::default() };
+        assert_ne!(a, choose('A', &other_seed));
+    }
+
+    #[test]
+    fn test_choose_picks_registered_variants() {
+        let mut practice = PracticeOptions::default();
+        practice.variants.insert('a', vec!['ɑ']);
+
+        let glyphs: Vec<char> = (0..32)
+            .map(|seed| choose('a', &PracticeOptions { seed, ..practice.clone() }).glyph)
+            .collect();
+        assert!(glyphs.contains(&'a'));
+        assert!(glyphs.contains(&'ɑ'));
+    }
+
+    #[test]
+    fn test_injected_random_source() {
+        // Replays recorded draws: the variant, then the top of the size and
+        // offset ranges, whatever the seed
+        struct Replay(std::vec::IntoIter<u64>);
+        impl RandomSource for Replay {
+            fn next_u64(&mut self) -> u64 {
+                self.0.next().unwrap_or(0)
+            }
+        }
+
+        let mut practice = PracticeOptions::default();
+        practice.variants.insert('a', vec!['ɑ']);
+        let chosen = [1, 2].map(|seed| {
+            let mut replay = Replay(vec![1, u64::MAX, u64::MAX, u64::MAX].into_iter());
+            ReferenceVariation::choose('a', &PracticeOptions { seed, ..practice.clone() }, &mut replay)
+        });
+        assert_eq!(chosen[0], chosen[1]);
+        assert_eq!(chosen[0].glyph, 'ɑ');
+        assert!((chosen[0].scale - 1.1).abs() < 1e-3 && (chosen[0].offset_x - 0.05).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,120 @@
+//! Python bindings
+//!
+//! A `learning_letters_scoring` module for batch analysis of collected
+//! handwriting with the production scoring. Options and strokes are the
+//! same dicts and lists the JavaScript API takes as objects, and results
+//! come back as plain dicts. Scoring releases the GIL, so a thread pool
+//! scores several drawings at once.
+
+use crate::{ScoringError, ScoringOptions, Stroke};
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+create_exception!(learning_letters_scoring, GlyphMissingError, PyValueError, "The font has no glyph for the character");
+
+fn py_error(error: ScoringError) -> PyErr {
+    match error {
+        ScoringError::GlyphMissing(_) => GlyphMissingError::new_err(error.to_string()),
+        ScoringError::Cancelled => PyValueError::new_err(error.to_string()),
+        ScoringError::Invalid(message) => PyValueError::new_err(message),
+    }
+}
+
+/// A parsed font, kept between calls
+#[pyclass(name = "Font", frozen)]
+struct PyFont(crate::Font);
+
+#[pymethods]
+impl PyFont {
+    /// Parse TTF, OTF, WOFF or WOFF2 font bytes
+    #[new]
+    fn new(data: &[u8]) -> PyResult<Self> {
+        crate::Font::from_bytes(data).map(PyFont).map_err(py_error)
+    }
+
+    /// Whether the font or one of its fallbacks can render the character
+    fn has_glyph(&self, character: char) -> bool {
+        self.0.has_glyph(character)
+    }
+
+    /// This font falling back on `fonts`, in order, for characters it lacks
+    fn with_fallbacks(&self, fonts: Vec<PyRef<'_, PyFont>>) -> PyFont {
+        PyFont(self.0.clone().with_fallbacks(fonts.iter().map(|font| font.0.clone())))
+    }
+}
+
+/// Score a drawing against a character, returning the result as a dict
+///
+/// `strokes` is a list of stroke dicts in the image's pixel coordinates and
+/// `options` a dict of scoring options, both optional.
+#[pyfunction]
+#[pyo3(signature = (image, character, font, strokes=None, options=None))]
+fn score_drawing(
+    py: Python<'_>,
+    image: &[u8],
+    character: char,
+    font: &PyFont,
+    strokes: Option<&Bound<'_, PyAny>>,
+    options: Option<&Bound<'_, PyAny>>,
+) -> PyResult<PyObject> {
+    let strokes: Option<Vec<Stroke>> = match strokes {
+        Some(strokes) => Some(
+            serde_json::from_str(&to_json(py, strokes)?)
+                .map_err(|e| PyValueError::new_err(format!("Invalid stroke data: {}", e)))?,
+        ),
+        None => None,
+    };
+    let options = parse_options(py, options)?;
+
+    let scored = py
+        .allow_threads(|| crate::score_drawing(image, character, &font.0, strokes.as_deref(), &options))
+        .map_err(py_error)?;
+    let json = serde_json::to_string(&scored.result).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    from_json(py, &json)
+}
+
+/// Render the reference image for a character as PNG bytes
+#[pyfunction]
+#[pyo3(signature = (character, font, size, options=None))]
+fn generate_reference_image<'py>(
+    py: Python<'py>,
+    character: char,
+    font: &PyFont,
+    size: u32,
+    options: Option<&Bound<'py, PyAny>>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let options = parse_options(py, options)?;
+    let png = py
+        .allow_threads(|| crate::generate_reference_image(character, &font.0, size, &options))
+        .map_err(py_error)?;
+    Ok(PyBytes::new(py, &png))
+}
+
+/// Options from a dict, merged over the defaults (or the `age_band` bundle)
+fn parse_options(py: Python<'_>, options: Option<&Bound<'_, PyAny>>) -> PyResult<ScoringOptions> {
+    let Some(options) = options else {
+        return Ok(ScoringOptions::default());
+    };
+    let value = serde_json::from_str(&to_json(py, options)?)
+        .map_err(|e| PyValueError::new_err(format!("Invalid scoring options: {}", e)))?;
+    ScoringOptions::from_json(value).map_err(PyValueError::new_err)
+}
+
+fn to_json(py: Python<'_>, value: &Bound<'_, PyAny>) -> PyResult<String> {
+    py.import("json")?.call_method1("dumps", (value,))?.extract()
+}
+
+fn from_json(py: Python<'_>, json: &str) -> PyResult<PyObject> {
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+#[pymodule]
+fn learning_letters_scoring(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyFont>()?;
+    m.add_function(wrap_pyfunction!(score_drawing, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_reference_image, m)?)?;
+    m.add("GlyphMissingError", m.py().get_type::<GlyphMissingError>())?;
+    Ok(())
+}
@@ -0,0 +1,301 @@
+//! Prepared references and the reference cache
+//!
+//! Everything derived from the reference glyph alone (the rendered image,
+//! its normalized mask, skeleton and distance fields) is the same for every
+//! attempt at a letter. It is prepared once and kept in a small per-thread
+//! LRU cache keyed by glyph, variation, font, axes, style, sizing, frame,
+//! thinning algorithm and size, so repeated scoring of the same letter skips
+//! rendering and the reference transforms.
+
+use crate::bit_image::BitImage;
+use crate::error::ScoringError;
+use crate::font::Font;
+use crate::image_ops::{ThinningAlgorithm, distance_transform_edt, orientation_field, thin_fully};
+use crate::logging::log;
+use crate::options::ScoringOptions;
+use crate::practice::ReferenceVariation;
+#[cfg(feature = "coaching")]
+use crate::scoring::NormalizeTransform;
+use crate::scoring::{
+    MIN_SPUR_LENGTH, apply_normalize_transform, encode_grayscale_to_png, frame_pixels, frame_scale,
+    frame_side, checked_frame_size, halve_frame, THRESHOLD, find_normalize_transform, normalize_line_thickness, render_reference
+};
+use crate::skeleton_graph::SkeletonGraph;
+use image::GrayImage;
+use std::cell::RefCell;
+use std::sync::{Arc, OnceLock};
+
+// References kept per thread; a lesson cycles through a handful of letters,
+// and each entry holds roughly 300 KB
+const REFERENCE_CACHE_CAPACITY: usize = 8;
+
+/// Reference data in the normalized frame, shared by all metrics
+pub(crate) struct PreparedReference {
+    /// Side of the normalized frame
+    pub(crate) size: usize,
+    /// Normalized intensities (0 = ink, 1 = paper)
+    pub(crate) processed: Vec<f32>,
+    /// Ink mask and the distance from each pixel to the nearest ink
+    pub(crate) binary: BitImage,
+    pub(crate) binary_distance: Vec<f32>,
+    /// Ink redrawn at the comparison thickness, its distance field and
+    /// stroke orientations
+    pub(crate) norm: BitImage,
+    pub(crate) norm_distance: Vec<f32>,
+    pub(crate) norm_orientation: Vec<f32>,
+    /// One-pixel skeleton and its graph
+    pub(crate) skeleton: BitImage,
+    pub(crate) graph: SkeletonGraph,
+    /// How the skeleton was thinned; drawings are thinned the same way
+    pub(crate) algorithm: ThinningAlgorithm,
+    /// The same at half the frame size, prepared when pyramid scoring first
+    /// asks for it
+    coarse: OnceLock<Box<PreparedReference>>,
+}
+
+impl PreparedReference {
+    #[cfg(test)]
+    pub(crate) fn new(processed: &[f32]) -> Self {
+        Self::thinned_with(processed, ThinningAlgorithm::default())
+    }
+
+    pub(crate) fn thinned_with(processed: &[f32], algorithm: ThinningAlgorithm) -> Self {
+        let size = frame_side(processed);
+
+        // Cached for calls with other limits, so never cut short
+        let binary = BitImage::ink(processed, size, size);
+        let (norm, skeleton) = thin_fully(algorithm, || {
+            (normalize_line_thickness(&binary, frame_pixels(5, size), false), normalize_line_thickness(&binary, 1, false))
+        });
+        let norm_orientation = orientation_field(&norm, frame_pixels(3, size) as usize);
+        Self::from_masks(processed, norm, skeleton, norm_orientation, algorithm)
+    }
+
+    /// Prepare with the thickness-normalized mask, skeleton and stroke
+    /// orientations already known, thinned with `algorithm`; the rest is
+    /// cheap to derive
+    pub(crate) fn from_masks(
+        processed: &[f32],
+        norm: BitImage,
+        skeleton: BitImage,
+        norm_orientation: Vec<f32>,
+        algorithm: ThinningAlgorithm,
+    ) -> Self {
+        let size = frame_side(processed);
+
+        let binary = BitImage::ink(processed, size, size);
+        let binary_distance = distance_transform_edt(&binary);
+        let norm_distance = distance_transform_edt(&norm);
+        let graph = SkeletonGraph::from_skeleton(&skeleton, MIN_SPUR_LENGTH * frame_scale(size));
+
+        Self {
+            size,
+            processed: processed.to_vec(),
+            binary,
+            binary_distance,
+            norm,
+            norm_distance,
+            norm_orientation,
+            skeleton,
+            graph,
+            algorithm,
+            coarse: OnceLock::new(),
+        }
+    }
+
+    /// The reference at half the frame size, for pyramid scoring
+    pub(crate) fn coarse(&self) -> &PreparedReference {
+        self.coarse.get_or_init(|| Box::new(PreparedReference::thinned_with(&halve_frame(&self.processed), self.algorithm)))
+    }
+}
+
+/// A rendered reference glyph with everything scoring derives from it
+pub(crate) struct Reference {
+    /// Glyph as rendered, before normalization
+    pub(crate) image: GrayImage,
+    /// PNG bytes of `image`, returned with scoring results
+    pub(crate) png: Vec<u8>,
+    /// Mapping of `image` into the normalized frame (`None` for a blank glyph)
+    #[cfg(feature = "coaching")]
+    pub(crate) transform: Option<NormalizeTransform>,
+    /// Margin around the letter in the normalized frame; drawings scored
+    /// against this reference are normalized with the same margin
+    pub(crate) padding: f32,
+    pub(crate) prepared: PreparedReference,
+}
+
+impl Reference {
+    fn render(
+        variation: &ReferenceVariation,
+        font: &Font,
+        options: &ScoringOptions,
+        size: u32,
+    ) -> Result<Self, ScoringError> {
+        let frame = checked_frame_size(options.frame_size)?;
+        let image = render_reference(variation, font, options, size)?;
+        let png = encode_grayscale_to_png(&image)?;
+        Ok(Self::from_image(image, png, options.normalize_padding, frame, options.thinning, None))
+    }
+
+    /// Prepare a rendered reference, given its PNG encoding, the margin,
+    /// frame size and thinning algorithm to normalize with and, when they
+    /// were stored (as in a reference pack), its normalized mask, skeleton
+    /// and orientations
+    pub(crate) fn from_image(
+        image: GrayImage,
+        png: Vec<u8>,
+        padding: f32,
+        frame: u32,
+        algorithm: ThinningAlgorithm,
+        masks: Option<(BitImage, BitImage, Vec<f32>)>,
+    ) -> Self {
+        let transform = find_normalize_transform(&image, padding, THRESHOLD, frame);
+        let processed = match &transform {
+            Some(transform) => apply_normalize_transform(&image, transform, 1.0, 1.0, 0.5),
+            None => vec![1.0; (frame * frame) as usize],
+        };
+        let prepared = match masks {
+            Some((norm, skeleton, orientation)) => PreparedReference::from_masks(&processed, norm, skeleton, orientation, algorithm),
+            None => PreparedReference::thinned_with(&processed, algorithm),
+        };
+
+        Self {
+            image,
+            png,
+            #[cfg(feature = "coaching")]
+            transform,
+            padding,
+            prepared,
+        }
+    }
+}
+
+/// Everything the rendered reference depends on; floats compare by bits
+#[derive(PartialEq)]
+struct ReferenceKey {
+    glyph: char,
+    variation: [u32; 3],
+    font: u64,
+    axes: Vec<(String, u32)>,
+    style: Option<String>,
+    /// Font size, stroke width, normalization padding, supersampling and
+    /// frame size
+    shape: [u32; 5],
+    thinning: ThinningAlgorithm,
+    size: u32,
+}
+
+impl ReferenceKey {
+    fn new(variation: &ReferenceVariation, font: &Font, options: &ScoringOptions, size: u32) -> Self {
+        let mut axes: Vec<(String, u32)> = options.font_axes.iter().map(|(tag, value)| (tag.clone(), value.to_bits())).collect();
+        axes.sort();
+        Self {
+            glyph: variation.glyph,
+            variation: [variation.scale.to_bits(), variation.offset_x.to_bits(), variation.offset_y.to_bits()],
+            font: font.for_glyph(variation.glyph).fingerprint(),
+            axes,
+            style: options.style.clone(),
+            shape: [
+                options.reference_font_size.to_bits(),
+                options.reference_stroke_width.map_or(u32::MAX, f32::to_bits),
+                options.normalize_padding.to_bits(),
+                options.reference_supersampling,
+                options.frame_size,
+            ],
+            thinning: options.thinning,
+            size,
+        }
+    }
+}
+
+thread_local! {
+    // Least recently used first
+    static REFERENCE_CACHE: RefCell<Vec<(ReferenceKey, Arc<Reference>)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The prepared reference for a glyph, from the cache when possible
+pub(crate) fn cached_reference(
+    variation: &ReferenceVariation,
+    font: &Font,
+    options: &ScoringOptions,
+    size: u32,
+) -> Result<Arc<Reference>, ScoringError> {
+    let key = ReferenceKey::new(variation, font, options, size);
+    let hit = REFERENCE_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        let index = cache.iter().position(|(cached, _)| *cached == key)?;
+        let entry = cache.remove(index);
+        let reference = entry.1.clone();
+        cache.push(entry);
+        Some(reference)
+    });
+    if let Some(reference) = hit {
+        log!(Debug, "reference cache hit for '{}'", variation.glyph);
+        return Ok(reference);
+    }
+
+    log!(Debug, "reference cache miss for '{}', rendering at {} pixels", variation.glyph, size);
+    let reference = Arc::new(Reference::render(variation, font, options, size)?);
+    REFERENCE_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= REFERENCE_CACHE_CAPACITY {
+            cache.remove(0);
+        }
+        cache.push((key, reference.clone()));
+    });
+    Ok(reference)
+}
+
+/// Drop all cached references on this thread
+pub fn clear_reference_cache() {
+    REFERENCE_CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+#[cfg(test)]
+fn cached_count() -> usize {
+    REFERENCE_CACHE.with(|cache| cache.borrow().len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::REFERENCE_SIZE;
+
+    #[test]
+    fn test_cached_reference_reuse_and_eviction() {
+        let font = Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap();
+        let options = ScoringOptions::default();
+        clear_reference_cache();
+
+        let a = cached_reference(&ReferenceVariation::identity('a'), &font, &options, REFERENCE_SIZE).unwrap();
+        let again = cached_reference(&ReferenceVariation::identity('a'), &font, &options, REFERENCE_SIZE).unwrap();
+        assert!(Arc::ptr_eq(&a, &again));
+
+        // Any input that changes the rendering is a different entry
+        let smaller = cached_reference(&ReferenceVariation::identity('a'), &font, &options, 100).unwrap();
+        assert!(!Arc::ptr_eq(&a, &smaller));
+        let scaled = ReferenceVariation { scale: 0.9, ..ReferenceVariation::identity('a') };
+        assert!(!Arc::ptr_eq(&a, &cached_reference(&scaled, &font, &options, REFERENCE_SIZE).unwrap()));
+        assert_eq!(cached_count(), 3);
+        #[cfg(feature = "shapes")]
+        {
+            let styled = ScoringOptions { style: Some("continental".to_string()), ..Default::default() };
+            let continental = cached_reference(&ReferenceVariation::identity('a'), &font, &styled, REFERENCE_SIZE).unwrap();
+            assert_ne!(a.image, continental.image);
+        }
+
+        // A cached reference matches a fresh one
+        assert_eq!(a.image, render_reference(&ReferenceVariation::identity('a'), &font, &options, REFERENCE_SIZE).unwrap());
+
+        // Least recently used entries go first; 'a' was just used
+        for c in "bcdefghijk".chars() {
+            cached_reference(&ReferenceVariation::identity(c), &font, &options, REFERENCE_SIZE).unwrap();
+            cached_reference(&ReferenceVariation::identity('a'), &font, &options, REFERENCE_SIZE).unwrap();
+        }
+        assert_eq!(cached_count(), REFERENCE_CACHE_CAPACITY);
+        assert!(Arc::ptr_eq(&a, &cached_reference(&ReferenceVariation::identity('a'), &font, &options, REFERENCE_SIZE).unwrap()));
+
+        clear_reference_cache();
+        assert_eq!(cached_count(), 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! Scaffold escalation policy
+//!
+//! Decides how much help to show on the next attempt at a letter. Each run
+//! of consecutive low scores moves the learner one level further up the
+//! ladder (animation, then arrows, then tracing, then a smaller canvas); a
+//! good score drops all scaffolding again. The app passes the scores of the
+//! previous attempts at the letter and acts on the returned level, so every
+//! frontend escalates the same way.
+
+use serde::{Serialize, Deserialize};
+
+/// Help shown on the next attempt, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaffoldLevel {
+    /// Draw unaided
+    None,
+    /// Play the stroke animation before drawing
+    ShowAnimation,
+    /// Show stroke-order arrows on the guide
+    ShowArrows,
+    /// Switch to tracing over the letter
+    Tracing,
+    /// Trace on a smaller canvas, which is easier to control
+    ShrinkCanvas,
+}
+
+impl ScaffoldLevel {
+    pub const ALL: [ScaffoldLevel; 5] = [
+        ScaffoldLevel::None,
+        ScaffoldLevel::ShowAnimation,
+        ScaffoldLevel::ShowArrows,
+        ScaffoldLevel::Tracing,
+        ScaffoldLevel::ShrinkCanvas,
+    ];
+
+    /// Name used in JSON and by the wasm getters
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ScaffoldLevel::None => "none",
+            ScaffoldLevel::ShowAnimation => "show_animation",
+            ScaffoldLevel::ShowArrows => "show_arrows",
+            ScaffoldLevel::Tracing => "tracing",
+            ScaffoldLevel::ShrinkCanvas => "shrink_canvas",
+        }
+    }
+}
+
+/// Options for scaffold escalation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScaffoldOptions {
+    /// Scores of the previous attempts at this letter, oldest first
+    pub recent_scores: Vec<u8>,
+    /// Scores below this are low; when unset, anything under the
+    /// three-star threshold is
+    pub low_score: Option<u8>,
+    /// Consecutive low scores needed to go up one level
+    pub attempts_per_level: u32,
+}
+
+impl Default for ScaffoldOptions {
+    fn default() -> Self {
+        Self {
+            recent_scores: Vec::new(),
+            low_score: None,
+            attempts_per_level: 2,
+        }
+    }
+}
+
+impl ScaffoldOptions {
+    /// Scaffold level for the next attempt after scoring `score`, along
+    /// with the number of consecutive low scores ending with it
+    pub fn decide(&self, score: u8, default_low_score: u8) -> (ScaffoldLevel, u32) {
+        let low_score = self.low_score.unwrap_or(default_low_score);
+        let streak = self.recent_scores.iter()
+            .chain(std::iter::once(&score))
+            .rev()
+            .take_while(|&&s| s < low_score)
+            .count() as u32;
+
+        let step = (streak / self.attempts_per_level.max(1)) as usize;
+        (ScaffoldLevel::ALL[step.min(ScaffoldLevel::ALL.len() - 1)], streak)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaffold_escalates_with_low_streak() {
+        let options = |recent: &[u8]| ScaffoldOptions { recent_scores: recent.to_vec(), ..Default::default() };
+
+        assert_eq!(options(&[]).decide(30, 50), (ScaffoldLevel::None, 1));
+        assert_eq!(options(&[40]).decide(30, 50), (ScaffoldLevel::ShowAnimation, 2));
+        assert_eq!(options(&[20, 40, 45]).decide(30, 50), (ScaffoldLevel::ShowArrows, 4));
+        assert_eq!(options(&[10; 6]).decide(30, 50), (ScaffoldLevel::Tracing, 7));
+        assert_eq!(options(&[10; 20]).decide(30, 50), (ScaffoldLevel::ShrinkCanvas, 21));
+
+        // A good score resets the streak, before or at the current attempt
+        assert_eq!(options(&[10, 10, 80, 40]).decide(30, 50), (ScaffoldLevel::ShowAnimation, 2));
+        assert_eq!(options(&[10; 6]).decide(70, 50), (ScaffoldLevel::None, 0));
+    }
+
+    #[test]
+    fn test_scaffold_thresholds() {
+        let options = ScaffoldOptions { recent_scores: vec![55], low_score: Some(60), attempts_per_level: 1 };
+        assert_eq!(options.decide(58, 50), (ScaffoldLevel::ShowArrows, 2));
+        assert!(options.decide(58, 50).0 > ScaffoldLevel::ShowAnimation);
+    }
+}
@@ -2,120 +2,1070 @@
 //!
 //! Implements the scoring algorithm that compares user drawings against reference images.
 
+use crate::bit_image::BitImage;
 use crate::image_ops::{
-    distance_transform_edt, binary_dilation, skeletonize, bridge_gaps, prune_branches
+    distance_transform_edt, distance_transform_edt_into, skeletonize, bridge_gaps, prune_branches, image_moments,
+    hu_moments, connected_components, Component, difference_hash, enclosed_regions, otsu_threshold,
+    remove_specks, ThinningScope, cancelled, check_cancelled
 };
-use crate::WasmScoringResult;
-use crate::ScoringResult;
-use image::{DynamicImage, GrayImage, ImageBuffer, Luma, ImageEncoder};
+use crate::features::{CriticalFeature, check_critical_feature};
+use crate::photo::{clean_photo, warp_to_rectangle};
+use crate::pipeline::{PreparedDrawing, ScoringBuffers, ScoringPipeline};
+use crate::options::{GuideLine, InputLimits, ScoringOptions, ScoringMode, SimilarityMethod, StarThresholds, ThresholdMethod, InkColor};
+use crate::practice::ReferenceVariation;
+use crate::reference::{PreparedReference, Reference, cached_reference};
+use crate::shape_context::shape_context_similarity;
+use crate::skeleton_graph::graph_similarity;
+use crate::strokes::{
+    Stroke, StrokePoint, stroke_order_score, stroke_directions, count_strokes, has_pressure,
+    rasterize_strokes, pressure_consistency, drawing_duration, ink_strokes, smooth_strokes
+};
+use crate::styles::{render_style_glyph, styled_template};
+use crate::logging::log;
+use crate::timing::{self, Recording, Stage};
+use crate::font::Font;
+use crate::{DrawingGraph, DrawingIssue, DrawingQuality, GraphEdge, GraphNode, ScoreCap, ScoreContribution, ScoreExplanation, ScoredDrawing, ScoringResult};
+use crate::error::ScoringError;
+use image::{DynamicImage, GrayImage, ImageBuffer, ImageEncoder, ImageFormat, ImageReader, Luma};
 use image::codecs::png::PngEncoder;
-use rusttype::{Font, Scale, point};
+use ab_glyph::{Font as _, FontRef, Glyph, PxScale, point};
+#[cfg(feature = "guides")]
+use ab_glyph::{OutlineCurve, ScaleFont as _};
+use std::collections::HashMap;
 use std::io::Cursor;
-
-const TARGET_SIZE: u32 = 128;
-const THRESHOLD: u8 = 200;
+use serde::{Serialize, Deserialize};
+
+// Default side of the normalized frame drawings are compared in; lengths
+// in normalized pixels here and elsewhere are tuned for it and scaled with
+// the frame, and the smallest and largest allowed
+pub(crate) const TARGET_SIZE: u32 = 128;
+const MIN_FRAME_SIZE: u32 = 64;
+const MAX_FRAME_SIZE: u32 = 512;
+// Size the reference glyph is rendered at before normalization
+pub(crate) const REFERENCE_SIZE: u32 = 200;
+// Default font size of the reference glyph, as a fraction of the image size
+pub(crate) const REFERENCE_FONT_SIZE: f32 = 0.75;
+// Default margin around the letter in the normalized frame, as a fraction
+// of the frame, and the most allowed
+pub(crate) const NORMALIZE_PADDING: f32 = 0.1;
+const MAX_NORMALIZE_PADDING: f32 = 0.4;
+// Default and largest factor the reference is rendered larger by before
+// being averaged down; pen-stamped letterforms have hard edges that leave
+// ragged skeletons at small sizes otherwise
+pub(crate) const REFERENCE_SUPERSAMPLING: u32 = 2;
+const MAX_REFERENCE_SUPERSAMPLING: u32 = 4;
+// Largest edge smoothing radius in image pixels; more erases all but the
+// boldest ink and costs time with the radius
+const MAX_EDGE_SMOOTHING: u32 = 8;
+// Line segments per outline curve when tracing a glyph for guides
+#[cfg(feature = "guides")]
+const OUTLINE_CURVE_SEGMENTS: usize = 8;
+pub(crate) const THRESHOLD: u8 = 200;
+// Paper behind transparent pixels unless the options say otherwise
+pub(crate) const WHITE: [u8; 3] = [255, 255, 255];
+
+// Auto-contrast: ink must sit at least this many gray levels below the
+// background to be stretched, so blank-canvas noise is left alone
+const AUTO_CONTRAST_MIN_SEPARATION: u8 = 24;
+// Gray levels between the mean ink and mean paper for Otsu's threshold to be
+// trusted; closer classes are paper texture or compression noise
+const OTSU_MIN_SEPARATION: f32 = 48.0;
+
+// Registration limits: rotation is only corrected for elongated shapes
+// and never by more than a small angle, since letters are not rotation invariant
+const MAX_REGISTRATION_ROTATION: f32 = 15.0 * std::f32::consts::PI / 180.0;
+const MIN_REGISTRATION_ECCENTRICITY: f32 = 0.6;
+const MIN_REGISTRATION_SCALE: f32 = 0.6;
+const MAX_REGISTRATION_SCALE: f32 = 1.6;
+
+// Directional chamfer: matches are searched within this window, and a
+// perpendicular match costs this many extra pixels of distance
+pub(crate) const ORIENTATION_SEARCH_RADIUS: u32 = 6;
+const ORIENTATION_PENALTY: f32 = 6.0;
+
+// SSIM window radius and stabilizing constants for intensities in [0, 1]
+const SSIM_RADIUS: u32 = 3;
+const SSIM_C1: f32 = 0.01 * 0.01;
+const SSIM_C2: f32 = 0.03 * 0.03;
+
+// Highest score (top of the one-star band) when the minimum-ink gate fails,
+// or the drawing fills the canvas or is a scribble
+const MAX_GATED_SCORE: u8 = 29;
+
+// Canvas-fill detection: far more ink than the reference, little of it on the letter
+const CANVAS_FILL_INK_RATIO: f32 = 3.0;
+const CANVAS_FILL_MAX_PRECISION: f32 = 0.4;
+const CANVAS_FILL_MIN_COVERAGE: f32 = 0.7;
+
+// Scribble detection: a filled blob has a mean stroke width (ink area over
+// skeleton length) far above any pen stroke, and much of it survives erosion
+const SCRIBBLE_MIN_MEAN_WIDTH: f32 = 24.0;
+const SCRIBBLE_EROSION_ITERATIONS: u32 = 6;
+const SCRIBBLE_MIN_EROSION_SURVIVAL: f32 = 0.4;
+
+// Trace detection: ink overlap and mean distance to the reference that a
+// freehand drawing practically never reaches
+const TRACE_MIN_IOU: f32 = 0.8;
+const TRACE_MAX_CHAMFER: f32 = 0.25;
+
+// Grid size of the drawing hash (HASH_SIZE^2 bits), fine enough that
+// separate attempts at a simple letter don't collide
+const HASH_SIZE: usize = 16;
+
+// Fraction of the combined score lost when a critical detail (the dot on
+// an i, the cross on a t) is missing
+const MISSING_FEATURE_PENALTY: f32 = 0.2;
+
+// Stray marks: ink blobs holding at most this fraction of the ink, further
+// than this fraction of the image from both the letter and the reference
+const STRAY_MAX_AREA_FRACTION: f32 = 0.05;
+const STRAY_MIN_DISTANCE: f32 = 0.15;
+// Fraction of the combined score lost per stray mark, and at most
+const STRAY_MARK_PENALTY: f32 = 0.03;
+const MAX_STRAY_MARK_PENALTY: f32 = 0.15;
+
+// Confidence: drawings inking at most the first fraction of their image
+// give nothing to go on, and from the second the score is fully trusted
+// (20 and 200 pixels of a 400-pixel square)
+const CONFIDENCE_MIN_INK: f32 = 0.000125;
+const CONFIDENCE_FULL_INK: f32 = 0.00125;
+// Stray marks lower confidence until this many, where it bottoms out
+const CONFIDENCE_NOISY_MARKS: f32 = 4.0;
+// Drawings this many times more (or less) elongated than the reference
+// start to lose confidence, bottoming out at the second
+const CONFIDENCE_ASPECT_START: f32 = 4.0;
+const CONFIDENCE_ASPECT_END: f32 = 12.0;
+// References inking less than the first fraction of their image (dots,
+// dashes) leave little to compare; from the second they are fully trusted
+const CONFIDENCE_MIN_REFERENCE_INK: f32 = 0.002;
+const CONFIDENCE_FULL_REFERENCE_INK: f32 = 0.01;
+// Lowest share of confidence that noise or an odd shape leaves
+const CONFIDENCE_FLOOR: f32 = 0.4;
+
+// Quality check: ink at least this many levels below the paper that still
+// doesn't reach the threshold is too faint to score, not absent
+const FAINT_INK_SEPARATION: u8 = 8;
+// Quality check: ink covering at most the first fraction of the image is
+// too little to score (20 pixels of a 400-pixel square), and faint marks
+// covering the second are a drawing that is too light (200 pixels)
+const QUALITY_MIN_INK: f32 = 0.000125;
+const QUALITY_FAINT_MARKS: f32 = 0.00125;
+// Ink along the image border over a run of at least this fraction of the
+// side (and this many pixels) means the letter was cut off there
+const CUT_OFF_MIN_RUN: f32 = 0.02;
+const CUT_OFF_MIN_PIXELS: usize = 3;
+
+// Skeleton graph branches shorter than this are treated as thinning spurs
+pub(crate) const MIN_SPUR_LENGTH: f32 = 6.0;
+
+// Number of skeleton points sampled for shape context matching
+const SHAPE_CONTEXT_POINTS: usize = 96;
+
+// Worst-region reporting: percentile of the distance distribution and the
+// distance (in normalized pixels) above which the region is called out
+const HAUSDORFF_PERCENTILE: f32 = 0.95;
+const WORST_REGION_MIN_DISTANCE: f32 = 8.0;
+// Gap detection: parts smaller than this (normalized pixels) are specks,
+// not strokes, and breaks longer than this are missing strokes, not gaps
+const GAP_MIN_PART_AREA: usize = 12;
+const GAP_MAX_LENGTH: f32 = 24.0;
+const GAP_MAX_PARTS: usize = 8;
+
+// Loop closure: holes smaller than this (normalized pixels) are ignored,
+// and a drawn hole must cover this fraction of the smaller of itself and
+// the reference loop to count as closing it
+const LOOP_MIN_HOLE_AREA: usize = 12;
+const LOOP_MIN_OVERLAP: f32 = 0.3;
+
+const REGION_LABELS: [&str; 9] = [
+    "top-left", "top", "top-right",
+    "left", "middle", "right",
+    "bottom-left", "bottom", "bottom-right",
+];
 
 /// Main scoring function
 pub fn score_drawing_internal(
     image_data: &[u8],
     character: char,
-    font_data: &[u8],
-) -> Result<WasmScoringResult, String> {
+    font: &Font,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    // Generate reference image (varied in practice mode)
+    let recording = options.with_timings.then(|| Recording::start(None)).flatten();
+    let variation = options.reference_variation(character);
+    let reference = timing::time(Stage::Reference, || cached_reference(&variation, font, options, REFERENCE_SIZE))?;
+
+    let mut scored = score_against_reference(image_data, character, &variation, &reference, strokes, options)?;
+    if let Some(recording) = recording {
+        scored.result.timings = Some(recording.finish());
+    }
+    Ok(scored)
+}
+
+/// Score a drawing against a prepared reference of the given variation
+pub(crate) fn score_against_reference(
+    image_data: &[u8],
+    character: char,
+    variation: &ReferenceVariation,
+    reference: &Reference,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+) -> Result<ScoredDrawing, ScoringError> {
+    score_against_reference_with_buffers(
+        image_data, character, variation, reference, strokes, options, &mut ScoringBuffers::default(),
+    )
+}
+
+/// `score_against_reference`, filling pixel buffers kept between calls
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn score_against_reference_with_buffers(
+    image_data: &[u8],
+    character: char,
+    variation: &ReferenceVariation,
+    reference: &Reference,
+    strokes: Option<&[Stroke]>,
+    options: &ScoringOptions,
+    buffers: &mut ScoringBuffers,
+) -> Result<ScoredDrawing, ScoringError> {
+    // Callers that prepared the reference themselves are timed from here,
+    // and thin within the options' limits unless they set their own; the
+    // drawing is thinned like the reference, which a pack or handle fixed
+    let recording = options.with_timings.then(|| Recording::start(None)).flatten();
+    let _thinning = ThinningScope::enter(reference.prepared.algorithm, options.input_limits.thinning, None);
+    check_cancelled()?;
+
     // Decode the user's drawing
-    let drawn_image = image::load_from_memory(image_data)
-        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    let drawn_image = timing::time(Stage::Decode, || {
+        decode_image(image_data, &options.input_limits).map(|image| flatten_alpha(image, options.background))
+    })?;
+    log!(Info, "scoring '{}': {}x{} drawing, {} bytes, {} strokes", character, drawn_image.width(), drawn_image.height(),
+        image_data.len(), strokes.map_or(0, |s| s.len()));
+
+    // Bring raw pointer coordinates into image pixels
+    let transformed: Option<Vec<Stroke>> = strokes
+        .zip(options.stroke_transform)
+        .map(|(strokes, transform)| strokes.iter().map(|s| transform.apply(s)).collect());
+    let strokes = transformed.as_deref().or(strokes);
+
+    // Strokes are re-rendered (with variable width if they carry pressure,
+    // and with erasures applied) in the same frame as the submitted image
+    let rerender = strokes.is_some_and(|s| {
+        options.rasterize_strokes || options.stroke_smoothing > 0.0 || has_pressure(s) || s.iter().any(|stroke| stroke.erase)
+    });
+    let drawn_gray = match strokes {
+        Some(strokes) if rerender => {
+            let smoothed = (options.stroke_smoothing > 0.0).then(|| smooth_strokes(strokes, options.stroke_smoothing));
+            let strokes = smoothed.as_deref().unwrap_or(strokes);
+            rasterize_strokes(strokes, drawn_image.width(), drawn_image.height(), options.stroke_width)
+        }
+        _ if options.photo => {
+            let gray = ink_gray(&drawn_image, options.ink);
+            match &options.photo_corners {
+                Some(corners) => clean_photo(&warp_to_rectangle(&gray, corners)?),
+                None => clean_photo(&gray),
+            }
+        }
+        _ => ink_gray(&drawn_image, options.ink),
+    };
+
+    // Faint stylus ink would vanish at the fixed threshold
+    let (drawn_gray, contrast_gain) = if options.auto_contrast && !rerender {
+        auto_contrast(&drawn_gray)
+    } else {
+        (drawn_gray, 1.0)
+    };
+
+    // Wobbly edges from a shaky hand would thin into side branches
+    let drawn_gray = if options.edge_smoothing > 0 && !rerender {
+        smooth_edges(drawn_gray, options.edge_smoothing.min(MAX_EDGE_SMOOTHING))
+    } else {
+        drawn_gray
+    };
+
+    // Single-pixel glitches would be counted as stray marks, or thinned
+    // into stroke ends and bridged to the letter
+    let drawn_gray = if options.speck_area > 0 {
+        erase_specks(drawn_gray, options.speck_area as usize)
+    } else {
+        drawn_gray
+    };
 
-    // Generate reference image
-    let reference_image = generate_reference_gray(character, font_data, 200)?;
+    // Small blobs away from the letter would stretch its bounding box
+    let stray_marks = find_stray_marks(&drawn_gray, &reference.image);
+    let drawn_gray = if options.exclude_stray_marks && !stray_marks.is_empty() {
+        let mut cleaned = drawn_gray;
+        let width = cleaned.width() as usize;
+        for mark in &stray_marks {
+            for &idx in &mark.pixels {
+                cleaned.put_pixel((idx % width) as u32, (idx / width) as u32, Luma([255u8]));
+            }
+        }
+        cleaned
+    } else {
+        drawn_gray
+    };
 
-    // Process both images
-    let drawn_processed = extract_and_center_character(&drawn_image.to_luma8());
-    let reference_processed = extract_and_center_character(&reference_image);
+    // Scores of marginal inputs are flagged rather than trusted
+    let confidence = score_confidence(&drawn_gray, &reference.image, stray_marks.len());
+
+    // Centering would hide a letter that ran off the canvas; photos are
+    // left out, since ruled lines at the crop's edge would count
+    let clipped = !options.photo && cut_off_edges(&ink_mask(&drawn_gray)) > 0;
+    log!(Debug, "{} ink pixels after contrast gain {:.2}, {} stray marks, confidence {:.2}, clipped {}",
+        ink_mask(&drawn_gray).count(), contrast_gain, stray_marks.len(), confidence, clipped);
+
+    // Formation metrics only look at strokes whose ink is still visible
+    let pen_strokes = strokes.map(|strokes| {
+        let mut visible = ink_strokes(strokes);
+        if rerender {
+            visible.retain(|stroke| stroke.points.iter().any(|p| {
+                p.x >= 0.0 && p.y >= 0.0
+                    && drawn_gray.get_pixel_checked(p.x as u32, p.y as u32).is_some_and(|px| px.0[0] < THRESHOLD)
+            }));
+        }
+        visible
+    });
+    let strokes = pen_strokes.as_deref();
+
+    // Process both images, the drawing with the reference's padding and frame
+    let prepared = &reference.prepared;
+    let size = prepared.size;
+    let drawn_processed = extract_and_center_character(&drawn_gray, reference.padding, options.threshold, size as u32);
+    let reference_processed = &prepared.processed;
+
+    // Hashed before registration so it depends on the drawing alone
+    let drawing_hash = difference_hash(&drawn_processed, size, size, HASH_SIZE);
+
+    // Small details are checked before registration, which would stretch
+    // a dotless i over the dot's place
+    let critical_feature = check_critical_feature(variation.glyph, &drawn_processed, reference_processed);
+    let missing_feature = critical_feature.and_then(|(feature, present)| (!present).then_some(feature));
+
+    // A near pixel-perfect copy of the reference means it was traced from
+    // a visible guide or fed back by the app
+    let suspected_trace = detect_trace(&drawn_processed, prepared);
+
+    // Pyramid scoring finds the alignment, the letter's gross shape and the
+    // directional chamfer, whose windows grow with the frame, on a frame half
+    // the size; only the distance metrics run at full size
+    let pyramid = options.pyramid && size >= 2 * MIN_FRAME_SIZE as usize;
+
+    // Align the drawing's centroid, scale and orientation with the reference
+    let metrics_lap = timing::lap();
+    let mut buffers_in = std::mem::take(buffers);
+    let mut registered = std::mem::take(&mut buffers_in.processed);
+    let registration = if pyramid {
+        find_registration(&halve_frame(&drawn_processed), &prepared.coarse().processed).map(|r| r.scaled(2.0))
+    } else {
+        find_registration(&drawn_processed, reference_processed)
+    };
+    warp_to_registration(&drawn_processed, registration, &mut registered);
+    log!(Debug, "registration: {:?}", registration);
+
+    // Masks, skeletons and distance fields are derived once for all metrics
+    let pipeline = ScoringPipeline::with_buffers(registered, prepared, buffers_in);
+    let coarse = pyramid.then(|| pipeline.coarse());
+    let gross = coarse.as_ref().unwrap_or(&pipeline);
+    log!(Debug, "drawing: {} ink and {} centerline pixels, {} endpoints, {} junctions, {} loops; reference: {} skeleton pixels",
+        pipeline.drawn.binary.count(), pipeline.drawn.centerline.count(), pipeline.drawn.graph.endpoint_count(),
+        pipeline.drawn.graph.junction_count(), pipeline.drawn.graph.loop_count(), prepared.skeleton.count());
+    if cancelled() {
+        *buffers = pipeline.drawn.into_buffers();
+        return Err(ScoringError::Cancelled);
+    }
 
     // Calculate scores
-    let coverage = calculate_coverage_score(&drawn_processed, &reference_processed);
-    let accuracy = calculate_accuracy_score(&drawn_processed, &reference_processed);
-    let similarity = calculate_stroke_similarity(&drawn_processed, &reference_processed);
+    let coverage = calculate_coverage_score(&pipeline);
+    let (tolerance, falloff) = (options.accuracy_tolerance * frame_scale(size), options.accuracy_falloff * frame_scale(size));
+    let accuracy = match options.mode {
+        ScoringMode::Freehand => calculate_accuracy_score(&pipeline, tolerance, falloff),
+        ScoringMode::Tracing => calculate_outline_accuracy(&pipeline, tolerance, falloff),
+    };
+    let similarity = match options.similarity_method {
+        SimilarityMethod::Skeleton => calculate_stroke_similarity(gross),
+        SimilarityMethod::Ssim => calculate_ssim_similarity(&pipeline.drawn.processed, reference_processed),
+        SimilarityMethod::Blended => {
+            (calculate_stroke_similarity(gross)
+                + calculate_ssim_similarity(&pipeline.drawn.processed, reference_processed)) / 2.0
+        }
+        SimilarityMethod::ShapeContext => calculate_shape_context_similarity(&pipeline),
+    };
+
+    let structure = calculate_structure_similarity(&pipeline);
+    let legibility = calculate_legibility(&gross.drawn);
+    let moment_similarity = calculate_moment_similarity(&gross.drawn.processed, &gross.reference.processed);
+    let worst = calculate_worst_region(&pipeline);
+    let mut hints = Vec::new();
+    if let Some(label) = &worst.label {
+        hints.push(format!("The {} part is the most off", label));
+    }
+    if !stray_marks.is_empty() {
+        hints.push("Try not to leave extra marks around the letter".to_string());
+    }
+    let loops = detect_loop_closure(&gross.drawn.processed, &gross.reference.processed);
+    for (closed, region) in &loops {
+        if !closed {
+            hints.push(format!("Close the loop at the {}", region));
+        }
+    }
+    let gaps = detect_gaps(&gross.drawn.processed, &gross.reference.processed);
+    let mut gap_regions: Vec<&str> = gaps.iter().map(|gap| gap.region.as_str()).collect();
+    gap_regions.dedup();
+    for region in gap_regions {
+        hints.push(format!("Connect your lines at the {}", region));
+    }
+
+    // Formation metrics need stroke data and a template for the character
+    let template = styled_template(character, options.style.as_deref());
+    let order_score = strokes
+        .zip(template)
+        .map(|(strokes, template)| stroke_order_score(strokes, template));
+    let directions = strokes
+        .zip(template)
+        .map(|(strokes, template)| stroke_directions(strokes, template))
+        .unwrap_or_default();
+
+    let expected_strokes = template.map(|t| t.strokes.len() as u32);
+    let actual_strokes = strokes.map(count_strokes);
+    if let (Some(expected), Some(actual)) = (expected_strokes, actual_strokes) {
+        if actual < expected || actual > expected + options.extra_strokes_allowed {
+            let noun = if expected == 1 { "stroke" } else { "strokes" };
+            hints.push(format!("Try writing {} with {} {}", character, expected, noun));
+        }
+    }
+
+    // Combined score, weights normalized by their sum
+    let weights = &options.weights;
+    let weight_total = weights.coverage + weights.accuracy + weights.similarity
+        + weights.structure + weights.legibility;
+    let share = |weight: f32| if weight_total > 0.0 { weight / weight_total } else { 0.0 };
+    let contributions = [
+        (coverage, share(weights.coverage)),
+        (accuracy, share(weights.accuracy)),
+        (similarity, share(weights.similarity)),
+        (structure, share(weights.structure)),
+        (legibility, share(weights.legibility)),
+    ];
+    let weighted_score: f32 = contributions.iter().map(|&(value, weight)| value * weight).sum();
+    log!(Debug, "coverage {:.3}, accuracy {:.3}, similarity {:.3}, structure {:.3}, legibility {:.3}, weighted {:.3}",
+        coverage, accuracy, similarity, structure, legibility, weighted_score);
+
+    // Very fast scribbles lose a little; careful drawing is never penalized
+    let duration_ms = options.duration_ms.or_else(|| strokes.and_then(drawing_duration));
+    let time_penalty = duration_ms.map_or(0.0, |d| options.time.penalty_for(d));
+    let after_time = weighted_score * (1.0 - time_penalty);
+    let stray_penalty = (stray_marks.len() as f32 * STRAY_MARK_PENALTY).min(MAX_STRAY_MARK_PENALTY);
+    let after_stray = after_time * (1.0 - stray_penalty);
+    let combined_score = if missing_feature.is_some() {
+        after_stray * (1.0 - MISSING_FEATURE_PENALTY)
+    } else {
+        after_stray
+    };
+
+    let mut percentage_score = (combined_score * 100.0).clamp(0.0, 100.0) as u8;
+    // Caps below keep a result in the one-star band, wherever it starts
+    let one_star_max = options.stars.one_star_max();
+    let mut caps = Vec::new();
+
+    // Minimum-ink gate: without enough of the letter drawn, no other metric
+    // can lift the result above one star
+    let skeleton_coverage = calculate_skeleton_coverage(&pipeline);
+    let min_skeleton_coverage = options.min_skeleton_coverage_for(character);
+    if skeleton_coverage < min_skeleton_coverage {
+        caps.push(ScoreCap::new("min_skeleton_coverage", MAX_GATED_SCORE.min(one_star_max)));
+    }
 
-    // Combined score with weights: 35% coverage, 35% accuracy, 30% similarity
-    let combined_score = coverage * 0.35 + accuracy * 0.35 + similarity * 0.30;
-    let percentage_score = (combined_score * 100.0).min(100.0).max(0.0) as u8;
+    // Scribbling over the whole area covers the letter but isn't writing it,
+    // and neither is coloring in a solid blob
+    let mut feedback_code = None;
+    let scribble = detect_scribble(&gross.drawn);
+    if coverage >= CANVAS_FILL_MIN_COVERAGE && detect_canvas_fill(gross) {
+        caps.push(ScoreCap::new("canvas_filled", MAX_GATED_SCORE.min(one_star_max)));
+        feedback_code = Some("canvas_filled");
+    } else if scribble {
+        caps.push(ScoreCap::new("scribble", MAX_GATED_SCORE.min(one_star_max)));
+        feedback_code = Some("scribble");
+    } else if clipped {
+        feedback_code = Some("clipped");
+    } else if let Some(feature) = missing_feature {
+        feedback_code = Some(feature.missing_code());
+    }
+    for cap in &caps {
+        percentage_score = percentage_score.min(cap.max_score);
+    }
+    *buffers = pipeline.drawn.into_buffers();
+    check_cancelled()?;
+
+    let points = |score: f32| (score * 1000.0).round() / 10.0;
+    let contribution = |(value, weight): (f32, f32)| ScoreContribution {
+        value: (value * 100.0).round(),
+        weight: (weight * 1000.0).round() / 1000.0,
+        points: points(value * weight),
+    };
+    let explanation = ScoreExplanation {
+        coverage: contribution(contributions[0]),
+        accuracy: contribution(contributions[1]),
+        similarity: contribution(contributions[2]),
+        structure: contribution(contributions[3]),
+        legibility: contribution(contributions[4]),
+        weighted_score: points(weighted_score),
+        time_penalty: points(weighted_score - after_time),
+        stray_mark_penalty: points(after_time - after_stray),
+        missing_feature_penalty: points(after_stray - combined_score),
+        caps,
+        stars: options.stars.clone(),
+        min_skeleton_coverage: (min_skeleton_coverage * 100.0).round(),
+    };
 
     // Star rating
-    let (stars, feedback) = get_star_rating(percentage_score);
+    let (stars, mut feedback) = get_star_rating(percentage_score, &options.stars);
+    match feedback_code {
+        Some("canvas_filled") => feedback = "Draw just the letter, not the whole box!".to_string(),
+        Some("scribble") => feedback = "Draw the letter with lines, not by coloring it in!".to_string(),
+        Some("clipped") => feedback = DrawingIssue::CutOff.message().to_string(),
+        _ => {
+            if let Some(feature) = missing_feature {
+                feedback = feature.reminder(variation.glyph);
+            }
+        }
+    }
 
-    // Generate reference image PNG for display
-    let reference_png = encode_grayscale_to_png(&reference_image)?;
+    let (scaffold, low_score_streak) = options.scaffold.decide(percentage_score, options.stars.three);
+    metrics_lap.record(Stage::Metrics);
+    log!(Info, "scored '{}': {} ({} stars), feedback code {:?}, caps {:?}", character, percentage_score, stars, feedback_code,
+        explanation.caps.iter().map(|cap| cap.reason.as_str()).collect::<Vec<_>>());
 
-    Ok(WasmScoringResult {
-        inner: ScoringResult {
+    Ok(ScoredDrawing {
+        result: ScoringResult {
             score: percentage_score,
             stars,
             feedback,
+            feedback_code: feedback_code.map(str::to_string),
+            scribble,
+            suspected_trace,
+            clipped,
             coverage: (coverage * 100.0).round(),
             accuracy: (accuracy * 100.0).round(),
             similarity: (similarity * 100.0).round(),
+            skeleton_coverage: (skeleton_coverage * 100.0).round(),
+            structure: (structure * 100.0).round(),
+            legibility: (legibility * 100.0).round(),
+            moment_similarity: (moment_similarity * 100.0).round(),
+            hausdorff_95: (worst.hausdorff_95 * 10.0).round() / 10.0,
+            worst_region: worst.label,
+            gaps,
+            loop_closed: loops.iter().map(|&(closed, _)| closed).collect(),
+            dot_present: feature_flag(critical_feature, CriticalFeature::Dot),
+            crossbar_present: feature_flag(critical_feature, CriticalFeature::Crossbar),
+            tail_present: feature_flag(critical_feature, CriticalFeature::Tail),
+            order_score: order_score.map(|o| (o * 100.0).round()),
+            expected_strokes,
+            actual_strokes,
+            stroke_directions: directions,
+            duration_ms,
+            time_penalty: (time_penalty * 100.0).round(),
+            pressure_consistency: strokes
+                .and_then(pressure_consistency)
+                .map(|p| (p * 100.0).round()),
+            reference_glyph: options.practice.as_ref().map(|_| variation.glyph.to_string()),
+            contrast_gain: (contrast_gain * 100.0).round() / 100.0,
+            stray_marks: stray_marks.len() as u32,
+            drawing_hash,
+            hints,
+            scaffold,
+            low_score_streak,
+            confidence: (confidence * 100.0).round(),
+            explanation,
+            timings: recording.map(Recording::finish),
         },
-        reference_image: reference_png,
+        reference_image: reference.png.clone(),
+    })
+}
+
+/// Quick check of whether a drawing has enough visible ink to be scored
+pub fn check_drawing_quality_internal(image_data: &[u8]) -> Result<DrawingQuality, String> {
+    let drawn_image = flatten_alpha(decode_image(image_data, &InputLimits::default())?, WHITE);
+    let gray = ink_gray(&drawn_image, InkColor::default());
+    let area = (gray.width() * gray.height()).max(1) as f32;
+    let marked = gray.pixels().filter(|p| p.0[0] < 255 - FAINT_INK_SEPARATION).count();
+
+    let (gray, _) = auto_contrast(&gray);
+    let ink = ink_mask(&erase_specks(gray, ScoringOptions::default().speck_area as usize));
+    let ink_pixels = ink.count();
+
+    let mut issues = Vec::new();
+    if ink_pixels as f32 <= QUALITY_MIN_INK * area {
+        issues.push(if marked as f32 >= QUALITY_FAINT_MARKS * area {
+            DrawingIssue::Faint
+        } else if ink_pixels == 0 {
+            DrawingIssue::Empty
+        } else {
+            DrawingIssue::TooLittleInk
+        });
+    }
+    if cut_off_edges(&ink) > 0 {
+        issues.push(DrawingIssue::CutOff);
+    }
+    log!(Info, "quality check: {} marked and {} ink pixels, issues {:?}", marked, ink_pixels, issues);
+
+    Ok(DrawingQuality {
+        scoreable: issues.is_empty(),
+        message: issues.first().map(|issue| issue.message().to_string()),
+        issues,
+        ink_pixels: ink_pixels as u32,
     })
 }
 
+/// Number of image sides the ink runs along for long enough to have been
+/// cut off there
+pub(crate) fn cut_off_edges(ink: &BitImage) -> usize {
+    let (width, height) = (ink.width(), ink.height());
+    if width == 0 || height == 0 {
+        return 0;
+    }
+    let longest_run = |pixels: &mut dyn Iterator<Item = bool>| {
+        let (mut run, mut longest) = (0, 0);
+        for inked in pixels {
+            run = if inked { run + 1 } else { 0 };
+            longest = longest.max(run);
+        }
+        longest
+    };
+    let cut = |run: usize, side: usize| run >= CUT_OFF_MIN_PIXELS && run as f32 >= CUT_OFF_MIN_RUN * side as f32;
+
+    [
+        cut(longest_run(&mut (0..width).map(|x| ink.get(x, 0))), width),
+        cut(longest_run(&mut (0..width).map(|x| ink.get(x, height - 1))), width),
+        cut(longest_run(&mut (0..height).map(|y| ink.get(0, y))), height),
+        cut(longest_run(&mut (0..height).map(|y| ink.get(width - 1, y))), height),
+    ].iter().filter(|&&cut| cut).count()
+}
+
+/// Estimate legibility of a drawing on its own, as a 0-100 score
+pub fn estimate_legibility_internal(image_data: &[u8]) -> Result<f32, String> {
+    let drawn_image = flatten_alpha(decode_image(image_data, &InputLimits::default())?, WHITE);
+
+    let (drawn_gray, _) = auto_contrast(&ink_gray(&drawn_image, InkColor::default()));
+    let drawn_processed = extract_and_center_character(&drawn_gray, NORMALIZE_PADDING, ThresholdMethod::default(), TARGET_SIZE);
+
+    Ok((calculate_legibility(&PreparedDrawing::new(&drawn_processed)) * 100.0).round())
+}
+
+/// Perceptual hash of a drawing, as reported in `drawing_hash`
+pub fn hash_drawing_internal(image_data: &[u8]) -> Result<String, String> {
+    let drawn_image = flatten_alpha(decode_image(image_data, &InputLimits::default())?, WHITE);
+
+    let (drawn_gray, _) = auto_contrast(&ink_gray(&drawn_image, InkColor::default()));
+    let drawn_processed = extract_and_center_character(&drawn_gray, NORMALIZE_PADDING, ThresholdMethod::default(), TARGET_SIZE);
+
+    Ok(difference_hash(&drawn_processed, TARGET_SIZE as usize, TARGET_SIZE as usize, HASH_SIZE))
+}
+
+/// Centerline graph of a drawing in its own pixels
+///
+/// The graph scoring builds in the normalized frame, with specks erased,
+/// pinholes filled and short spurs pruned, mapped back onto the image.
+pub fn extract_skeleton_graph_internal(image_data: &[u8]) -> Result<DrawingGraph, String> {
+    let drawn_image = flatten_alpha(decode_image(image_data, &InputLimits::default())?, WHITE);
+
+    let (drawn_gray, _) = auto_contrast(&ink_gray(&drawn_image, InkColor::default()));
+    let drawn_gray = erase_specks(drawn_gray, ScoringOptions::default().speck_area as usize);
+    let Some((drawn_processed, transform)) = extract_character(&drawn_gray, NORMALIZE_PADDING, ThresholdMethod::default(), TARGET_SIZE) else {
+        return Ok(DrawingGraph::default());
+    };
+    let graph = PreparedDrawing::new(&drawn_processed).graph;
+
+    // Pixel centers in the frame, to a tenth of an image pixel
+    let source = |x: f32, y: f32| {
+        let (sx, sy) = transform.source_point(x + 0.5, y + 0.5);
+        [(sx * 10.0).round() / 10.0, (sy * 10.0).round() / 10.0]
+    };
+    Ok(DrawingGraph {
+        nodes: graph.nodes.iter().map(|node| {
+            let [x, y] = source(node.x, node.y);
+            GraphNode { x, y, kind: node.kind }
+        }).collect(),
+        edges: graph.edges.iter().map(|edge| GraphEdge {
+            from: edge.from as u32,
+            to: edge.to as u32,
+            points: edge.points.iter().map(|&(x, y)| source(x as f32, y as f32)).collect(),
+            length: (edge.length / transform.scale * 10.0).round() / 10.0,
+        }).collect(),
+        components: graph.components as u32,
+        loops: graph.loop_count() as u32,
+    })
+}
+
+/// Hamming distance between two hex hashes of the same length
+pub fn hash_distance_internal(a: &str, b: &str) -> Result<u32, String> {
+    if a.len() != b.len() {
+        return Err(format!("Hash lengths differ: {} and {}", a.len(), b.len()));
+    }
+    a.chars().zip(b.chars())
+        .map(|(x, y)| match (x.to_digit(16), y.to_digit(16)) {
+            (Some(x), Some(y)) => Ok((x ^ y).count_ones()),
+            _ => Err(format!("Invalid hash digit in {:?} or {:?}", x, y)),
+        })
+        .sum()
+}
+
 /// Generate a reference image as PNG bytes
 pub fn generate_reference_image_internal(
     character: char,
-    font_data: &[u8],
+    font: &Font,
     size: u32,
-) -> Result<Vec<u8>, String> {
-    let gray = generate_reference_gray(character, font_data, size)?;
-    encode_grayscale_to_png(&gray)
+    options: &ScoringOptions,
+) -> Result<Vec<u8>, ScoringError> {
+    generate_sized_reference_image_internal(character, font, size, size, options)
 }
 
-fn generate_reference_gray(character: char, font_data: &[u8], size: u32) -> Result<GrayImage, String> {
-    let font = Font::try_from_bytes(font_data)
-        .ok_or("Failed to parse font data")?;
+/// Generate a reference image of any shape as PNG bytes
+///
+/// The letter is drawn in a square as wide as the image's shorter side,
+/// placed by `options.guide.anchor`.
+pub fn generate_sized_reference_image_internal(
+    character: char,
+    font: &Font,
+    width: u32,
+    height: u32,
+    options: &ScoringOptions,
+) -> Result<Vec<u8>, ScoringError> {
+    let side = width.min(height);
+    if side == 0 {
+        return Err("Reference image must be at least 1 pixel wide and high".into());
+    }
+    let variation = options.reference_variation(character);
+    let square = match options.guide.line {
+        GuideLine::Solid => render_reference(&variation, font, options, side)?,
+        #[cfg(feature = "guides")]
+        GuideLine::Dotted | GuideLine::Dashed => crate::guides::render_guide(&variation, font, options, side)?,
+        #[cfg(not(feature = "guides"))]
+        GuideLine::Dotted | GuideLine::Dashed => {
+            return Err("Dotted and dashed guides need the `guides` feature".into());
+        }
+    };
+    let (left, top) = options.guide.anchor.offset(width - side, height - side);
+    let gray = if width == height {
+        square
+    } else {
+        let mut canvas: GrayImage = ImageBuffer::from_pixel(width, height, Luma([255u8]));
+        image::imageops::overlay(&mut canvas, &square, left as i64, top as i64);
+        canvas
+    };
+    match &options.guide.ruling {
+        None => Ok(encode_grayscale_to_png(&gray)?),
+        #[cfg(feature = "guides")]
+        Some(ruling) => Ok(encode_rgb_to_png(&crate::guides::rule_reference(&gray, &variation, font, options, ruling, (side, top))?)?),
+        #[cfg(not(feature = "guides"))]
+        Some(_) => Err("Writing lines need the `guides` feature".into()),
+    }
+}
 
-    let mut img: GrayImage = ImageBuffer::from_pixel(size, size, Luma([255u8]));
+/// Render the reference for a variation: the handwriting style's letterform
+/// if the style has one, the font's glyph otherwise, at the options' font
+/// size and stroke width
+pub(crate) fn render_reference(
+    variation: &ReferenceVariation,
+    font: &Font,
+    options: &ScoringOptions,
+    size: u32,
+) -> Result<GrayImage, ScoringError> {
+    let variation = &sized_variation(variation, options)?;
+    let factor = options.reference_supersampling;
+    if !(1..=MAX_REFERENCE_SUPERSAMPLING).contains(&factor) {
+        return Err(format!("Reference supersampling must be 1-{}", MAX_REFERENCE_SUPERSAMPLING).into());
+    }
+    let render_size = size * factor;
+    let styled = match &options.style {
+        Some(style) => render_style_glyph(style, variation, render_size)?,
+        None => None,
+    };
+    let image = match styled {
+        Some(image) => image,
+        None => generate_reference_gray(variation, font, &options.font_axes, render_size)?,
+    };
 
-    let font_size = size as f32 * 0.75;
-    let scale = Scale::uniform(font_size);
+    let image = match options.reference_stroke_width {
+        None => image,
+        Some(width) if width.is_finite() && width > 0.0 => {
+            // Stamp the pen along the glyph's skeleton
+            let skeleton = normalize_line_thickness(&ink_mask(&image), 1, false);
+            let dabs: Vec<Stroke> = skeleton.ones()
+                .map(|(x, y)| Stroke {
+                    points: vec![StrokePoint { x: x as f32 + 0.5, y: y as f32 + 0.5, t: None, pressure: None }],
+                    ..Default::default()
+                })
+                .collect();
+            rasterize_strokes(&dabs, image.width(), image.height(), width * render_size as f32)
+        }
+        Some(_) => return Err("Reference stroke width must be positive".into()),
+    };
+    Ok(downsample_area(&image, factor))
+}
 
-    // Get glyph metrics for centering
-    let glyph = font.glyph(character).scaled(scale);
-    let h_metrics = glyph.h_metrics();
+/// A variation with its scale adjusted for the options' reference font size
+pub(crate) fn sized_variation(variation: &ReferenceVariation, options: &ScoringOptions) -> Result<ReferenceVariation, ScoringError> {
+    let font_size = options.reference_font_size;
+    if !(font_size.is_finite() && font_size > 0.0) {
+        return Err("Reference font size must be positive".into());
+    }
+    Ok(ReferenceVariation { scale: variation.scale * font_size / REFERENCE_FONT_SIZE, ..*variation })
+}
+
+/// Index of the first font in a list with a glyph for the character (the
+/// practice variant, if one is chosen); `Font::with_fallbacks` resolves
+/// every glyph this way
+pub fn select_font_internal(fonts: &[&Font], character: char, options: &ScoringOptions) -> Result<usize, ScoringError> {
+    let glyph = options.reference_variation(character).glyph;
+    fonts.iter()
+        .position(|font| font.has_glyph(glyph))
+        .ok_or(ScoringError::GlyphMissing(glyph))
+}
+
+pub(crate) fn generate_reference_gray(
+    variation: &ReferenceVariation,
+    font: &Font,
+    axes: &HashMap<String, f32>,
+    size: u32,
+) -> Result<GrayImage, ScoringError> {
+    let font = font.for_glyph(variation.glyph).with_axes(axes)?;
+    let glyph = place_glyph(variation, &font, size)?;
 
-    let glyph = glyph.positioned(point(0.0, 0.0));
+    let mut img: GrayImage = ImageBuffer::from_pixel(size, size, Luma([255u8]));
 
-    if let Some(bb) = glyph.pixel_bounding_box() {
-        let glyph_width = bb.max.x - bb.min.x;
-        let glyph_height = bb.max.y - bb.min.y;
+    // Draw the glyph
+    if let Some(outline) = glyph.and_then(|glyph| font.outline_glyph(glyph)) {
+        let bb = outline.px_bounds();
+        outline.draw(|x, y, v| {
+            let px = x as i32 + bb.min.x as i32;
+            let py = y as i32 + bb.min.y as i32;
 
-        // Center the glyph
-        let x_offset = ((size as i32 - glyph_width) / 2) - bb.min.x;
-        let y_offset = ((size as i32 - glyph_height) / 2) - bb.min.y;
+            if px >= 0 && px < size as i32 && py >= 0 && py < size as i32 {
+                let intensity = (255.0 * (1.0 - v.min(1.0))) as u8;
+                img.put_pixel(px as u32, py as u32, Luma([intensity]));
+            }
+        });
+    }
 
-        // Reposition glyph centered
-        let glyph = font.glyph(character)
-            .scaled(scale)
-            .positioned(point(x_offset as f32, y_offset as f32 + font_size * 0.8));
+    Ok(img)
+}
 
-        // Draw the glyph
-        if let Some(bb) = glyph.pixel_bounding_box() {
-            glyph.draw(|x, y, v| {
-                let px = x as i32 + bb.min.x;
-                let py = y as i32 + bb.min.y;
+/// Outline contours of the reference glyph in image pixels, each closed
+/// (ending at its first point)
+#[cfg(feature = "guides")]
+pub(crate) fn glyph_outline(
+    variation: &ReferenceVariation,
+    font: &Font,
+    axes: &HashMap<String, f32>,
+    size: u32,
+) -> Result<Vec<Vec<(f32, f32)>>, ScoringError> {
+    let font = font.for_glyph(variation.glyph).with_axes(axes)?;
+    let Some(glyph) = place_glyph(variation, &font, size)? else {
+        return Ok(Vec::new());
+    };
+    let Some(outline) = font.outline(glyph.id) else {
+        return Ok(Vec::new());
+    };
 
-                if px >= 0 && px < size as i32 && py >= 0 && py < size as i32 {
-                    let intensity = (255.0 * (1.0 - v)) as u8;
-                    img.put_pixel(px as u32, py as u32, Luma([intensity]));
+    // The mapping outline_glyph draws with
+    let factor = font.as_scaled(glyph.scale).scale_factor();
+    let to_image = |p: &ab_glyph::Point| {
+        (p.x * factor.horizontal + glyph.position.x, -p.y * factor.vertical + glyph.position.y)
+    };
+    let mut contours: Vec<Vec<(f32, f32)>> = Vec::new();
+    for curve in &outline.curves {
+        let (start, controls): (_, Vec<_>) = match curve {
+            OutlineCurve::Line(p0, p1) => (p0, vec![p1]),
+            OutlineCurve::Quad(p0, p1, p2) => (p0, vec![p1, p2]),
+            OutlineCurve::Cubic(p0, p1, p2, p3) => (p0, vec![p1, p2, p3]),
+        };
+        let start = to_image(start);
+        let controls: Vec<(f32, f32)> = controls.into_iter().map(to_image).collect();
+        // A curve not starting where the last one ended begins a new contour
+        if contours.last().and_then(|contour| contour.last()) != Some(&start) {
+            contours.push(vec![start]);
+        }
+        let contour = contours.last_mut().unwrap();
+        match controls.as_slice() {
+            [end] => contour.push(*end),
+            controls => {
+                for k in 1..=OUTLINE_CURVE_SEGMENTS {
+                    let t = k as f32 / OUTLINE_CURVE_SEGMENTS as f32;
+                    // De Casteljau on the control polygon
+                    let mut points: Vec<(f32, f32)> = std::iter::once(start).chain(controls.iter().copied()).collect();
+                    while points.len() > 1 {
+                        points = points.windows(2)
+                            .map(|w| (w[0].0 + (w[1].0 - w[0].0) * t, w[0].1 + (w[1].1 - w[0].1) * t))
+                            .collect();
+                    }
+                    contour.push(points[0]);
                 }
-            });
+            }
+        }
+    }
+    for contour in &mut contours {
+        if contour.first() != contour.last() {
+            contour.push(contour[0]);
         }
     }
+    Ok(contours)
+}
 
-    Ok(img)
+/// Topline, midline and baseline heights in image pixels for the reference
+/// glyph, from the font's capital and x heights (`None` for a glyph without
+/// an outline)
+#[cfg(feature = "guides")]
+pub(crate) fn glyph_lines(
+    variation: &ReferenceVariation,
+    font: &Font,
+    axes: &HashMap<String, f32>,
+    size: u32,
+) -> Result<Option<[f32; 3]>, ScoringError> {
+    let font = font.for_glyph(variation.glyph).with_axes(axes)?;
+    let Some(glyph) = place_glyph(variation, &font, size)? else {
+        return Ok(None);
+    };
+    // Top of another glyph set on the same baseline
+    let top_of = |c: char| {
+        let id = font.glyph_id(c);
+        if id.0 == 0 {
+            return None;
+        }
+        font.outline_glyph(id.with_scale_and_position(glyph.scale, glyph.position))
+            .map(|outline| outline.px_bounds().min.y)
+    };
+    let baseline = glyph.position.y;
+    let topline = top_of('H').unwrap_or(baseline - font.as_scaled(glyph.scale).ascent());
+    let midline = top_of('x').unwrap_or((topline + baseline) / 2.0);
+    Ok(Some([topline, midline, baseline]))
+}
+
+/// The glyph for a variation, scaled and centered as the reference draws
+/// it (`None` for a glyph without an outline, such as a space)
+fn place_glyph(variation: &ReferenceVariation, font: &FontRef, size: u32) -> Result<Option<Glyph>, ScoringError> {
+    // Glyph 0 is .notdef, which fonts substitute for characters they lack
+    let glyph_id = font.glyph_id(variation.glyph);
+    if glyph_id.0 == 0 {
+        return Err(ScoringError::GlyphMissing(variation.glyph));
+    }
+
+    let font_size = size as f32 * REFERENCE_FONT_SIZE * variation.scale;
+    let scale = PxScale::from(font_size);
+
+    // Get glyph metrics for centering
+    let glyph = glyph_id.with_scale_and_position(scale, point(0.0, 0.0));
+    let Some(outline) = font.outline_glyph(glyph) else {
+        return Ok(None);
+    };
+    let bb = outline.px_bounds();
+    let glyph_width = (bb.max.x - bb.min.x) as i32;
+    let glyph_height = (bb.max.y - bb.min.y) as i32;
+
+    // Center the glyph, then apply any practice offset
+    let x_offset = ((size as i32 - glyph_width) / 2) - bb.min.x as i32
+        + (variation.offset_x * size as f32).round() as i32;
+    let y_offset = ((size as i32 - glyph_height) / 2) - bb.min.y as i32
+        + (variation.offset_y * size as f32).round() as i32;
+
+    Ok(Some(glyph_id.with_scale_and_position(scale, point(x_offset as f32, y_offset as f32))))
+}
+
+/// Decode a submitted image, rejecting it first if it is over `limits`
+///
+/// PNG is always accepted, JPEG and WebP with the `jpeg` and `webp`
+/// features. The file size and the dimensions in its header are checked
+/// before any pixels are allocated.
+pub(crate) fn decode_image(image_data: &[u8], limits: &InputLimits) -> Result<DynamicImage, String> {
+    if image_data.len() > limits.max_bytes {
+        return Err(format!(
+            "Image is {} bytes, over the {} byte limit", image_data.len(), limits.max_bytes,
+        ));
+    }
+
+    let reader = || {
+        ImageReader::new(Cursor::new(image_data))
+            .with_guessed_format()
+            .map_err(|e| format!("Failed to decode image: {}", e))
+    };
+    // Formats behind features are still recognized, to name the feature
+    match reader()?.format() {
+        Some(ImageFormat::Jpeg) if !cfg!(feature = "jpeg") => {
+            return Err("JPEG drawings need the `jpeg` feature".to_string());
+        }
+        Some(ImageFormat::WebP) if !cfg!(feature = "webp") => {
+            return Err("WebP drawings need the `webp` feature".to_string());
+        }
+        _ => {}
+    }
+    let (width, height) = reader()?.into_dimensions()
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+    if width > limits.max_width || height > limits.max_height {
+        return Err(format!(
+            "Image is {}x{} pixels, over the {}x{} limit", width, height, limits.max_width, limits.max_height,
+        ));
+    }
+
+    reader()?.decode().map_err(|e| format!("Failed to decode image: {}", e))
+}
+
+/// Composite an image with transparency over an opaque background
+///
+/// Canvas exports often leave the paper transparent black, which would
+/// otherwise read as solid ink. Opaque images are returned as they are.
+pub(crate) fn flatten_alpha(image: DynamicImage, background: [u8; 3]) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return image;
+    }
+    let rgba = image.to_rgba8();
+    let flattened = image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let over = |ink: u8, paper: u8| ((ink as u32 * a as u32 + paper as u32 * (255 - a as u32) + 127) / 255) as u8;
+        image::Rgb([over(r, background[0]), over(g, background[1]), over(b, background[2])])
+    });
+    DynamicImage::ImageRgb8(flattened)
+}
+
+/// The drawing as dark ink on white paper
+///
+/// With `InkColor::Any` the paper is the per-channel median color and each
+/// pixel is as dark as it is far from it in RGB, so a yellow brush on white
+/// or chalk on a dark board come out as solid ink. Gray drawings on white
+/// paper come out as they went in.
+pub(crate) fn ink_gray(image: &DynamicImage, ink: InkColor) -> GrayImage {
+    if ink == InkColor::Dark {
+        return image.to_luma8();
+    }
+    let rgb = image.to_rgb8();
+    let total = rgb.width() as usize * rgb.height() as usize;
+    if total == 0 {
+        return image.to_luma8();
+    }
+
+    let mut histograms = [[0usize; 256]; 3];
+    for pixel in rgb.pixels() {
+        for (channel, &value) in pixel.0.iter().enumerate() {
+            histograms[channel][value as usize] += 1;
+        }
+    }
+    let paper = histograms.map(|histogram| {
+        let mut seen = 0;
+        histogram.iter().position(|&count| {
+            seen += count;
+            seen > total / 2
+        }).unwrap_or(255) as f32
+    });
+
+    ImageBuffer::from_fn(rgb.width(), rgb.height(), |x, y| {
+        let distance = rgb.get_pixel(x, y).0.iter().zip(paper)
+            .map(|(&value, paper)| (value as f32 - paper).powi(2))
+            .sum::<f32>()
+            .sqrt() / 3f32.sqrt();
+        Luma([(255.0 - distance).round().clamp(0.0, 255.0) as u8])
+    })
 }
 
-fn encode_grayscale_to_png(img: &GrayImage) -> Result<Vec<u8>, String> {
+pub(crate) fn encode_grayscale_to_png(img: &GrayImage) -> Result<Vec<u8>, String> {
     let mut buffer = Vec::new();
     let encoder = PngEncoder::new(&mut buffer);
     encoder.write_image(
@@ -127,8 +1077,313 @@ fn encode_grayscale_to_png(img: &GrayImage) -> Result<Vec<u8>, String> {
     Ok(buffer)
 }
 
+#[cfg(feature = "guides")]
+pub(crate) fn encode_rgb_to_png(img: &image::RgbImage) -> Result<Vec<u8>, String> {
+    let mut buffer = Vec::new();
+    let encoder = PngEncoder::new(&mut buffer);
+    encoder.write_image(
+        img.as_raw(),
+        img.width(),
+        img.height(),
+        image::ExtendedColorType::Rgb8,
+    ).map_err(|e| format!("Failed to encode PNG: {}", e))?;
+    Ok(buffer)
+}
+
+/// A normalized frame size, checked
+pub(crate) fn checked_frame_size(size: u32) -> Result<u32, ScoringError> {
+    if !(MIN_FRAME_SIZE..=MAX_FRAME_SIZE).contains(&size) {
+        return Err(format!("Frame size must be {}-{}", MIN_FRAME_SIZE, MAX_FRAME_SIZE).into());
+    }
+    Ok(size)
+}
+
+/// Side of a square normalized image
+pub(crate) fn frame_side(values: &[f32]) -> usize {
+    (values.len() as f64).sqrt().round() as usize
+}
+
+/// Normalized pixels in a frame `size` wide per pixel of the default frame
+pub(crate) fn frame_scale(size: usize) -> f32 {
+    size as f32 / TARGET_SIZE as f32
+}
+
+/// A whole number of default-frame pixels in a frame `size` wide, at least one
+pub(crate) fn frame_pixels(pixels: u32, size: usize) -> u32 {
+    ((pixels as f32 * frame_scale(size)).round() as u32).max(1)
+}
+
+/// An area of default-frame pixels in a frame `size` wide
+pub(crate) fn frame_area(area: usize, size: usize) -> usize {
+    (area as f32 * frame_scale(size).powi(2)).round() as usize
+}
+
+/// A normalized image at half the size
+///
+/// Each pixel is the mean of the two darkest in its 2x2 block, so lines two
+/// pixels wide survive while single stray pixels fade to the cut.
+pub(crate) fn halve_frame(values: &[f32]) -> Vec<f32> {
+    let size = frame_side(values);
+    let half = size / 2;
+    let mut halved = Vec::with_capacity(half * half);
+    for y in 0..half {
+        for x in 0..half {
+            let mut block = [
+                values[2 * y * size + 2 * x],
+                values[2 * y * size + 2 * x + 1],
+                values[(2 * y + 1) * size + 2 * x],
+                values[(2 * y + 1) * size + 2 * x + 1],
+            ];
+            block.sort_by(f32::total_cmp);
+            halved.push((block[0] + block[1]) / 2.0);
+        }
+    }
+    halved
+}
+
+/// Mapping from a source image to the normalized frame
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NormalizeTransform {
+    pub(crate) min_x: u32,
+    pub(crate) min_y: u32,
+    pub(crate) scale: f32,
+    new_width: u32,
+    new_height: u32,
+    pub(crate) x_offset: u32,
+    pub(crate) y_offset: u32,
+    /// Side of the normalized frame
+    pub(crate) size: u32,
+}
+
+impl NormalizeTransform {
+    /// Map a point in the normalized frame back to source image pixels
+    pub(crate) fn source_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            self.min_x as f32 + (x - self.x_offset as f32) / self.scale,
+            self.min_y as f32 + (y - self.y_offset as f32) / self.scale,
+        )
+    }
+}
+
+/// Shrink an image by a whole factor, averaging each factor-square block
+/// into one pixel
+fn downsample_area(image: &GrayImage, factor: u32) -> GrayImage {
+    if factor <= 1 {
+        return image.clone();
+    }
+    let area = factor * factor;
+    GrayImage::from_fn(image.width() / factor, image.height() / factor, |x, y| {
+        let mut sum = 0;
+        for dy in 0..factor {
+            for dx in 0..factor {
+                sum += image.get_pixel(x * factor + dx, y * factor + dy).0[0] as u32;
+            }
+        }
+        Luma([((sum + area / 2) / area) as u8])
+    })
+}
+
+/// Stretch faint ink to full contrast against the background
+///
+/// The background is the median gray level and the ink level the median of
+/// pixels clearly darker than it. Images whose ink already crosses the
+/// threshold are returned unchanged. Returns the image and the gain applied.
+pub(crate) fn auto_contrast(image: &GrayImage) -> (GrayImage, f32) {
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+
+    let level_at = |histogram: &[usize], rank: usize| {
+        let mut seen = 0;
+        histogram.iter().position(|&count| {
+            seen += count;
+            seen > rank
+        }).unwrap_or(0)
+    };
+
+    let total = (image.width() * image.height()) as usize;
+    if total == 0 {
+        return (image.clone(), 1.0);
+    }
+    let background = level_at(&histogram, total / 2);
+    if background < AUTO_CONTRAST_MIN_SEPARATION as usize {
+        return (image.clone(), 1.0);
+    }
+
+    let ink_levels = &histogram[..=background - AUTO_CONTRAST_MIN_SEPARATION as usize];
+    let ink_total: usize = ink_levels.iter().sum();
+    if ink_total == 0 {
+        return (image.clone(), 1.0);
+    }
+    let ink = level_at(ink_levels, ink_total / 2);
+    if ink < THRESHOLD as usize {
+        return (image.clone(), 1.0);
+    }
+
+    let gain = 255.0 / (background - ink) as f32;
+    let stretched = ImageBuffer::from_fn(image.width(), image.height(), |x, y| {
+        let value = (image.get_pixel(x, y).0[0] as f32 - ink as f32) * gain;
+        Luma([value.clamp(0.0, 255.0) as u8])
+    });
+    (stretched, gain)
+}
+
+/// Whether a checked critical detail of the given kind was drawn
+fn feature_flag(checked: Option<(CriticalFeature, bool)>, kind: CriticalFeature) -> Option<bool> {
+    checked.and_then(|(feature, present)| (feature == kind).then_some(present))
+}
+
+/// Find small ink blobs far from both the rest of the drawing and the letter
+///
+/// The drawing is taken to share the reference's frame scaled to the image
+/// size, as when it is drawn over the guide.
+fn find_stray_marks(image: &GrayImage, reference: &GrayImage) -> Vec<Component> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let components = connected_components(&ink_mask(image));
+    let Some((main, rest)) = components.split_first() else {
+        return Vec::new();
+    };
+    let total_ink: usize = components.iter().map(|c| c.area).sum();
+
+    let (ref_width, ref_height) = (reference.width() as usize, reference.height() as usize);
+    let reference_dist = distance_transform_edt(&ink_mask(reference));
+    let scale_x = ref_width as f32 / width as f32;
+    let scale_y = ref_height as f32 / height as f32;
+    let min_reference_distance = STRAY_MIN_DISTANCE * ref_width.max(ref_height) as f32;
+    let min_gap = STRAY_MIN_DISTANCE * width.max(height) as f32;
+
+    rest.iter()
+        .filter(|mark| {
+            mark.area as f32 <= STRAY_MAX_AREA_FRACTION * total_ink as f32
+                && mark.box_distance(main) > min_gap
+                && mark.pixels.iter().all(|&idx| {
+                    let rx = (((idx % width) as f32 * scale_x) as usize).min(ref_width - 1);
+                    let ry = (((idx / width) as f32 * scale_y) as usize).min(ref_height - 1);
+                    reference_dist[ry * ref_width + rx] > min_reference_distance
+                })
+        })
+        .cloned()
+        .collect()
+}
+
+/// How far a score can be trusted (0-1), from how marginal its inputs are
+///
+/// Very little ink, several stray marks, a drawing far more or less
+/// elongated than the reference, and a reference with almost no ink each
+/// lower it.
+fn score_confidence(drawn: &GrayImage, reference: &GrayImage, stray_marks: usize) -> f32 {
+    let ramp = |value: f32, start: f32, end: f32| ((value - start) / (end - start)).clamp(0.0, 1.0);
+    let drawn_ink = ink_mask(drawn);
+    let reference_ink = ink_mask(reference);
+
+    let ink_fraction = drawn_ink.count() as f32 / (drawn_ink.width() * drawn_ink.height()).max(1) as f32;
+    let ink = ramp(ink_fraction, CONFIDENCE_MIN_INK, CONFIDENCE_FULL_INK);
+    let noise = 1.0 - (1.0 - CONFIDENCE_FLOOR) * ramp(stray_marks as f32, 0.0, CONFIDENCE_NOISY_MARKS);
+    let aspect = match (elongation(&drawn_ink), elongation(&reference_ink)) {
+        (Some(drawn), Some(reference)) => {
+            let mismatch = (drawn / reference).max(reference / drawn);
+            1.0 - (1.0 - CONFIDENCE_FLOOR) * ramp(mismatch, CONFIDENCE_ASPECT_START, CONFIDENCE_ASPECT_END)
+        }
+        _ => 1.0,
+    };
+    let reference_fraction = reference_ink.count() as f32 / (reference_ink.width() * reference_ink.height()).max(1) as f32;
+    let reference_amount = ramp(reference_fraction, CONFIDENCE_MIN_REFERENCE_INK, CONFIDENCE_FULL_REFERENCE_INK);
+
+    ink * noise * aspect * reference_amount
+}
+
+/// Long side over short side of the ink's bounding box, if there is ink
+fn elongation(ink: &BitImage) -> Option<f32> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (usize::MAX, usize::MAX, 0, 0);
+    for (x, y) in ink.ones() {
+        (min_x, min_y, max_x, max_y) = (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y));
+    }
+    if min_x > max_x {
+        return None;
+    }
+    let (width, height) = ((max_x - min_x + 1) as f32, (max_y - min_y + 1) as f32);
+    Some(width.max(height) / width.min(height))
+}
+
+/// The image with ink blobs of at most `max_area` pixels turned to paper
+fn erase_specks(mut image: GrayImage, max_area: usize) -> GrayImage {
+    let ink = ink_mask(&image);
+    let kept = remove_specks(&ink, max_area);
+    for (x, y) in ink.ones().filter(|&(x, y)| !kept.get(x, y)) {
+        image.put_pixel(x as u32, y as u32, Luma([255u8]));
+    }
+    image
+}
+
+/// The image with its ink closed then opened by `radius` pixels, filling
+/// notches and shaving bumps along the edges
+fn smooth_edges(mut image: GrayImage, radius: u32) -> GrayImage {
+    let ink = ink_mask(&image);
+    let smoothed = ink.dilate(radius).erode(radius).erode(radius).dilate(radius);
+    for y in 0..ink.height() {
+        for x in 0..ink.width() {
+            if ink.get(x, y) != smoothed.get(x, y) {
+                image.put_pixel(x as u32, y as u32, Luma([if smoothed.get(x, y) { 0u8 } else { 255u8 }]));
+            }
+        }
+    }
+    image
+}
+
+/// Pixels of a grayscale image dark enough to count as ink
+pub(crate) fn ink_mask(image: &GrayImage) -> BitImage {
+    BitImage::from_fn(image.width() as usize, image.height() as usize, |x, y| image.get_pixel(x as u32, y as u32).0[0] < THRESHOLD)
+}
+
 /// Extract the drawn character, center it, and normalize to target size
-fn extract_and_center_character(image: &GrayImage) -> Vec<f32> {
+///
+/// With a threshold other than the fixed one, intensities are rescaled so
+/// it falls at mid-gray, where the normalized ink mask is cut.
+fn extract_and_center_character(image: &GrayImage, padding: f32, method: ThresholdMethod, size: u32) -> Vec<f32> {
+    extract_character(image, padding, method, size)
+        .map_or_else(|| vec![1.0; (size * size) as usize], |(processed, _)| processed)
+}
+
+/// `extract_and_center_character` with the transform it used, or `None`
+/// for a blank image
+fn extract_character(image: &GrayImage, padding: f32, method: ThresholdMethod, size: u32) -> Option<(Vec<f32>, NormalizeTransform)> {
+    let threshold = ink_threshold(image, method);
+    // Fixed-threshold ink is cut at mid-gray once normalized, like references
+    let cut = if threshold != THRESHOLD { threshold as f32 / 255.0 } else { 0.5 };
+    let transform = find_normalize_transform(image, padding, threshold, size)?;
+    let mut processed = apply_normalize_transform(image, &transform, 1.0, 1.0, cut);
+    if threshold != THRESHOLD {
+        let cut = threshold as f32 / 255.0;
+        for value in &mut processed {
+            *value = if *value < cut { 0.5 * *value / cut } else { 0.5 + 0.5 * (*value - cut) / (1.0 - cut) };
+        }
+    }
+    Some((processed, transform))
+}
+
+/// Gray level below which a drawing's pixels are ink
+///
+/// Otsu's level when the drawing has distinct ink and paper classes, else
+/// the fixed `THRESHOLD`.
+fn ink_threshold(image: &GrayImage, method: ThresholdMethod) -> u8 {
+    if method == ThresholdMethod::Fixed {
+        return THRESHOLD;
+    }
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    match otsu_threshold(&histogram) {
+        Some((level, dark, light)) if light - dark >= OTSU_MIN_SEPARATION => level,
+        _ => THRESHOLD,
+    }
+}
+
+/// Find the transform that fits the character's bounding box, with padding,
+/// into a normalized frame `size` wide; pixels darker than `threshold` are
+/// the character. Returns `None` for a blank image.
+pub(crate) fn find_normalize_transform(image: &GrayImage, padding: f32, threshold: u8, size: u32) -> Option<NormalizeTransform> {
     let (width, height) = image.dimensions();
     let mut drawn_mask = vec![false; (width * height) as usize];
 
@@ -136,7 +1391,7 @@ fn extract_and_center_character(image: &GrayImage) -> Vec<f32> {
     for y in 0..height {
         for x in 0..width {
             let pixel = image.get_pixel(x, y).0[0];
-            drawn_mask[(y * width + x) as usize] = pixel < THRESHOLD;
+            drawn_mask[(y * width + x) as usize] = pixel < threshold;
         }
     }
 
@@ -160,7 +1415,7 @@ fn extract_and_center_character(image: &GrayImage) -> Vec<f32> {
     }
 
     if !has_content {
-        return vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize];
+        return None;
     }
 
     // Extract region
@@ -168,408 +1423,2259 @@ fn extract_and_center_character(image: &GrayImage) -> Vec<f32> {
     let region_height = max_y - min_y + 1;
 
     // Calculate scale to fit in target size with padding
-    let padding = 0.1;
-    let available_size = (TARGET_SIZE as f32 * (1.0 - 2.0 * padding)) as u32;
+    let padding = if padding.is_nan() { NORMALIZE_PADDING } else { padding.clamp(0.0, MAX_NORMALIZE_PADDING) };
+    let available_size = (size as f32 * (1.0 - 2.0 * padding)) as u32;
     let scale = (available_size as f32 / region_width as f32)
         .min(available_size as f32 / region_height as f32);
 
     let new_width = ((region_width as f32 * scale) as u32).max(1);
     let new_height = ((region_height as f32 * scale) as u32).max(1);
 
-    // Create output
-    let mut output = vec![1.0f32; (TARGET_SIZE * TARGET_SIZE) as usize];
+    Some(NormalizeTransform {
+        min_x,
+        min_y,
+        scale,
+        new_width,
+        new_height,
+        x_offset: (size - new_width) / 2,
+        y_offset: (size - new_height) / 2,
+        size,
+    })
+}
+
+/// Resample an image into the normalized frame
+///
+/// Each normalized pixel is the mean of the source pixels under it,
+/// weighted by overlap. A pixel whose mean is paper but which covers ink
+/// takes the darkest ink under it when no neighbour is ink already, so a
+/// line thinner than a normalized pixel stays visible without thicker
+/// strokes growing. Values below `cut` are ink.
+///
+/// `source_scale_x`/`source_scale_y` convert the transform's coordinates
+/// into this image's pixels, so a drawing made over the reference at a
+/// different canvas size can be normalized in the reference's frame.
+pub(crate) fn apply_normalize_transform(
+    image: &GrayImage,
+    transform: &NormalizeTransform,
+    source_scale_x: f32,
+    source_scale_y: f32,
+    cut: f32,
+) -> Vec<f32> {
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = (transform.new_width as usize, transform.new_height as usize);
+
+    // Source span of each normalized column and row
+    let spans = |origin: u32, count: usize, source_scale: f32, limit: u32| -> Vec<(f32, f32)> {
+        (0..count)
+            .map(|t| {
+                let start = (origin as f32 + t as f32 / transform.scale) * source_scale;
+                let end = (origin as f32 + (t + 1) as f32 / transform.scale) * source_scale;
+                (start.min(limit as f32), end.min(limit as f32))
+            })
+            .collect()
+    };
+    let columns = spans(transform.min_x, new_width, source_scale_x, width);
+    let rows = spans(transform.min_y, new_height, source_scale_y, height);
+    let overlap = |pixel: u32, (start, end): (f32, f32)| (end.min(pixel as f32 + 1.0) - start.max(pixel as f32)).max(0.0);
+
+    let mut mean = vec![1.0f32; new_width * new_height];
+    let mut darkest = vec![1.0f32; new_width * new_height];
+    for (ty, &row) in rows.iter().enumerate() {
+        for (tx, &column) in columns.iter().enumerate() {
+            let (mut sum, mut weight, mut min) = (0.0f32, 0.0f32, 255u8);
+            for sy in row.0 as u32..(row.1.ceil() as u32).min(height) {
+                let wy = overlap(sy, row);
+                for sx in column.0 as u32..(column.1.ceil() as u32).min(width) {
+                    let w = wy * overlap(sx, column);
+                    if w > 0.0 {
+                        let value = image.get_pixel(sx, sy).0[0];
+                        sum += w * value as f32;
+                        weight += w;
+                        min = min.min(value);
+                    }
+                }
+            }
+            if weight > 0.0 {
+                mean[ty * new_width + tx] = sum / weight / 255.0;
+                darkest[ty * new_width + tx] = min as f32 / 255.0;
+            }
+        }
+    }
+
+    let size = transform.size as usize;
+    let mut output = vec![1.0f32; size * size];
+    for ty in 0..new_height {
+        for tx in 0..new_width {
+            let i = ty * new_width + tx;
+            let inked_neighbour = (ty.saturating_sub(1)..(ty + 2).min(new_height))
+                .any(|ny| (tx.saturating_sub(1)..(tx + 2).min(new_width)).any(|nx| mean[ny * new_width + nx] < cut));
+            let value = if mean[i] >= cut && darkest[i] < cut && !inked_neighbour { darkest[i] } else { mean[i] };
+            output[(transform.y_offset as usize + ty) * size + transform.x_offset as usize + tx] = value;
+        }
+    }
+
+    output
+}
+
+/// Register a drawing to a reference into a fresh buffer
+#[cfg(test)]
+fn register_to_reference(drawn: &[f32], reference: &[f32]) -> Vec<f32> {
+    let mut output = Vec::new();
+    warp_to_registration(drawn, find_registration(drawn, reference), &mut output);
+    output
+}
+
+/// How a normalized drawing is moved onto the reference
+#[derive(Debug, Clone, Copy)]
+struct Registration {
+    drawn_center: (f32, f32),
+    reference_center: (f32, f32),
+    scale: f32,
+    rotation: f32,
+}
+
+impl Registration {
+    /// The same alignment in a frame `factor` times wider
+    fn scaled(self, factor: f32) -> Self {
+        let up = |(x, y): (f32, f32)| ((x + 0.5) * factor - 0.5, (y + 0.5) * factor - 0.5);
+        Self { drawn_center: up(self.drawn_center), reference_center: up(self.reference_center), ..self }
+    }
+}
+
+/// Align a normalized drawing to a normalized reference using image moments
+///
+/// Matches centroid, radius of gyration and (for elongated shapes) principal
+/// axis orientation. Unlike bounding-box centering this is not thrown off
+/// by a single stray mark far from the letter. `None` when either image
+/// has no ink.
+fn find_registration(drawn: &[f32], reference: &[f32]) -> Option<Registration> {
+    let size = frame_side(drawn);
+    let drawn_ink: Vec<f32> = drawn.iter().map(|&v| (1.0 - v).clamp(0.0, 1.0)).collect();
+    let reference_ink: Vec<f32> = reference.iter().map(|&v| (1.0 - v).clamp(0.0, 1.0)).collect();
+
+    let dm = image_moments(&drawn_ink, size, size)?;
+    let rm = image_moments(&reference_ink, size, size)?;
+
+    let drawn_spread = dm.spread();
+    let scale = if drawn_spread > f32::EPSILON {
+        (rm.spread() / drawn_spread).clamp(MIN_REGISTRATION_SCALE, MAX_REGISTRATION_SCALE)
+    } else {
+        1.0
+    };
+
+    let mut rotation = 0.0;
+    if dm.eccentricity() >= MIN_REGISTRATION_ECCENTRICITY
+        && rm.eccentricity() >= MIN_REGISTRATION_ECCENTRICITY
+    {
+        // Principal axes are only defined up to a half turn
+        let mut delta = rm.orientation() - dm.orientation();
+        while delta > std::f32::consts::FRAC_PI_2 {
+            delta -= std::f32::consts::PI;
+        }
+        while delta < -std::f32::consts::FRAC_PI_2 {
+            delta += std::f32::consts::PI;
+        }
+        rotation = delta.clamp(-MAX_REGISTRATION_ROTATION, MAX_REGISTRATION_ROTATION);
+    }
+
+    Some(Registration { drawn_center: (dm.cx, dm.cy), reference_center: (rm.cx, rm.cy), scale, rotation })
+}
+
+/// Resample a normalized drawing by a registration into `output`, a buffer
+/// kept between calls; without one the drawing is copied as is
+fn warp_to_registration(drawn: &[f32], registration: Option<Registration>, output: &mut Vec<f32>) {
+    let size = frame_side(drawn);
+    output.clear();
+    let Some(Registration { drawn_center, reference_center, scale, rotation }) = registration else {
+        output.extend_from_slice(drawn);
+        return;
+    };
+
+    let (sin, cos) = (-rotation).sin_cos();
+    output.resize(size * size, 1.0);
+
+    // Inverse mapping: for each output pixel find its source in the drawing
+    for y in 0..size {
+        for x in 0..size {
+            let ox = (x as f32 - reference_center.0) / scale;
+            let oy = (y as f32 - reference_center.1) / scale;
+            let src_x = ox * cos - oy * sin + drawn_center.0;
+            let src_y = ox * sin + oy * cos + drawn_center.1;
+            output[y * size + x] = sample_bilinear(drawn, size, size, src_x, src_y);
+        }
+    }
+}
+
+/// Bilinear sample of a grayscale buffer, treating outside pixels as white
+fn sample_bilinear(image: &[f32], width: usize, height: usize, x: f32, y: f32) -> f32 {
+    let x0 = x.floor();
+    let y0 = y.floor();
+    let fx = x - x0;
+    let fy = y - y0;
+
+    let pixel = |px: f32, py: f32| -> f32 {
+        if px < 0.0 || py < 0.0 || px >= width as f32 || py >= height as f32 {
+            1.0
+        } else {
+            image[py as usize * width + px as usize]
+        }
+    };
+
+    let top = pixel(x0, y0) * (1.0 - fx) + pixel(x0 + 1.0, y0) * fx;
+    let bottom = pixel(x0, y0 + 1.0) * (1.0 - fx) + pixel(x0 + 1.0, y0 + 1.0) * fx;
+    top * (1.0 - fy) + bottom * fy
+}
+
+/// Normalize line thickness using skeleton extraction
+///
+/// Sanding bridges gaps and prunes spurs by lengths scaled to the image
+/// as a normalized frame.
+pub(crate) fn normalize_line_thickness(binary: &BitImage, target_thickness: u32, apply_sanding: bool) -> BitImage {
+    if binary.is_empty() {
+        return binary.clone();
+    }
+
+    let skeleton = if apply_sanding {
+        let size = binary.width();
+        let mut skel = skeletonize(binary);
+        bridge_gaps(&mut skel, frame_pixels(10, size));
+        prune_branches(&mut skel, frame_pixels(8, size), 0.15);
+        skel
+    } else {
+        skeletonize(binary)
+    };
+
+    redraw_at_thickness(binary, &skeleton, target_thickness)
+}
+
+/// Redraw the skeleton of `binary` at `target_thickness`; a one-pixel target
+/// is the skeleton itself, and an empty skeleton keeps the original ink
+pub(crate) fn redraw_at_thickness(binary: &BitImage, skeleton: &BitImage, target_thickness: u32) -> BitImage {
+    redraw_at_thickness_with(binary, skeleton, target_thickness, &mut Vec::new())
+}
+
+/// `redraw_at_thickness` with a distance buffer kept between calls
+pub(crate) fn redraw_at_thickness_with(binary: &BitImage, skeleton: &BitImage, target_thickness: u32, dist: &mut Vec<f32>) -> BitImage {
+    if target_thickness <= 1 {
+        return skeleton.clone();
+    }
+    // Use distance transform for smooth stroke reconstruction
+    if skeleton.is_empty() {
+        return binary.clone();
+    }
+
+    distance_transform_edt_into(skeleton, dist);
+    let threshold = target_thickness as f32 / 2.0;
+
+    BitImage::from_fn(binary.width(), binary.height(), |x, y| dist[y * binary.width() + x] <= threshold)
+}
+
+/// Calculate coverage score: how much of the reference is covered
+///
+/// Each reference pixel is weighted by the ink intensity of the anti-aliased
+/// reference, so soft glyph edges count less than the stroke core.
+fn calculate_coverage_score(pipeline: &ScoringPipeline) -> f32 {
+    let (drawn, reference) = (&pipeline.drawn, pipeline.reference);
+    let tolerance = 4.0 * frame_scale(drawn.norm.width());
+
+    // Weight each reference pixel by its ink intensity (1.0 = solid core)
+    let ref_weight = |index: usize| (1.0 - reference.processed[index]).clamp(0.0, 1.0);
+    let ref_weight_total: f32 = reference.norm.indices().map(ref_weight).sum();
+    if ref_weight_total <= 0.0 {
+        return 0.0;
+    }
+
+    if drawn.norm.is_empty() {
+        return 0.0;
+    }
+
+    // Sum the weight of reference pixels that are covered (within tolerance of drawn pixels)
+    let covered: f32 = reference.norm.indices()
+        .filter(|&index| drawn.norm_distance[index] <= tolerance)
+        .map(ref_weight)
+        .sum();
+
+    (covered / ref_weight_total).min(1.0)
+}
+
+/// Fraction of the reference skeleton lying near drawn ink
+///
+/// Unlike `calculate_coverage_score` this looks only at the one-pixel
+/// skeleton, so a single thick stroke along part of the letter cannot
+/// account for more than its share of the letter's length.
+fn calculate_skeleton_coverage(pipeline: &ScoringPipeline) -> f32 {
+    let drawn = &pipeline.drawn;
+    let tolerance = 4.0 * frame_scale(drawn.norm.width());
+
+    let ref_skeleton = &pipeline.reference.skeleton;
+
+    let skeleton_pixels = ref_skeleton.count();
+    if skeleton_pixels == 0 || drawn.norm.is_empty() {
+        return 0.0;
+    }
+
+    let covered = ref_skeleton.indices()
+        .filter(|&index| drawn.norm_distance[index] <= tolerance)
+        .count();
+
+    covered as f32 / skeleton_pixels as f32
+}
+
+/// Detect a drawing that is a solid filled region rather than strokes
+///
+/// Looks at the mean stroke width (ink area over skeleton length) and at
+/// how much ink survives erosion, which strips away anything pen-thin.
+fn detect_scribble(drawn: &PreparedDrawing) -> bool {
+    let drawn_binary = &drawn.binary;
+    let area = drawn_binary.count();
+    if area == 0 {
+        return false;
+    }
+
+    let size = drawn_binary.width();
+    let skeleton_length = skeletonize(drawn_binary).count().max(1);
+    let mean_width = area as f32 / skeleton_length as f32 / frame_scale(size);
+
+    let eroded = drawn_binary.erode(frame_pixels(SCRIBBLE_EROSION_ITERATIONS, size));
+    let survival = eroded.count() as f32 / area as f32;
+
+    mean_width >= SCRIBBLE_MIN_MEAN_WIDTH && survival >= SCRIBBLE_MIN_EROSION_SURVIVAL
+}
+
+/// Detect a drawing that is a near pixel-perfect copy of the reference
+///
+/// Compares raw ink (no thickness normalization), so only a copy with the
+/// reference's own stroke width and shape matches: pixel IoU above
+/// `TRACE_MIN_IOU` and mean symmetric distance below `TRACE_MAX_CHAMFER`.
+fn detect_trace(drawn: &[f32], reference: &PreparedReference) -> bool {
+    let size = frame_side(drawn);
+
+    let drawn_binary = BitImage::ink(drawn, size, size);
+    let reference_binary = &reference.binary;
+
+    let intersection = drawn_binary.count_and(reference_binary);
+    let union = drawn_binary.count_or(reference_binary);
+    if union == 0 || (intersection as f32 / union as f32) < TRACE_MIN_IOU {
+        return false;
+    }
+
+    let drawn_dist = distance_transform_edt(&drawn_binary);
+    let reference_dist = &reference.binary_distance;
+    let mean_distance = |mask: &BitImage, dist: &[f32]| {
+        let (total, count) = mask.indices()
+            .fold((0.0, 0usize), |(t, c), index| (t + dist[index], c + 1));
+        if count == 0 { 0.0 } else { total / count as f32 }
+    };
+    let chamfer = (mean_distance(&drawn_binary, reference_dist)
+        + mean_distance(reference_binary, &drawn_dist)) / 2.0;
+
+    chamfer / frame_scale(size) < TRACE_MAX_CHAMFER
+}
+
+/// Detect a drawing that covers the letter by filling the whole area
+///
+/// Compares the raw ink amount against the reference and checks how much
+/// of that ink actually lies on the letter.
+fn detect_canvas_fill(pipeline: &ScoringPipeline) -> bool {
+    let (drawn_binary, reference) = (&pipeline.drawn.binary, pipeline.reference);
+
+    let drawn_ink = drawn_binary.count();
+    let ref_ink = reference.binary.count();
+    if drawn_ink == 0 || ref_ink == 0 {
+        return false;
+    }
+
+    let ink_ratio = drawn_ink as f32 / ref_ink as f32;
+
+    let on_letter = drawn_binary.indices()
+        .filter(|&index| reference.binary_distance[index] <= 3.0 * frame_scale(drawn_binary.width()))
+        .count();
+    let precision = on_letter as f32 / drawn_ink as f32;
+
+    ink_ratio > CANVAS_FILL_INK_RATIO && precision < CANVAS_FILL_MAX_PRECISION
+}
+
+/// Calculate accuracy score: how accurate is the drawing (staying on the lines)
+///
+/// Each drawn pixel earns full credit within `tolerance` pixels of the
+/// reference, with credit falling linearly to zero over the next `falloff`
+/// pixels, so "slightly outside" scores better than "way off".
+fn calculate_accuracy_score(pipeline: &ScoringPipeline, tolerance: f32, falloff: f32) -> f32 {
+    // The drawing is normalized with sanding; the reference was normalized without
+    let (drawn_norm, reference) = (&pipeline.drawn.norm, pipeline.reference);
+
+    let drawn_pixels = drawn_norm.count();
+    if drawn_pixels == 0 {
+        return 0.0;
+    }
+
+    if reference.norm.is_empty() {
+        return 0.0;
+    }
+
+    // Distance from each pixel to the nearest reference pixel
+    let credit: f32 = drawn_norm.indices()
+        .map(|index| distance_credit(reference.norm_distance[index], tolerance, falloff))
+        .sum();
+
+    (credit / drawn_pixels as f32).min(1.0)
+}
+
+/// Calculate tracing accuracy: how well the drawn strokes stay inside the glyph
+///
+/// Works on the centerline of the drawing, so pen width does not matter,
+/// and measures distance to the filled glyph outline rather than to its
+/// skeleton. Centerline pixels inside the outline earn full credit; outside
+/// it, credit follows `distance_credit`.
+fn calculate_outline_accuracy(pipeline: &ScoringPipeline, tolerance: f32, falloff: f32) -> f32 {
+    let (drawn_centerline, reference) = (&pipeline.drawn.centerline, pipeline.reference);
+
+    let centerline_pixels = drawn_centerline.count();
+    if centerline_pixels == 0 || reference.binary.is_empty() {
+        return 0.0;
+    }
+
+    // Zero inside the outline, distance to the outline outside it
+    let credit: f32 = drawn_centerline.indices()
+        .map(|index| distance_credit(reference.binary_distance[index], tolerance, falloff))
+        .sum();
+
+    (credit / centerline_pixels as f32).min(1.0)
+}
+
+/// Credit for a pixel at `dist` from the target: 1 inside the tolerance,
+/// then a linear ramp down to 0 over `falloff` pixels
+pub(crate) fn distance_credit(dist: f32, tolerance: f32, falloff: f32) -> f32 {
+    if dist <= tolerance {
+        1.0
+    } else if falloff <= 0.0 {
+        0.0
+    } else {
+        (1.0 - (dist - tolerance) / falloff).max(0.0)
+    }
+}
+
+/// Calculate stroke similarity using IoU and Chamfer distance
+fn calculate_stroke_similarity(pipeline: &ScoringPipeline) -> f32 {
+    let (drawn, reference) = (&pipeline.drawn, pipeline.reference);
+    let (drawn_norm, ref_norm) = (&drawn.norm, &reference.norm);
+
+    if drawn_norm.is_empty() || ref_norm.is_empty() {
+        return 0.0;
+    }
+
+    // IoU (40% weight)
+    let intersection = drawn_norm.count_and(ref_norm);
+    let union = drawn_norm.count_or(ref_norm);
+    let iou = intersection as f32 / (union as f32 + 1e-8);
+
+    // Directional Chamfer distance (60% weight)
+    let drawn_orientation = pipeline.drawn_orientation();
+
+    // Average distance from drawn to reference
+    let drawn_to_ref = directional_chamfer(
+        drawn_norm, drawn_orientation, ref_norm, &reference.norm_orientation, &reference.norm_distance,
+    );
+
+    // Average distance from reference to drawn
+    let ref_to_drawn = directional_chamfer(
+        ref_norm, &reference.norm_orientation, drawn_norm, drawn_orientation, &drawn.norm_distance,
+    );
+
+    // Symmetric Chamfer distance
+    let chamfer_dist = (drawn_to_ref + ref_to_drawn) / 2.0;
+
+    // Convert to similarity score, in default-frame pixels
+    let max_dist = 20.0;
+    let chamfer_score = (-chamfer_dist / frame_scale(drawn_norm.width()) / (max_dist / 3.0)).exp();
+
+    // Combine
+    let similarity = iou * 0.4 + chamfer_score * 0.6;
+    similarity.clamp(0.0, 1.0)
+}
+
+/// Calculate similarity by shape context matching of the two skeletons
+fn calculate_shape_context_similarity(pipeline: &ScoringPipeline) -> f32 {
+    shape_context_similarity(&pipeline.drawn.centerline, &pipeline.reference.skeleton, SHAPE_CONTEXT_POINTS)
+}
+
+/// Calculate structural similarity by comparing skeleton graphs
+fn calculate_structure_similarity(pipeline: &ScoringPipeline) -> f32 {
+    graph_similarity(&pipeline.drawn.graph, &pipeline.reference.graph)
+}
+
+/// Estimate legibility of a drawing without a reference
+///
+/// Combines stroke smoothness (turning per unit length along the skeleton),
+/// stroke width consistency (variation of the distance from the skeleton to
+/// the stroke edge), and clean topology (few fragments, spurs and tangles).
+fn calculate_legibility(drawn: &PreparedDrawing) -> f32 {
+    let (drawn_binary, skeleton, graph) = (&drawn.binary, &drawn.centerline, &drawn.graph);
+    if drawn_binary.is_empty() {
+        return 0.0;
+    }
+
+    let length = graph.total_length();
+    if length <= 0.0 {
+        return 0.0;
+    }
+
+    // Smoothness: a circle of radius 40 turns ~0.025 rad per default-frame pixel
+    let curvature: f32 = graph.edges.iter().map(|e| e.curvature).sum();
+    let smoothness = (-(curvature * frame_scale(drawn_binary.width()) / length) * 4.0).exp();
+
+    // Width consistency from the half-width measured at skeleton pixels
+    let to_background = distance_transform_edt(&drawn_binary.inverted());
+    let widths: Vec<f32> = skeleton.indices().map(|index| to_background[index]).collect();
+    let width_consistency = if widths.is_empty() {
+        0.0
+    } else {
+        let mean = widths.iter().sum::<f32>() / widths.len() as f32;
+        let variance = widths.iter().map(|w| (w - mean).powi(2)).sum::<f32>() / widths.len() as f32;
+        let cv = if mean > 0.0 { variance.sqrt() / mean } else { 1.0 };
+        (1.0 - cv).clamp(0.0, 1.0)
+    };
+
+    // Topology: letters have at most a couple of pieces and a handful of ends
+    let excess = graph.components.saturating_sub(2)
+        + graph.endpoint_count().saturating_sub(6)
+        + graph.junction_count().saturating_sub(4);
+    let topology = 1.0 / (1.0 + 0.2 * excess as f32);
+
+    (smoothness * 0.4 + width_consistency * 0.3 + topology * 0.3).clamp(0.0, 1.0)
+}
+
+/// Calculate global shape similarity from Hu moment invariants
+///
+/// Compares the invariants by relative difference, so it is cheap and
+/// insensitive to where individual strokes land, catching "right strokes,
+/// wrong overall shape".
+fn calculate_moment_similarity(drawn: &[f32], reference: &[f32]) -> f32 {
+    let size = frame_side(drawn);
+
+    let drawn_ink: Vec<f32> = drawn.iter().map(|&v| (1.0 - v).clamp(0.0, 1.0)).collect();
+    let reference_ink: Vec<f32> = reference.iter().map(|&v| (1.0 - v).clamp(0.0, 1.0)).collect();
+
+    let (Some(a), Some(b)) = (
+        hu_moments(&drawn_ink, size, size),
+        hu_moments(&reference_ink, size, size),
+    ) else {
+        return 0.0;
+    };
+
+    // Take roots matching each invariant's degree in the normalized moments
+    // so all seven live on a comparable scale (signs are preserved, which
+    // keeps the seventh invariant sensitive to mirroring)
+    const DEGREES: [f64; 7] = [1.0, 2.0, 2.0, 2.0, 4.0, 3.0, 4.0];
+    let root = |h: f64, degree: f64| -> f64 { h.signum() * h.abs().powf(1.0 / degree) };
+
+    let mut difference = 0.0f64;
+    let mut magnitude = 0.0f64;
+    for i in 0..7 {
+        let fa = root(a[i], DEGREES[i]);
+        let fb = root(b[i], DEGREES[i]);
+        difference += (fa - fb).abs();
+        magnitude += fa.abs().max(fb.abs());
+    }
+
+    if magnitude <= 0.0 {
+        return 0.0;
+    }
+
+    ((1.0 - difference / magnitude) as f32).clamp(0.0, 1.0)
+}
+
+/// A break between parts of the drawing that the reference joins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeGap {
+    /// Midpoint of the gap as fractions (0-1) of the normalized frame
+    pub x: f32,
+    pub y: f32,
+    /// Gap length in pixels of the default 128-pixel normalized frame
+    pub length: f32,
+    /// 3x3 grid cell holding the gap, named as in `worst_region`
+    pub region: String,
+}
+
+/// Find breaks that split the drawing into more parts than the reference
+///
+/// Unlike the gap bridging used before comparison, this reports the breaks:
+/// the shortest links that would join the extra parts, closest first.
+fn detect_gaps(drawn: &[f32], reference: &[f32]) -> Vec<StrokeGap> {
+    let size = frame_side(drawn);
+    let parts_of = |image: &[f32]| {
+        let mut parts = connected_components(&BitImage::ink(image, size, size));
+        parts.retain(|part| part.area >= frame_area(GAP_MIN_PART_AREA, size));
+        parts
+    };
+
+    // Only the largest parts are linked; a reference with as many parts as
+    // that leaves none to call extra
+    let mut parts = parts_of(drawn);
+    parts.sort_by_key(|part| std::cmp::Reverse(part.area));
+    parts.truncate(GAP_MAX_PARTS);
+    let reference_parts = parts_of(reference).len();
+    if parts.len() <= reference_parts {
+        return Vec::new();
+    }
+
+    // Closest pixel pair between every two parts
+    let mut links = Vec::new();
+    for (a, part) in parts.iter().enumerate() {
+        let mut mask = BitImage::new(size, size);
+        for &idx in &part.pixels {
+            mask.set_at(idx, true);
+        }
+        let dist = distance_transform_edt(&mask);
+        for (b, other) in parts.iter().enumerate().skip(a + 1) {
+            let to = *other.pixels.iter().min_by(|&&i, &&j| dist[i].total_cmp(&dist[j])).unwrap();
+            let (tx, ty) = ((to % size) as f32, (to / size) as f32);
+            let from = *part.pixels.iter()
+                .min_by(|&&i, &&j| {
+                    let d = |idx: usize| ((idx % size) as f32 - tx).powi(2) + ((idx / size) as f32 - ty).powi(2);
+                    d(i).total_cmp(&d(j))
+                })
+                .unwrap();
+            links.push((dist[to], a, b, from, to));
+        }
+    }
+    links.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // Join parts along the shortest links until the reference count is reached
+    let mut group: Vec<usize> = (0..parts.len()).collect();
+    let mut remaining = parts.len() - reference_parts;
+    let mut gaps = Vec::new();
+    for (length, a, b, from, to) in links {
+        let length = length / frame_scale(size);
+        if remaining == 0 || length > GAP_MAX_LENGTH {
+            break;
+        }
+        let (ga, gb) = (group[a], group[b]);
+        if ga == gb {
+            continue;
+        }
+        for g in group.iter_mut() {
+            if *g == gb {
+                *g = ga;
+            }
+        }
+        remaining -= 1;
+
+        let mx = ((from % size) + (to % size)) as f32 / 2.0;
+        let my = ((from / size) + (to / size)) as f32 / 2.0;
+        let cx = ((mx as usize) * 3 / size).min(2);
+        let cy = ((my as usize) * 3 / size).min(2);
+        gaps.push(StrokeGap {
+            x: mx / size as f32,
+            y: my / size as f32,
+            length: (length * 10.0).round() / 10.0,
+            region: REGION_LABELS[cy * 3 + cx].to_string(),
+        });
+    }
+    gaps
+}
+
+/// Check each loop of the reference for a matching closed loop in the drawing
+///
+/// Loops are the holes of the reference glyph, ordered top to bottom and
+/// then left to right. Returns whether each was closed and its region.
+fn detect_loop_closure(drawn: &[f32], reference: &[f32]) -> Vec<(bool, &'static str)> {
+    let size = frame_side(drawn);
+    let holes_of = |binary: &BitImage| {
+        let mut holes = enclosed_regions(binary);
+        holes.retain(|hole| hole.area >= frame_area(LOOP_MIN_HOLE_AREA, size));
+        holes
+    };
+
+    let mut loops = holes_of(&BitImage::ink(reference, size, size));
+    let centroid = |hole: &Component| {
+        let (sx, sy) = hole.pixels.iter().fold((0, 0), |(sx, sy), &idx| (sx + idx % size, sy + idx / size));
+        (sx / hole.area, sy / hole.area)
+    };
+    loops.sort_by_key(|hole| {
+        let (x, y) = centroid(hole);
+        (y * 3 / size, x)
+    });
+    if loops.is_empty() {
+        return Vec::new();
+    }
+
+    // Slight dilation so a hairline seam between pen strokes still closes
+    let drawn_holes = holes_of(&BitImage::ink(drawn, size, size).dilate(frame_pixels(1, size)));
+    let mut hole_label = vec![usize::MAX; size * size];
+    for (label, hole) in drawn_holes.iter().enumerate() {
+        for &idx in &hole.pixels {
+            hole_label[idx] = label;
+        }
+    }
+
+    let mut used = vec![false; drawn_holes.len()];
+    loops.iter()
+        .map(|expected| {
+            let mut overlap = vec![0usize; drawn_holes.len()];
+            for &idx in &expected.pixels {
+                if hole_label[idx] != usize::MAX {
+                    overlap[hole_label[idx]] += 1;
+                }
+            }
+            let best = (0..drawn_holes.len())
+                .filter(|&label| !used[label])
+                .filter(|&label| {
+                    let smaller = expected.area.min(drawn_holes[label].area) as f32;
+                    overlap[label] as f32 >= LOOP_MIN_OVERLAP * smaller
+                })
+                .max_by_key(|&label| overlap[label]);
+            if let Some(label) = best {
+                used[label] = true;
+            }
+
+            let (x, y) = centroid(expected);
+            let cell = (y * 3 / size).min(2) * 3 + (x * 3 / size).min(2);
+            (best.is_some(), REGION_LABELS[cell])
+        })
+        .collect()
+}
+
+/// Worst-matching region of a drawing
+struct WorstRegion {
+    hausdorff_95: f32,
+    label: Option<String>,
+}
+
+/// Find the region where drawing and reference disagree most
+///
+/// Computes the 95th percentile of the symmetric point-to-set distances
+/// (a robust Hausdorff distance) and, when it is large, names the 3x3 grid
+/// cell holding most of the distances above that percentile. The averaged
+/// chamfer distance hides such localized errors.
+fn calculate_worst_region(pipeline: &ScoringPipeline) -> WorstRegion {
+    let (drawn_norm, drawn_dist) = (&pipeline.drawn.norm, &pipeline.drawn.norm_distance);
+    let size = drawn_norm.width();
+    let (ref_norm, ref_dist) = (&pipeline.reference.norm, &pipeline.reference.norm_distance);
+
+    if drawn_norm.is_empty() || ref_norm.is_empty() {
+        return WorstRegion { hausdorff_95: 0.0, label: None };
+    }
+
+    // (pixel index, distance) for both directions
+    let samples: Vec<(usize, f32)> = drawn_norm.indices().map(|i| (i, ref_dist[i]))
+        .chain(ref_norm.indices().map(|i| (i, drawn_dist[i])))
+        .collect();
+
+    let mut distances: Vec<f32> = samples.iter().map(|&(_, d)| d).collect();
+    distances.sort_by(|a, b| a.total_cmp(b));
+    let rank = ((distances.len() - 1) as f32 * HAUSDORFF_PERCENTILE).round() as usize;
+    let cutoff = distances[rank];
+    // Reported in default-frame pixels, like the threshold
+    let hausdorff_95 = cutoff / frame_scale(size);
+
+    if hausdorff_95 < WORST_REGION_MIN_DISTANCE {
+        return WorstRegion { hausdorff_95, label: None };
+    }
+
+    let mut cell_error = [0.0f32; 9];
+    for &(i, d) in &samples {
+        if d >= cutoff {
+            let cx = ((i % size) * 3 / size).min(2);
+            let cy = ((i / size) * 3 / size).min(2);
+            cell_error[cy * 3 + cx] += d;
+        }
+    }
+
+    let worst_cell = cell_error.iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(4);
+
+    WorstRegion {
+        hausdorff_95,
+        label: Some(REGION_LABELS[worst_cell].to_string()),
+    }
+}
+
+/// Calculate structural similarity (SSIM) between the normalized images
+///
+/// SSIM is averaged only over windows touching ink in either image, since
+/// the shared white background would otherwise dominate the mean.
+fn calculate_ssim_similarity(drawn: &[f32], reference: &[f32]) -> f32 {
+    let size = frame_side(drawn) as i32;
+    let r = frame_pixels(SSIM_RADIUS, size as usize) as i32;
+
+    let mut sum = 0.0f32;
+    let mut count = 0u32;
+
+    for y in 0..size {
+        for x in 0..size {
+            let mut n = 0.0f32;
+            let mut sum_d = 0.0f32;
+            let mut sum_r = 0.0f32;
+            let mut sum_dd = 0.0f32;
+            let mut sum_rr = 0.0f32;
+            let mut sum_dr = 0.0f32;
+            let mut has_ink = false;
+
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    let nx = x + dx;
+                    let ny = y + dy;
+                    if nx < 0 || ny < 0 || nx >= size || ny >= size {
+                        continue;
+                    }
+                    let idx = (ny * size + nx) as usize;
+                    let d = drawn[idx];
+                    let rv = reference[idx];
+                    has_ink |= d < 0.5 || rv < 0.5;
+                    n += 1.0;
+                    sum_d += d;
+                    sum_r += rv;
+                    sum_dd += d * d;
+                    sum_rr += rv * rv;
+                    sum_dr += d * rv;
+                }
+            }
+
+            if !has_ink {
+                continue;
+            }
+
+            let mean_d = sum_d / n;
+            let mean_r = sum_r / n;
+            let var_d = (sum_dd / n - mean_d * mean_d).max(0.0);
+            let var_r = (sum_rr / n - mean_r * mean_r).max(0.0);
+            let covariance = sum_dr / n - mean_d * mean_r;
+
+            let ssim = ((2.0 * mean_d * mean_r + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mean_d * mean_d + mean_r * mean_r + SSIM_C1) * (var_d + var_r + SSIM_C2));
+
+            sum += ssim;
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return 0.0;
+    }
+
+    (sum / count as f32).clamp(0.0, 1.0)
+}
+
+/// Average oriented distance from each `source` pixel to the `target` mask
+///
+/// The cost of matching two pixels is their distance plus a penalty that
+/// grows as their stroke orientations diverge, so a perpendicular stroke
+/// passing through the target (a plus sign against an X) no longer counts
+/// as a good match. Pixels with no target within the search window fall
+/// back to their plain distance plus the full penalty.
+fn directional_chamfer(
+    source: &BitImage,
+    source_orientation: &[f32],
+    target: &BitImage,
+    target_orientation: &[f32],
+    target_dist: &[f32],
+) -> f32 {
+    let size = source.width();
+    let r = frame_pixels(ORIENTATION_SEARCH_RADIUS, size) as i32;
+    let penalty = ORIENTATION_PENALTY * frame_scale(size);
+    let mut sum = 0.0f32;
+    let mut count = 0u32;
+
+    for (x, y) in source.ones() {
+        let (x, y) = (x as i32, y as i32);
+        let idx = y as usize * size + x as usize;
+
+        let mut best = target_dist[idx] + penalty;
+        for dy in -r..=r {
+            for dx in -r..=r {
+                let nx = x + dx;
+                let ny = y + dy;
+                if !target.get_signed(nx, ny) {
+                    continue;
+                }
+                let nidx = ny as usize * size + nx as usize;
+
+                let dist = ((dx * dx + dy * dy) as f32).sqrt();
+                let alignment = (source_orientation[idx] - target_orientation[nidx]).cos().abs();
+                let cost = dist + penalty * (1.0 - alignment);
+                if cost < best {
+                    best = cost;
+                }
+            }
+        }
+
+        sum += best;
+        count += 1;
+    }
+
+    if count > 0 {
+        sum / count as f32
+    } else {
+        0.0
+    }
+}
+
+fn get_star_rating(score: u8, thresholds: &StarThresholds) -> (u8, String) {
+    if score >= thresholds.five {
+        (5, "Amazing! Perfect!".to_string())
+    } else if score >= thresholds.four {
+        (4, "Great job!".to_string())
+    } else if score >= thresholds.three {
+        (3, "Good work!".to_string())
+    } else if score >= thresholds.two {
+        (2, "Nice try!".to_string())
+    } else {
+        (1, "Keep practicing!".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_frames::{bar, blank, erase, fill, ring, square_ring};
+    use crate::image_ops::{ThinningAlgorithm, ThinningLimits};
+
+    fn test_font() -> Font {
+        Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap()
+    }
+
+    /// The test font's glyph for `c`, unvaried, on a 400-pixel canvas
+    fn reference_gray(c: char) -> GrayImage {
+        generate_reference_gray(&ReferenceVariation::identity(c), &test_font(), &HashMap::new(), 400).unwrap()
+    }
+
+    /// `reference_gray` as PNG bytes, as a drawing is passed in
+    fn reference_png(c: char) -> Vec<u8> {
+        encode_grayscale_to_png(&reference_gray(c)).unwrap()
+    }
+
+    #[test]
+    fn test_get_star_rating_5_stars() {
+        let (stars, feedback) = get_star_rating(100, &StarThresholds::default());
+        assert_eq!(stars, 5);
+        assert_eq!(feedback, "Amazing! Perfect!");
+
+        let (stars, feedback) = get_star_rating(80, &StarThresholds::default());
+        assert_eq!(stars, 5);
+        assert_eq!(feedback, "Amazing! Perfect!");
+    }
+
+    #[test]
+    fn test_get_star_rating_4_stars() {
+        let (stars, feedback) = get_star_rating(79, &StarThresholds::default());
+        assert_eq!(stars, 4);
+        assert_eq!(feedback, "Great job!");
+
+        let (stars, feedback) = get_star_rating(65, &StarThresholds::default());
+        assert_eq!(stars, 4);
+        assert_eq!(feedback, "Great job!");
+    }
+
+    #[test]
+    fn test_get_star_rating_3_stars() {
+        let (stars, feedback) = get_star_rating(64, &StarThresholds::default());
+        assert_eq!(stars, 3);
+        assert_eq!(feedback, "Good work!");
+
+        let (stars, feedback) = get_star_rating(50, &StarThresholds::default());
+        assert_eq!(stars, 3);
+        assert_eq!(feedback, "Good work!");
+    }
+
+    #[test]
+    fn test_get_star_rating_2_stars() {
+        let (stars, feedback) = get_star_rating(49, &StarThresholds::default());
+        assert_eq!(stars, 2);
+        assert_eq!(feedback, "Nice try!");
+
+        let (stars, feedback) = get_star_rating(30, &StarThresholds::default());
+        assert_eq!(stars, 2);
+        assert_eq!(feedback, "Nice try!");
+    }
+
+    #[test]
+    fn test_get_star_rating_1_star() {
+        let (stars, feedback) = get_star_rating(29, &StarThresholds::default());
+        assert_eq!(stars, 1);
+        assert_eq!(feedback, "Keep practicing!");
+
+        let (stars, feedback) = get_star_rating(0, &StarThresholds::default());
+        assert_eq!(stars, 1);
+        assert_eq!(feedback, "Keep practicing!");
+    }
+
+    #[test]
+    fn test_get_star_rating_custom_thresholds() {
+        let thresholds = StarThresholds { five: 70, four: 55, three: 40, two: 20 };
+        assert_eq!(get_star_rating(72, &thresholds).0, 5);
+        assert_eq!(get_star_rating(54, &thresholds).0, 3);
+        assert_eq!(get_star_rating(20, &thresholds).0, 2);
+        assert_eq!(get_star_rating(thresholds.one_star_max(), &thresholds).0, 1);
+    }
+
+    #[test]
+    fn test_extract_and_center_character_empty() {
+        // All white image (no drawing)
+        let img = GrayImage::from_pixel(100, 100, Luma([255u8]));
+        let result = extract_and_center_character(&img, NORMALIZE_PADDING, ThresholdMethod::Fixed, TARGET_SIZE);
+
+        // Should return all 1.0 (white)
+        assert_eq!(result.len(), (TARGET_SIZE * TARGET_SIZE) as usize);
+        assert!(result.iter().all(|&v| v == 1.0));
+    }
+
+    #[test]
+    fn test_extract_and_center_character_with_content() {
+        // Create image with a black square in the center
+        let mut img = GrayImage::from_pixel(100, 100, Luma([255u8]));
+        for y in 40..60 {
+            for x in 40..60 {
+                img.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+
+        let result = extract_and_center_character(&img, NORMALIZE_PADDING, ThresholdMethod::Fixed, TARGET_SIZE);
+
+        // Should have some dark pixels (< 0.5)
+        let dark_count = result.iter().filter(|&&v| v < 0.5).count();
+        assert!(dark_count > 0);
+    }
+
+    #[test]
+    fn test_extract_and_center_character_keeps_thin_lines() {
+        // An L drawn with a 2-pixel pen on a large canvas
+        let mut img = GrayImage::from_pixel(1500, 1500, Luma([255u8]));
+        for y in 200..1301 {
+            for x in 200..202 {
+                img.put_pixel(x, y, Luma([0u8]));
+                img.put_pixel(y - 200 + 200, 1299 + x - 200, Luma([0u8]));
+            }
+        }
+
+        let result = extract_and_center_character(&img, NORMALIZE_PADDING, ThresholdMethod::Fixed, TARGET_SIZE);
+        let ink = BitImage::ink(&result, TARGET_SIZE as usize, TARGET_SIZE as usize);
+        let rows: Vec<usize> = (0..TARGET_SIZE as usize).filter(|&y| (0..TARGET_SIZE as usize).any(|x| ink.get(x, y))).collect();
+        let columns: Vec<usize> = (0..TARGET_SIZE as usize).filter(|&x| (0..TARGET_SIZE as usize).any(|y| ink.get(x, y))).collect();
+        // Both strokes unbroken, and no thicker than a couple of pixels
+        assert_eq!(rows.len(), rows[rows.len() - 1] - rows[0] + 1);
+        assert_eq!(columns.len(), columns[columns.len() - 1] - columns[0] + 1);
+        assert!(rows.len() > 100 && columns.len() > 100, "{} rows, {} columns", rows.len(), columns.len());
+        assert!(ink.count() < (rows.len() + columns.len()) * 2, "{} ink pixels", ink.count());
+    }
+
+    #[test]
+    fn test_register_to_reference_translation() {
+        // Same square, offset in the drawing
+        let size = TARGET_SIZE as usize;
+        let mut reference = vec![1.0f32; size * size];
+        let mut drawn = vec![1.0f32; size * size];
+        for y in 50..70 {
+            for x in 50..70 {
+                reference[y * size + x] = 0.0;
+                drawn[(y - 20) * size + (x + 15)] = 0.0;
+            }
+        }
+
+        let registered = register_to_reference(&drawn, &reference);
+
+        let mismatched = registered.iter()
+            .zip(reference.iter())
+            .filter(|(&d, &r)| (d < 0.5) != (r < 0.5))
+            .count();
+        assert!(mismatched < 10);
+    }
+
+    #[test]
+    fn test_register_to_reference_empty_drawing() {
+        let size = TARGET_SIZE as usize;
+        let drawn = vec![1.0f32; size * size];
+        let reference: Vec<f32> = (0..size * size)
+            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
+            .collect();
+
+        let registered = register_to_reference(&drawn, &reference);
+
+        assert_eq!(registered, drawn);
+    }
+
+    #[test]
+    fn test_normalize_line_thickness_empty() {
+        let binary = BitImage::new(10, 10);
+        let result = normalize_line_thickness(&binary, 5, false);
+
+        // Should remain empty
+        assert!(result.is_empty());
+    }
+
+    #[test]
+    fn test_normalize_line_thickness_with_content() {
+        // Create a thick horizontal line
+        let binary = BitImage::from_fn(10, 10, |x, y| (2..8).contains(&x) && (3..7).contains(&y));
+
+        let result = normalize_line_thickness(&binary, 3, false);
+
+        // Should have fewer true pixels than original (thinned)
+        let original_count = binary.count();
+        let result_count = result.count();
+
+        // The line should be thinner but still present
+        assert!(result_count > 0);
+        assert!(result_count <= original_count);
+    }
+
+    #[test]
+    fn test_calculate_coverage_score_perfect() {
+        // Identical images should give high coverage
+        let image: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
+            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
+            .collect();
+
+        let score = calculate_coverage_score(&ScoringPipeline::new(&image, &PreparedReference::new(&image)));
+
+        // Should be very high (close to 1.0)
+        assert!(score > 0.9);
+    }
+
+    #[test]
+    fn test_calculate_coverage_score_empty_drawn() {
+        let drawn: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize]; // all white
+        let reference: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
+            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
+            .collect();
+
+        let score = calculate_coverage_score(&ScoringPipeline::new(&drawn, &PreparedReference::new(&reference)));
+
+        // Should be 0 (nothing drawn)
+        assert_eq!(score, 0.0);
+    }
+
+    #[test]
+    fn test_calculate_coverage_score_weights_faint_reference() {
+        // Reference has a solid bar and an equally sized faint bar
+        let size = TARGET_SIZE as usize;
+        let mut reference = vec![1.0f32; size * size];
+        let mut drawn = vec![1.0f32; size * size];
+        for y in 20..110 {
+            for x in 30..40 {
+                reference[y * size + x] = 0.0;
+                drawn[y * size + x] = 0.0;
+            }
+            for x in 90..100 {
+                reference[y * size + x] = 0.45;
+            }
+        }
+
+        let score = calculate_coverage_score(&ScoringPipeline::new(&drawn, &PreparedReference::new(&reference)));
+
+        // Covering only the solid bar counts for more than half
+        assert!(score > 0.6);
+        assert!(score < 0.9);
+    }
+
+    #[test]
+    fn test_calculate_skeleton_coverage_partial() {
+        // Reference is an L; the drawing is only its vertical arm
+        let size = TARGET_SIZE as usize;
+        let mut reference = vec![1.0f32; size * size];
+        let mut drawn = vec![1.0f32; size * size];
+        for i in 20..108 {
+            for t in 0..5 {
+                reference[i * size + 20 + t] = 0.0;
+                reference[(103 + t) * size + i] = 0.0;
+                drawn[i * size + 20 + t] = 0.0;
+            }
+        }
+
+        let full = calculate_skeleton_coverage(&ScoringPipeline::new(&reference, &PreparedReference::new(&reference)));
+        let partial = calculate_skeleton_coverage(&ScoringPipeline::new(&drawn, &PreparedReference::new(&reference)));
+
+        assert!(full > 0.95);
+        assert!(partial > 0.3 && partial < 0.7, "partial {}", partial);
+    }
+
+    #[test]
+    fn test_detect_scribble() {
+        let blob = bar(30..98, 13..115);
+        // An L drawn with a pen
+        let mut letter = bar(30..40, 13..115);
+        fill(&mut letter, 30..98, 105..115);
+
+        assert!(detect_scribble(&PreparedDrawing::new(&blob)));
+        assert!(!detect_scribble(&PreparedDrawing::new(&letter)));
+        assert!(!detect_scribble(&PreparedDrawing::new(&blank())));
+    }
+
+    #[test]
+    fn test_auto_contrast() {
+        // Light-gray stroke from a passive stylus
+        let mut faint = GrayImage::from_pixel(100, 100, Luma([255u8]));
+        for y in 20..80 {
+            for x in 45..55 {
+                faint.put_pixel(x, y, Luma([220u8]));
+            }
+        }
+        let (stretched, gain) = auto_contrast(&faint);
+        assert!((gain - 255.0 / 35.0).abs() < 0.01, "gain {}", gain);
+        assert_eq!(stretched.get_pixel(50, 50).0[0], 0);
+        assert_eq!(stretched.get_pixel(10, 10).0[0], 255);
+
+        // Dark ink and blank canvases are left alone
+        let mut dark = faint.clone();
+        for y in 20..80 {
+            for x in 45..55 {
+                dark.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+        assert_eq!(auto_contrast(&dark), (dark.clone(), 1.0));
+        let blank = GrayImage::from_pixel(100, 100, Luma([250u8]));
+        assert_eq!(auto_contrast(&blank).1, 1.0);
+    }
+
+    #[test]
+    fn test_oversized_images_are_rejected() {
+        let png = encode_grayscale_to_png(&GrayImage::from_pixel(300, 200, Luma([255u8]))).unwrap();
+        assert!(decode_image(&png, &InputLimits::default()).is_ok());
+
+        let narrow = InputLimits { max_width: 299, ..Default::default() };
+        assert_eq!(decode_image(&png, &narrow).unwrap_err(), "Image is 300x200 pixels, over the 299x4096 limit");
+        let short = InputLimits { max_height: 100, ..Default::default() };
+        assert!(decode_image(&png, &short).is_err());
+        let small = InputLimits { max_bytes: png.len() - 1, ..Default::default() };
+        assert!(decode_image(&png, &small).unwrap_err().contains("byte limit"));
+
+        let font = &test_font();
+        let options = ScoringOptions { input_limits: narrow, ..Default::default() };
+        assert!(matches!(score_drawing_internal(&png, 'L', font, None, &options), Err(ScoringError::Invalid(_))));
+    }
+
+    #[test]
+    fn test_thinning_limits_from_options() {
+        // Without a thinning pass the drawing's skeleton is its whole
+        // mask; the cached reference is thinned in full either way
+        let font = &test_font();
+        let png = reference_png('a');
+        let score = |options: &ScoringOptions| score_drawing_internal(&png, 'a', font, None, options).unwrap().result;
+        let unthinned = ScoringOptions {
+            input_limits: InputLimits { thinning: ThinningLimits { max_iterations: 0, ..Default::default() }, ..Default::default() },
+            ..Default::default()
+        };
+        let default = score(&ScoringOptions::default());
+        assert!(score(&unthinned).structure < default.structure);
+        assert_eq!(score(&ScoringOptions::default()).structure, default.structure);
+    }
+
+    #[test]
+    fn test_jpeg_and_webp_drawings() {
+        let font = &test_font();
+        let options = ScoringOptions::default();
+        let png = generate_reference_image_internal('k', font, 300, &options).unwrap();
+        let image = decode_image(&png, &InputLimits::default()).unwrap();
+        let encode = |format: ImageFormat| {
+            let mut bytes = Vec::new();
+            image.write_to(&mut Cursor::new(&mut bytes), format).map(|_| bytes)
+        };
+        let score = |bytes: &[u8]| score_drawing_internal(bytes, 'k', font, None, &options).map(|scored| scored.result.score);
+        let expected = score(&png).unwrap();
+
+        // Without their features the formats are still recognized by header
+        if cfg!(feature = "jpeg") {
+            assert!(score(&encode(ImageFormat::Jpeg).unwrap()).unwrap().abs_diff(expected) <= 3);
+        } else {
+            let header = [0xff, 0xd8, 0xff, 0xe0, 0, 0x10, b'J', b'F', b'I', b'F', 0];
+            assert_eq!(decode_image(&header, &InputLimits::default()).unwrap_err(), "JPEG drawings need the `jpeg` feature");
+        }
+        if cfg!(feature = "webp") {
+            assert_eq!(score(&encode(ImageFormat::WebP).unwrap()).unwrap(), expected);
+        } else {
+            let header = b"RIFF\x10\0\0\0WEBPVP8L";
+            assert_eq!(decode_image(header, &InputLimits::default()).unwrap_err(), "WebP drawings need the `webp` feature");
+        }
+    }
+
+    #[test]
+    fn test_pastel_drawing_is_scored() {
+        // Ink at a gray level past mid-gray but short of the fixed threshold
+        let font = &test_font();
+        let reference = reference_gray('L');
+        let pastel = ImageBuffer::from_fn(400, 400, |x, y| {
+            Luma([170 + (reference.get_pixel(x, y).0[0] as u32 * 85 / 255) as u8])
+        });
+        let png = encode_grayscale_to_png(&pastel).unwrap();
+
+        let otsu = ScoringOptions::default();
+        let result = score_drawing_internal(&png, 'L', font, None, &otsu).unwrap().result;
+        assert!(result.score >= 60, "score {}", result.score);
+
+        let fixed = ScoringOptions { threshold: ThresholdMethod::Fixed, ..Default::default() };
+        let result = score_drawing_internal(&png, 'L', font, None, &fixed).unwrap().result;
+        assert!(result.score < 30, "score {}", result.score);
+    }
+
+    #[test]
+    fn test_transparent_background_is_paper() {
+        // Dark ink on transparent black, as a canvas without a fill exports
+        let font = &test_font();
+        let reference = reference_gray('L');
+        let canvas = image::RgbaImage::from_fn(400, 400, |x, y| image::Rgba([20, 20, 20, 255 - reference.get_pixel(x, y).0[0]]));
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png).write_image(canvas.as_raw(), 400, 400, image::ExtendedColorType::Rgba8).unwrap();
+
+        let flattened = flatten_alpha(decode_image(&png, &InputLimits::default()).unwrap(), WHITE).to_luma8();
+        assert_eq!(flattened.get_pixel(0, 0).0[0], 255);
+
+        let result = score_drawing_internal(&png, 'L', font, None, &ScoringOptions::default()).unwrap().result;
+        assert!(result.score >= 60, "score {}", result.score);
+        let on_black = ScoringOptions { background: [0, 0, 0], ..Default::default() };
+        let result = score_drawing_internal(&png, 'L', font, None, &on_black).unwrap().result;
+        assert!(result.score < 30, "score {}", result.score);
+    }
+
+    #[test]
+    fn test_colored_drawings_are_scored() {
+        let font = &test_font();
+        let reference = reference_gray('L');
+        let paint = |ink: [u8; 3], paper: [u8; 3]| {
+            let canvas = image::RgbImage::from_fn(400, 400, |x, y| {
+                let a = 255 - reference.get_pixel(x, y).0[0] as u32;
+                image::Rgb(std::array::from_fn(|c| ((ink[c] as u32 * a + paper[c] as u32 * (255 - a)) / 255) as u8))
+            });
+            let mut png = Vec::new();
+            PngEncoder::new(&mut png).write_image(canvas.as_raw(), 400, 400, image::ExtendedColorType::Rgb8).unwrap();
+            png
+        };
+
+        // A yellow brush on white, and white chalk on a dark blue board
+        let options = ScoringOptions::default();
+        for png in [paint([255, 230, 0], [255, 255, 255]), paint([255, 255, 255], [20, 30, 90])] {
+            let result = score_drawing_internal(&png, 'L', font, None, &options).unwrap().result;
+            assert!(result.score >= 60, "score {}", result.score);
+        }
+
+        let dark = ScoringOptions { ink: InkColor::Dark, ..Default::default() };
+        let result = score_drawing_internal(&paint([255, 255, 255], [20, 30, 90]), 'L', font, None, &dark).unwrap().result;
+        assert!(result.score < 30, "score {}", result.score);
+    }
 
-    let x_offset = (TARGET_SIZE - new_width) / 2;
-    let y_offset = (TARGET_SIZE - new_height) / 2;
+    #[test]
+    fn test_photographed_drawing_is_scored() {
+        // Gray paper lit from one side and turned a few degrees
+        let font = &test_font();
+        let reference = reference_gray('L');
+        let (sin, cos) = 4f32.to_radians().sin_cos();
+        let photo = ImageBuffer::from_fn(600, 600, |x, y| {
+            let paper = 110.0 + 90.0 * y as f32 / 600.0;
+            let (dx, dy) = (x as f32 - 300.0, y as f32 - 300.0);
+            let (sx, sy) = (dx * cos + dy * sin + 200.0, -dx * sin + dy * cos + 200.0);
+            let ink = if (0.0..400.0).contains(&sx) && (0.0..400.0).contains(&sy) {
+                1.0 - reference.get_pixel(sx as u32, sy as u32).0[0] as f32 / 255.0
+            } else {
+                0.0
+            };
+            Luma([(paper * (1.0 - 0.6 * ink)) as u8])
+        });
+        let png = encode_grayscale_to_png(&photo).unwrap();
+
+        let options = ScoringOptions { photo: true, ..Default::default() };
+        let result = score_drawing_internal(&png, 'L', font, None, &options).unwrap().result;
+        assert!(result.score >= 60, "score {}", result.score);
+    }
 
-    // Resample to target size
-    for ty in 0..new_height {
-        for tx in 0..new_width {
-            let src_x = min_x + (tx as f32 / scale) as u32;
-            let src_y = min_y + (ty as f32 / scale) as u32;
+    #[test]
+    fn test_faint_drawing_is_scored() {
+        let font = &test_font();
+        let reference = reference_gray('L');
+        let faint = ImageBuffer::from_fn(400, 400, |x, y| {
+            Luma([220 + (reference.get_pixel(x, y).0[0] as u32 * 35 / 255) as u8])
+        });
+        let png = encode_grayscale_to_png(&faint).unwrap();
+
+        let options = ScoringOptions::default();
+        let result = score_drawing_internal(&png, 'L', font, None, &options).unwrap().result;
+        assert!(result.score >= 60, "score {}", result.score);
+        assert!(result.contrast_gain > 5.0);
+
+        let off = ScoringOptions { auto_contrast: false, ..Default::default() };
+        let result = score_drawing_internal(&png, 'L', font, None, &off).unwrap().result;
+        assert!(result.score < 30, "score {}", result.score);
+        assert_eq!(result.contrast_gain, 1.0);
+    }
 
-            if src_x < width && src_y < height {
-                let src_pixel = image.get_pixel(src_x, src_y).0[0];
-                let dst_idx = ((y_offset + ty) * TARGET_SIZE + (x_offset + tx)) as usize;
-                output[dst_idx] = src_pixel as f32 / 255.0;
+    #[test]
+    fn test_stray_marks_are_excluded_and_counted() {
+        let font = &test_font();
+        let clean = reference_gray('L');
+        // A dot in the far corner, and the same dot just off the letter
+        let mut marked = clean.clone();
+        let mut near = clean.clone();
+        for y in 380..388 {
+            for x in 380..388 {
+                marked.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+        let letter_edge = (0..400).find(|&x| clean.get_pixel(x, 200).0[0] < THRESHOLD).unwrap();
+        for y in 196..204 {
+            for x in letter_edge - 12..letter_edge - 4 {
+                near.put_pixel(x, y, Luma([0u8]));
             }
         }
-    }
-
-    output
-}
 
-/// Normalize line thickness using skeleton extraction
-fn normalize_line_thickness(binary: &[bool], width: usize, height: usize, target_thickness: u32, apply_sanding: bool) -> Vec<bool> {
-    if !binary.iter().any(|&x| x) {
-        return binary.to_vec();
+        let score = |image: &GrayImage, options: &ScoringOptions| {
+            let png = encode_grayscale_to_png(image).unwrap();
+            score_drawing_internal(&png, 'L', font, None, options).unwrap().result
+        };
+        let options = ScoringOptions::default();
+        let baseline = score(&clean, &options);
+        let result = score(&marked, &options);
+        assert_eq!(baseline.stray_marks, 0);
+        assert_eq!(result.stray_marks, 1);
+        assert!(result.score + 5 >= baseline.score, "{} vs {}", result.score, baseline.score);
+        assert_eq!(score(&near, &options).stray_marks, 0);
+
+        let kept = score(&marked, &ScoringOptions { exclude_stray_marks: false, ..Default::default() });
+        assert_eq!(kept.stray_marks, 1);
+        assert!(kept.score < result.score, "{} vs {}", kept.score, result.score);
     }
 
-    let skeleton = if apply_sanding {
-        let mut skel = skeletonize(binary, width, height);
-        bridge_gaps(&mut skel, width, height, 10);
-        prune_branches(&mut skel, width, height, 8, 0.15);
-        skel
-    } else {
-        skeletonize(binary, width, height)
-    };
-
-    if target_thickness > 1 {
-        // Use distance transform for smooth stroke reconstruction
-        if !skeleton.iter().any(|&x| x) {
-            return binary.to_vec();
+    #[test]
+    fn test_score_explanation() {
+        let font = &test_font();
+        let mut image = reference_gray('L');
+        for y in 380..388 {
+            for x in 380..388 {
+                image.put_pixel(x, y, Luma([0u8]));
+            }
+        }
+        let png = encode_grayscale_to_png(&image).unwrap();
+        let options = ScoringOptions { duration_ms: Some(500.0), ..Default::default() };
+        let result = score_drawing_internal(&png, 'L', font, None, &options).unwrap().result;
+        let explanation = &result.explanation;
+
+        let parts = [&explanation.coverage, &explanation.accuracy, &explanation.similarity, &explanation.structure, &explanation.legibility];
+        assert_eq!(explanation.coverage.value, result.coverage);
+        assert!((parts.iter().map(|part| part.weight).sum::<f32>() - 1.0).abs() < 0.01);
+        assert!((parts.iter().map(|part| part.points).sum::<f32>() - explanation.weighted_score).abs() < 0.3);
+        assert!(explanation.time_penalty > 0.0 && explanation.stray_mark_penalty > 0.0);
+        assert_eq!(explanation.missing_feature_penalty, 0.0);
+        let after_penalties = explanation.weighted_score - explanation.time_penalty - explanation.stray_mark_penalty;
+        assert!((after_penalties - result.score as f32).abs() < 1.2, "{} vs {}", after_penalties, result.score);
+        assert!(explanation.caps.is_empty());
+        assert_eq!(explanation.stars.five, 80);
+
+        // Only the top of the letter: held to one star by the coverage gate
+        for y in 120..400 {
+            for x in 0..400 {
+                image.put_pixel(x, y, Luma([255u8]));
+            }
         }
+        let png = encode_grayscale_to_png(&image).unwrap();
+        let result = score_drawing_internal(&png, 'L', font, None, &ScoringOptions::default()).unwrap().result;
+        let cap = &result.explanation.caps[0];
+        assert_eq!(cap.reason, "min_skeleton_coverage");
+        assert!(result.score <= cap.max_score);
+        assert!(result.skeleton_coverage < result.explanation.min_skeleton_coverage);
+    }
 
-        let dist = distance_transform_edt(&skeleton, width, height);
-        let threshold = target_thickness as f32 / 2.0;
+    #[test]
+    fn test_score_confidence() {
+        let font = &test_font();
+        let render = |c: char, size: u32| generate_reference_gray(&ReferenceVariation::identity(c), font, &HashMap::new(), size).unwrap();
+        let letter = render('a', 400);
+        assert_eq!(score_confidence(&letter, &letter, 0), 1.0);
+
+        // A tiny letter in the corner of the canvas
+        let mut tiny = GrayImage::from_pixel(400, 400, Luma([255u8]));
+        image::imageops::overlay(&mut tiny, &render('a', 20), 10, 10);
+        assert!(score_confidence(&tiny, &letter, 0) < 0.1);
+        // Scaled up, the same drawing is trusted as much
+        let large = image::imageops::resize(&tiny, 1200, 1200, image::imageops::FilterType::Nearest);
+        assert!((score_confidence(&large, &letter, 0) - score_confidence(&tiny, &letter, 0)).abs() < 0.01);
+        assert_eq!(score_confidence(&GrayImage::from_pixel(400, 400, Luma([255u8])), &letter, 0), 0.0);
+
+        // Stray marks and a long line for a round letter
+        assert!(score_confidence(&letter, &letter, 4) < 0.5);
+        let mut line = GrayImage::from_pixel(400, 400, Luma([255u8]));
+        for (x, y) in (20..380).flat_map(|x| (196..204).map(move |y| (x, y))) {
+            line.put_pixel(x, y, Luma([0u8]));
+        }
+        assert!(score_confidence(&line, &render('o', 400), 0) < 0.5);
 
-        dist.iter().map(|&d| d <= threshold).collect()
-    } else {
-        skeleton
+        // A full stop has too little ink to judge by
+        let stop = render('.', 400);
+        assert!(score_confidence(&stop, &stop, 0) < 0.5);
     }
-}
 
-/// Calculate coverage score: how much of the reference is covered
-fn calculate_coverage_score(drawn: &[f32], reference: &[f32]) -> f32 {
-    let size = TARGET_SIZE as usize;
-    let tolerance = 4;
+    #[test]
+    fn test_check_drawing_quality() {
+        let letter = reference_gray('a');
+        let check = |image: &GrayImage| check_drawing_quality_internal(&encode_grayscale_to_png(image).unwrap()).unwrap();
 
-    // Convert to binary
-    let drawn_binary: Vec<bool> = drawn.iter().map(|&v| v < 0.5).collect();
-    let reference_binary: Vec<bool> = reference.iter().map(|&v| v < 0.5).collect();
+        let quality = check(&letter);
+        assert!(quality.scoreable && quality.issues.is_empty() && quality.message.is_none());
+        assert!(quality.ink_pixels > 1000);
 
-    // Normalize line thickness
-    let drawn_norm = normalize_line_thickness(&drawn_binary, size, size, 5, true);
-    let reference_norm = normalize_line_thickness(&reference_binary, size, size, 5, false);
+        let mut blank = GrayImage::from_pixel(400, 400, Luma([255u8]));
+        let quality = check(&blank);
+        assert_eq!(quality.issues, vec![DrawingIssue::Empty]);
+        assert_eq!(quality.message.as_deref(), Some("Draw something first!"));
 
-    let ref_pixels: u32 = reference_norm.iter().filter(|&&x| x).count() as u32;
-    if ref_pixels == 0 {
-        return 0.0;
+        for (x, y) in (200..203).flat_map(|x| (200..203).map(move |y| (x, y))) {
+            blank.put_pixel(x, y, Luma([0u8]));
+        }
+        assert_eq!(check(&blank).issues, vec![DrawingIssue::TooLittleInk]);
+        // The same dot on a canvas twice the size is just as little
+        let mut large = GrayImage::from_pixel(800, 800, Luma([255u8]));
+        for (x, y) in (400..406).flat_map(|x| (400..406).map(move |y| (x, y))) {
+            large.put_pixel(x, y, Luma([0u8]));
+        }
+        assert_eq!(check(&large).issues, vec![DrawingIssue::TooLittleInk]);
+
+        // Ink a few levels off the paper can't be stretched to full contrast
+        let faint = ImageBuffer::from_fn(400, 400, |x, y| Luma([240 + (letter.get_pixel(x, y).0[0] as u32 * 15 / 255) as u8]));
+        assert_eq!(check(&faint).issues, vec![DrawingIssue::Faint]);
+
+        // The letter moved half off the left edge
+        let mut cut = GrayImage::from_pixel(400, 400, Luma([255u8]));
+        image::imageops::overlay(&mut cut, &letter, -150, 0);
+        let quality = check(&cut);
+        assert_eq!(quality.issues, vec![DrawingIssue::CutOff]);
+        assert!(!quality.scoreable);
     }
 
-    let drawn_pixels: u32 = drawn_norm.iter().filter(|&&x| x).count() as u32;
-    if drawn_pixels == 0 {
-        return 0.0;
+    #[test]
+    fn test_clipped_drawing_is_flagged() {
+        let font = &test_font();
+        let letter = reference_gray('a');
+        let score = |image: &GrayImage| {
+            let png = encode_grayscale_to_png(image).unwrap();
+            score_drawing_internal(&png, 'a', font, None, &ScoringOptions::default()).unwrap().result
+        };
+        let result = score(&letter);
+        assert!(!result.clipped);
+        assert_ne!(result.feedback_code.as_deref(), Some("clipped"));
+
+        let mut cut = GrayImage::from_pixel(400, 400, Luma([255u8]));
+        image::imageops::overlay(&mut cut, &letter, -150, 0);
+        let result = score(&cut);
+        assert!(result.clipped);
+        assert_eq!(result.feedback_code.as_deref(), Some("clipped"));
+        assert_eq!(result.feedback, "Keep your letter inside the box!");
+    }
+
+    #[test]
+    fn test_extract_skeleton_graph() {
+        let graph = |c: char| extract_skeleton_graph_internal(&reference_png(c)).unwrap();
+
+        // Stroke ends, on the ink in image pixels
+        let l = graph('l');
+        assert_eq!(l.components, 1);
+        assert_eq!(l.loops, 0);
+        assert!(l.nodes.iter().filter(|n| n.kind == crate::NodeKind::Endpoint).count() >= 2);
+        let letter = reference_gray('l');
+        for node in &l.nodes {
+            let (x, y) = (node.x.round() as u32, node.y.round() as u32);
+            assert!(letter.get_pixel(x, y).0[0] < THRESHOLD, "node off the ink at ({}, {})", x, y);
+        }
+        let edge = &l.edges[0];
+        let first = edge.points[0];
+        let last = edge.points[edge.points.len() - 1];
+        let span = ((last[0] - first[0]).powi(2) + (last[1] - first[1]).powi(2)).sqrt();
+        assert!(span > 100.0 && edge.length >= span * 0.95, "span {}, length {}", span, edge.length);
+
+        let o = graph('o');
+        assert_eq!(o.loops, 1);
+        assert_eq!(o.nodes[0].kind, crate::NodeKind::Loop);
+        assert_eq!(graph('i').components, 2);
+
+        let blank = encode_grayscale_to_png(&GrayImage::from_pixel(200, 200, Luma([255u8]))).unwrap();
+        let empty = extract_skeleton_graph_internal(&blank).unwrap();
+        assert!(empty.nodes.is_empty() && empty.edges.is_empty() && empty.components == 0);
     }
 
-    // Distance from each pixel to nearest drawn pixel
-    let drawn_dist = distance_transform_edt(&drawn_norm, size, size);
+    #[test]
+    fn test_with_timings() {
+        let font = &test_font();
+        let png = reference_png('e');
+        assert!(score_drawing_internal(&png, 'e', font, None, &ScoringOptions::default()).unwrap().result.timings.is_none());
+
+        let options = ScoringOptions { with_timings: true, ..Default::default() };
+        let timings = score_drawing_internal(&png, 'e', font, None, &options).unwrap().result.timings.unwrap();
+        assert!(timings.total_ms > 0.0);
+        assert!(timings.skeleton_ms > 0.0 && timings.distance_transform_ms > 0.0);
+        assert!(timings.decode_ms + timings.reference_ms + timings.metrics_ms <= timings.total_ms);
+
+        // A failed call leaves nothing recording for the next one
+        assert!(score_drawing_internal(b"not an image", 'e', font, None, &options).is_err());
+        assert!(score_drawing_internal(&png, 'e', font, None, &options).unwrap().result.timings.is_some());
+    }
 
-    // Count reference pixels that are covered (within tolerance of drawn pixels)
-    let covered: u32 = reference_norm.iter()
-        .zip(drawn_dist.iter())
-        .filter(|(&is_ref, &dist)| is_ref && dist <= tolerance as f32)
-        .count() as u32;
+    #[test]
+    fn test_specks_are_erased() {
+        let font = &test_font();
+        let clean = reference_gray('L');
+        // Palm-rejection dots, one of them two pixels
+        let mut speckled = clean.clone();
+        for (x, y) in [(20, 20), (390, 15), (385, 390), (386, 390), (10, 300)] {
+            speckled.put_pixel(x, y, Luma([0u8]));
+        }
 
-    (covered as f32 / ref_pixels as f32).min(1.0)
-}
+        let score = |image: &GrayImage, options: &ScoringOptions| {
+            let png = encode_grayscale_to_png(image).unwrap();
+            score_drawing_internal(&png, 'L', font, None, options).unwrap().result
+        };
+        let options = ScoringOptions::default();
+        let baseline = score(&clean, &options);
+        let result = score(&speckled, &options);
+        assert_eq!(result.stray_marks, 0);
+        assert_eq!(result.score, baseline.score);
+
+        let kept = score(&speckled, &ScoringOptions { speck_area: 0, ..Default::default() });
+        assert_eq!(kept.stray_marks, 4);
+    }
 
-/// Calculate accuracy score: how accurate is the drawing (staying on the lines)
-fn calculate_accuracy_score(drawn: &[f32], reference: &[f32]) -> f32 {
-    let size = TARGET_SIZE as usize;
+    #[test]
+    fn test_shaky_drawings_are_smoothed() {
+        let font = &test_font();
+        let reference = reference_gray('l');
+        let ink: Vec<(u32, u32)> = reference.enumerate_pixels().filter(|p| p.2 .0[0] < THRESHOLD).map(|p| (p.0, p.1)).collect();
+        let center = ink.iter().map(|p| p.0 as f32).sum::<f32>() / ink.len() as f32;
+        let (top, bottom) = (ink.iter().map(|p| p.1).min().unwrap(), ink.iter().map(|p| p.1).max().unwrap());
+        let stem_width = ink.len() as f32 / (bottom - top) as f32;
+
+        // The stem traced with a tremor of a third of its width either side
+        let points = (top..bottom).step_by(3).enumerate()
+            .map(|(i, y)| StrokePoint {
+                x: center + if i % 2 == 0 { stem_width / 3.0 } else { -stem_width / 3.0 },
+                y: y as f32,
+                t: None,
+                pressure: None,
+            })
+            .collect();
+        let strokes = vec![Stroke { points, erase: false, width: None }];
+        let png = encode_grayscale_to_png(&reference).unwrap();
+        // Zhang-Suen thins the tremor into side branches; Guo-Hall mostly
+        // shaves it off, leaving smoothing little to gain
+        let score = |options: ScoringOptions| {
+            let options = ScoringOptions { stroke_width: stem_width, thinning: ThinningAlgorithm::ZhangSuen, ..options };
+            score_drawing_internal(&png, 'l', font, Some(&strokes), &options).unwrap().result
+        };
+        let shaky = score(ScoringOptions { rasterize_strokes: true, ..Default::default() });
+        let smoothed = score(ScoringOptions { stroke_smoothing: stem_width, ..Default::default() });
+        assert!(smoothed.score > shaky.score, "{} vs {}", smoothed.score, shaky.score);
+
+        // A raster drawing with notches bitten out of its edges
+        let mut notched = reference.clone();
+        for &(x, y) in &ink {
+            let edge = [(0, 3), (0, -3), (3, 0), (-3, 0)].iter().any(|&(dx, dy)| {
+                reference.get_pixel_checked((x as i32 + dx) as u32, (y as i32 + dy) as u32).is_none_or(|p| p.0[0] >= THRESHOLD)
+            });
+            if edge && (x / 4 + y / 4) % 2 == 0 {
+                notched.put_pixel(x, y, Luma([255u8]));
+            }
+        }
+        let differences = |image: &GrayImage| {
+            let (drawn, expected) = (ink_mask(image), ink_mask(&reference));
+            drawn.count_or(&expected) - drawn.count_and(&expected)
+        };
+        assert!(differences(&smooth_edges(notched.clone(), 2)) * 4 < differences(&notched));
+        let png = encode_grayscale_to_png(&notched).unwrap();
+        let score = |options: &ScoringOptions| score_drawing_internal(&png, 'l', font, None, options).unwrap().result;
+        let rough = score(&ScoringOptions::default());
+        let smooth = score(&ScoringOptions { edge_smoothing: 2, ..Default::default() });
+        assert!(smooth.score >= rough.score, "{} vs {}", smooth.score, rough.score);
+
+        // Radii past the cap smooth as much as the cap
+        let capped = score(&ScoringOptions { edge_smoothing: MAX_EDGE_SMOOTHING, ..Default::default() });
+        let huge = score(&ScoringOptions { edge_smoothing: u32::MAX, ..Default::default() });
+        assert_eq!(serde_json::to_string(&capped).unwrap(), serde_json::to_string(&huge).unwrap());
+    }
 
-    // Convert to binary
-    let drawn_binary: Vec<bool> = drawn.iter().map(|&v| v < 0.5).collect();
-    let reference_binary: Vec<bool> = reference.iter().map(|&v| v < 0.5).collect();
+    #[test]
+    fn test_detect_gaps() {
+        // An L as one stroke, and with its foot drawn 6 pixels short of the stem
+        let l_shape = |foot_start: usize| {
+            let mut image = bar(30..38, 20..110);
+            fill(&mut image, foot_start..100, 102..110);
+            image
+        };
+        let reference = l_shape(30);
+
+        assert!(detect_gaps(&reference, &reference).is_empty());
+
+        let gaps = detect_gaps(&l_shape(44), &reference);
+        assert_eq!(gaps.len(), 1);
+        assert_eq!(gaps[0].region, "bottom-left");
+        assert!((gaps[0].length - 7.0).abs() < 0.5, "length {}", gaps[0].length);
+        assert!(gaps[0].x > 0.25 && gaps[0].x < 0.35, "x {}", gaps[0].x);
+
+        // A foot far from the stem is a misplaced stroke, not a gap
+        let mut apart = bar(30..38, 20..60);
+        fill(&mut apart, 70..100, 102..110);
+        assert!(detect_gaps(&apart, &reference).is_empty());
+
+        // A reference of more parts than are linked: nothing to report, and
+        // no count to underflow
+        let mut dots = blank();
+        for i in 0..10 {
+            fill(&mut dots, 10 + i * 11..16 + i * 11, 60..66);
+        }
+        let mut broken = dots.clone();
+        erase(&mut broken, 13..14, 60..66);
+        assert!(detect_gaps(&broken, &dots).is_empty());
+    }
 
-    // Normalize with sanding for drawn, without for reference
-    let drawn_norm = normalize_line_thickness(&drawn_binary, size, size, 5, true);
-    let reference_norm = normalize_line_thickness(&reference_binary, size, size, 5, false);
+    #[test]
+    fn test_detect_loop_closure() {
+        // A square ring, optionally with a break in its right side
+        let ring = |gap: bool| {
+            let mut image = square_ring(30..100, 38..92);
+            if gap {
+                erase(&mut image, 92..100, 60..70);
+            }
+            image
+        };
+        let reference = ring(false);
+
+        assert_eq!(detect_loop_closure(&reference, &reference), vec![(true, "middle")]);
+        assert_eq!(detect_loop_closure(&ring(true), &reference), vec![(false, "middle")]);
+        // Letters without loops report nothing
+        assert!(detect_loop_closure(&ring(true), &ring(true)).is_empty());
+    }
 
-    let drawn_pixels: u32 = drawn_norm.iter().filter(|&&x| x).count() as u32;
-    if drawn_pixels == 0 {
-        return 0.0;
+    #[test]
+    fn test_scoring_is_deterministic() {
+        // No clock or global random source is read: practice variation is
+        // seeded and durations come from the options or stroke timestamps
+        let font = &test_font();
+        let options = ScoringOptions {
+            practice: Some(crate::practice::PracticeOptions { seed: 7, ..Default::default() }),
+            ..Default::default()
+        };
+        let png = generate_reference_image_internal('k', font, 300, &ScoringOptions::default()).unwrap();
+
+        let run = || {
+            let scored = score_drawing_internal(&png, 'k', font, None, &options).unwrap();
+            (serde_json::to_string(&scored.result).unwrap(), scored.reference_image)
+        };
+        assert_eq!(run(), run());
     }
 
-    // Dilate reference to create acceptable zone
-    let reference_zone = binary_dilation(&reference_norm, size, size, 5);
+    #[test]
+    fn test_hash_drawing_matches_result() {
+        let font = &test_font();
+        let options = ScoringOptions::default();
+        let png = generate_reference_image_internal('e', font, 300, &options).unwrap();
+
+        let hash = hash_drawing_internal(&png).unwrap();
+        assert_eq!(hash.len(), HASH_SIZE * HASH_SIZE / 4);
+        let result = score_drawing_internal(&png, 'e', font, None, &options).unwrap().result;
+        assert_eq!(result.drawing_hash, hash);
+
+        let other = hash_drawing_internal(&generate_reference_image_internal('o', font, 300, &options).unwrap()).unwrap();
+        assert!(hash_distance_internal(&hash, &other).unwrap() > 0);
+    }
 
-    // Count drawn pixels within acceptable zone
-    let within_bounds: u32 = drawn_norm.iter()
-        .zip(reference_zone.iter())
-        .filter(|(&is_drawn, &is_zone)| is_drawn && is_zone)
-        .count() as u32;
+    #[test]
+    fn test_hash_distance() {
+        assert_eq!(hash_distance_internal("00ff", "00ff"), Ok(0));
+        assert_eq!(hash_distance_internal("00ff", "01fe"), Ok(2));
+        assert!(hash_distance_internal("00", "000").is_err());
+        assert!(hash_distance_internal("0g", "00").is_err());
+    }
 
-    (within_bounds as f32 / drawn_pixels as f32).min(1.0)
-}
+    #[test]
+    fn test_detect_trace() {
+        let reference = bar(54..74, 20..110);
+        assert!(detect_trace(&reference, &PreparedReference::new(&reference)));
+        // Same letter, different stroke width: a freehand drawing
+        assert!(!detect_trace(&bar(60..68, 20..110), &PreparedReference::new(&reference)));
+        assert!(!detect_trace(&blank(), &PreparedReference::new(&reference)));
+    }
 
-/// Calculate stroke similarity using IoU and Chamfer distance
-fn calculate_stroke_similarity(drawn: &[f32], reference: &[f32]) -> f32 {
-    let size = TARGET_SIZE as usize;
+    #[test]
+    fn test_detect_canvas_fill() {
+        let reference = bar(60..68, 20..110);
+        let filled = bar(13..115, 13..115);
 
-    // Convert to binary
-    let drawn_binary: Vec<bool> = drawn.iter().map(|&v| v < 0.5).collect();
-    let reference_binary: Vec<bool> = reference.iter().map(|&v| v < 0.5).collect();
+        assert!(detect_canvas_fill(&ScoringPipeline::new(&filled, &PreparedReference::new(&reference))));
+        assert!(!detect_canvas_fill(&ScoringPipeline::new(&reference, &PreparedReference::new(&reference))));
+    }
 
-    // Normalize both
-    let drawn_norm = normalize_line_thickness(&drawn_binary, size, size, 5, true);
-    let ref_norm = normalize_line_thickness(&reference_binary, size, size, 5, false);
+    #[test]
+    fn test_calculate_outline_accuracy() {
+        // A thick vertical bar as the glyph
+        let reference = bar(54..74, 20..110);
+        let line_at = |x0: usize| bar(x0..x0 + 3, 25..105);
 
-    let drawn_pixels: u32 = drawn_norm.iter().filter(|&&x| x).count() as u32;
-    let ref_pixels: u32 = ref_norm.iter().filter(|&&x| x).count() as u32;
+        // Inside the outline, even off the centerline, is fully accurate
+        let inside = calculate_outline_accuracy(&ScoringPipeline::new(&line_at(56), &PreparedReference::new(&reference)), 1.0, 3.0);
+        assert!(inside > 0.95, "inside {}", inside);
 
-    if drawn_pixels == 0 || ref_pixels == 0 {
-        return 0.0;
+        let outside = calculate_outline_accuracy(&ScoringPipeline::new(&line_at(90), &PreparedReference::new(&reference)), 1.0, 3.0);
+        assert!(outside < 0.05, "outside {}", outside);
     }
 
-    // IoU (40% weight)
-    let intersection: u32 = drawn_norm.iter()
-        .zip(ref_norm.iter())
-        .filter(|(&d, &r)| d && r)
-        .count() as u32;
-    let union: u32 = drawn_norm.iter()
-        .zip(ref_norm.iter())
-        .filter(|(&d, &r)| d || r)
-        .count() as u32;
-    let iou = intersection as f32 / (union as f32 + 1e-8);
+    #[test]
+    fn test_calculate_accuracy_score_perfect() {
+        // Identical images should give high accuracy
+        let image: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
+            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
+            .collect();
 
-    // Chamfer distance (60% weight)
-    let ref_dist = distance_transform_edt(&ref_norm, size, size);
-    let drawn_dist = distance_transform_edt(&drawn_norm, size, size);
+        let score = calculate_accuracy_score(&ScoringPipeline::new(&image, &PreparedReference::new(&image)), 3.0, 6.0);
 
-    // Average distance from drawn to reference
-    let mut drawn_to_ref_sum = 0.0f32;
-    let mut drawn_to_ref_count = 0u32;
-    for (i, &is_drawn) in drawn_norm.iter().enumerate() {
-        if is_drawn {
-            drawn_to_ref_sum += ref_dist[i];
-            drawn_to_ref_count += 1;
-        }
+        // Should be very high (close to 1.0)
+        assert!(score > 0.9);
     }
-    let drawn_to_ref = if drawn_to_ref_count > 0 {
-        drawn_to_ref_sum / drawn_to_ref_count as f32
-    } else {
-        0.0
-    };
 
-    // Average distance from reference to drawn
-    let mut ref_to_drawn_sum = 0.0f32;
-    let mut ref_to_drawn_count = 0u32;
-    for (i, &is_ref) in ref_norm.iter().enumerate() {
-        if is_ref {
-            ref_to_drawn_sum += drawn_dist[i];
-            ref_to_drawn_count += 1;
-        }
-    }
-    let ref_to_drawn = if ref_to_drawn_count > 0 {
-        ref_to_drawn_sum / ref_to_drawn_count as f32
-    } else {
-        0.0
-    };
+    #[test]
+    fn test_calculate_accuracy_score_empty_drawn() {
+        let drawn: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize]; // all white
+        let reference: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
+            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
+            .collect();
 
-    // Symmetric Chamfer distance
-    let chamfer_dist = (drawn_to_ref + ref_to_drawn) / 2.0;
+        let score = calculate_accuracy_score(&ScoringPipeline::new(&drawn, &PreparedReference::new(&reference)), 3.0, 6.0);
 
-    // Convert to similarity score
-    let max_dist = 20.0;
-    let chamfer_score = (-chamfer_dist / (max_dist / 3.0)).exp();
+        // Should be 0 (nothing drawn)
+        assert_eq!(score, 0.0);
+    }
 
-    // Combine
-    let similarity = iou * 0.4 + chamfer_score * 0.6;
-    similarity.min(1.0).max(0.0)
-}
+    #[test]
+    fn test_calculate_accuracy_score_distance_weighted() {
+        // Vertical reference bar; drawings offset slightly and far to the right
+        let bar_at = |offset: usize| bar(40 + offset..46 + offset, 20..110);
+        let reference = bar_at(0);
 
-fn get_star_rating(score: u8) -> (u8, String) {
-    match score {
-        80..=100 => (5, "Amazing! Perfect!".to_string()),
-        65..=79 => (4, "Great job!".to_string()),
-        50..=64 => (3, "Good work!".to_string()),
-        30..=49 => (2, "Nice try!".to_string()),
-        _ => (1, "Keep practicing!".to_string()),
-    }
-}
+        let near = calculate_accuracy_score(&ScoringPipeline::new(&bar_at(5), &PreparedReference::new(&reference)), 3.0, 6.0);
+        let far = calculate_accuracy_score(&ScoringPipeline::new(&bar_at(40), &PreparedReference::new(&reference)), 3.0, 6.0);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        assert!(near > far);
+        assert!(near > 0.5);
+        assert_eq!(far, 0.0);
+    }
 
     #[test]
-    fn test_get_star_rating_5_stars() {
-        let (stars, feedback) = get_star_rating(100);
-        assert_eq!(stars, 5);
-        assert_eq!(feedback, "Amazing! Perfect!");
-
-        let (stars, feedback) = get_star_rating(80);
-        assert_eq!(stars, 5);
-        assert_eq!(feedback, "Amazing! Perfect!");
+    fn test_distance_credit() {
+        assert_eq!(distance_credit(0.0, 3.0, 6.0), 1.0);
+        assert_eq!(distance_credit(3.0, 3.0, 6.0), 1.0);
+        assert!((distance_credit(6.0, 3.0, 6.0) - 0.5).abs() < 1e-6);
+        assert_eq!(distance_credit(9.0, 3.0, 6.0), 0.0);
+        assert_eq!(distance_credit(4.0, 3.0, 0.0), 0.0);
     }
 
     #[test]
-    fn test_get_star_rating_4_stars() {
-        let (stars, feedback) = get_star_rating(79);
-        assert_eq!(stars, 4);
-        assert_eq!(feedback, "Great job!");
+    fn test_calculate_stroke_similarity_identical() {
+        // Identical images should give high similarity
+        let image: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
+            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
+            .collect();
 
-        let (stars, feedback) = get_star_rating(65);
-        assert_eq!(stars, 4);
-        assert_eq!(feedback, "Great job!");
+        let score = calculate_stroke_similarity(&ScoringPipeline::new(&image, &PreparedReference::new(&image)));
+
+        // Should be high (close to 1.0)
+        assert!(score > 0.8);
     }
 
     #[test]
-    fn test_get_star_rating_3_stars() {
-        let (stars, feedback) = get_star_rating(64);
-        assert_eq!(stars, 3);
-        assert_eq!(feedback, "Good work!");
+    fn test_calculate_stroke_similarity_plus_vs_x() {
+        // A plus sign should match itself far better than an X
+        let size = TARGET_SIZE as usize;
+        let mut plus = vec![1.0f32; size * size];
+        let mut cross = vec![1.0f32; size * size];
+        for i in 24..104 {
+            for t in 0..4 {
+                plus[62 * size + i + t * size] = 0.0;
+                plus[i * size + 62 + t] = 0.0;
+                cross[i * size + i + t] = 0.0;
+                cross[i * size + (127 - i) - t] = 0.0;
+            }
+        }
 
-        let (stars, feedback) = get_star_rating(50);
-        assert_eq!(stars, 3);
-        assert_eq!(feedback, "Good work!");
+        let same = calculate_stroke_similarity(&ScoringPipeline::new(&plus, &PreparedReference::new(&plus)));
+        let different = calculate_stroke_similarity(&ScoringPipeline::new(&plus, &PreparedReference::new(&cross)));
+
+        assert!(same > 0.8);
+        assert!(different < 0.5);
     }
 
     #[test]
-    fn test_get_star_rating_2_stars() {
-        let (stars, feedback) = get_star_rating(49);
-        assert_eq!(stars, 2);
-        assert_eq!(feedback, "Nice try!");
+    fn test_calculate_stroke_similarity_empty() {
+        let drawn: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize]; // all white
+        let reference: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize];
 
-        let (stars, feedback) = get_star_rating(30);
-        assert_eq!(stars, 2);
-        assert_eq!(feedback, "Nice try!");
+        let score = calculate_stroke_similarity(&ScoringPipeline::new(&drawn, &PreparedReference::new(&reference)));
+
+        // Should be 0 (no content to compare)
+        assert_eq!(score, 0.0);
     }
 
     #[test]
-    fn test_get_star_rating_1_star() {
-        let (stars, feedback) = get_star_rating(29);
-        assert_eq!(stars, 1);
-        assert_eq!(feedback, "Keep practicing!");
+    fn test_calculate_ssim_similarity() {
+        let bar_at = |offset: usize| bar(40 + offset..46 + offset, 20..110);
 
-        let (stars, feedback) = get_star_rating(0);
-        assert_eq!(stars, 1);
-        assert_eq!(feedback, "Keep practicing!");
+        let identical = calculate_ssim_similarity(&bar_at(0), &bar_at(0));
+        let shifted = calculate_ssim_similarity(&bar_at(0), &bar_at(30));
+
+        assert!(identical > 0.99);
+        assert!(shifted < 0.2);
     }
 
     #[test]
-    fn test_extract_and_center_character_empty() {
-        // All white image (no drawing)
-        let img = GrayImage::from_pixel(100, 100, Luma([255u8]));
-        let result = extract_and_center_character(&img);
+    fn test_calculate_ssim_similarity_empty() {
+        let blank = vec![1.0f32; (TARGET_SIZE * TARGET_SIZE) as usize];
+        assert_eq!(calculate_ssim_similarity(&blank, &blank), 0.0);
+    }
 
-        // Should return all 1.0 (white)
-        assert_eq!(result.len(), (TARGET_SIZE * TARGET_SIZE) as usize);
-        assert!(result.iter().all(|&v| v == 1.0));
+    #[test]
+    fn test_calculate_structure_similarity() {
+        // An O-like ring against itself and against a C-like open arc
+        let size = TARGET_SIZE as usize;
+        let ring = ring(36.0..42.0);
+        let mut arc = ring.clone();
+        erase(&mut arc, 84..size, 0..size);
+
+        let same = calculate_structure_similarity(&ScoringPipeline::new(&ring, &PreparedReference::new(&ring)));
+        let open = calculate_structure_similarity(&ScoringPipeline::new(&arc, &PreparedReference::new(&ring)));
+
+        assert!(same > 0.95);
+        assert!(open < same - 0.2);
     }
 
     #[test]
-    fn test_extract_and_center_character_with_content() {
-        // Create image with a black square in the center
-        let mut img = GrayImage::from_pixel(100, 100, Luma([255u8]));
-        for y in 40..60 {
-            for x in 40..60 {
-                img.put_pixel(x, y, Luma([0u8]));
+    fn test_calculate_legibility_clean_vs_noisy() {
+        let size = TARGET_SIZE as usize;
+        let mut clean = vec![1.0f32; size * size];
+        let mut noisy = vec![1.0f32; size * size];
+        for y in 20..110 {
+            for x in 60..66 {
+                clean[y * size + x] = 0.0;
             }
         }
+        // Zig-zag scribble with scattered specks
+        for y in 20..110 {
+            let x = 40 + ((y / 3) % 2) * 12 + (y % 3) * 4;
+            for t in 0..3 {
+                noisy[y * size + x + t] = 0.0;
+            }
+        }
+        for i in 0..12 {
+            noisy[(10 + i * 9) * size + 100 + (i % 3) * 7] = 0.0;
+        }
 
-        let result = extract_and_center_character(&img);
+        let clean_score = calculate_legibility(&PreparedDrawing::new(&clean));
+        let noisy_score = calculate_legibility(&PreparedDrawing::new(&noisy));
 
-        // Should have some dark pixels (< 0.5)
-        let dark_count = result.iter().filter(|&&v| v < 0.5).count();
-        assert!(dark_count > 0);
+        assert!(clean_score > 0.8);
+        assert!(noisy_score < clean_score);
     }
 
     #[test]
-    fn test_normalize_line_thickness_empty() {
-        let binary = vec![false; 100];
-        let result = normalize_line_thickness(&binary, 10, 10, 5, false);
+    fn test_calculate_legibility_empty() {
+        let blank = vec![1.0f32; (TARGET_SIZE * TARGET_SIZE) as usize];
+        assert_eq!(calculate_legibility(&PreparedDrawing::new(&blank)), 0.0);
+    }
 
-        // Should remain empty
-        assert!(result.iter().all(|&x| !x));
+    #[test]
+    fn test_calculate_moment_similarity() {
+        let bar = bar(60..66, 20..110);
+        let ring = ring(36.0..42.0);
+
+        let same = calculate_moment_similarity(&bar, &bar);
+        let different = calculate_moment_similarity(&bar, &ring);
+
+        assert!(same > 0.99);
+        assert!(different < 0.5);
     }
 
     #[test]
-    fn test_normalize_line_thickness_with_content() {
-        // Create a thick horizontal line
-        let mut binary = vec![false; 100];
-        for y in 3..7 {
-            for x in 2..8 {
-                binary[y * 10 + x] = true;
+    fn test_calculate_worst_region_localized_error() {
+        // Reference is an L shape; the drawing puts the horizontal arm at the top,
+        // so the far end of the missing bottom arm is the most off
+        let size = TARGET_SIZE as usize;
+        let mut reference = vec![1.0f32; size * size];
+        let mut drawn = vec![1.0f32; size * size];
+        for i in 20..108 {
+            for t in 0..5 {
+                reference[i * size + 20 + t] = 0.0;
+                drawn[i * size + 20 + t] = 0.0;
+            }
+        }
+        for x in 20..108 {
+            for t in 0..5 {
+                reference[(103 + t) * size + x] = 0.0;
+            }
+        }
+        for x in 60..108 {
+            for t in 0..5 {
+                drawn[(20 + t) * size + x] = 0.0;
             }
         }
 
-        let result = normalize_line_thickness(&binary, 10, 10, 3, false);
-
-        // Should have fewer true pixels than original (thinned)
-        let original_count: usize = binary.iter().filter(|&&x| x).count();
-        let result_count: usize = result.iter().filter(|&&x| x).count();
+        let worst = calculate_worst_region(&ScoringPipeline::new(&drawn, &PreparedReference::new(&reference)));
 
-        // The line should be thinner but still present
-        assert!(result_count > 0);
-        assert!(result_count <= original_count);
+        assert!(worst.hausdorff_95 >= WORST_REGION_MIN_DISTANCE);
+        let label = worst.label.unwrap();
+        assert_eq!(label, "bottom-right");
     }
 
     #[test]
-    fn test_calculate_coverage_score_perfect() {
-        // Identical images should give high coverage
+    fn test_calculate_worst_region_identical() {
         let image: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
             .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
             .collect();
 
-        let score = calculate_coverage_score(&image, &image);
+        let worst = calculate_worst_region(&ScoringPipeline::new(&image, &PreparedReference::new(&image)));
 
-        // Should be very high (close to 1.0)
-        assert!(score > 0.9);
+        assert!(worst.hausdorff_95 < 1.0);
+        assert!(worst.label.is_none());
     }
 
     #[test]
-    fn test_calculate_coverage_score_empty_drawn() {
-        let drawn: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize]; // all white
-        let reference: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
-            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
-            .collect();
-
-        let score = calculate_coverage_score(&drawn, &reference);
+    fn test_generate_reference_gray() {
+        let fonts_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../../public/fonts");
+        for entry in std::fs::read_dir(fonts_dir).unwrap() {
+            let font = Font::from_bytes(&std::fs::read(entry.unwrap().path()).unwrap()).unwrap();
+            let reference = generate_reference_gray(&ReferenceVariation::identity('a'), &font, &HashMap::new(), 100).unwrap();
+            assert!(reference.pixels().any(|p| p.0[0] < THRESHOLD));
+        }
 
-        // Should be 0 (nothing drawn)
-        assert_eq!(score, 0.0);
+        // Characters the font lacks are an error rather than a blank reference
+        let font = &test_font();
+        assert_eq!(
+            generate_reference_gray(&ReferenceVariation::identity('Ω'), font, &HashMap::new(), 100).unwrap_err(),
+            ScoringError::GlyphMissing('Ω')
+        );
+        let png = generate_reference_image_internal('a', font, 100, &ScoringOptions::default()).unwrap();
+        assert!(matches!(
+            score_drawing_internal(&png, 'Ω', font, None, &ScoringOptions::default()),
+            Err(ScoringError::GlyphMissing('Ω'))
+        ));
     }
 
     #[test]
-    fn test_calculate_accuracy_score_perfect() {
-        // Identical images should give high accuracy
-        let image: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
-            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
-            .collect();
-
-        let score = calculate_accuracy_score(&image, &image);
+    fn test_reference_glyph_is_centered() {
+        // Placed by its own bounds, with no extra shift for the baseline:
+        // capitals, descenders and ascenders alike sit in the middle, whole
+        let font = &test_font();
+        let size = 200;
+        for character in ['A', 'a', 'g', 'j', 'l', 'y', '.'] {
+            let reference = generate_reference_gray(&ReferenceVariation::identity(character), font, &HashMap::new(), size).unwrap();
+            let ink = ink_mask(&reference);
+            let (mut min_y, mut max_y) = (usize::MAX, 0);
+            for (_, y) in ink.ones() {
+                (min_y, max_y) = (min_y.min(y), max_y.max(y));
+            }
+            assert!(min_y > 0 && max_y < size as usize - 1, "'{}' spans rows {}-{}", character, min_y, max_y);
+            let middle = (min_y + max_y) as f32 / 2.0;
+            assert!((middle - size as f32 / 2.0).abs() <= 2.0, "'{}' is centered on row {}", character, middle);
+        }
+    }
 
-        // Should be very high (close to 1.0)
-        assert!(score > 0.9);
+    #[test]
+    fn test_cff_font_reference() {
+        // Cantarell's outlines are CFF2 cubics, not TrueType quadratics
+        let font = &Font::from_bytes(include_bytes!("../fixtures/fonts/Cantarell-VF.otf")).unwrap();
+        assert!(font.has_glyph('a') && !font.has_glyph('字'));
+        let ink = |axes: &[(&str, f32)]| {
+            let axes: HashMap<String, f32> = axes.iter().map(|&(tag, value)| (tag.to_string(), value)).collect();
+            let reference = generate_reference_gray(&ReferenceVariation::identity('a'), font, &axes, 200).unwrap();
+            reference.pixels().filter(|p| p.0[0] < THRESHOLD).count()
+        };
+        assert!(ink(&[("wght", 100.0)]) > 200);
+        assert!(ink(&[("wght", 800.0)]) as f32 > ink(&[("wght", 100.0)]) as f32 * 1.5);
+
+        let options = ScoringOptions::default();
+        let png = generate_reference_image_internal('a', font, 400, &options).unwrap();
+        let result = score_drawing_internal(&png, 'a', font, None, &options).unwrap().result;
+        assert!(result.score >= 80, "score {}", result.score);
     }
 
     #[test]
-    fn test_calculate_accuracy_score_empty_drawn() {
-        let drawn: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize]; // all white
-        let reference: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
-            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
-            .collect();
+    fn test_generate_reference_gray_font_axes() {
+        let font = &Font::from_bytes(include_bytes!("../../../public/fonts/Nunito-Regular.ttf")).unwrap();
+        let ink = |axes: &[(&str, f32)]| {
+            let axes: HashMap<String, f32> = axes.iter().map(|&(tag, value)| (tag.to_string(), value)).collect();
+            generate_reference_gray(&ReferenceVariation::identity('l'), font, &axes, 100)
+                .map(|image| image.pixels().filter(|p| p.0[0] < THRESHOLD).count())
+        };
+
+        let thin = ink(&[("wght", 200.0)]).unwrap();
+        let bold = ink(&[("wght", 900.0)]).unwrap();
+        assert!(bold as f32 > thin as f32 * 1.5);
+        // Axes the font lacks are ignored; malformed tags are errors
+        assert_eq!(ink(&[("wght", 200.0), ("wdth", 50.0)]).unwrap(), thin);
+        assert!(ink(&[("weight", 600.0)]).is_err());
+    }
 
-        let score = calculate_accuracy_score(&drawn, &reference);
+    #[test]
+    fn test_reference_sizing_options() {
+        let font = &test_font();
+        let render = |options: &ScoringOptions| render_reference(&ReferenceVariation::identity('l'), font, options, 200).unwrap();
+        let ink_rows = |image: &GrayImage| {
+            let rows: Vec<u32> = image.enumerate_pixels().filter(|(_, _, p)| p.0[0] < THRESHOLD).map(|(_, y, _)| y).collect();
+            rows.iter().max().unwrap() - rows.iter().min().unwrap()
+        };
+        let ink_columns = |image: &GrayImage, y: u32| (0..image.width()).filter(|&x| image.get_pixel(x, y).0[0] < THRESHOLD).count();
+
+        // Font size scales the glyph
+        let default = render(&ScoringOptions::default());
+        let small = render(&ScoringOptions { reference_font_size: 0.5, ..Default::default() });
+        let ratio = ink_rows(&small) as f32 / ink_rows(&default) as f32;
+        assert!((ratio - 0.5 / 0.75).abs() < 0.05, "{}", ratio);
+        assert!(render_reference(&ReferenceVariation::identity('l'), font, &ScoringOptions { reference_font_size: 0.0, ..Default::default() }, 200).is_err());
+
+        // Stroke width redraws the stem with the pen
+        let brush = render(&ScoringOptions { reference_stroke_width: Some(0.1), ..Default::default() });
+        let width = ink_columns(&brush, 100);
+        assert!((19..=22).contains(&width), "{}", width);
+        assert!(ink_columns(&default, 100) < width);
+
+        // Padding shrinks the letter in the normalized frame, for the
+        // reference and the drawing alike
+        let png = generate_reference_image_internal('k', font, 300, &ScoringOptions::default()).unwrap();
+        let padded = ScoringOptions { normalize_padding: 0.25, ..Default::default() };
+        let reference = cached_reference(&ReferenceVariation::identity('k'), font, &padded, REFERENCE_SIZE).unwrap();
+        let reference_ink = reference.prepared.binary.count();
+        let default_ink = cached_reference(&ReferenceVariation::identity('k'), font, &ScoringOptions::default(), REFERENCE_SIZE)
+            .unwrap().prepared.binary.count();
+        assert!(reference_ink < default_ink);
+        let score = |options: &ScoringOptions| score_drawing_internal(&png, 'k', font, None, options).unwrap().result.score;
+        // A copy of the reference still scores about as well
+        assert!(score(&padded).abs_diff(score(&ScoringOptions::default())) <= 5);
+    }
 
-        // Should be 0 (nothing drawn)
-        assert_eq!(score, 0.0);
+    #[test]
+    fn test_reference_supersampling() {
+        let font = &test_font();
+        let render = |factor: u32| {
+            let options = ScoringOptions { reference_stroke_width: Some(0.1), reference_supersampling: factor, ..Default::default() };
+            render_reference(&ReferenceVariation::identity('o'), font, &options, 48)
+        };
+        let edge_pixels = |image: &GrayImage| image.pixels().filter(|p| (1..255).contains(&p.0[0])).count();
+
+        // A pen-stamped letter has hard edges; averaging down softens them
+        // without changing the size or moving the ink
+        let aliased = render(1).unwrap();
+        let smooth = render(4).unwrap();
+        assert_eq!(smooth.dimensions(), (48, 48));
+        assert_eq!(edge_pixels(&aliased), 0);
+        assert!(edge_pixels(&smooth) > 20);
+        let ink = |image: &GrayImage| image.pixels().map(|p| 255 - p.0[0] as u32).sum::<u32>() as f32;
+        assert!((ink(&smooth) / ink(&aliased) - 1.0).abs() < 0.1);
+
+        assert!(render(0).is_err());
+        assert!(render(5).is_err());
+
+        let blocks = GrayImage::from_fn(4, 2, |x, _| Luma([if x % 2 == 0 { 0 } else { 255 }]));
+        assert_eq!(downsample_area(&blocks, 2).into_raw(), vec![128, 128]);
     }
 
     #[test]
-    fn test_calculate_stroke_similarity_identical() {
-        // Identical images should give high similarity
-        let image: Vec<f32> = (0..TARGET_SIZE * TARGET_SIZE)
-            .map(|i| if i % 10 == 0 { 0.0 } else { 1.0 })
-            .collect();
+    fn test_frame_size() {
+        let font = &test_font();
+        let copy = generate_reference_image_internal('a', font, 300, &ScoringOptions::default()).unwrap();
+        let other = generate_reference_image_internal('o', font, 300, &ScoringOptions::default()).unwrap();
+        let score = |png: &[u8], frame_size: u32| {
+            let options = ScoringOptions { frame_size, ..Default::default() };
+            score_drawing_internal(png, 'a', font, None, &options).map(|scored| scored.result.score)
+        };
+
+        // Tolerances scale with the frame, so scores barely move
+        for size in [96, 256, 512] {
+            assert!(score(&copy, size).unwrap().abs_diff(score(&copy, TARGET_SIZE).unwrap()) <= 5);
+            assert!(score(&other, size).unwrap().abs_diff(score(&other, TARGET_SIZE).unwrap()) <= 8);
+        }
+        assert!(score(&copy, 32).is_err());
+        assert!(score(&copy, 1024).is_err());
+    }
 
-        let score = calculate_stroke_similarity(&image, &image);
+    #[test]
+    fn test_pyramid_scoring() {
+        let font = &test_font();
+        let pyramid = ScoringOptions { pyramid: true, ..Default::default() };
+        for (drawn, target) in [('a', 'a'), ('o', 'a'), ('e', 'e'), ('l', 'e')] {
+            let png = generate_reference_image_internal(drawn, font, 300, &ScoringOptions::default()).unwrap();
+            let one = score_drawing_internal(&png, target, font, None, &ScoringOptions::default()).unwrap().result;
+            let two = score_drawing_internal(&png, target, font, None, &pyramid).unwrap().result;
+            assert!(one.score.abs_diff(two.score) <= 5, "{} as {}: {} vs {}", drawn, target, one.score, two.score);
+            assert_eq!(one.loop_closed, two.loop_closed);
+        }
 
-        // Should be high (close to 1.0)
-        assert!(score > 0.8);
+        // Too small a frame to halve: one level, the same result
+        let png = generate_reference_image_internal('e', font, 300, &ScoringOptions::default()).unwrap();
+        let small = |pyramid| ScoringOptions { frame_size: 96, pyramid, ..Default::default() };
+        let one = score_drawing_internal(&png, 'e', font, None, &small(false)).unwrap().result;
+        let two = score_drawing_internal(&png, 'e', font, None, &small(true)).unwrap().result;
+        assert_eq!(serde_json::to_string(&one).unwrap(), serde_json::to_string(&two).unwrap());
     }
 
     #[test]
-    fn test_calculate_stroke_similarity_empty() {
-        let drawn: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize]; // all white
-        let reference: Vec<f32> = vec![1.0; (TARGET_SIZE * TARGET_SIZE) as usize];
+    fn test_halve_frame() {
+        // A two-pixel line survives at any offset; a lone pixel fades
+        let size = 8;
+        for row in 2..4 {
+            let mut image = vec![1.0f32; size * size];
+            image[row * size..(row + 2) * size].fill(0.0);
+            image[7 * size + 7] = 0.0;
+            let halved = halve_frame(&image);
+            assert_eq!(halved.len(), 16);
+            assert!((0..4).all(|x| (1..=2).any(|y| halved[y * 4 + x] < 0.5)));
+            assert_eq!(halved[15], 0.5);
+        }
+    }
 
-        let score = calculate_stroke_similarity(&drawn, &reference);
+    #[test]
+    fn test_generate_sized_reference_image() {
+        use crate::options::{GuideOptions, ReferenceAnchor};
+
+        let font = &test_font();
+        let render = |width: u32, height: u32, anchor: ReferenceAnchor| {
+            let options = ScoringOptions { guide: GuideOptions { anchor, ..Default::default() }, ..Default::default() };
+            let png = generate_sized_reference_image_internal('g', font, width, height, &options).unwrap();
+            image::load_from_memory(&png).unwrap().to_luma8()
+        };
+        let ink_box = |image: &GrayImage| {
+            let ink: Vec<(u32, u32)> = image.enumerate_pixels().filter(|(_, _, p)| p.0[0] < THRESHOLD).map(|(x, y, _)| (x, y)).collect();
+            (
+                ink.iter().map(|p| p.0).min().unwrap(), ink.iter().map(|p| p.0).max().unwrap(),
+                ink.iter().map(|p| p.1).min().unwrap(), ink.iter().map(|p| p.1).max().unwrap(),
+            )
+        };
+
+        // The letter is the size it would be in a square of the short side
+        let square = render(100, 100, ReferenceAnchor::Center);
+        let wide = render(300, 100, ReferenceAnchor::Left);
+        assert_eq!(wide.dimensions(), (300, 100));
+        assert_eq!(ink_box(&wide), ink_box(&square));
+        let (min_x, max_x, _, _) = ink_box(&render(300, 100, ReferenceAnchor::Center));
+        assert_eq!((min_x, max_x), (ink_box(&square).0 + 100, ink_box(&square).1 + 100));
+        let (_, _, min_y, _) = ink_box(&render(100, 250, ReferenceAnchor::Bottom));
+        assert_eq!(min_y, ink_box(&square).2 + 150);
+
+        assert_eq!(generate_sized_reference_image_internal('g', font, 120, 120, &ScoringOptions::default()).unwrap(),
+            generate_reference_image_internal('g', font, 120, &ScoringOptions::default()).unwrap());
+        assert!(generate_sized_reference_image_internal('g', font, 0, 100, &ScoringOptions::default()).is_err());
+    }
 
-        // Should be 0 (no content to compare)
-        assert_eq!(score, 0.0);
+    #[test]
+    fn test_select_font() {
+        let fredoka = &test_font();
+        let nunito = &Font::from_bytes(include_bytes!("../../../public/fonts/Nunito-Regular.ttf")).unwrap();
+        let options = ScoringOptions::default();
+
+        assert_eq!(select_font_internal(&[fredoka, nunito], 'a', &options), Ok(0));
+        // Fredoka has no Greek, so omega falls back to Nunito
+        assert_eq!(select_font_internal(&[fredoka, nunito], 'Ω', &options), Ok(1));
+        assert_eq!(select_font_internal(&[fredoka, nunito], 'α', &options), Err(ScoringError::GlyphMissing('α')));
+        assert!(select_font_internal(&[], 'a', &options).is_err());
+
+        // A chain renders and scores each character in the font that has it
+        let chain = fredoka.clone().with_fallbacks([nunito.clone()]);
+        let omega = generate_reference_image_internal('Ω', &chain, 200, &options).unwrap();
+        assert_eq!(omega, generate_reference_image_internal('Ω', nunito, 200, &options).unwrap());
+        let a = generate_reference_image_internal('a', &chain, 200, &options).unwrap();
+        assert_eq!(a, generate_reference_image_internal('a', fredoka, 200, &options).unwrap());
+        assert!(score_drawing_internal(&omega, 'Ω', &chain, None, &options).unwrap().result.score > 90);
+        assert!(score_drawing_internal(&omega, 'Ω', fredoka, None, &options).is_err());
     }
 
     #[test]
@@ -0,0 +1,442 @@
+//! Per-segment coverage of the reference skeleton
+//!
+//! Splits the reference skeleton into its graph edges and measures how much
+//! of each one the drawing covers, so partial drawings can be coached one
+//! segment at a time ("now draw this part").
+
+use crate::error::ScoringError;
+use crate::font::Font;
+use crate::bit_image::BitImage;
+use crate::image_ops::distance_transform_edt;
+use crate::options::ScoringOptions;
+use crate::reference::{PreparedReference, Reference, cached_reference};
+use crate::scoring::{NormalizeTransform, REFERENCE_SIZE, auto_contrast, apply_normalize_transform, decode_image, flatten_alpha, frame_scale, ink_gray};
+use crate::skeleton_graph::SkeletonGraph;
+use crate::strokes::{normalize_to_unit, resample};
+use crate::styles::styled_template;
+use crate::templates::CharacterTemplate;
+use image::GrayImage;
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+
+/// A segment counts as drawn once this fraction of it is covered
+const COVERED_FRACTION: f32 = 0.6;
+
+/// Segments shorter than this (in pixels) are too small to suggest
+const MIN_SEGMENT_LENGTH: f32 = 4.0;
+
+/// Points used when comparing segments with template strokes
+const MATCH_POINTS: usize = 12;
+
+/// Distance (in normalized pixels) within which drawn ink covers a segment
+const SEGMENT_COVERAGE_TOLERANCE: f32 = 4.0;
+
+/// A reference segment chosen for the learner to draw next
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentChoice {
+    /// Index of the edge in the reference skeleton graph
+    pub edge: usize,
+    /// Whether the edge should be drawn from its last point to its first
+    pub reversed: bool,
+    /// Template stroke the segment belongs to, if a template was used
+    pub template_stroke: Option<usize>,
+}
+
+/// Suggested next stroke, in fractions (0-1) of the reference image size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NextStroke {
+    /// Polyline to highlight, in drawing direction
+    pub points: Vec<(f32, f32)>,
+    /// Where the stroke should start
+    pub start: (f32, f32),
+    /// Index of the matching stroke in the character's stroke template
+    pub template_stroke: Option<u32>,
+}
+
+/// The reference for a character in the normalized frame, for comparing
+/// drawings made over the displayed reference (partial and live drawings)
+///
+/// Unlike full scoring, drawings are not centered on their own bounding
+/// box: they are mapped with the reference's transform, so half a letter
+/// stays half a letter.
+pub(crate) struct ReferenceFrame {
+    transform: NormalizeTransform,
+    /// Reference ink, skeleton and the skeleton graph whose edges are the
+    /// coachable segments, shared with the reference cache
+    reference: Arc<Reference>,
+    template: Option<&'static CharacterTemplate>,
+}
+
+impl ReferenceFrame {
+    pub(crate) fn new(character: char, font: &Font, options: &ScoringOptions) -> Result<Self, ScoringError> {
+        let variation = options.reference_variation(character);
+        let reference = cached_reference(&variation, font, options, REFERENCE_SIZE)?;
+        let transform = reference.transform
+            .ok_or_else(|| format!("Glyph for '{}' is empty", variation.glyph))?;
+
+        Ok(Self { transform, reference, template: styled_template(character, options.style.as_deref()) })
+    }
+
+    /// The reference in the normalized frame
+    pub(crate) fn prepared(&self) -> &PreparedReference {
+        &self.reference.prepared
+    }
+
+    /// Side of the square normalized frame, in pixels
+    pub(crate) fn size(&self) -> usize {
+        self.transform.size as usize
+    }
+
+    /// Normalize a drawing made over the reference displayed at the image's size
+    pub(crate) fn normalize_drawing(&self, image: &GrayImage) -> BitImage {
+        let normalized = apply_normalize_transform(
+            image,
+            &self.transform,
+            image.width() as f32 / REFERENCE_SIZE as f32,
+            image.height() as f32 / REFERENCE_SIZE as f32,
+            0.5,
+        );
+        BitImage::ink(&normalized, self.size(), self.size())
+    }
+
+    /// Map a point on a canvas of the given size into the normalized frame
+    pub(crate) fn canvas_to_frame(&self, x: f32, y: f32, canvas_width: f32, canvas_height: f32) -> (f32, f32) {
+        let rx = x * REFERENCE_SIZE as f32 / canvas_width;
+        let ry = y * REFERENCE_SIZE as f32 / canvas_height;
+        (
+            (rx - self.transform.min_x as f32) * self.transform.scale + self.transform.x_offset as f32,
+            (ry - self.transform.min_y as f32) * self.transform.scale + self.transform.y_offset as f32,
+        )
+    }
+
+    /// Normalized-frame pixels per canvas pixel, for a canvas of this width
+    pub(crate) fn canvas_scale(&self, canvas_width: f32) -> f32 {
+        self.transform.scale * REFERENCE_SIZE as f32 / canvas_width
+    }
+
+    /// Map a normalized-frame pixel to fractions (0-1) of the reference image
+    pub(crate) fn frame_to_fraction(&self, x: u32, y: u32) -> (f32, f32) {
+        let (sx, sy) = self.transform.source_point(x as f32 + 0.5, y as f32 + 0.5);
+        (sx / REFERENCE_SIZE as f32, sy / REFERENCE_SIZE as f32)
+    }
+
+    /// Coverage of each reference segment by drawn ink
+    pub(crate) fn segment_coverage(&self, drawn: &BitImage) -> Vec<f32> {
+        segment_coverage(&self.prepared().graph, drawn, SEGMENT_COVERAGE_TOLERANCE * frame_scale(self.size()))
+    }
+
+    /// Choose the next reference segment to draw, given drawn ink
+    pub(crate) fn next_stroke(&self, drawn: &BitImage) -> Option<NextStroke> {
+        let coverage = self.segment_coverage(drawn);
+        let choice = choose_next_segment(&self.prepared().graph, &coverage, self.template)?;
+        Some(self.segment_stroke(&choice))
+    }
+
+    /// The longest reference segment not yet drawn
+    pub(crate) fn largest_uncovered(&self, drawn: &BitImage) -> Option<NextStroke> {
+        let coverage = self.segment_coverage(drawn);
+        let choice = largest_uncovered_segment(&self.prepared().graph, &coverage)?;
+        Some(self.segment_stroke(&choice))
+    }
+
+    /// A chosen segment as a polyline in reference image fractions
+    fn segment_stroke(&self, choice: &SegmentChoice) -> NextStroke {
+        let mut points: Vec<(f32, f32)> = self.prepared().graph.edges[choice.edge].points.iter()
+            .map(|&(x, y)| self.frame_to_fraction(x, y))
+            .collect();
+        if choice.reversed {
+            points.reverse();
+        }
+
+        NextStroke {
+            start: points[0],
+            points,
+            template_stroke: choice.template_stroke.map(|s| s as u32),
+        }
+    }
+}
+
+/// Suggest the next part of the letter to draw on a partial drawing
+///
+/// The drawing is assumed to be made over the reference overlay, so it is
+/// normalized in the reference's frame. Returns `None` once every
+/// reference segment is covered.
+pub fn suggest_next_stroke_internal(
+    image_data: &[u8],
+    character: char,
+    font: &Font,
+    options: &ScoringOptions,
+) -> Result<Option<NextStroke>, ScoringError> {
+    let drawn_image = ink_gray(&flatten_alpha(decode_image(image_data, &options.input_limits)?, options.background), options.ink);
+    let drawn_image = if options.auto_contrast { auto_contrast(&drawn_image).0 } else { drawn_image };
+
+    let frame = ReferenceFrame::new(character, font, options)?;
+    let drawn = frame.normalize_drawing(&drawn_image);
+
+    Ok(frame.next_stroke(&drawn))
+}
+
+/// Fraction of each graph edge lying within `tolerance` pixels of drawn ink
+pub fn segment_coverage(
+    graph: &SkeletonGraph,
+    drawn: &BitImage,
+    tolerance: f32,
+) -> Vec<f32> {
+    if drawn.is_empty() {
+        return vec![0.0; graph.edges.len()];
+    }
+
+    let width = drawn.width();
+    let dist = distance_transform_edt(drawn);
+    graph.edges.iter()
+        .map(|edge| {
+            if edge.points.is_empty() {
+                return 0.0;
+            }
+            let covered = edge.points.iter()
+                .filter(|&&(x, y)| dist[y as usize * width + x as usize] <= tolerance)
+                .count();
+            covered as f32 / edge.points.len() as f32
+        })
+        .collect()
+}
+
+/// Pick the uncovered segment the learner should draw next
+///
+/// With a stroke template, each uncovered segment is attributed to its
+/// nearest template stroke and the one earliest in stroke order wins
+/// (among segments of the same stroke, the one nearest the stroke's
+/// start), starting from the end nearest that stroke's start. Without a
+/// template the longest uncovered segment is chosen, starting from its top.
+/// Returns `None` once every segment is covered.
+pub fn choose_next_segment(
+    graph: &SkeletonGraph,
+    coverage: &[f32],
+    template: Option<&CharacterTemplate>,
+) -> Option<SegmentChoice> {
+    let candidates = uncovered_segments(graph, coverage);
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let template = match template {
+        Some(t) if !t.strokes.is_empty() => t,
+        _ => return Some(choose_longest(graph, &candidates)),
+    };
+
+    // Bring all skeleton edges and all template strokes into unit frames
+    let edges: Vec<Vec<(f32, f32)>> = graph.edges.iter()
+        .map(|e| e.points.iter().map(|&(x, y)| (x as f32, y as f32)).collect())
+        .collect();
+    let edges = normalize_to_unit(&edges);
+    let strokes: Vec<Vec<(f32, f32)>> = template.strokes.iter().map(|s| s.points.clone()).collect();
+    let strokes: Vec<Vec<(f32, f32)>> = normalize_to_unit(&strokes).iter()
+        .map(|s| resample(s, MATCH_POINTS * 2))
+        .collect();
+
+    // (edge, template stroke, distance from the stroke's start, reversed)
+    let mut best: Option<(usize, usize, f32, bool)> = None;
+    for &i in &candidates {
+        let samples = resample(&edges[i], MATCH_POINTS);
+        let stroke = (0..strokes.len())
+            .min_by(|&a, &b| {
+                mean_nearest(&samples, &strokes[a]).total_cmp(&mean_nearest(&samples, &strokes[b]))
+            })
+            .unwrap_or(0);
+
+        let (sx, sy) = strokes[stroke][0];
+        let first = edges[i][0];
+        let last = edges[i][edges[i].len() - 1];
+        let first_distance = ((first.0 - sx).powi(2) + (first.1 - sy).powi(2)).sqrt();
+        let last_distance = ((last.0 - sx).powi(2) + (last.1 - sy).powi(2)).sqrt();
+        let reversed = last_distance < first_distance;
+        let distance = first_distance.min(last_distance);
+
+        let better = match best {
+            None => true,
+            Some((_, best_stroke, best_distance, _)) => {
+                stroke < best_stroke || (stroke == best_stroke && distance < best_distance)
+            }
+        };
+        if better {
+            best = Some((i, stroke, distance, reversed));
+        }
+    }
+
+    let (edge, stroke, _, reversed) = best?;
+    Some(SegmentChoice { edge, reversed, template_stroke: Some(stroke) })
+}
+
+/// Pick the longest segment not yet drawn, starting from its top end
+///
+/// Returns `None` once every segment is covered.
+pub fn largest_uncovered_segment(graph: &SkeletonGraph, coverage: &[f32]) -> Option<SegmentChoice> {
+    let candidates = uncovered_segments(graph, coverage);
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(choose_longest(graph, &candidates))
+}
+
+/// Indices of segments long enough to suggest and not yet covered
+fn uncovered_segments(graph: &SkeletonGraph, coverage: &[f32]) -> Vec<usize> {
+    graph.edges.iter()
+        .enumerate()
+        .filter(|(i, e)| coverage[*i] < COVERED_FRACTION && e.length >= MIN_SEGMENT_LENGTH)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn choose_longest(graph: &SkeletonGraph, candidates: &[usize]) -> SegmentChoice {
+    let edge = candidates.iter()
+        .copied()
+        .max_by(|&a, &b| graph.edges[a].length.total_cmp(&graph.edges[b].length))
+        .unwrap_or(candidates[0]);
+
+    // Conventional start: the top end, or the left end of a level segment
+    let points = &graph.edges[edge].points;
+    let (fx, fy) = points[0];
+    let (lx, ly) = points[points.len() - 1];
+    let reversed = ly < fy || (ly == fy && lx < fx);
+
+    SegmentChoice { edge, reversed, template_stroke: None }
+}
+
+/// Mean distance from each point in `a` to its nearest point in `b`
+fn mean_nearest(a: &[(f32, f32)], b: &[(f32, f32)]) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return f32::MAX;
+    }
+    let total: f32 = a.iter()
+        .map(|&(ax, ay)| {
+            b.iter()
+                .map(|&(bx, by)| ((ax - bx).powi(2) + (ay - by).powi(2)).sqrt())
+                .fold(f32::MAX, f32::min)
+        })
+        .sum();
+    total / a.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "stroke-templates")]
+    use crate::templates::template_for;
+    #[cfg(feature = "stroke-templates")]
+    use crate::scoring::{encode_grayscale_to_png, generate_reference_image_internal};
+    #[cfg(feature = "stroke-templates")]
+    use image::{ImageBuffer, Luma};
+
+    #[cfg(feature = "stroke-templates")]
+    fn test_font() -> Font {
+        Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap()
+    }
+
+    #[cfg(feature = "stroke-templates")]
+    fn blank_png(size: u32) -> Vec<u8> {
+        encode_grayscale_to_png(&ImageBuffer::from_pixel(size, size, Luma([255u8]))).unwrap()
+    }
+
+    const SIZE: usize = 64;
+
+    /// A 'T' skeleton: bar along y=10 from x=10..54, stem along x=32 from y=10..54
+    fn t_skeleton() -> BitImage {
+        let mut skel = BitImage::new(SIZE, SIZE);
+        for x in 10..=54 {
+            skel.set(x, 10, true);
+        }
+        for y in 11..=54 {
+            skel.set(32, y, true);
+        }
+        skel
+    }
+
+    #[test]
+    fn test_segment_coverage() {
+        let graph = SkeletonGraph::from_skeleton(&t_skeleton(), 0.0);
+        let mut drawn = BitImage::new(SIZE, SIZE);
+        for y in 11..=54 {
+            drawn.set(32, y, true);
+        }
+
+        let coverage = segment_coverage(&graph, &drawn, 2.0);
+        assert_eq!(coverage.len(), graph.edges.len());
+        assert!(coverage.iter().any(|&c| c > 0.9));
+        assert!(coverage.iter().any(|&c| c < 0.3));
+    }
+
+    #[test]
+    #[cfg(feature = "stroke-templates")]
+    fn test_choose_next_segment_follows_template_order() {
+        let graph = SkeletonGraph::from_skeleton(&t_skeleton(), 0.0);
+        let template = template_for('T').unwrap();
+
+        // Nothing drawn: the bar comes first, left to right
+        let coverage = vec![0.0; graph.edges.len()];
+        let choice = choose_next_segment(&graph, &coverage, Some(template)).unwrap();
+        assert_eq!(choice.template_stroke, Some(0));
+        let points = &graph.edges[choice.edge].points;
+        let start = if choice.reversed { points[points.len() - 1] } else { points[0] };
+        assert_eq!(start.1, 10);
+        assert!(start.0 < 20);
+
+        // Bar drawn: the stem is next, from the top
+        let mut drawn = BitImage::new(SIZE, SIZE);
+        for x in 10..=54 {
+            drawn.set(x, 10, true);
+        }
+        let coverage = segment_coverage(&graph, &drawn, 2.0);
+        let choice = choose_next_segment(&graph, &coverage, Some(template)).unwrap();
+        assert_eq!(choice.template_stroke, Some(1));
+        let points = &graph.edges[choice.edge].points;
+        let start = if choice.reversed { points[points.len() - 1] } else { points[0] };
+        assert!(start.1 < 15);
+
+        // Everything drawn
+        let complete = vec![1.0; graph.edges.len()];
+        assert!(choose_next_segment(&graph, &complete, Some(template)).is_none());
+    }
+
+    #[test]
+    fn test_largest_uncovered_segment() {
+        let graph = SkeletonGraph::from_skeleton(&t_skeleton(), 0.0);
+        let mut drawn = BitImage::new(SIZE, SIZE);
+        for y in 11..=54 {
+            drawn.set(32, y, true);
+        }
+        let coverage = segment_coverage(&graph, &drawn, 2.0);
+
+        // Stem drawn: one of the bar halves is left
+        let choice = largest_uncovered_segment(&graph, &coverage).unwrap();
+        assert!(graph.edges[choice.edge].points.iter().all(|&(_, y)| y <= 11));
+        assert!(largest_uncovered_segment(&graph, &vec![1.0; graph.edges.len()]).is_none());
+    }
+
+    #[test]
+    fn test_choose_next_segment_without_template() {
+        let graph = SkeletonGraph::from_skeleton(&t_skeleton(), 0.0);
+        let coverage = vec![0.0; graph.edges.len()];
+
+        let choice = choose_next_segment(&graph, &coverage, None).unwrap();
+        assert_eq!(choice.template_stroke, None);
+        assert!(graph.edges[choice.edge].length >= graph.edges.iter().map(|e| e.length).fold(0.0, f32::max) - 1e-3);
+    }
+
+    #[test]
+    #[cfg(feature = "stroke-templates")]
+    fn test_suggest_next_stroke() {
+        let options = ScoringOptions::default();
+
+        // Nothing drawn yet: start with the bar of the T at its left end
+        let next = suggest_next_stroke_internal(&blank_png(400), 'T', &test_font(), &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.template_stroke, Some(0));
+        assert!(next.start.0 < 0.4 && next.start.1 < 0.4, "start {:?}", next.start);
+        assert!(next.points.len() > 2);
+
+        // The complete letter needs nothing more
+        let complete = generate_reference_image_internal('T', &test_font(), 400, &options).unwrap();
+        assert!(suggest_next_stroke_internal(&complete, 'T', &test_font(), &options).unwrap().is_none());
+    }
+}
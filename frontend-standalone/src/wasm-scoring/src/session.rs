@@ -0,0 +1,360 @@
+//! Session analytics
+//!
+//! Aggregates the attempts of one practice session into a summary. The app
+//! keeps the attempts (character, strokes and result) as the session goes
+//! and asks for a summary whenever it wants to show one.
+
+use crate::strokes::{Stroke, ink_strokes};
+use serde::{Serialize, Deserialize};
+
+// A stroke counts as horizontal when its net vertical travel is at most
+// this fraction of its horizontal travel, and it is at least this long
+const HORIZONTAL_MAX_SLOPE: f32 = 0.35;
+const MIN_FEATURE_LENGTH: f32 = 10.0;
+// A stroke counts as a loop when it ends within this fraction of its path
+// length from where it started
+const LOOP_MAX_END_GAP: f32 = 0.2;
+
+// Handedness needs this many observations, and the left share of them must
+// leave this band around one half before a guess is made
+const HANDEDNESS_MIN_OBSERVATIONS: u32 = 8;
+const HANDEDNESS_MIN_CONFIDENCE: f32 = 0.7;
+
+// Fatigue: trends need this many attempts, must be significant at the 5%
+// level (one-sided) and must lose at least this fraction of the starting
+// level over the session to count
+const FATIGUE_MIN_ATTEMPTS: usize = 5;
+const FATIGUE_MIN_DECLINE: f32 = 0.15;
+
+/// One scored attempt within a session
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionAttempt {
+    pub character: Option<char>,
+    /// Strokes as drawn, in canvas coordinates
+    pub strokes: Vec<Stroke>,
+    /// Score the attempt received
+    pub score: Option<f32>,
+}
+
+/// Summary of a practice session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub attempts: u32,
+    pub handedness: HandednessEstimate,
+    pub fatigue: FatigueEstimate,
+}
+
+/// Probable writing hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Handedness {
+    Left,
+    Right,
+    /// Too little or too mixed evidence
+    Unknown,
+}
+
+/// Handedness guess with the stroke statistics behind it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandednessEstimate {
+    pub handedness: Handedness,
+    /// Share of the observations typical of left-handers, smoothed towards
+    /// one half when there are few
+    pub left_probability: f32,
+    /// Horizontal strokes drawn right to left / left to right
+    pub right_to_left_strokes: u32,
+    pub left_to_right_strokes: u32,
+    /// Closed loops drawn clockwise / counterclockwise (as seen on screen)
+    pub clockwise_loops: u32,
+    pub counterclockwise_loops: u32,
+    /// Whether to suggest enabling left-handed mode
+    pub suggest_left_handed_mode: bool,
+}
+
+/// Trend of a per-attempt measure over a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trend {
+    /// Fitted change per attempt
+    pub slope: f32,
+    /// Fitted value at the first attempt
+    pub start: f32,
+    /// Whether the slope is a significant decline that is large enough to matter
+    pub declining: bool,
+}
+
+/// Signs of fatigue or frustration within a session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FatigueEstimate {
+    /// Score trend, when enough attempts have scores
+    pub quality: Option<Trend>,
+    /// Pen speed trend (canvas units per second), when enough attempts
+    /// have timestamped strokes
+    pub fluency: Option<Trend>,
+    pub fatigued: bool,
+    /// Message for the learner when fatigued
+    pub suggestion: Option<String>,
+}
+
+/// Summarize the attempts of a session
+pub fn summarize_session(attempts: &[SessionAttempt]) -> SessionSummary {
+    SessionSummary {
+        attempts: attempts.len() as u32,
+        handedness: estimate_handedness(attempts),
+        fatigue: estimate_fatigue(attempts),
+    }
+}
+
+/// Estimate handedness from stroke direction biases
+///
+/// Left-handers tend to pull horizontal strokes right to left and to draw
+/// loops clockwise; right-handers the opposite. Each horizontal stroke and
+/// each loop is one observation.
+pub fn estimate_handedness(attempts: &[SessionAttempt]) -> HandednessEstimate {
+    let (mut rtl, mut ltr, mut cw, mut ccw) = (0u32, 0u32, 0u32, 0u32);
+
+    for stroke in attempts.iter().flat_map(|attempt| ink_strokes(&attempt.strokes)) {
+        let points: Vec<(f32, f32)> = stroke.points.iter().map(|p| (p.x, p.y)).collect();
+        if points.len() < 3 {
+            continue;
+        }
+        let path_length: f32 = points.windows(2)
+            .map(|w| ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt())
+            .sum();
+        if path_length < MIN_FEATURE_LENGTH {
+            continue;
+        }
+
+        let (first, last) = (points[0], points[points.len() - 1]);
+        let (dx, dy) = (last.0 - first.0, last.1 - first.1);
+        let end_gap = (dx * dx + dy * dy).sqrt();
+
+        if end_gap <= LOOP_MAX_END_GAP * path_length {
+            // Shoelace sum; positive is clockwise with y pointing down
+            let area: f32 = points.iter()
+                .zip(points.iter().cycle().skip(1))
+                .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+                .sum();
+            if area > 0.0 {
+                cw += 1;
+            } else if area < 0.0 {
+                ccw += 1;
+            }
+        } else if dx.abs() >= MIN_FEATURE_LENGTH && dy.abs() <= HORIZONTAL_MAX_SLOPE * dx.abs() {
+            if dx < 0.0 {
+                rtl += 1;
+            } else {
+                ltr += 1;
+            }
+        }
+    }
+
+    let left = rtl + cw;
+    let total = left + ltr + ccw;
+    let left_probability = (left as f32 + 1.0) / (total as f32 + 2.0);
+    let handedness = if total < HANDEDNESS_MIN_OBSERVATIONS {
+        Handedness::Unknown
+    } else if left_probability >= HANDEDNESS_MIN_CONFIDENCE {
+        Handedness::Left
+    } else if left_probability <= 1.0 - HANDEDNESS_MIN_CONFIDENCE {
+        Handedness::Right
+    } else {
+        Handedness::Unknown
+    };
+
+    HandednessEstimate {
+        handedness,
+        left_probability: (left_probability * 100.0).round() / 100.0,
+        right_to_left_strokes: rtl,
+        left_to_right_strokes: ltr,
+        clockwise_loops: cw,
+        counterclockwise_loops: ccw,
+        suggest_left_handed_mode: handedness == Handedness::Left,
+    }
+}
+
+/// Look for a significant decline in quality or fluency across attempts
+pub fn estimate_fatigue(attempts: &[SessionAttempt]) -> FatigueEstimate {
+    let scores: Vec<(f32, f32)> = attempts.iter()
+        .enumerate()
+        .filter_map(|(i, attempt)| attempt.score.map(|score| (i as f32, score)))
+        .collect();
+    let speeds: Vec<(f32, f32)> = attempts.iter()
+        .enumerate()
+        .filter_map(|(i, attempt)| pen_speed(&attempt.strokes).map(|speed| (i as f32, speed)))
+        .collect();
+
+    let quality = fit_trend(&scores);
+    let fluency = fit_trend(&speeds);
+    let fatigued = quality.iter().chain(fluency.iter()).any(|trend| trend.declining);
+
+    FatigueEstimate {
+        quality,
+        fluency,
+        fatigued,
+        suggestion: fatigued.then(|| "Great effort! How about a little break?".to_string()),
+    }
+}
+
+/// Mean speed while the pen is down, from stroke timestamps
+fn pen_speed(strokes: &[Stroke]) -> Option<f32> {
+    let (mut distance, mut seconds) = (0.0f32, 0.0f64);
+    for stroke in ink_strokes(strokes) {
+        let timed: Vec<_> = stroke.points.iter().filter(|p| p.t.is_some()).collect();
+        for pair in timed.windows(2) {
+            distance += ((pair[1].x - pair[0].x).powi(2) + (pair[1].y - pair[0].y).powi(2)).sqrt();
+            seconds += (pair[1].t.unwrap() - pair[0].t.unwrap()).max(0.0) / 1000.0;
+        }
+    }
+    (seconds > 0.0).then(|| distance / seconds as f32)
+}
+
+/// Least-squares trend with a one-sided t-test for a negative slope
+fn fit_trend(samples: &[(f32, f32)]) -> Option<Trend> {
+    let n = samples.len();
+    if n < FATIGUE_MIN_ATTEMPTS {
+        return None;
+    }
+    let mean_x = samples.iter().map(|s| s.0).sum::<f32>() / n as f32;
+    let mean_y = samples.iter().map(|s| s.1).sum::<f32>() / n as f32;
+    let sxx: f32 = samples.iter().map(|s| (s.0 - mean_x).powi(2)).sum();
+    let sxy: f32 = samples.iter().map(|s| (s.0 - mean_x) * (s.1 - mean_y)).sum();
+    if sxx <= 0.0 {
+        return None;
+    }
+
+    let slope = sxy / sxx;
+    let intercept = mean_y - slope * mean_x;
+    let start = intercept + slope * samples[0].0;
+    let end = intercept + slope * samples[n - 1].0;
+
+    let df = (n - 2) as f32;
+    let residual: f32 = samples.iter().map(|s| (s.1 - intercept - slope * s.0).powi(2)).sum();
+    let standard_error = (residual / df / sxx).sqrt();
+    let significant = if standard_error > 0.0 {
+        slope / standard_error < -t_critical_95(df)
+    } else {
+        slope < 0.0
+    };
+    let large = start > 0.0 && (start - end) >= FATIGUE_MIN_DECLINE * start;
+
+    Some(Trend {
+        slope: (slope * 100.0).round() / 100.0,
+        start: (start * 10.0).round() / 10.0,
+        declining: significant && large,
+    })
+}
+
+/// One-sided 95% critical value of Student's t, slightly conservative
+/// (within about 5% of the exact value for 3 or more degrees of freedom)
+fn t_critical_95(df: f32) -> f32 {
+    1.645 + 2.4 / df.max(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strokes::StrokePoint;
+
+    fn stroke(points: &[(f32, f32)]) -> Stroke {
+        Stroke {
+            points: points.iter().map(|&(x, y)| StrokePoint { x, y, t: None, pressure: None }).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn circle(clockwise: bool) -> Stroke {
+        let points: Vec<(f32, f32)> = (0..=24)
+            .map(|i| {
+                let angle = i as f32 / 24.0 * std::f32::consts::TAU * if clockwise { 1.0 } else { -1.0 };
+                (100.0 + 40.0 * angle.cos(), 100.0 + 40.0 * angle.sin())
+            })
+            .collect();
+        stroke(&points)
+    }
+
+    fn session(left_handed: bool, attempts: usize) -> Vec<SessionAttempt> {
+        let bar = if left_handed {
+            stroke(&[(150.0, 60.0), (100.0, 62.0), (50.0, 61.0)])
+        } else {
+            stroke(&[(50.0, 60.0), (100.0, 62.0), (150.0, 61.0)])
+        };
+        (0..attempts)
+            .map(|_| SessionAttempt {
+                character: Some('o'),
+                strokes: vec![circle(left_handed), bar.clone()],
+                score: Some(80.0),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate_handedness() {
+        let left = estimate_handedness(&session(true, 5));
+        assert_eq!(left.handedness, Handedness::Left);
+        assert_eq!((left.clockwise_loops, left.right_to_left_strokes), (5, 5));
+        assert!(left.suggest_left_handed_mode);
+
+        let right = estimate_handedness(&session(false, 5));
+        assert_eq!(right.handedness, Handedness::Right);
+        assert!(!right.suggest_left_handed_mode);
+    }
+
+    #[test]
+    fn test_handedness_needs_enough_evidence() {
+        assert_eq!(estimate_handedness(&session(true, 2)).handedness, Handedness::Unknown);
+
+        let mut mixed = session(true, 4);
+        mixed.extend(session(false, 4));
+        assert_eq!(estimate_handedness(&mixed).handedness, Handedness::Unknown);
+        assert_eq!(summarize_session(&mixed).attempts, 8);
+    }
+
+    fn timed_attempt(score: f32, speed: f32) -> SessionAttempt {
+        // A 100-unit horizontal stroke drawn at the given speed
+        let duration = 100.0 / speed as f64 * 1000.0;
+        let points = (0..=10)
+            .map(|i| StrokePoint { x: 10.0 * i as f32, y: 0.0, t: Some(duration * i as f64 / 10.0), pressure: None })
+            .collect();
+        SessionAttempt {
+            character: Some('a'),
+            strokes: vec![Stroke { points, ..Default::default() }],
+            score: Some(score),
+        }
+    }
+
+    #[test]
+    fn test_estimate_fatigue() {
+        let tiring: Vec<SessionAttempt> = [85.0, 82.0, 84.0, 75.0, 70.0, 66.0, 60.0, 58.0]
+            .iter()
+            .map(|&score| timed_attempt(score, 200.0))
+            .collect();
+        let fatigue = estimate_fatigue(&tiring);
+        assert!(fatigue.fatigued);
+        assert!(fatigue.quality.as_ref().unwrap().declining);
+        assert!(!fatigue.fluency.as_ref().unwrap().declining);
+        assert!(fatigue.suggestion.is_some());
+
+        let steady: Vec<SessionAttempt> = [80.0, 72.0, 85.0, 78.0, 70.0, 83.0, 76.0, 74.0]
+            .iter()
+            .map(|&score| timed_attempt(score, 200.0))
+            .collect();
+        assert!(!estimate_fatigue(&steady).fatigued);
+
+        let slowing: Vec<SessionAttempt> = [300.0, 290.0, 260.0, 240.0, 200.0, 180.0]
+            .iter()
+            .map(|&speed| timed_attempt(80.0, speed))
+            .collect();
+        let fatigue = estimate_fatigue(&slowing);
+        assert!(fatigue.fatigued);
+        assert!(fatigue.fluency.unwrap().declining);
+    }
+
+    #[test]
+    fn test_fatigue_needs_enough_attempts() {
+        let short: Vec<SessionAttempt> = [90.0, 70.0, 50.0].iter().map(|&s| timed_attempt(s, 200.0)).collect();
+        let fatigue = estimate_fatigue(&short);
+        assert!(fatigue.quality.is_none() && fatigue.fluency.is_none());
+        assert!(!fatigue.fatigued);
+    }
+}
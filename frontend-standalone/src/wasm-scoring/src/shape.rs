@@ -0,0 +1,480 @@
+//! Reference shapes from SVG path data
+//!
+//! Teachers can supply their own letterforms, or shapes no font has, as an
+//! SVG path `d` string instead of a font glyph. The path is flattened to
+//! polylines, scaled to fit the reference image as a glyph would be, and
+//! either filled (nonzero, like glyph outlines) or stroked along its
+//! centerline at a given pen width.
+
+use crate::error::ScoringError;
+use crate::practice::ReferenceVariation;
+use crate::strokes::{Stroke, StrokePoint, rasterize_strokes};
+use ab_glyph_rasterizer::{Rasterizer, point};
+use image::{GrayImage, ImageBuffer, Luma};
+use std::f32::consts::PI;
+
+// Line segments per Bézier curve when flattening
+const CURVE_SEGMENTS: usize = 16;
+// Largest angle of an elliptical arc covered by one line segment
+const ARC_STEP: f32 = PI / 16.0;
+// The larger side of the shape's bounds, as a fraction of the image size
+// (about the height of a glyph at the reference font size)
+const SHAPE_FIT: f32 = 0.6;
+
+/// A reference drawn from SVG path data
+#[derive(Debug, Clone)]
+pub struct ReferenceShape {
+    /// Flattened subpaths in path units; closed ones end at their start
+    subpaths: Vec<Vec<(f32, f32)>>,
+    /// Pen width in path units when stroked, `None` to fill
+    stroke_width: Option<f32>,
+    /// Path units scaled to `SHAPE_FIT` of the image; `None` fits the
+    /// shape's own bounds
+    extent: Option<f32>,
+}
+
+impl ReferenceShape {
+    /// Parse an SVG path `d` string (all commands, absolute and relative)
+    ///
+    /// With `stroke_width`, the path is a centerline drawn with a pen that
+    /// wide (in path units); without, its outlines are filled.
+    pub fn from_svg_path(path: &str, stroke_width: Option<f32>) -> Result<Self, ScoringError> {
+        if let Some(width) = stroke_width {
+            if !(width.is_finite() && width > 0.0) {
+                return Err("Stroke width must be positive".into());
+            }
+        }
+        let subpaths = parse_path(path)?;
+        let (min, max) = bounds(&subpaths).ok_or("SVG path has no segments")?;
+        let pen = stroke_width.unwrap_or(0.0);
+        if (max.0 - min.0).max(max.1 - min.1) + pen <= 0.0 {
+            return Err("SVG path has no extent".into());
+        }
+        Ok(Self { subpaths, stroke_width, extent: None })
+    }
+
+    /// Scale as though the shape spanned `extent` path units, so shapes
+    /// drawn on a common box keep their relative sizes
+    pub(crate) fn with_extent(self, extent: f32) -> Self {
+        Self { extent: Some(extent), ..self }
+    }
+
+    /// Render the shape centered in a `size`×`size` image, black on white
+    pub(crate) fn render(&self, size: u32) -> GrayImage {
+        self.render_variation(size, &ReferenceVariation::identity(' '))
+    }
+
+    /// Render with a practice variation's size and position jitter
+    pub(crate) fn render_variation(&self, size: u32, variation: &ReferenceVariation) -> GrayImage {
+        let (subpaths, scale) = self.place(size, variation);
+
+        match self.stroke_width {
+            Some(width) => {
+                let strokes: Vec<Stroke> = subpaths.iter()
+                    .map(|subpath| Stroke {
+                        points: subpath.iter()
+                            .map(|&(x, y)| StrokePoint { x, y, t: None, pressure: None })
+                            .collect(),
+                        ..Default::default()
+                    })
+                    .collect();
+                rasterize_strokes(&strokes, size, size, width * scale)
+            }
+            None => {
+                let mut rasterizer = Rasterizer::new(size as usize, size as usize);
+                for points in &subpaths {
+                    // Fill closes every subpath
+                    for (a, b) in points.iter().zip(points.iter().cycle().skip(1)) {
+                        rasterizer.draw_line(point(a.0, a.1), point(b.0, b.1));
+                    }
+                }
+                let mut img: GrayImage = ImageBuffer::from_pixel(size, size, Luma([255u8]));
+                rasterizer.for_each_pixel_2d(|x, y, v| {
+                    img.put_pixel(x, y, Luma([(255.0 * (1.0 - v.min(1.0))) as u8]));
+                });
+                img
+            }
+        }
+    }
+
+    /// Subpaths in image pixels as `render_variation` draws them: the
+    /// centerline of a stroked shape, the closed outlines of a filled one
+    #[cfg(feature = "guides")]
+    pub(crate) fn image_subpaths(&self, size: u32, variation: &ReferenceVariation) -> Vec<Vec<(f32, f32)>> {
+        let (mut subpaths, _) = self.place(size, variation);
+        if self.stroke_width.is_none() {
+            for subpath in &mut subpaths {
+                if subpath.first() != subpath.last() {
+                    subpath.push(subpath[0]);
+                }
+            }
+        }
+        subpaths
+    }
+
+    /// Height in image pixels of a path-unit height, as `render_variation`
+    /// places the shape
+    #[cfg(feature = "guides")]
+    pub(crate) fn image_y(&self, size: u32, variation: &ReferenceVariation, y: f32) -> f32 {
+        let (scale, center, origin) = self.transform(size, variation);
+        (y - center.1) * scale + origin.1
+    }
+
+    /// Subpaths mapped into a `size`×`size` image, and the scale from path
+    /// units to pixels
+    fn place(&self, size: u32, variation: &ReferenceVariation) -> (Vec<Vec<(f32, f32)>>, f32) {
+        let (scale, center, origin) = self.transform(size, variation);
+        let subpaths = self.subpaths.iter()
+            .map(|subpath| subpath.iter()
+                .map(|&(x, y)| ((x - center.0) * scale + origin.0, (y - center.1) * scale + origin.1))
+                .collect())
+            .collect();
+        (subpaths, scale)
+    }
+
+    /// Scale from path units to pixels, and the path point that lands on
+    /// the image point
+    fn transform(&self, size: u32, variation: &ReferenceVariation) -> (f32, (f32, f32), (f32, f32)) {
+        let (min, max) = bounds(&self.subpaths).expect("shape was validated on parse");
+        let pen = self.stroke_width.unwrap_or(0.0);
+        let extent = self.extent.unwrap_or((max.0 - min.0).max(max.1 - min.1) + pen);
+        let scale = size as f32 * SHAPE_FIT * variation.scale / extent;
+        let center = ((min.0 + max.0) / 2.0, (min.1 + max.1) / 2.0);
+        let origin = (
+            size as f32 * (0.5 + variation.offset_x),
+            size as f32 * (0.5 + variation.offset_y),
+        );
+        (scale, center, origin)
+    }
+
+    /// Flattened subpaths in path units, in drawing order
+    pub(crate) fn subpaths(&self) -> &[Vec<(f32, f32)>] {
+        &self.subpaths
+    }
+}
+
+fn bounds(subpaths: &[Vec<(f32, f32)>]) -> Option<((f32, f32), (f32, f32))> {
+    subpaths.iter().flatten().fold(None, |acc, &(x, y)| match acc {
+        None => Some(((x, y), (x, y))),
+        Some((min, max)) => Some(((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y)))),
+    })
+}
+
+/// Flatten path data into polylines
+fn parse_path(path: &str) -> Result<Vec<Vec<(f32, f32)>>, ScoringError> {
+    let mut parser = PathParser { bytes: path.as_bytes(), pos: 0 };
+    let mut subpaths: Vec<Vec<(f32, f32)>> = Vec::new();
+    let mut current = (0.0f32, 0.0f32);
+    let mut start = current;
+    // Control point for S and T reflection, and whether the last command
+    // was a cubic (true) or quadratic (false) curve
+    let mut last_control: Option<((f32, f32), bool)> = None;
+    let mut command: Option<u8> = None;
+
+    loop {
+        parser.skip_separators();
+        if parser.at_end() {
+            break;
+        }
+        command = match parser.command() {
+            Some(c) => Some(c),
+            // Repeated arguments repeat the command; after a move they are lines
+            None => match command {
+                Some(b'M') => Some(b'L'),
+                Some(b'm') => Some(b'l'),
+                Some(b'Z' | b'z') | None => {
+                    return Err(format!("Expected a path command at position {}", parser.pos).into());
+                }
+                other => other,
+            },
+        };
+        let c = command.unwrap();
+        let relative = c.is_ascii_lowercase();
+        let origin = if relative { current } else { (0.0, 0.0) };
+        let coords = |parser: &mut PathParser| -> Result<(f32, f32), ScoringError> {
+            Ok((origin.0 + parser.number()?, origin.1 + parser.number()?))
+        };
+
+        let mut control = None;
+        match c.to_ascii_uppercase() {
+            b'M' => {
+                current = coords(&mut parser)?;
+                start = current;
+                subpaths.push(vec![current]);
+            }
+            b'L' => {
+                current = coords(&mut parser)?;
+                line_to(&mut subpaths, current);
+            }
+            b'H' => {
+                current.0 = parser.number()? + if relative { current.0 } else { 0.0 };
+                line_to(&mut subpaths, current);
+            }
+            b'V' => {
+                current.1 = parser.number()? + if relative { current.1 } else { 0.0 };
+                line_to(&mut subpaths, current);
+            }
+            b'C' | b'S' => {
+                let c1 = if c.eq_ignore_ascii_case(&b'C') {
+                    coords(&mut parser)?
+                } else {
+                    reflect(last_control, true, current)
+                };
+                let c2 = coords(&mut parser)?;
+                let end = coords(&mut parser)?;
+                for k in 1..=CURVE_SEGMENTS {
+                    let t = k as f32 / CURVE_SEGMENTS as f32;
+                    let u = 1.0 - t;
+                    let blend = |a: f32, b: f32, c: f32, d: f32| u * u * u * a + 3.0 * u * u * t * b + 3.0 * u * t * t * c + t * t * t * d;
+                    line_to(&mut subpaths, (blend(current.0, c1.0, c2.0, end.0), blend(current.1, c1.1, c2.1, end.1)));
+                }
+                control = Some((c2, true));
+                current = end;
+            }
+            b'Q' | b'T' => {
+                let c1 = if c.eq_ignore_ascii_case(&b'Q') {
+                    coords(&mut parser)?
+                } else {
+                    reflect(last_control, false, current)
+                };
+                let end = coords(&mut parser)?;
+                for k in 1..=CURVE_SEGMENTS {
+                    let t = k as f32 / CURVE_SEGMENTS as f32;
+                    let u = 1.0 - t;
+                    let blend = |a: f32, b: f32, c: f32| u * u * a + 2.0 * u * t * b + t * t * c;
+                    line_to(&mut subpaths, (blend(current.0, c1.0, end.0), blend(current.1, c1.1, end.1)));
+                }
+                control = Some((c1, false));
+                current = end;
+            }
+            b'A' => {
+                let (rx, ry) = (parser.number()?, parser.number()?);
+                let rotation = parser.number()?;
+                let large_arc = parser.flag()?;
+                let sweep = parser.flag()?;
+                let end = coords(&mut parser)?;
+                for p in flatten_arc(current, rx, ry, rotation, large_arc, sweep, end) {
+                    line_to(&mut subpaths, p);
+                }
+                current = end;
+            }
+            b'Z' => {
+                line_to(&mut subpaths, start);
+                current = start;
+            }
+            _ => return Err(format!("Unknown path command '{}'", c as char).into()),
+        }
+        last_control = control;
+    }
+
+    subpaths.retain(|subpath| subpath.len() > 1);
+    Ok(subpaths)
+}
+
+fn line_to(subpaths: &mut Vec<Vec<(f32, f32)>>, p: (f32, f32)) {
+    match subpaths.last_mut() {
+        Some(subpath) => subpath.push(p),
+        None => subpaths.push(vec![(0.0, 0.0), p]),
+    }
+}
+
+/// First control point of a smooth curve: the previous curve's last control
+/// point mirrored through the current point, if that curve was the same kind
+fn reflect(last_control: Option<((f32, f32), bool)>, cubic: bool, current: (f32, f32)) -> (f32, f32) {
+    match last_control {
+        Some((control, was_cubic)) if was_cubic == cubic => (2.0 * current.0 - control.0, 2.0 * current.1 - control.1),
+        _ => current,
+    }
+}
+
+/// Points along an SVG elliptical arc after `from`, ending at `to`
+/// (endpoint to center conversion from the SVG implementation notes)
+fn flatten_arc(
+    from: (f32, f32),
+    rx: f32,
+    ry: f32,
+    rotation: f32,
+    large_arc: bool,
+    sweep: bool,
+    to: (f32, f32),
+) -> Vec<(f32, f32)> {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if from == to {
+        return Vec::new();
+    }
+    if rx == 0.0 || ry == 0.0 {
+        return vec![to];
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let dx = (from.0 - to.0) / 2.0;
+    let dy = (from.1 - to.1) / 2.0;
+    let x1 = cos * dx + sin * dy;
+    let y1 = -sin * dx + cos * dy;
+
+    // Radii too small to reach the end point are scaled up
+    let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+    let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+    let sign = if large_arc == sweep { -1.0 } else { 1.0 };
+    let coef = sign * (numerator / denominator).max(0.0).sqrt();
+    let cx1 = coef * rx * y1 / ry;
+    let cy1 = -coef * ry * x1 / rx;
+    let cx = cos * cx1 - sin * cy1 + (from.0 + to.0) / 2.0;
+    let cy = sin * cx1 + cos * cy1 + (from.1 + to.1) / 2.0;
+
+    let angle = |ux: f32, uy: f32, vx: f32, vy: f32| (ux * vy - uy * vx).atan2(ux * vx + uy * vy);
+    let theta = angle(1.0, 0.0, (x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = angle((x1 - cx1) / rx, (y1 - cy1) / ry, (-x1 - cx1) / rx, (-y1 - cy1) / ry);
+    if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    } else if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    }
+
+    let steps = (delta.abs() / ARC_STEP).ceil().max(1.0) as usize;
+    let mut points: Vec<(f32, f32)> = (1..steps)
+        .map(|k| {
+            let t = theta + delta * k as f32 / steps as f32;
+            let (x, y) = (rx * t.cos(), ry * t.sin());
+            (cx + cos * x - sin * y, cy + sin * x + cos * y)
+        })
+        .collect();
+    // End exactly on the given point
+    points.push(to);
+    points
+}
+
+struct PathParser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl PathParser<'_> {
+    fn at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_separators(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_whitespace() || b == b',') {
+            self.pos += 1;
+        }
+    }
+
+    fn command(&mut self) -> Option<u8> {
+        self.skip_separators();
+        let b = self.peek().filter(u8::is_ascii_alphabetic)?;
+        self.pos += 1;
+        Some(b)
+    }
+
+    fn number(&mut self) -> Result<f32, ScoringError> {
+        self.skip_separators();
+        let start = self.pos;
+        if matches!(self.peek(), Some(b'+' | b'-')) {
+            self.pos += 1;
+        }
+        let mut seen_dot = false;
+        while let Some(b) = self.peek() {
+            match b {
+                b'0'..=b'9' => {}
+                // A second dot starts the next number, as in "0.5.5"
+                b'.' if !seen_dot => seen_dot = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        if matches!(self.peek(), Some(b'e' | b'E'))
+            && self.bytes.get(self.pos + 1).is_some_and(|b| b.is_ascii_digit() || *b == b'+' || *b == b'-')
+        {
+            self.pos += 2;
+            while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                self.pos += 1;
+            }
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos]).ok()
+            .and_then(|text| text.parse::<f32>().ok())
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| format!("Expected a number at position {} of the SVG path", start).into())
+    }
+
+    /// Arc flags may be written without separators, as in "a5 5 0 015 5"
+    fn flag(&mut self) -> Result<bool, ScoringError> {
+        self.skip_separators();
+        match self.peek() {
+            Some(b'0') => { self.pos += 1; Ok(false) }
+            Some(b'1') => { self.pos += 1; Ok(true) }
+            _ => Err(format!("Expected an arc flag at position {} of the SVG path", self.pos).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ink(img: &GrayImage) -> usize {
+        img.pixels().filter(|p| p.0[0] < 128).count()
+    }
+
+    #[test]
+    fn test_parse_path_commands() {
+        // Relative and absolute forms of the same square
+        let absolute = parse_path("M10 10 L20 10 L20 20 L10 20 Z").unwrap();
+        let relative = parse_path("m10,10 h10 v10 h-10 z").unwrap();
+        assert_eq!(absolute, relative);
+        assert_eq!(absolute[0].first(), absolute[0].last());
+
+        // Implicit lines after a move, packed numbers and exponents
+        assert_eq!(parse_path("M0 0 10 0 10-1e1").unwrap(), vec![vec![(0.0, 0.0), (10.0, 0.0), (10.0, -10.0)]]);
+        assert_eq!(parse_path("M.5.5l.5.5").unwrap(), vec![vec![(0.5, 0.5), (1.0, 1.0)]]);
+
+        // Curves and arcs end on their end points
+        let curves = parse_path("M0 0 C0 10 10 10 10 0 S20 -10 20 0 Q25 5 30 0 T40 0 A5 5 0 0 1 50 0 a5 5 0 1110 0").unwrap();
+        let last = *curves[0].last().unwrap();
+        assert!((last.0 - 60.0).abs() < 1e-4 && last.1.abs() < 1e-4);
+        // A half-circle arc bulges by its radius, on the side the sweep
+        // flag picks (positive sweep turns clockwise on screen)
+        let lowest = |path: &str| parse_path(path).unwrap()[0].iter().map(|p| p.1).fold(f32::MIN, f32::max);
+        assert!((lowest("M0 0 A5 5 0 0 0 10 0") - 5.0).abs() < 0.01);
+        assert!(lowest("M0 0 A5 5 0 0 1 10 0").abs() < 0.01);
+
+        assert!(parse_path("L10 10").is_ok());
+        assert!(parse_path("10 10").is_err());
+        assert!(parse_path("M0 0 X5").is_err());
+        assert!(parse_path("M0 0 L5").is_err());
+        assert!(parse_path("M0 0 A5 5 0 2 0 10 10").is_err());
+    }
+
+    #[test]
+    fn test_filled_and_stroked_shapes() {
+        // A square ring: the inner contour runs the other way and cuts a hole
+        let ring = ReferenceShape::from_svg_path("M0 0H100V100H0Z M25 25V75H75V25Z", None).unwrap();
+        let img = ring.render(200);
+        assert_eq!(img.get_pixel(100, 100).0[0], 255);
+        assert_eq!(img.get_pixel(100, 50).0[0], 0);
+        // Fitted to SHAPE_FIT of the image, centered
+        let filled = ink(&img) as f32;
+        let expected = (120.0 * 120.0) * 0.75;
+        assert!((filled - expected).abs() / expected < 0.05);
+
+        // A stroked line is as thick as the scaled pen
+        let line = ReferenceShape::from_svg_path("M0 0 L0 100", Some(10.0)).unwrap();
+        let img = line.render(200);
+        let row: Vec<u8> = (0..200).map(|x| img.get_pixel(x, 100).0[0]).collect();
+        let width = row.iter().filter(|&&v| v < 128).count();
+        assert!((10..=13).contains(&width), "width {}", width);
+
+        assert!(ReferenceShape::from_svg_path("", None).is_err());
+        assert!(ReferenceShape::from_svg_path("M5 5", Some(2.0)).is_err());
+        assert!(ReferenceShape::from_svg_path("M5 5 L5 5", None).is_err());
+        assert!(ReferenceShape::from_svg_path("M0 0 L5 5", Some(0.0)).is_err());
+    }
+}
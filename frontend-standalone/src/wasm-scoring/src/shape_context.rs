@@ -0,0 +1,253 @@
+//! Shape context descriptor matching
+//!
+//! Samples points along two skeletons, describes each point by a log-polar
+//! histogram of where the other points lie, and finds the minimum-cost
+//! one-to-one matching between the two point sets. Slower than the
+//! pixel-distance metrics but much more tolerant of local deformation.
+
+use crate::bit_image::BitImage;
+
+const RADIAL_BINS: usize = 5;
+const ANGULAR_BINS: usize = 12;
+const INNER_RADIUS: f32 = 0.125;
+const OUTER_RADIUS: f32 = 2.0;
+
+/// Compare two binary skeletons with shape contexts
+///
+/// Returns a similarity in [0, 1], or 0 when either skeleton is empty.
+pub fn shape_context_similarity(
+    a: &BitImage,
+    b: &BitImage,
+    max_points: usize,
+) -> f32 {
+    let points_a = collect_points(a);
+    let points_b = collect_points(b);
+
+    let n = max_points.min(points_a.len()).min(points_b.len());
+    if n < 2 {
+        return 0.0;
+    }
+
+    let samples_a = sample_evenly(&points_a, n);
+    let samples_b = sample_evenly(&points_b, n);
+
+    let descriptors_a = describe(&samples_a);
+    let descriptors_b = describe(&samples_b);
+
+    let cost: Vec<Vec<f32>> = descriptors_a.iter()
+        .map(|ha| descriptors_b.iter().map(|hb| chi_squared(ha, hb)).collect())
+        .collect();
+
+    let assignment = hungarian(&cost);
+    let total: f32 = assignment.iter()
+        .enumerate()
+        .map(|(i, &j)| cost[i][j])
+        .sum();
+
+    (1.0 - total / n as f32).clamp(0.0, 1.0)
+}
+
+fn collect_points(binary: &BitImage) -> Vec<(f32, f32)> {
+    binary.ones().map(|(x, y)| (x as f32, y as f32)).collect()
+}
+
+/// Pick `n` points spread evenly through the list
+fn sample_evenly(points: &[(f32, f32)], n: usize) -> Vec<(f32, f32)> {
+    (0..n)
+        .map(|i| points[i * points.len() / n])
+        .collect()
+}
+
+/// Build a normalized log-polar histogram for every point
+fn describe(points: &[(f32, f32)]) -> Vec<Vec<f32>> {
+    let n = points.len();
+
+    // Normalize radii by the mean pairwise distance for scale invariance
+    let mut total = 0.0f32;
+    let mut pairs = 0u32;
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let dx = points[j].0 - points[i].0;
+            let dy = points[j].1 - points[i].1;
+            total += (dx * dx + dy * dy).sqrt();
+            pairs += 1;
+        }
+    }
+    let mean_dist = if pairs > 0 && total > 0.0 { total / pairs as f32 } else { 1.0 };
+
+    let log_inner = INNER_RADIUS.ln();
+    let log_span = OUTER_RADIUS.ln() - log_inner;
+
+    points.iter()
+        .enumerate()
+        .map(|(i, &(px, py))| {
+            let mut histogram = vec![0.0f32; RADIAL_BINS * ANGULAR_BINS];
+            for (j, &(qx, qy)) in points.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let dx = qx - px;
+                let dy = qy - py;
+                let r = (dx * dx + dy * dy).sqrt() / mean_dist;
+                if !(INNER_RADIUS..OUTER_RADIUS).contains(&r) {
+                    continue;
+                }
+
+                let radial = (((r.ln() - log_inner) / log_span) * RADIAL_BINS as f32) as usize;
+                let angle = dy.atan2(dx) + std::f32::consts::PI;
+                let angular = ((angle / (2.0 * std::f32::consts::PI)) * ANGULAR_BINS as f32) as usize;
+                let bin = radial.min(RADIAL_BINS - 1) * ANGULAR_BINS + angular.min(ANGULAR_BINS - 1);
+                histogram[bin] += 1.0;
+            }
+
+            let sum: f32 = histogram.iter().sum();
+            if sum > 0.0 {
+                for v in histogram.iter_mut() {
+                    *v /= sum;
+                }
+            }
+            histogram
+        })
+        .collect()
+}
+
+/// Chi-squared distance between two normalized histograms, in [0, 1]
+fn chi_squared(a: &[f32], b: &[f32]) -> f32 {
+    0.5 * a.iter()
+        .zip(b.iter())
+        .filter(|(&x, &y)| x + y > 0.0)
+        .map(|(&x, &y)| (x - y) * (x - y) / (x + y))
+        .sum::<f32>()
+}
+
+/// Hungarian algorithm for a square cost matrix
+///
+/// Returns, for each row, the column assigned to it in the minimum-cost
+/// perfect matching.
+pub fn hungarian(cost: &[Vec<f32>]) -> Vec<usize> {
+    let n = cost.len();
+    let mut u = vec![0.0f32; n + 1];
+    let mut v = vec![0.0f32; n + 1];
+    let mut p = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+
+    for i in 1..=n {
+        p[0] = i;
+        let mut j0 = 0usize;
+        let mut minv = vec![f32::INFINITY; n + 1];
+        let mut used = vec![false; n + 1];
+
+        loop {
+            used[j0] = true;
+            let i0 = p[j0];
+            let mut delta = f32::INFINITY;
+            let mut j1 = 0usize;
+
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let current = cost[i0 - 1][j - 1] - u[i0] - v[j];
+                if current < minv[j] {
+                    minv[j] = current;
+                    way[j] = j0;
+                }
+                if minv[j] < delta {
+                    delta = minv[j];
+                    j1 = j;
+                }
+            }
+
+            for j in 0..=n {
+                if used[j] {
+                    u[p[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    minv[j] -= delta;
+                }
+            }
+
+            j0 = j1;
+            if p[j0] == 0 {
+                break;
+            }
+        }
+
+        loop {
+            let j1 = way[j0];
+            p[j0] = p[j1];
+            j0 = j1;
+            if j0 == 0 {
+                break;
+            }
+        }
+    }
+
+    let mut assignment = vec![0usize; n];
+    for j in 1..=n {
+        if p[j] > 0 {
+            assignment[p[j] - 1] = j - 1;
+        }
+    }
+    assignment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(width: usize, height: usize, horizontal: bool) -> BitImage {
+        let mut binary = BitImage::new(width, height);
+        for i in 5..(width - 5) {
+            if horizontal {
+                binary.set(i, height / 2, true);
+            } else {
+                binary.set(width / 2, i, true);
+            }
+        }
+        binary
+    }
+
+    #[test]
+    fn test_shape_context_identical() {
+        let a = line(40, 40, true);
+        let score = shape_context_similarity(&a, &a, 32);
+        assert!(score > 0.99);
+    }
+
+    #[test]
+    fn test_shape_context_different_shapes() {
+        let horizontal = line(40, 40, true);
+        let mut ring = BitImage::new(40, 40);
+        for step in 0..120 {
+            let angle = step as f32 / 120.0 * 2.0 * std::f32::consts::PI;
+            let x = (20.0 + 12.0 * angle.cos()) as usize;
+            let y = (20.0 + 12.0 * angle.sin()) as usize;
+            ring.set(x, y, true);
+        }
+
+        let same = shape_context_similarity(&horizontal, &horizontal, 32);
+        let different = shape_context_similarity(&horizontal, &ring, 32);
+        assert!(different < same);
+        assert!(different < 0.7);
+    }
+
+    #[test]
+    fn test_shape_context_empty() {
+        let empty = BitImage::new(10, 10);
+        let a = line(10, 10, true);
+        assert_eq!(shape_context_similarity(&empty, &a, 32), 0.0);
+    }
+
+    #[test]
+    fn test_hungarian_assignment() {
+        let cost = vec![
+            vec![4.0, 1.0, 3.0],
+            vec![2.0, 0.0, 5.0],
+            vec![3.0, 2.0, 2.0],
+        ];
+        let assignment = hungarian(&cost);
+        let total: f32 = assignment.iter().enumerate().map(|(i, &j)| cost[i][j]).sum();
+        assert_eq!(total, 5.0);
+    }
+}
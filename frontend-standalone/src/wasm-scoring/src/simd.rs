@@ -0,0 +1,211 @@
+//! Vector kernels for the morphology and distance transform hot loops
+//!
+//! With the `simd` feature, a wasm32 build with SIMD enabled
+//! (`RUSTFLAGS="-C target-feature=+simd128"`) runs these on 128-bit lanes:
+//! four distances or two mask words at a time. Everywhere else they are
+//! plain loops, which also handle the tails the lanes don't cover.
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+pub(crate) use lanes::*;
+#[cfg(not(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128")))]
+pub(crate) use scalar::*;
+
+#[cfg_attr(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"), allow(dead_code))]
+mod scalar {
+    /// `row = min(row, neighbour + 1)`: one step of a distance scan
+    pub(crate) fn relax(row: &mut [f32], neighbour: &[f32]) {
+        for (value, &next) in row.iter_mut().zip(neighbour) {
+            *value = value.min(next + 1.0);
+        }
+    }
+
+    pub(crate) fn square(values: &mut [f32]) {
+        for value in values {
+            *value *= *value;
+        }
+    }
+
+    /// Square roots, with infinity (nothing reached) clamped to `f32::MAX`
+    pub(crate) fn sqrt_clamped(values: &mut [f32]) {
+        for value in values {
+            *value = value.sqrt().min(f32::MAX);
+        }
+    }
+
+    /// Each pixel of a mask row or'd with its left and right neighbours
+    pub(crate) fn spread(out: &mut [u64], row: &[u64]) {
+        for (i, word) in out.iter_mut().enumerate() {
+            let (left, right) = neighbours(row, i);
+            *word = row[i] | left | right;
+        }
+    }
+
+    /// Each pixel of a mask row and'd with its left and right neighbours
+    pub(crate) fn shrink(out: &mut [u64], row: &[u64]) {
+        for (i, word) in out.iter_mut().enumerate() {
+            let (left, right) = neighbours(row, i);
+            *word = row[i] & left & right;
+        }
+    }
+
+    /// Word `i` of the row shifted one pixel right and one pixel left,
+    /// carrying bits across word boundaries; pixels off the row are clear
+    pub(crate) fn neighbours(row: &[u64], i: usize) -> (u64, u64) {
+        let previous = if i > 0 { row[i - 1] >> 63 } else { 0 };
+        let next = row.get(i + 1).map_or(0, |word| word << 63);
+        (row[i] << 1 | previous, row[i] >> 1 | next)
+    }
+
+    pub(crate) fn or3(out: &mut [u64], a: &[u64], b: &[u64], c: &[u64]) {
+        for (i, word) in out.iter_mut().enumerate() {
+            *word = a[i] | b[i] | c[i];
+        }
+    }
+
+    pub(crate) fn and3(out: &mut [u64], a: &[u64], b: &[u64], c: &[u64]) {
+        for (i, word) in out.iter_mut().enumerate() {
+            *word = a[i] & b[i] & c[i];
+        }
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "wasm32", target_feature = "simd128"))]
+mod lanes {
+    use super::scalar;
+    use core::arch::wasm32::*;
+
+    fn load(values: &[f32]) -> v128 {
+        // SAFETY: callers pass chunks of exactly four values; v128 loads
+        // have no alignment requirement
+        unsafe { v128_load(values.as_ptr() as *const v128) }
+    }
+
+    fn store(values: &mut [f32], vector: v128) {
+        // SAFETY: as for `load`
+        unsafe { v128_store(values.as_mut_ptr() as *mut v128, vector) }
+    }
+
+    fn load_words(words: &[u64]) -> v128 {
+        // SAFETY: callers pass chunks of exactly two words
+        unsafe { v128_load(words.as_ptr() as *const v128) }
+    }
+
+    fn store_words(words: &mut [u64], vector: v128) {
+        // SAFETY: as for `load_words`
+        unsafe { v128_store(words.as_mut_ptr() as *mut v128, vector) }
+    }
+
+    pub(crate) fn relax(row: &mut [f32], neighbour: &[f32]) {
+        let one = f32x4_splat(1.0);
+        let mut rows = row.chunks_exact_mut(4);
+        let mut neighbours = neighbour.chunks_exact(4);
+        for (values, next) in (&mut rows).zip(&mut neighbours) {
+            store(values, f32x4_min(load(values), f32x4_add(load(next), one)));
+        }
+        scalar::relax(rows.into_remainder(), neighbours.remainder());
+    }
+
+    pub(crate) fn square(values: &mut [f32]) {
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            let v = load(chunk);
+            store(chunk, f32x4_mul(v, v));
+        }
+        scalar::square(chunks.into_remainder());
+    }
+
+    pub(crate) fn sqrt_clamped(values: &mut [f32]) {
+        let max = f32x4_splat(f32::MAX);
+        let mut chunks = values.chunks_exact_mut(4);
+        for chunk in &mut chunks {
+            store(chunk, f32x4_min(f32x4_sqrt(load(chunk)), max));
+        }
+        scalar::sqrt_clamped(chunks.into_remainder());
+    }
+
+    /// Word pair `i, i + 1` of the row shifted one pixel right and one
+    /// pixel left, carrying bits between the pair and its neighbours
+    fn neighbour_pair(row: &[u64], i: usize) -> (v128, v128) {
+        let words = load_words(&row[i..i + 2]);
+        let previous = if i > 0 { row[i - 1] >> 63 } else { 0 };
+        let next = row.get(i + 2).map_or(0, |word| word << 63);
+        let carry_right = u64x2(previous, row[i] >> 63);
+        let carry_left = u64x2(row[i + 1] << 63, next);
+        (v128_or(u64x2_shl(words, 1), carry_right), v128_or(u64x2_shr(words, 1), carry_left))
+    }
+
+    pub(crate) fn spread(out: &mut [u64], row: &[u64]) {
+        let pairs = row.len() / 2 * 2;
+        for i in (0..pairs).step_by(2) {
+            let (left, right) = neighbour_pair(row, i);
+            store_words(&mut out[i..i + 2], v128_or(v128_or(load_words(&row[i..i + 2]), left), right));
+        }
+        for i in pairs..row.len() {
+            let (left, right) = scalar::neighbours(row, i);
+            out[i] = row[i] | left | right;
+        }
+    }
+
+    pub(crate) fn shrink(out: &mut [u64], row: &[u64]) {
+        let pairs = row.len() / 2 * 2;
+        for i in (0..pairs).step_by(2) {
+            let (left, right) = neighbour_pair(row, i);
+            store_words(&mut out[i..i + 2], v128_and(v128_and(load_words(&row[i..i + 2]), left), right));
+        }
+        for i in pairs..row.len() {
+            let (left, right) = scalar::neighbours(row, i);
+            out[i] = row[i] & left & right;
+        }
+    }
+
+    pub(crate) fn or3(out: &mut [u64], a: &[u64], b: &[u64], c: &[u64]) {
+        let pairs = out.len() / 2 * 2;
+        for i in (0..pairs).step_by(2) {
+            let v = v128_or(v128_or(load_words(&a[i..i + 2]), load_words(&b[i..i + 2])), load_words(&c[i..i + 2]));
+            store_words(&mut out[i..i + 2], v);
+        }
+        scalar::or3(&mut out[pairs..], &a[pairs..], &b[pairs..], &c[pairs..]);
+    }
+
+    pub(crate) fn and3(out: &mut [u64], a: &[u64], b: &[u64], c: &[u64]) {
+        let pairs = out.len() / 2 * 2;
+        for i in (0..pairs).step_by(2) {
+            let v = v128_and(v128_and(load_words(&a[i..i + 2]), load_words(&b[i..i + 2])), load_words(&c[i..i + 2]));
+            store_words(&mut out[i..i + 2], v);
+        }
+        scalar::and3(&mut out[pairs..], &a[pairs..], &b[pairs..], &c[pairs..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance_kernels() {
+        let mut row = [0.0, f32::INFINITY, 5.0, f32::INFINITY, 2.0, 9.0];
+        relax(&mut row, &[3.0, 1.0, 1.0, f32::INFINITY, 0.0, 0.0]);
+        assert_eq!(row, [0.0, 2.0, 2.0, f32::INFINITY, 1.0, 1.0]);
+
+        square(&mut row);
+        assert_eq!(row, [0.0, 4.0, 4.0, f32::INFINITY, 1.0, 1.0]);
+        sqrt_clamped(&mut row);
+        assert_eq!(row, [0.0, 2.0, 2.0, f32::MAX, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_mask_kernels() {
+        // Bits on both sides of each word boundary
+        let row = [1 << 63, 1, 1];
+        let mut out = [0; 3];
+        spread(&mut out, &row);
+        assert_eq!(out, [3 << 62, 1 << 63 | 3, 3]);
+        shrink(&mut out, &[u64::MAX, u64::MAX, u64::MAX >> 1]);
+        assert_eq!(out, [u64::MAX - 1, u64::MAX, u64::MAX >> 2]);
+
+        or3(&mut out, &[1, 0, 0], &[0, 2, 0], &[0, 0, 4]);
+        assert_eq!(out, [1, 2, 4]);
+        and3(&mut out, &[3, 7, 1], &[1, 6, 1], &[1, 2, 0]);
+        assert_eq!(out, [1, 2, 0]);
+    }
+}
@@ -0,0 +1,519 @@
+//! Skeleton graph extraction and matching
+//!
+//! Turns a one-pixel-wide skeleton into an attributed graph: nodes are
+//! endpoints and junctions, edges are the pixel paths between them with
+//! their length and curvature. Comparing graphs catches structural errors
+//! (a missing bowl on b, an extra loop on l) that pixel metrics blur away.
+
+use crate::bit_image::BitImage;
+use serde::{Serialize, Deserialize};
+
+/// Kind of a skeleton graph node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    /// End of a stroke (one neighbor)
+    Endpoint,
+    /// Point where three or more segments meet
+    Junction,
+    /// Arbitrary anchor placed on a closed loop with no other nodes
+    Loop,
+}
+
+/// A node of the skeleton graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkeletonNode {
+    pub x: f32,
+    pub y: f32,
+    pub kind: NodeKind,
+}
+
+/// A segment of the skeleton between two nodes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkeletonEdge {
+    pub from: usize,
+    pub to: usize,
+    /// Pixel path from `from` to `to`
+    pub points: Vec<(u32, u32)>,
+    /// Path length in pixels
+    pub length: f32,
+    /// Total absolute turning along the path in radians
+    pub curvature: f32,
+}
+
+/// Graph representation of a skeleton
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SkeletonGraph {
+    pub nodes: Vec<SkeletonNode>,
+    pub edges: Vec<SkeletonEdge>,
+    /// Number of connected components
+    pub components: usize,
+}
+
+impl SkeletonGraph {
+    /// Build a graph from a skeleton, removing spurs shorter than `min_spur_length`
+    pub fn from_skeleton(skeleton: &BitImage, min_spur_length: f32) -> Self {
+        let mut current = skeleton.clone();
+
+        // Removing a spur can expose another one, so repeat a few times
+        for _ in 0..3 {
+            let graph = trace_graph(&current);
+            let mut removed = false;
+
+            for edge in &graph.edges {
+                let from = graph.nodes[edge.from].kind;
+                let to = graph.nodes[edge.to].kind;
+                let is_spur = (from == NodeKind::Endpoint && to == NodeKind::Junction)
+                    || (from == NodeKind::Junction && to == NodeKind::Endpoint);
+
+                if is_spur && edge.length < min_spur_length {
+                    // Keep the pixel touching the junction so the junction stays connected
+                    let keep = if from == NodeKind::Junction { 0 } else { edge.points.len() - 1 };
+                    for (i, &(x, y)) in edge.points.iter().enumerate() {
+                        if i != keep {
+                            current.set(x as usize, y as usize, false);
+                        }
+                    }
+                    removed = true;
+                }
+            }
+
+            if !removed {
+                return graph;
+            }
+        }
+
+        trace_graph(&current)
+    }
+
+    pub fn endpoint_count(&self) -> usize {
+        self.nodes.iter().filter(|n| n.kind == NodeKind::Endpoint).count()
+    }
+
+    pub fn junction_count(&self) -> usize {
+        self.nodes.iter().filter(|n| n.kind == NodeKind::Junction).count()
+    }
+
+    /// Number of independent closed loops (cyclomatic number)
+    pub fn loop_count(&self) -> usize {
+        (self.edges.len() + self.components).saturating_sub(self.nodes.len())
+    }
+
+    pub fn total_length(&self) -> f32 {
+        self.edges.iter().map(|e| e.length).sum()
+    }
+}
+
+/// Compare two skeleton graphs structurally
+///
+/// Combines a topology term (endpoint, junction, loop and component counts)
+/// with an edge term comparing the length and curvature distribution of
+/// the segments. Returns a similarity in [0, 1].
+pub fn graph_similarity(a: &SkeletonGraph, b: &SkeletonGraph) -> f32 {
+    if a.edges.is_empty() || b.edges.is_empty() {
+        return 0.0;
+    }
+
+    let diff = |x: usize, y: usize| (x as f32 - y as f32).abs();
+    let penalty = 0.15 * diff(a.endpoint_count(), b.endpoint_count())
+        + 0.15 * diff(a.junction_count(), b.junction_count())
+        + 0.3 * diff(a.loop_count(), b.loop_count())
+        + 0.2 * diff(a.components, b.components);
+    let topology = (1.0 - penalty).max(0.0);
+
+    // Pair edges by rank of normalized length, longest first
+    let profile = |g: &SkeletonGraph| -> Vec<(f32, f32)> {
+        let total = g.total_length().max(1.0);
+        let mut edges: Vec<(f32, f32)> = g.edges.iter()
+            .map(|e| (e.length / total, e.curvature))
+            .collect();
+        edges.sort_by(|x, y| y.0.total_cmp(&x.0));
+        edges
+    };
+    let pa = profile(a);
+    let pb = profile(b);
+
+    let mut cost = 0.0f32;
+    for i in 0..pa.len().max(pb.len()) {
+        match (pa.get(i), pb.get(i)) {
+            (Some(&(la, ca)), Some(&(lb, cb))) => {
+                let curvature = (ca - cb).abs() / std::f32::consts::PI;
+                cost += (la - lb).abs() + 0.25 * la.max(lb) * curvature.min(1.0);
+            }
+            (Some(&(l, _)), None) | (None, Some(&(l, _))) => cost += l,
+            (None, None) => {}
+        }
+    }
+    let edge_similarity = (1.0 - cost / 2.0).clamp(0.0, 1.0);
+
+    topology * 0.6 + edge_similarity * 0.4
+}
+
+const NEIGHBOR_OFFSETS: [(i32, i32); 8] = [
+    (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1),
+];
+
+/// Classify a skeleton pixel by its 8-neighborhood
+fn classify(skeleton: &BitImage, x: i32, y: i32) -> Option<NodeKind> {
+    let ring: Vec<bool> = NEIGHBOR_OFFSETS.iter()
+        .map(|&(dx, dy)| skeleton.get_signed(x + dx, y + dy))
+        .collect();
+    let neighbors = ring.iter().filter(|&&v| v).count();
+    let transitions = (0..8).filter(|&i| !ring[i] && ring[(i + 1) % 8]).count();
+
+    if neighbors <= 1 || (transitions == 1 && neighbors == 2) {
+        Some(NodeKind::Endpoint)
+    } else if transitions >= 3 {
+        Some(NodeKind::Junction)
+    } else {
+        None
+    }
+}
+
+fn trace_graph(skeleton: &BitImage) -> SkeletonGraph {
+    let (width, height) = (skeleton.width(), skeleton.height());
+    let mut graph = SkeletonGraph::default();
+
+    // Node label per pixel (clusters of adjacent junction pixels share a node)
+    let mut node_of: Vec<Option<usize>> = vec![None; width * height];
+    let mut node_pixels: Vec<Vec<(i32, i32)>> = Vec::new();
+
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let idx = y as usize * width + x as usize;
+            if !skeleton.get(x as usize, y as usize) || node_of[idx].is_some() {
+                continue;
+            }
+            let Some(kind) = classify(skeleton, x, y) else {
+                continue;
+            };
+
+            let id = graph.nodes.len();
+            let mut cluster = vec![(x, y)];
+            node_of[idx] = Some(id);
+
+            if kind == NodeKind::Junction {
+                let mut i = 0;
+                while i < cluster.len() {
+                    let (cx, cy) = cluster[i];
+                    for &(dx, dy) in &NEIGHBOR_OFFSETS {
+                        let (nx, ny) = (cx + dx, cy + dy);
+                        if !skeleton.get_signed(nx, ny) {
+                            continue;
+                        }
+                        let nidx = ny as usize * width + nx as usize;
+                        if node_of[nidx].is_none()
+                            && classify(skeleton, nx, ny) == Some(NodeKind::Junction)
+                        {
+                            node_of[nidx] = Some(id);
+                            cluster.push((nx, ny));
+                        }
+                    }
+                    i += 1;
+                }
+            }
+
+            let n = cluster.len() as f32;
+            graph.nodes.push(SkeletonNode {
+                x: cluster.iter().map(|p| p.0 as f32).sum::<f32>() / n,
+                y: cluster.iter().map(|p| p.1 as f32).sum::<f32>() / n,
+                kind,
+            });
+            node_pixels.push(cluster);
+        }
+    }
+
+    let mut visited = vec![false; width * height];
+
+    // Trace edges leaving every node
+    for start in 0..graph.nodes.len() {
+        for &(sx, sy) in &node_pixels[start].clone() {
+            for &(dx, dy) in &NEIGHBOR_OFFSETS {
+                let (nx, ny) = (sx + dx, sy + dy);
+                if !skeleton.get_signed(nx, ny) {
+                    continue;
+                }
+                let nidx = ny as usize * width + nx as usize;
+                if visited[nidx] {
+                    continue;
+                }
+                if let Some(other) = node_of[nidx] {
+                    // Directly adjacent distinct nodes form a zero-length edge once
+                    if other > start {
+                        let already = graph.edges.iter().any(|e| {
+                            (e.from == start && e.to == other) || (e.from == other && e.to == start)
+                        });
+                        if !already {
+                            graph.edges.push(make_edge(start, other, vec![(sx, sy), (nx, ny)]));
+                        }
+                    }
+                    continue;
+                }
+
+                let (end, path) = walk(skeleton, &node_of, &mut visited, (sx, sy), (nx, ny), start);
+                let end = match end {
+                    Some(end) => end,
+                    None => {
+                        // Dead end without an endpoint classification
+                        let &(ex, ey) = path.last().unwrap();
+                        graph.nodes.push(SkeletonNode { x: ex as f32, y: ey as f32, kind: NodeKind::Endpoint });
+                        node_pixels.push(vec![(ex, ey)]);
+                        graph.nodes.len() - 1
+                    }
+                };
+                graph.edges.push(make_edge(start, end, path));
+            }
+        }
+    }
+
+    // Closed loops without any node
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let idx = y as usize * width + x as usize;
+            if !skeleton.get(x as usize, y as usize) || visited[idx] || node_of[idx].is_some() {
+                continue;
+            }
+
+            let id = graph.nodes.len();
+            graph.nodes.push(SkeletonNode { x: x as f32, y: y as f32, kind: NodeKind::Loop });
+            node_of[idx] = Some(id);
+            visited[idx] = true;
+
+            let next = NEIGHBOR_OFFSETS.iter()
+                .map(|&(dx, dy)| (x + dx, y + dy))
+                .find(|&(nx, ny)| {
+                    skeleton.get_signed(nx, ny) && !visited[ny as usize * width + nx as usize]
+                });
+
+            match next {
+                Some(next) => {
+                    let (_, mut path) = walk(skeleton, &node_of, &mut visited, (x, y), next, id);
+                    if path.last() != Some(&(x, y)) {
+                        path.push((x, y));
+                    }
+                    graph.edges.push(make_edge(id, id, path));
+                }
+                None => {
+                    graph.nodes[id].kind = NodeKind::Endpoint;
+                }
+            }
+        }
+    }
+
+    graph.components = count_components(skeleton);
+    graph
+}
+
+/// Follow a path of degree-2 pixels until reaching a node pixel
+fn walk(
+    skeleton: &BitImage,
+    node_of: &[Option<usize>],
+    visited: &mut [bool],
+    start: (i32, i32),
+    first: (i32, i32),
+    start_node: usize,
+) -> (Option<usize>, Vec<(i32, i32)>) {
+    let width = skeleton.width();
+    let mut path = vec![start, first];
+    visited[first.1 as usize * width + first.0 as usize] = true;
+    let mut current = first;
+
+    loop {
+        let mut next_free = None;
+        let mut next_node = None;
+
+        for &(dx, dy) in &NEIGHBOR_OFFSETS {
+            let (nx, ny) = (current.0 + dx, current.1 + dy);
+            if !skeleton.get_signed(nx, ny) {
+                continue;
+            }
+            let nidx = ny as usize * width + nx as usize;
+            let previous = path[path.len() - 2];
+
+            if let Some(node) = node_of[nidx] {
+                // Don't step straight back into the node we just left
+                if (nx, ny) != previous && (node != start_node || path.len() > 3) {
+                    next_node = Some((node, (nx, ny)));
+                }
+            } else if !visited[nidx] {
+                // Prefer 4-connected steps to avoid cutting corners
+                let straight = dx == 0 || dy == 0;
+                if next_free.is_none() || straight {
+                    next_free = Some((nx, ny));
+                }
+            }
+        }
+
+        if let Some((node, pixel)) = next_node {
+            path.push(pixel);
+            return (Some(node), path);
+        }
+
+        match next_free {
+            Some(pixel) => {
+                visited[pixel.1 as usize * width + pixel.0 as usize] = true;
+                path.push(pixel);
+                current = pixel;
+            }
+            None => return (None, path),
+        }
+    }
+}
+
+fn make_edge(from: usize, to: usize, path: Vec<(i32, i32)>) -> SkeletonEdge {
+    let points: Vec<(u32, u32)> = path.iter().map(|&(x, y)| (x as u32, y as u32)).collect();
+
+    let mut length = 0.0f32;
+    for w in path.windows(2) {
+        let dx = (w[1].0 - w[0].0) as f32;
+        let dy = (w[1].1 - w[0].1) as f32;
+        length += (dx * dx + dy * dy).sqrt();
+    }
+
+    // Measure turning on a coarser stride so pixel staircases don't count
+    let stride = 3;
+    let samples: Vec<(i32, i32)> = path.iter().step_by(stride).copied().collect();
+    let mut curvature = 0.0f32;
+    for w in samples.windows(3) {
+        let a1 = ((w[1].1 - w[0].1) as f32).atan2((w[1].0 - w[0].0) as f32);
+        let a2 = ((w[2].1 - w[1].1) as f32).atan2((w[2].0 - w[1].0) as f32);
+        let mut turn = a2 - a1;
+        while turn > std::f32::consts::PI {
+            turn -= 2.0 * std::f32::consts::PI;
+        }
+        while turn < -std::f32::consts::PI {
+            turn += 2.0 * std::f32::consts::PI;
+        }
+        curvature += turn.abs();
+    }
+
+    SkeletonEdge { from, to, points, length, curvature }
+}
+
+fn count_components(skeleton: &BitImage) -> usize {
+    let width = skeleton.width();
+    let mut seen = vec![false; width * skeleton.height()];
+    let mut components = 0;
+    let mut stack = Vec::new();
+
+    for start in skeleton.indices() {
+        if seen[start] {
+            continue;
+        }
+        components += 1;
+        seen[start] = true;
+        stack.push(start);
+
+        while let Some(idx) = stack.pop() {
+            let x = (idx % width) as i32;
+            let y = (idx / width) as i32;
+            for &(dx, dy) in &NEIGHBOR_OFFSETS {
+                let (nx, ny) = (x + dx, y + dy);
+                if skeleton.get_signed(nx, ny) {
+                    let nidx = ny as usize * width + nx as usize;
+                    if !seen[nidx] {
+                        seen[nidx] = true;
+                        stack.push(nidx);
+                    }
+                }
+            }
+        }
+    }
+
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(image: &mut [bool], width: usize, x: usize, y: usize) {
+        image[y * width + x] = true;
+    }
+
+    #[test]
+    fn test_graph_straight_line() {
+        let mut skeleton = vec![false; 20 * 20];
+        for x in 3..17 {
+            set(&mut skeleton, 20, x, 10);
+        }
+
+        let graph = SkeletonGraph::from_skeleton(&BitImage::from_bools(&skeleton, 20, 20), 0.0);
+
+        assert_eq!(graph.endpoint_count(), 2);
+        assert_eq!(graph.junction_count(), 0);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.loop_count(), 0);
+        assert!((graph.edges[0].length - 13.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_graph_closed_loop() {
+        // Square ring without endpoints or junctions
+        let mut skeleton = vec![false; 20 * 20];
+        for i in 4..16 {
+            set(&mut skeleton, 20, i, 4);
+            set(&mut skeleton, 20, i, 15);
+            set(&mut skeleton, 20, 4, i);
+            set(&mut skeleton, 20, 15, i);
+        }
+
+        let graph = SkeletonGraph::from_skeleton(&BitImage::from_bools(&skeleton, 20, 20), 0.0);
+
+        assert_eq!(graph.endpoint_count(), 0);
+        assert_eq!(graph.loop_count(), 1);
+        assert_eq!(graph.components, 1);
+    }
+
+    #[test]
+    fn test_graph_t_junction() {
+        let mut skeleton = vec![false; 21 * 21];
+        for x in 3..18 {
+            set(&mut skeleton, 21, x, 5);
+        }
+        for y in 6..18 {
+            set(&mut skeleton, 21, 10, y);
+        }
+
+        let graph = SkeletonGraph::from_skeleton(&BitImage::from_bools(&skeleton, 21, 21), 0.0);
+
+        assert_eq!(graph.endpoint_count(), 3);
+        assert_eq!(graph.junction_count(), 1);
+        assert_eq!(graph.edges.len(), 3);
+    }
+
+    #[test]
+    fn test_graph_removes_short_spurs() {
+        // Long line with a 2-pixel spur
+        let mut skeleton = vec![false; 21 * 21];
+        for x in 2..19 {
+            set(&mut skeleton, 21, x, 10);
+        }
+        set(&mut skeleton, 21, 10, 9);
+        set(&mut skeleton, 21, 10, 8);
+
+        let graph = SkeletonGraph::from_skeleton(&BitImage::from_bools(&skeleton, 21, 21), 4.0);
+
+        assert_eq!(graph.endpoint_count(), 2);
+    }
+
+    #[test]
+    fn test_graph_similarity() {
+        let mut line = vec![false; 20 * 20];
+        let mut ring = vec![false; 20 * 20];
+        for i in 4..16 {
+            set(&mut line, 20, i, 10);
+            set(&mut ring, 20, i, 4);
+            set(&mut ring, 20, i, 15);
+            set(&mut ring, 20, 4, i);
+            set(&mut ring, 20, 15, i);
+        }
+
+        let line = SkeletonGraph::from_skeleton(&BitImage::from_bools(&line, 20, 20), 0.0);
+        let ring = SkeletonGraph::from_skeleton(&BitImage::from_bools(&ring, 20, 20), 0.0);
+
+        assert!(graph_similarity(&line, &line) > 0.99);
+        assert!(graph_similarity(&line, &ring) < 0.7);
+        assert_eq!(graph_similarity(&line, &SkeletonGraph::default()), 0.0);
+    }
+}
@@ -0,0 +1,857 @@
+//! Temporal stroke input
+//!
+//! Stroke data recorded from pointer events, used for metrics that depend
+//! on how a character was drawn rather than only on the final image.
+
+use crate::shape_context::hungarian;
+use crate::templates::CharacterTemplate;
+use image::{GrayImage, ImageBuffer, Luma};
+use serde::{Serialize, Deserialize};
+
+/// Points each stroke is resampled to before comparison
+const RESAMPLE_POINTS: usize = 16;
+
+/// Cost assigned to leaving a stroke unmatched
+const UNMATCHED_COST: f32 = 1.0;
+
+/// How far (in the unit frame) a stroke may start from the template's start
+const START_TOLERANCE: f32 = 0.2;
+
+/// A pause longer than this (ms) inside one recorded stroke is a pen lift
+const PEN_LIFT_PAUSE_MS: f64 = 150.0;
+
+/// A jump longer than this fraction of the drawing's extent (and at least
+/// `MIN_PEN_LIFT_JUMP` canvas units) is a pen lift
+const PEN_LIFT_JUMP: f32 = 0.15;
+const MIN_PEN_LIFT_JUMP: f32 = 10.0;
+
+/// Pressure reported by devices without pressure sensing (per Pointer Events)
+const NOMINAL_PRESSURE: f32 = 0.5;
+
+/// A single sampled pointer position
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct StrokePoint {
+    pub x: f32,
+    pub y: f32,
+    /// Timestamp in milliseconds, if recorded
+    #[serde(default)]
+    pub t: Option<f64>,
+    /// Pen pressure in [0, 1] from pointer events, if reported
+    #[serde(default)]
+    pub pressure: Option<f32>,
+}
+
+/// One continuous pen-down to pen-up stroke
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Stroke {
+    pub points: Vec<StrokePoint>,
+    /// Eraser stroke: removes ink instead of adding it
+    #[serde(default)]
+    pub erase: bool,
+    /// Pen or eraser width in canvas units, if not the default
+    #[serde(default)]
+    pub width: Option<f32>,
+}
+
+/// Per-stroke start-point and direction check, for coaching animations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StrokeDirection {
+    /// Index of the drawn stroke
+    pub stroke: u32,
+    /// Index of the template stroke it was matched to, if any
+    pub template_stroke: Option<u32>,
+    /// Whether the stroke began near the template stroke's conventional start
+    pub start_ok: bool,
+    /// Whether the stroke was drawn in the template direction
+    pub direction_ok: bool,
+}
+
+/// Drawn strokes paired with template strokes in a common frame
+struct StrokeMatch {
+    drawn: Vec<Vec<(f32, f32)>>,
+    expected: Vec<Vec<(f32, f32)>>,
+    /// (drawn index, template index) pairs in drawn order
+    pairs: Vec<(usize, usize)>,
+    /// Whether each drawn/template pair runs the same way
+    forward: Vec<Vec<bool>>,
+    /// Size of the padded assignment problem
+    size: usize,
+}
+
+/// Normalize, resample and pair drawn strokes with template strokes
+///
+/// Pairing is a minimum-cost matching on mean point distance, trying each
+/// template stroke in both directions. Returns `None` if either side is empty.
+fn match_strokes(strokes: &[Stroke], template: &CharacterTemplate) -> Option<StrokeMatch> {
+    let drawn: Vec<Vec<(f32, f32)>> = strokes.iter()
+        .filter(|s| !s.points.is_empty())
+        .map(|s| s.points.iter().map(|p| (p.x, p.y)).collect())
+        .collect();
+    let expected: Vec<Vec<(f32, f32)>> = template.strokes.iter()
+        .map(|s| s.points.clone())
+        .collect();
+
+    if drawn.is_empty() || expected.is_empty() {
+        return None;
+    }
+
+    let drawn = normalize_to_unit(&drawn);
+    let expected = normalize_to_unit(&expected);
+
+    let drawn: Vec<Vec<(f32, f32)>> = drawn.iter().map(|s| resample(s, RESAMPLE_POINTS)).collect();
+    let expected: Vec<Vec<(f32, f32)>> = expected.iter().map(|s| resample(s, RESAMPLE_POINTS)).collect();
+
+    // Square cost matrix, padded so extra strokes on either side go unmatched
+    let n = drawn.len().max(expected.len());
+    let mut cost = vec![vec![UNMATCHED_COST; n]; n];
+    let mut forward = vec![vec![true; n]; n];
+    for (i, d) in drawn.iter().enumerate() {
+        for (j, e) in expected.iter().enumerate() {
+            let fwd = mean_distance(d, e, false);
+            let rev = mean_distance(d, e, true);
+            cost[i][j] = fwd.min(rev);
+            forward[i][j] = fwd <= rev;
+        }
+    }
+
+    let assignment = hungarian(&cost);
+    let pairs: Vec<(usize, usize)> = assignment.iter()
+        .enumerate()
+        .filter(|&(i, &j)| i < drawn.len() && j < expected.len())
+        .map(|(i, &j)| (i, j))
+        .collect();
+
+    Some(StrokeMatch { drawn, expected, pairs, forward, size: n })
+}
+
+/// Mapping from raw pointer coordinates to scoring space
+///
+/// Points are scaled, then rotated about the origin, then offset:
+/// `p' = rotate(p * scale, rotation) + (offset_x, offset_y)`. Useful for
+/// zoomable or pannable canvases whose pointer events are not in image
+/// pixels.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StrokeTransform {
+    pub scale: f32,
+    /// Rotation in degrees, clockwise on screen (y pointing down)
+    pub rotation: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+}
+
+impl Default for StrokeTransform {
+    fn default() -> Self {
+        Self { scale: 1.0, rotation: 0.0, offset_x: 0.0, offset_y: 0.0 }
+    }
+}
+
+impl StrokeTransform {
+    /// Map a single point
+    pub fn apply_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let (sin, cos) = self.rotation.to_radians().sin_cos();
+        let (x, y) = (x * self.scale, y * self.scale);
+        (x * cos - y * sin + self.offset_x, x * sin + y * cos + self.offset_y)
+    }
+
+    /// Map every point of a stroke, scaling its width to match
+    pub fn apply(&self, stroke: &Stroke) -> Stroke {
+        Stroke {
+            points: stroke.points.iter()
+                .map(|p| {
+                    let (x, y) = self.apply_point(p.x, p.y);
+                    StrokePoint { x, y, ..*p }
+                })
+                .collect(),
+            erase: stroke.erase,
+            width: stroke.width.map(|w| w * self.scale.abs()),
+        }
+    }
+}
+
+/// One entry of a drawing's event log
+///
+/// Serialized as `{ type: "stroke", points }`, `{ type: "undo" }`,
+/// `{ type: "redo" }` or `{ type: "clear" }`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StrokeEvent {
+    Stroke(Stroke),
+    Undo,
+    Redo,
+    Clear,
+}
+
+/// An undoable change to the visible strokes
+enum Action {
+    Add(Stroke),
+    Clear(Vec<Stroke>),
+}
+
+/// Replay an event log and return the strokes still visible at the end
+///
+/// Undo and redo follow the usual editor rules: a new stroke or clear
+/// discards anything that could have been redone, and clearing can itself
+/// be undone.
+pub fn replay_events(events: &[StrokeEvent]) -> Vec<Stroke> {
+    let mut visible: Vec<Stroke> = Vec::new();
+    let mut history: Vec<Action> = Vec::new();
+    let mut redo: Vec<Action> = Vec::new();
+
+    for event in events {
+        match event {
+            StrokeEvent::Stroke(stroke) => {
+                visible.push(stroke.clone());
+                history.push(Action::Add(stroke.clone()));
+                redo.clear();
+            }
+            StrokeEvent::Clear => {
+                history.push(Action::Clear(std::mem::take(&mut visible)));
+                redo.clear();
+            }
+            StrokeEvent::Undo => match history.pop() {
+                Some(Action::Add(stroke)) => {
+                    visible.pop();
+                    redo.push(Action::Add(stroke));
+                }
+                Some(Action::Clear(cleared)) => {
+                    visible = cleared.clone();
+                    redo.push(Action::Clear(cleared));
+                }
+                None => {}
+            },
+            StrokeEvent::Redo => match redo.pop() {
+                Some(Action::Add(stroke)) => {
+                    visible.push(stroke.clone());
+                    history.push(Action::Add(stroke));
+                }
+                Some(Action::Clear(cleared)) => {
+                    visible.clear();
+                    history.push(Action::Clear(cleared));
+                }
+                None => {}
+            },
+        }
+    }
+
+    visible
+}
+
+/// Score how well the order and direction of drawn strokes follow a template
+///
+/// Strokes are normalized to a common frame, paired with template strokes by
+/// minimum-cost matching, and scored on whether the pairs appear in template
+/// order and are drawn in the template direction. Missing or extra strokes
+/// scale the score down. Returns a value in [0, 1].
+pub fn stroke_order_score(strokes: &[Stroke], template: &CharacterTemplate) -> f32 {
+    let matched = match match_strokes(strokes, template) {
+        Some(m) => m,
+        None => return 0.0,
+    };
+    let pairs = &matched.pairs;
+
+    if pairs.is_empty() {
+        return 0.0;
+    }
+
+    // Fraction of stroke pairs drawn in the same relative order as the template
+    let mut concordant = 0u32;
+    let mut comparisons = 0u32;
+    for a in 0..pairs.len() {
+        for b in (a + 1)..pairs.len() {
+            comparisons += 1;
+            if pairs[a].1 < pairs[b].1 {
+                concordant += 1;
+            }
+        }
+    }
+    let order = if comparisons > 0 { concordant as f32 / comparisons as f32 } else { 1.0 };
+
+    let direction = pairs.iter().filter(|&&(i, j)| matched.forward[i][j]).count() as f32 / pairs.len() as f32;
+
+    let completeness = pairs.len() as f32 / matched.size as f32;
+
+    ((order * 0.5 + direction * 0.5) * completeness).clamp(0.0, 1.0)
+}
+
+/// Check where each drawn stroke started and which way it went
+///
+/// A stroke's start is accepted if it lies within `START_TOLERANCE` (in the
+/// unit frame) of the matched template stroke's first point. Unmatched
+/// strokes are flagged as wrong on both counts.
+pub fn stroke_directions(strokes: &[Stroke], template: &CharacterTemplate) -> Vec<StrokeDirection> {
+    let matched = match match_strokes(strokes, template) {
+        Some(m) => m,
+        None => return Vec::new(),
+    };
+
+    (0..matched.drawn.len())
+        .map(|i| {
+            let pair = matched.pairs.iter().find(|&&(d, _)| d == i);
+            match pair {
+                Some(&(_, j)) => {
+                    let (dx, dy) = matched.drawn[i][0];
+                    let (ex, ey) = matched.expected[j][0];
+                    let start_distance = ((dx - ex).powi(2) + (dy - ey).powi(2)).sqrt();
+                    StrokeDirection {
+                        stroke: i as u32,
+                        template_stroke: Some(j as u32),
+                        start_ok: start_distance <= START_TOLERANCE,
+                        direction_ok: matched.forward[i][j],
+                    }
+                }
+                None => StrokeDirection {
+                    stroke: i as u32,
+                    template_stroke: None,
+                    start_ok: false,
+                    direction_ok: false,
+                },
+            }
+        })
+        .collect()
+}
+
+/// Count the strokes actually drawn
+///
+/// Each recorded stroke counts once, plus any pen lifts detected inside it
+/// (a long pause or a large jump between consecutive points), since some
+/// input pipelines merge everything into a single point list.
+pub fn count_strokes(strokes: &[Stroke]) -> u32 {
+    let mut min_x = f32::MAX;
+    let mut min_y = f32::MAX;
+    let mut max_x = f32::MIN;
+    let mut max_y = f32::MIN;
+    for p in strokes.iter().flat_map(|s| s.points.iter()) {
+        min_x = min_x.min(p.x);
+        min_y = min_y.min(p.y);
+        max_x = max_x.max(p.x);
+        max_y = max_y.max(p.y);
+    }
+    let jump_limit = ((max_x - min_x).max(max_y - min_y) * PEN_LIFT_JUMP).max(MIN_PEN_LIFT_JUMP);
+
+    let mut count = 0;
+    for stroke in strokes.iter().filter(|s| !s.points.is_empty()) {
+        count += 1;
+        for w in stroke.points.windows(2) {
+            let paused = match (w[0].t, w[1].t) {
+                (Some(t0), Some(t1)) => t1 - t0 > PEN_LIFT_PAUSE_MS,
+                _ => false,
+            };
+            let jump = ((w[1].x - w[0].x).powi(2) + (w[1].y - w[0].y).powi(2)).sqrt();
+            if paused || jump > jump_limit {
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Time from the first to the last timestamped point, in milliseconds
+pub fn drawing_duration(strokes: &[Stroke]) -> Option<f64> {
+    let mut times = strokes.iter().flat_map(|s| s.points.iter()).filter_map(|p| p.t);
+    let first = times.next()?;
+    let (min, max) = times.fold((first, first), |(lo, hi), t| (lo.min(t), hi.max(t)));
+    Some(max - min)
+}
+
+/// Whether any point carries a pressure reading
+pub fn has_pressure(strokes: &[Stroke]) -> bool {
+    strokes.iter().flat_map(|s| s.points.iter()).any(|p| p.pressure.is_some())
+}
+
+/// Strokes that put down ink, leaving out eraser strokes
+pub fn ink_strokes(strokes: &[Stroke]) -> Vec<Stroke> {
+    strokes.iter().filter(|s| !s.erase).cloned().collect()
+}
+
+/// Strokes with hand tremor averaged out
+///
+/// Each point moves to the mean of the points within `radius` canvas units
+/// of it along its stroke, the reach shrinking toward the ends so strokes
+/// still start and finish where they did. Timing, pressure and widths are
+/// kept.
+pub fn smooth_strokes(strokes: &[Stroke], radius: f32) -> Vec<Stroke> {
+    strokes.iter()
+        .map(|stroke| {
+            let points = &stroke.points;
+            let mut along = Vec::with_capacity(points.len());
+            let mut sums = vec![(0.0f32, 0.0f32)];
+            let mut length = 0.0f32;
+            for (i, p) in points.iter().enumerate() {
+                if i > 0 {
+                    length += ((p.x - points[i - 1].x).powi(2) + (p.y - points[i - 1].y).powi(2)).sqrt();
+                }
+                along.push(length);
+                let &(sx, sy) = sums.last().unwrap();
+                sums.push((sx + p.x, sy + p.y));
+            }
+
+            let smoothed = points.iter().zip(&along)
+                .map(|(p, &s)| {
+                    let reach = radius.min(s).min(length - s);
+                    let start = along.partition_point(|&t| t < s - reach);
+                    let end = along.partition_point(|&t| t <= s + reach);
+                    let n = (end - start) as f32;
+                    StrokePoint { x: (sums[end].0 - sums[start].0) / n, y: (sums[end].1 - sums[start].1) / n, ..*p }
+                })
+                .collect();
+            Stroke { points: smoothed, ..stroke.clone() }
+        })
+        .collect()
+}
+
+/// Rasterize strokes to a white image with black ink
+///
+/// Strokes are drawn in order, each point as a disc interpolated along
+/// every segment. Pen width is the stroke's own `width` or `base_width`,
+/// scaled by pressure from half at no pressure to 1.5× at full pressure;
+/// points without pressure use the nominal value, giving exactly that
+/// width. Eraser strokes clear pixels at their width, ignoring pressure.
+pub fn rasterize_strokes(strokes: &[Stroke], width: u32, height: u32, base_width: f32) -> GrayImage {
+    let mut img: GrayImage = ImageBuffer::from_pixel(width, height, Luma([255u8]));
+
+    for stroke in strokes {
+        let stroke_width = stroke.width.unwrap_or(base_width);
+        let erase = stroke.erase;
+        let value = if erase { 255u8 } else { 0u8 };
+        let radius_at = |p: &StrokePoint| {
+            let pressure = if erase { NOMINAL_PRESSURE } else { p.pressure.unwrap_or(NOMINAL_PRESSURE).clamp(0.0, 1.0) };
+            (stroke_width * (0.5 + pressure) / 2.0).max(0.5)
+        };
+
+        if let [only] = stroke.points.as_slice() {
+            if only.x.is_finite() && only.y.is_finite() {
+                stamp_disc(&mut img, only.x, only.y, radius_at(only), value);
+            }
+        }
+        for w in stroke.points.windows(2) {
+            let (r0, r1) = (radius_at(&w[0]), radius_at(&w[1]));
+            // Only the part of the segment whose discs can reach the canvas
+            // is stepped along, however far outside it the points are
+            let Some((start, end)) = clip_segment(&w[0], &w[1], r0.max(r1), width as f32, height as f32) else {
+                continue;
+            };
+            let (a, b) = (along(&w[0], &w[1], start), along(&w[0], &w[1], end));
+            let (ra, rb) = (r0 + (r1 - r0) * start as f32, r0 + (r1 - r0) * end as f32);
+            let length = ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt();
+            let steps = (length / (r0.min(r1) * 0.5).max(0.5)).ceil().max(1.0) as u32;
+            for k in 0..=steps {
+                let t = k as f32 / steps as f32;
+                stamp_disc(&mut img, a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t, ra + (rb - ra) * t, value);
+            }
+        }
+    }
+
+    img
+}
+
+/// The range of `t` in `[0, 1]` where `along(a, b, t)` lies within
+/// `margin` of a `width` by `height` canvas (Liang-Barsky), if any; `None`
+/// too for non-finite points. Worked in f64, as points may be far enough
+/// out that f32 loses whole pixels.
+fn clip_segment(a: &StrokePoint, b: &StrokePoint, margin: f32, width: f32, height: f32) -> Option<(f64, f64)> {
+    if ![a.x, a.y, b.x, b.y].iter().all(|v| v.is_finite()) {
+        return None;
+    }
+    let (margin, width, height) = (margin as f64, width as f64, height as f64);
+    let (ax, ay, bx, by) = (a.x as f64, a.y as f64, b.x as f64, b.y as f64);
+    let (mut start, mut end) = (0.0f64, 1.0f64);
+    for (from, delta, low, high) in [(ax, bx - ax, -margin, width + margin), (ay, by - ay, -margin, height + margin)] {
+        if delta == 0.0 {
+            if from < low || from > high {
+                return None;
+            }
+            continue;
+        }
+        let (t0, t1) = ((low - from) / delta, (high - from) / delta);
+        start = start.max(t0.min(t1));
+        end = end.min(t0.max(t1));
+    }
+    (start <= end).then_some((start, end))
+}
+
+fn along(a: &StrokePoint, b: &StrokePoint, t: f64) -> (f32, f32) {
+    let lerp = |from: f32, to: f32| (from as f64 + (to as f64 - from as f64) * t) as f32;
+    (lerp(a.x, b.x), lerp(a.y, b.y))
+}
+
+fn stamp_disc(img: &mut GrayImage, cx: f32, cy: f32, radius: f32, value: u8) {
+    let (width, height) = img.dimensions();
+    let min_x = (cx - radius).floor().max(0.0) as u32;
+    let min_y = (cy - radius).floor().max(0.0) as u32;
+    let max_x = (cx + radius).ceil().min(width as f32 - 1.0);
+    let max_y = (cy + radius).ceil().min(height as f32 - 1.0);
+    if max_x < 0.0 || max_y < 0.0 {
+        return;
+    }
+
+    for y in min_y..=max_y as u32 {
+        for x in min_x..=max_x as u32 {
+            let dx = x as f32 + 0.5 - cx;
+            let dy = y as f32 + 0.5 - cy;
+            if dx * dx + dy * dy <= radius * radius {
+                img.put_pixel(x, y, Luma([value]));
+            }
+        }
+    }
+}
+
+/// How steady the pen pressure was, in [0, 1]
+///
+/// One minus the coefficient of variation of all pressure readings.
+/// Returns `None` without pressure data.
+pub fn pressure_consistency(strokes: &[Stroke]) -> Option<f32> {
+    let pressures: Vec<f32> = strokes.iter()
+        .flat_map(|s| s.points.iter())
+        .filter_map(|p| p.pressure)
+        .collect();
+    if pressures.is_empty() {
+        return None;
+    }
+
+    let n = pressures.len() as f32;
+    let mean = pressures.iter().sum::<f32>() / n;
+    if mean <= f32::EPSILON {
+        return Some(0.0);
+    }
+    let variance = pressures.iter().map(|p| (p - mean).powi(2)).sum::<f32>() / n;
+
+    Some((1.0 - variance.sqrt() / mean).clamp(0.0, 1.0))
+}
+
+/// Scale a set of polylines so their joint bounding box fits the unit square
+///
+/// Aspect ratio is preserved and the content is centered.
+pub fn normalize_to_unit(strokes: &[Vec<(f32, f32)>]) -> Vec<Vec<(f32, f32)>> {
+    let mut min_x = f32::MAX;
+    let mut min_y = f32::MAX;
+    let mut max_x = f32::MIN;
+    let mut max_y = f32::MIN;
+    for &(x, y) in strokes.iter().flatten() {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+
+    let extent = (max_x - min_x).max(max_y - min_y).max(f32::EPSILON);
+    let offset_x = (extent - (max_x - min_x)) / 2.0;
+    let offset_y = (extent - (max_y - min_y)) / 2.0;
+
+    strokes.iter()
+        .map(|s| s.iter()
+            .map(|&(x, y)| ((x - min_x + offset_x) / extent, (y - min_y + offset_y) / extent))
+            .collect())
+        .collect()
+}
+
+/// Resample a polyline to `n` points evenly spaced by arc length
+pub fn resample(points: &[(f32, f32)], n: usize) -> Vec<(f32, f32)> {
+    if points.len() < 2 || n < 2 {
+        return vec![points.first().copied().unwrap_or((0.0, 0.0)); n.max(1)];
+    }
+
+    let mut cumulative = vec![0.0f32];
+    for w in points.windows(2) {
+        let d = ((w[1].0 - w[0].0).powi(2) + (w[1].1 - w[0].1).powi(2)).sqrt();
+        cumulative.push(cumulative.last().unwrap() + d);
+    }
+    let total = *cumulative.last().unwrap();
+    if total <= f32::EPSILON {
+        return vec![points[0]; n];
+    }
+
+    let mut result = Vec::with_capacity(n);
+    let mut segment = 0;
+    for k in 0..n {
+        let target = total * k as f32 / (n - 1) as f32;
+        while segment < points.len() - 2 && cumulative[segment + 1] < target {
+            segment += 1;
+        }
+        let span = cumulative[segment + 1] - cumulative[segment];
+        let t = if span > 0.0 { ((target - cumulative[segment]) / span).clamp(0.0, 1.0) } else { 0.0 };
+        let (x0, y0) = points[segment];
+        let (x1, y1) = points[segment + 1];
+        result.push((x0 + (x1 - x0) * t, y0 + (y1 - y0) * t));
+    }
+    result
+}
+
+/// Mean point-to-point distance between two equally resampled polylines
+fn mean_distance(a: &[(f32, f32)], b: &[(f32, f32)], reverse_b: bool) -> f32 {
+    let n = a.len().min(b.len());
+    if n == 0 {
+        return UNMATCHED_COST;
+    }
+    let mut sum = 0.0;
+    for (i, &(ax, ay)) in a.iter().take(n).enumerate() {
+        let (bx, by) = if reverse_b { b[b.len() - 1 - i] } else { b[i] };
+        sum += ((ax - bx).powi(2) + (ay - by).powi(2)).sqrt();
+    }
+    sum / n as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "stroke-templates")]
+    use crate::templates::template_for;
+
+    fn stroke(points: &[(f32, f32)]) -> Stroke {
+        Stroke {
+            points: points.iter().map(|&(x, y)| StrokePoint { x, y, t: None, pressure: None }).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_count_strokes() {
+        let strokes = vec![
+            stroke(&[(0.0, 0.0), (0.0, 10.0), (0.0, 20.0), (0.0, 30.0)]),
+            stroke(&[(0.0, 0.0), (8.0, 0.0), (16.0, 0.0)]),
+        ];
+        assert_eq!(count_strokes(&strokes), 2);
+        assert_eq!(count_strokes(&[]), 0);
+    }
+
+    #[test]
+    fn test_count_strokes_detects_pen_lifts() {
+        // One recorded stroke that jumps across the drawing
+        let jumped = vec![stroke(&[(0.0, 0.0), (0.0, 5.0), (0.0, 100.0), (0.0, 105.0)])];
+        assert_eq!(count_strokes(&jumped), 2);
+
+        // One recorded stroke with a long pause between close points
+        let paused = vec![Stroke {
+            points: vec![
+                StrokePoint { x: 0.0, y: 0.0, t: Some(0.0), pressure: None },
+                StrokePoint { x: 0.0, y: 1.0, t: Some(10.0), pressure: None },
+                StrokePoint { x: 0.0, y: 2.0, t: Some(500.0), pressure: None },
+            ],
+            ..Default::default()
+        }];
+        assert_eq!(count_strokes(&paused), 2);
+    }
+
+    #[test]
+    fn test_rasterize_strokes_width_follows_pressure() {
+        let line = |pressure| Stroke {
+            points: vec![
+                StrokePoint { x: 10.0, y: 50.0, t: None, pressure },
+                StrokePoint { x: 90.0, y: 50.0, t: None, pressure },
+            ],
+            ..Default::default()
+        };
+        let ink = |img: &GrayImage| img.pixels().filter(|p| p.0[0] == 0).count();
+
+        let light = rasterize_strokes(&[line(Some(0.1))], 100, 100, 10.0);
+        let nominal = rasterize_strokes(&[line(None)], 100, 100, 10.0);
+        let heavy = rasterize_strokes(&[line(Some(1.0))], 100, 100, 10.0);
+
+        assert!(ink(&light) < ink(&nominal));
+        assert!(ink(&nominal) < ink(&heavy));
+        assert_eq!(light.get_pixel(50, 50).0[0], 0);
+        assert_eq!(light.get_pixel(50, 5).0[0], 255);
+    }
+
+    #[test]
+    fn test_rasterize_strokes_eraser() {
+        let line = stroke(&[(10.0, 50.0), (90.0, 50.0)]);
+        let eraser = Stroke {
+            erase: true,
+            width: Some(20.0),
+            ..stroke(&[(50.0, 30.0), (50.0, 70.0)])
+        };
+
+        let img = rasterize_strokes(&[line.clone(), eraser.clone()], 100, 100, 10.0);
+        assert_eq!(img.get_pixel(20, 50).0[0], 0);
+        assert_eq!(img.get_pixel(50, 50).0[0], 255);
+
+        // Erasing before drawing removes nothing
+        let img = rasterize_strokes(&[eraser, line], 100, 100, 10.0);
+        assert_eq!(img.get_pixel(50, 50).0[0], 0);
+    }
+
+    #[test]
+    fn test_rasterize_strokes_far_and_non_finite_points() {
+        // A line from far off the canvas is only stepped where it crosses it
+        let far = rasterize_strokes(&[stroke(&[(-1e12, 50.0), (1e12, 50.0)])], 100, 100, 10.0);
+        let near = rasterize_strokes(&[stroke(&[(-20.0, 50.0), (120.0, 50.0)])], 100, 100, 10.0);
+        assert_eq!(far, near);
+        let missed = rasterize_strokes(&[stroke(&[(-1e12, -50.0), (1e12, -50.0)])], 100, 100, 10.0);
+        assert!(missed.pixels().all(|p| p.0[0] == 255));
+
+        // Points that aren't numbers draw nothing, without hanging
+        for bad in [f32::NAN, f32::INFINITY] {
+            let img = rasterize_strokes(&[stroke(&[(bad, 50.0), (50.0, 50.0)]), stroke(&[(bad, bad)])], 100, 100, 10.0);
+            assert!(img.pixels().all(|p| p.0[0] == 255));
+        }
+    }
+
+    #[test]
+    fn test_smooth_strokes() {
+        // A horizontal line wobbling 3 units either side every 2 units
+        let points: Vec<StrokePoint> = (0..=100)
+            .map(|i| StrokePoint {
+                x: i as f32 * 2.0,
+                y: 50.0 + if i % 2 == 0 { 3.0 } else { -3.0 },
+                t: Some(i as f64 * 10.0),
+                pressure: Some(0.7),
+            })
+            .collect();
+        let shaky = Stroke { points, erase: false, width: Some(8.0) };
+
+        let smoothed = &smooth_strokes(std::slice::from_ref(&shaky), 30.0)[0];
+        assert_eq!(smoothed.points.len(), shaky.points.len());
+        assert_eq!(smoothed.width, Some(8.0));
+        for (before, after) in shaky.points.iter().zip(&smoothed.points).skip(20).take(60) {
+            assert!((after.y - 50.0).abs() < 1.0, "{} at x {}", after.y, after.x);
+            assert!((after.x - before.x).abs() < 0.5);
+            assert_eq!((after.t, after.pressure), (before.t, before.pressure));
+        }
+        let (first, last) = (smoothed.points[0], smoothed.points[100]);
+        assert_eq!((first.x, first.y, last.x, last.y), (0.0, 53.0, 200.0, 53.0));
+
+        let unchanged = &smooth_strokes(std::slice::from_ref(&shaky), 0.0)[0];
+        assert!(unchanged.points.iter().zip(&shaky.points).all(|(a, b)| (a.x, a.y) == (b.x, b.y)));
+    }
+
+    #[test]
+    fn test_pressure_consistency() {
+        let with_pressure = |values: &[f32]| vec![Stroke {
+            points: values.iter()
+                .map(|&p| StrokePoint { x: 0.0, y: 0.0, t: None, pressure: Some(p) })
+                .collect(),
+            ..Default::default()
+        }];
+
+        assert_eq!(pressure_consistency(&with_pressure(&[0.5, 0.5, 0.5])), Some(1.0));
+        assert!(pressure_consistency(&with_pressure(&[0.1, 0.9, 0.1, 0.9])).unwrap() < 0.3);
+        assert_eq!(pressure_consistency(&[stroke(&[(0.0, 0.0)])]), None);
+    }
+
+    #[test]
+    fn test_drawing_duration() {
+        let timed = |t| StrokePoint { x: 0.0, y: 0.0, t: Some(t), pressure: None };
+        let strokes = vec![
+            Stroke { points: vec![timed(100.0), timed(400.0)], ..Default::default() },
+            Stroke { points: vec![timed(900.0), timed(1600.0)], ..Default::default() },
+        ];
+
+        assert_eq!(drawing_duration(&strokes), Some(1500.0));
+        assert_eq!(drawing_duration(&[stroke(&[(0.0, 0.0)])]), None);
+    }
+
+    #[test]
+    fn test_stroke_transform() {
+        let transform = StrokeTransform { scale: 2.0, rotation: 90.0, offset_x: 10.0, offset_y: 5.0 };
+        let (x, y) = transform.apply_point(1.0, 0.0);
+        assert!((x - 10.0).abs() < 1e-4 && (y - 7.0).abs() < 1e-4, "({}, {})", x, y);
+
+        let wide = Stroke { width: Some(3.0), ..stroke(&[(0.0, 0.0), (4.0, 0.0)]) };
+        let mapped = transform.apply(&wide);
+        assert_eq!(mapped.width, Some(6.0));
+        assert!((mapped.points[1].y - 13.0).abs() < 1e-4);
+
+        let (x, y) = StrokeTransform::default().apply_point(3.0, 4.0);
+        assert_eq!((x, y), (3.0, 4.0));
+    }
+
+    #[test]
+    fn test_replay_events_undo_redo_clear() {
+        let a = stroke(&[(0.0, 0.0), (1.0, 0.0)]);
+        let b = stroke(&[(0.0, 5.0), (1.0, 5.0)]);
+        let c = stroke(&[(0.0, 9.0), (1.0, 9.0)]);
+        let first_y = |strokes: &[Stroke]| strokes.iter().map(|s| s.points[0].y).collect::<Vec<_>>();
+
+        let events = vec![
+            StrokeEvent::Stroke(a.clone()),
+            StrokeEvent::Stroke(b.clone()),
+            StrokeEvent::Undo,
+        ];
+        assert_eq!(first_y(&replay_events(&events)), vec![0.0]);
+
+        let mut redone = events.clone();
+        redone.push(StrokeEvent::Redo);
+        assert_eq!(first_y(&replay_events(&redone)), vec![0.0, 5.0]);
+
+        // A new stroke discards the redo stack
+        let mut replaced = events.clone();
+        replaced.extend([StrokeEvent::Stroke(c), StrokeEvent::Redo]);
+        assert_eq!(first_y(&replay_events(&replaced)), vec![0.0, 9.0]);
+
+        // Clearing can be undone
+        let cleared = vec![StrokeEvent::Stroke(a), StrokeEvent::Stroke(b), StrokeEvent::Clear];
+        assert!(replay_events(&cleared).is_empty());
+        let mut restored = cleared.clone();
+        restored.push(StrokeEvent::Undo);
+        assert_eq!(first_y(&replay_events(&restored)), vec![0.0, 5.0]);
+    }
+
+    #[test]
+    fn test_resample_evenly_spaced() {
+        let points = resample(&[(0.0, 0.0), (10.0, 0.0)], 6);
+        assert_eq!(points.len(), 6);
+        assert!((points[1].0 - 2.0).abs() < 1e-5);
+        assert!((points[5].0 - 10.0).abs() < 1e-5);
+    }
+
+    #[test]
+    #[cfg(feature = "stroke-templates")]
+    fn test_stroke_order_score_correct_order() {
+        // T: top bar left-to-right, then the stem downward (canvas coordinates)
+        let template = template_for('T').unwrap();
+        let strokes = vec![
+            stroke(&[(100.0, 50.0), (300.0, 50.0)]),
+            stroke(&[(200.0, 50.0), (200.0, 350.0)]),
+        ];
+
+        let score = stroke_order_score(&strokes, template);
+        assert!(score > 0.95, "score {}", score);
+    }
+
+    #[test]
+    #[cfg(feature = "stroke-templates")]
+    fn test_stroke_order_score_wrong_order_and_direction() {
+        let template = template_for('T').unwrap();
+        let strokes = vec![
+            stroke(&[(200.0, 350.0), (200.0, 50.0)]),
+            stroke(&[(300.0, 50.0), (100.0, 50.0)]),
+        ];
+
+        let score = stroke_order_score(&strokes, template);
+        assert!(score < 0.1, "score {}", score);
+    }
+
+    #[test]
+    #[cfg(feature = "stroke-templates")]
+    fn test_stroke_directions() {
+        let template = template_for('T').unwrap();
+
+        // Bar drawn correctly, stem drawn from the bottom up
+        let strokes = vec![
+            stroke(&[(100.0, 50.0), (300.0, 50.0)]),
+            stroke(&[(200.0, 350.0), (200.0, 50.0)]),
+            stroke(&[(290.0, 340.0), (300.0, 345.0)]),
+        ];
+
+        let flags = stroke_directions(&strokes, template);
+        assert_eq!(flags.len(), 3);
+        assert_eq!(flags[0].template_stroke, Some(0));
+        assert!(flags[0].start_ok && flags[0].direction_ok);
+        assert_eq!(flags[1].template_stroke, Some(1));
+        assert!(!flags[1].start_ok && !flags[1].direction_ok);
+        assert_eq!(flags[2].template_stroke, None);
+        assert!(stroke_directions(&[], template).is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "stroke-templates")]
+    fn test_stroke_order_score_missing_stroke() {
+        let template = template_for('T').unwrap();
+        let strokes = vec![stroke(&[(100.0, 50.0), (300.0, 50.0)])];
+
+        let score = stroke_order_score(&strokes, template);
+        assert!(score <= 0.5);
+        assert_eq!(stroke_order_score(&[], template), 0.0);
+    }
+}
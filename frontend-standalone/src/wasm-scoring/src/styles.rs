@@ -0,0 +1,256 @@
+//! Handwriting style presets
+//!
+//! Curricula disagree on the correct shape of a few characters: ball-and-stick
+//! print teaches a one-story 'a', an open '4' and a '7' without a bar, while
+//! continental forms join the 'a' into one stroke and bar the '7'. A style
+//! gives its letterforms as centerline SVG paths on the same 0-100 box as
+//! the stroke templates, one subpath per stroke in drawing order and
+//! direction. For the characters a style defines, the path is the reference
+//! (drawn with a round pen) and its subpaths are the stroke template;
+//! other characters fall back to the font and the embedded templates.
+//!
+//! Styles are drawn from SVG paths, so they need the `shapes` feature; they
+//! carry their own templates, so they don't need `stroke-templates`.
+
+use crate::error::ScoringError;
+use crate::practice::ReferenceVariation;
+use crate::templates::{CharacterTemplate, template_for};
+#[cfg(feature = "shapes")]
+use crate::shape::ReferenceShape;
+#[cfg(feature = "shapes")]
+use crate::templates::TemplateStroke;
+use image::GrayImage;
+#[cfg(feature = "shapes")]
+use std::collections::HashMap;
+#[cfg(feature = "shapes")]
+use std::sync::OnceLock;
+
+// Pen width of style letterforms on the 0-100 box, close to the stroke
+// weight of the bundled rounded fonts
+#[cfg(feature = "shapes")]
+const STYLE_STROKE_WIDTH: f32 = 10.0;
+// Style glyphs are scaled by their box rather than their own bounds, so a
+// lowercase letter stays smaller than a digit; full-height characters span
+// 10-90 plus the pen
+#[cfg(feature = "shapes")]
+const STYLE_EXTENT: f32 = 80.0 + STYLE_STROKE_WIDTH;
+// Writing lines of the style box: letters sit on 90, capitals and digits
+// reach up to 10, and lowercase bodies to 43
+#[cfg(all(feature = "shapes", feature = "guides"))]
+const STYLE_LINES: [f32; 3] = [10.0, 43.0, 90.0];
+
+// Subpaths in image pixels
+#[cfg(feature = "guides")]
+type Polylines = Vec<Vec<(f32, f32)>>;
+
+#[cfg(feature = "shapes")]
+const STYLES: &[(&str, &[(char, &str)])] = &[
+    ("ball_and_stick", &[
+        // Ball, then stick
+        ('a', "M64 54 A22 22 0 1 0 64 76 M67 43 V90"),
+        ('9', "M66 24 A18 18 0 1 0 66 36 M67 10 V90"),
+        // Open top: down and across, then down
+        ('4', "M30 10 V60 H78 M62 10 V90"),
+        ('7', "M25 10 H75 L40 90"),
+        ('1', "M50 10 V90"),
+    ]),
+    ("continental", &[
+        // Round, up and down the stem without lifting, with an exit flick
+        ('a', "M64 54 A22 22 0 1 0 64 76 L67 43 V84 Q68 90 75 90"),
+        ('9', "M66 22 A18 18 0 1 0 66 23 V90"),
+        // Closed top
+        ('4', "M60 10 L25 62 H78 M60 10 V90"),
+        // Barred seven and flagged one
+        ('7', "M25 10 H75 L40 90 M38 50 H66"),
+        ('1', "M35 28 L58 10 V90"),
+    ]),
+];
+
+/// Names of the built-in handwriting styles
+#[cfg(feature = "shapes")]
+pub fn handwriting_styles() -> Vec<&'static str> {
+    STYLES.iter().map(|(name, _)| *name).collect()
+}
+
+/// Names of the built-in handwriting styles
+#[cfg(not(feature = "shapes"))]
+pub fn handwriting_styles() -> Vec<&'static str> {
+    Vec::new()
+}
+
+#[cfg(feature = "shapes")]
+struct StyleGlyph {
+    shape: ReferenceShape,
+    template: CharacterTemplate,
+}
+
+#[cfg(feature = "shapes")]
+fn styles() -> &'static HashMap<&'static str, HashMap<char, StyleGlyph>> {
+    static PARSED: OnceLock<HashMap<&'static str, HashMap<char, StyleGlyph>>> = OnceLock::new();
+    PARSED.get_or_init(|| {
+        STYLES.iter()
+            .map(|&(name, glyphs)| {
+                let glyphs = glyphs.iter()
+                    .map(|&(character, path)| {
+                        let shape = ReferenceShape::from_svg_path(path, Some(STYLE_STROKE_WIDTH))
+                            .expect("built-in style paths are valid")
+                            .with_extent(STYLE_EXTENT);
+                        let strokes = shape.subpaths().iter()
+                            .map(|points| TemplateStroke { points: points.clone() })
+                            .collect();
+                        (character, StyleGlyph { shape, template: CharacterTemplate { strokes } })
+                    })
+                    .collect();
+                (name, glyphs)
+            })
+            .collect()
+    })
+}
+
+/// The style's letterform for a variation, or `None` if the style leaves
+/// the glyph to the font
+#[cfg(feature = "shapes")]
+pub(crate) fn render_style_glyph(
+    style: &str,
+    variation: &ReferenceVariation,
+    size: u32,
+) -> Result<Option<GrayImage>, ScoringError> {
+    let glyphs = styles().get(style)
+        .ok_or_else(|| format!("Unknown handwriting style '{}'", style))?;
+    Ok(glyphs.get(&variation.glyph).map(|glyph| glyph.shape.render_variation(size, variation)))
+}
+
+/// Centerlines of the style's letterform in image pixels, or `None` if the
+/// style leaves the glyph to the font
+#[cfg(all(feature = "shapes", feature = "guides"))]
+pub(crate) fn style_paths(
+    style: &str,
+    variation: &ReferenceVariation,
+    size: u32,
+) -> Result<Option<Polylines>, ScoringError> {
+    let glyphs = styles().get(style)
+        .ok_or_else(|| format!("Unknown handwriting style '{}'", style))?;
+    Ok(glyphs.get(&variation.glyph).map(|glyph| glyph.shape.image_subpaths(size, variation)))
+}
+
+/// Centerlines of the style's letterform in image pixels, or `None` if the
+/// style leaves the glyph to the font
+#[cfg(all(not(feature = "shapes"), feature = "guides"))]
+pub(crate) fn style_paths(
+    _style: &str,
+    _variation: &ReferenceVariation,
+    _size: u32,
+) -> Result<Option<Polylines>, ScoringError> {
+    Err("Handwriting styles need the `shapes` feature".into())
+}
+
+/// Topline, midline and baseline heights in image pixels for the style's
+/// letterform, or `None` if the style leaves the glyph to the font
+#[cfg(all(feature = "shapes", feature = "guides"))]
+pub(crate) fn style_lines(
+    style: &str,
+    variation: &ReferenceVariation,
+    size: u32,
+) -> Result<Option<[f32; 3]>, ScoringError> {
+    let glyphs = styles().get(style)
+        .ok_or_else(|| format!("Unknown handwriting style '{}'", style))?;
+    Ok(glyphs.get(&variation.glyph).map(|glyph| STYLE_LINES.map(|y| glyph.shape.image_y(size, variation, y))))
+}
+
+/// Topline, midline and baseline heights in image pixels for the style's
+/// letterform, or `None` if the style leaves the glyph to the font
+#[cfg(all(not(feature = "shapes"), feature = "guides"))]
+pub(crate) fn style_lines(
+    _style: &str,
+    _variation: &ReferenceVariation,
+    _size: u32,
+) -> Result<Option<[f32; 3]>, ScoringError> {
+    Err("Handwriting styles need the `shapes` feature".into())
+}
+
+/// The style's letterform for a variation, or `None` if the style leaves
+/// the glyph to the font
+#[cfg(not(feature = "shapes"))]
+pub(crate) fn render_style_glyph(
+    _style: &str,
+    _variation: &ReferenceVariation,
+    _size: u32,
+) -> Result<Option<GrayImage>, ScoringError> {
+    Err("Handwriting styles need the `shapes` feature".into())
+}
+
+/// Stroke template for a character in a style, falling back to the
+/// embedded templates
+#[cfg(feature = "shapes")]
+pub(crate) fn styled_template(character: char, style: Option<&str>) -> Option<&'static CharacterTemplate> {
+    style.and_then(|style| styles().get(style))
+        .and_then(|glyphs| glyphs.get(&character))
+        .map(|glyph| &glyph.template)
+        .or_else(|| template_for(character))
+}
+
+/// Stroke template for a character in a style, falling back to the
+/// embedded templates
+#[cfg(not(feature = "shapes"))]
+pub(crate) fn styled_template(character: char, _style: Option<&str>) -> Option<&'static CharacterTemplate> {
+    template_for(character)
+}
+
+#[cfg(all(test, feature = "shapes"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_styles_parse_and_render() {
+        assert_eq!(handwriting_styles(), vec!["ball_and_stick", "continental"]);
+        for name in handwriting_styles() {
+            for c in "a9471".chars() {
+                assert!(styles()[name].contains_key(&c), "{} lacks {}", name, c);
+            }
+        }
+
+        let seven = |style: &str| render_style_glyph(style, &ReferenceVariation::identity('7'), 200).unwrap().unwrap();
+        assert_ne!(seven("ball_and_stick"), seven("continental"));
+        assert!(render_style_glyph("continental", &ReferenceVariation::identity('b'), 200).unwrap().is_none());
+        assert!(render_style_glyph("cursive", &ReferenceVariation::identity('a'), 200).is_err());
+    }
+
+    #[test]
+    fn test_scoring_in_style() {
+        use crate::font::Font;
+        use crate::options::ScoringOptions;
+        use crate::scoring::{generate_reference_image_internal, score_drawing_internal};
+
+        let font = Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap();
+        let plain = ScoringOptions::default();
+        let continental = ScoringOptions { style: Some("continental".to_string()), ..Default::default() };
+
+        // A barred seven is right in the continental style and off against
+        // the font's seven
+        let barred = generate_reference_image_internal('7', &font, 300, &continental).unwrap();
+        let in_style = score_drawing_internal(&barred, '7', &font, None, &continental).unwrap().result.score;
+        let against_font = score_drawing_internal(&barred, '7', &font, None, &plain).unwrap().result.score;
+        assert!(in_style > against_font, "{} vs {}", in_style, against_font);
+
+        // Characters the style leaves alone still use the font
+        let b = generate_reference_image_internal('b', &font, 300, &plain).unwrap();
+        assert_eq!(generate_reference_image_internal('b', &font, 300, &continental).unwrap(), b);
+
+        let unknown = ScoringOptions { style: Some("cursive".to_string()), ..Default::default() };
+        assert!(score_drawing_internal(&b, 'b', &font, None, &unknown).is_err());
+    }
+
+    #[cfg(feature = "stroke-templates")]
+    #[test]
+    fn test_style_templates() {
+        // Ball-and-stick 'a' is two strokes, continental one; only the
+        // continental '7' has a bar
+        let strokes = |style: &str, c: char| styled_template(c, Some(style)).map(|t| t.strokes.len());
+        assert_eq!(strokes("ball_and_stick", 'a'), Some(2));
+        assert_eq!(strokes("continental", 'a'), Some(1));
+        assert_eq!(strokes("ball_and_stick", '7'), Some(1));
+        assert_eq!(strokes("continental", '7'), Some(2));
+        assert_eq!(strokes("continental", 'b'), template_for('b').map(|t| t.strokes.len()));
+        assert_eq!(styled_template('7', None).map(|t| t.strokes.len()), Some(2));
+    }
+}
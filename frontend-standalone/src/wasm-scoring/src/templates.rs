@@ -0,0 +1,83 @@
+//! Embedded stroke-order templates
+//!
+//! Stroke definitions for A-Z, a-z and 0-9 shared with the frontend's
+//! stroke guides. Coordinates are on a 0-100 box.
+//!
+//! The table is only embedded with the `stroke-templates` feature; without
+//! it no character has a template and stroke-order checks are skipped.
+
+use serde::Deserialize;
+#[cfg(feature = "stroke-templates")]
+use std::collections::HashMap;
+#[cfg(feature = "stroke-templates")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "stroke-templates")]
+const TEMPLATE_JSON: &str = include_str!("../../../public/strokes/fredoka.json");
+
+/// A single template stroke in drawing order and direction
+#[derive(Debug, Clone, Deserialize)]
+pub struct TemplateStroke {
+    pub points: Vec<(f32, f32)>,
+}
+
+/// Stroke-order template for one character
+#[derive(Debug, Clone, Deserialize)]
+pub struct CharacterTemplate {
+    pub strokes: Vec<TemplateStroke>,
+}
+
+#[cfg(feature = "stroke-templates")]
+#[derive(Deserialize)]
+struct TemplateFile {
+    characters: HashMap<String, CharacterTemplate>,
+}
+
+#[cfg(feature = "stroke-templates")]
+fn templates() -> &'static HashMap<char, CharacterTemplate> {
+    static TEMPLATES: OnceLock<HashMap<char, CharacterTemplate>> = OnceLock::new();
+    TEMPLATES.get_or_init(|| {
+        let file: TemplateFile = serde_json::from_str(TEMPLATE_JSON)
+            .expect("embedded stroke templates are valid JSON");
+        file.characters
+            .into_iter()
+            .filter_map(|(key, template)| key.chars().next().map(|c| (c, template)))
+            .collect()
+    })
+}
+
+/// Look up the stroke template for a character
+#[cfg(feature = "stroke-templates")]
+pub fn template_for(character: char) -> Option<&'static CharacterTemplate> {
+    templates().get(&character)
+}
+
+/// Look up the stroke template for a character
+#[cfg(not(feature = "stroke-templates"))]
+pub fn template_for(_character: char) -> Option<&'static CharacterTemplate> {
+    None
+}
+
+#[cfg(all(test, feature = "stroke-templates"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_templates_cover_alphabet_and_digits() {
+        for c in ('A'..='Z').chain('a'..='z').chain('0'..='9') {
+            let template = template_for(c).unwrap_or_else(|| panic!("missing template for {}", c));
+            assert!(!template.strokes.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_template_stroke_order() {
+        let e = template_for('E').unwrap();
+        assert_eq!(e.strokes.len(), 4);
+        // First stroke of E is the stem, drawn top to bottom
+        let stem = &e.strokes[0].points;
+        assert_eq!(stem[0].0, stem[1].0);
+        assert!(stem[0].1 < stem[1].1);
+        assert!(template_for('?').is_none());
+    }
+}
@@ -0,0 +1,57 @@
+//! Synthetic frames for unit tests
+//!
+//! Frames are `TARGET_SIZE` pixels square, 1.0 for paper and 0.0 for ink,
+//! like a drawing after normalization. Ranges are pixel columns (`xs`) and
+//! rows (`ys`).
+
+use crate::scoring::TARGET_SIZE;
+use std::ops::Range;
+
+const SIZE: usize = TARGET_SIZE as usize;
+
+/// A frame with no ink
+pub(crate) fn blank() -> Vec<f32> {
+    vec![1.0f32; SIZE * SIZE]
+}
+
+/// Ink a rectangle
+pub(crate) fn fill(image: &mut [f32], xs: Range<usize>, ys: Range<usize>) {
+    paint(image, xs, ys, 0.0);
+}
+
+/// Clear a rectangle back to paper, e.g. to break a stroke
+pub(crate) fn erase(image: &mut [f32], xs: Range<usize>, ys: Range<usize>) {
+    paint(image, xs, ys, 1.0);
+}
+
+/// A frame with one inked rectangle
+pub(crate) fn bar(xs: Range<usize>, ys: Range<usize>) -> Vec<f32> {
+    let mut image = blank();
+    fill(&mut image, xs, ys);
+    image
+}
+
+/// A square outline: the `outer` square less the `inner` one, each the same
+/// range of columns and rows
+pub(crate) fn square_ring(outer: Range<usize>, inner: Range<usize>) -> Vec<f32> {
+    let mut image = bar(outer.clone(), outer);
+    erase(&mut image, inner.clone(), inner);
+    image
+}
+
+/// A round ring about the middle of the frame, inked between two radii
+pub(crate) fn ring(radii: Range<f32>) -> Vec<f32> {
+    let middle = SIZE as f32 / 2.0;
+    (0..SIZE * SIZE)
+        .map(|i| {
+            let r = ((i % SIZE) as f32 - middle).hypot((i / SIZE) as f32 - middle);
+            if radii.contains(&r) { 0.0 } else { 1.0 }
+        })
+        .collect()
+}
+
+fn paint(image: &mut [f32], xs: Range<usize>, ys: Range<usize>, value: f32) {
+    for y in ys {
+        image[y * SIZE + xs.start..y * SIZE + xs.end].fill(value);
+    }
+}
@@ -0,0 +1,180 @@
+//! Stage timings for profiling scoring on real devices
+//!
+//! With `ScoringOptions::with_timings` a scoring call records how long its
+//! stages took on the current thread. Skeletonization and distance
+//! transforms are added up wherever they run, so they overlap the reference
+//! and metrics stages that call them. Without a recording every hook is a
+//! single thread-local check.
+//!
+//! Timings are the only thing scoring reads a clock for. A recording can
+//! be started with another clock (`ScoringEngine::set_clock`), so tests and
+//! replays get the same timings each run; stages read it until the
+//! recording stops.
+
+use crate::StageTimings;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+thread_local! {
+    static RECORDING: Cell<Option<StageTimings>> = const { Cell::new(None) };
+    static CLOCK: RefCell<Option<Rc<dyn Clock>>> = const { RefCell::new(None) };
+}
+
+/// A source of the current time, in milliseconds from any fixed point
+pub trait Clock {
+    fn now_ms(&self) -> f64;
+}
+
+/// A stage of the scoring pipeline
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Stage {
+    Decode,
+    Reference,
+    Skeleton,
+    DistanceTransform,
+    Metrics,
+}
+
+/// A recording of one scoring call's stages, stopped when dropped
+pub(crate) struct Recording {
+    started: f64,
+}
+
+impl Recording {
+    /// Start recording on this thread, unless a caller already is, timed
+    /// by `clock` or else the platform's monotonic clock
+    /// (`performance.now()` in browsers)
+    pub(crate) fn start(clock: Option<Rc<dyn Clock>>) -> Option<Recording> {
+        if RECORDING.get().is_some() {
+            return None;
+        }
+        RECORDING.set(Some(StageTimings::default()));
+        CLOCK.set(clock);
+        Some(Recording { started: now_ms() })
+    }
+
+    /// Stop recording and return the stage times so far
+    pub(crate) fn finish(self) -> StageTimings {
+        let mut timings = RECORDING.take().unwrap_or_default();
+        timings.total_ms = round_ms(now_ms() - self.started);
+        for stage in [&mut timings.decode_ms, &mut timings.reference_ms, &mut timings.skeleton_ms,
+            &mut timings.distance_transform_ms, &mut timings.metrics_ms] {
+            *stage = round_ms(*stage as f64);
+        }
+        timings
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        RECORDING.set(None);
+        CLOCK.set(None);
+    }
+}
+
+/// Start of a stage, when recording
+pub(crate) struct Lap(Option<f64>);
+
+/// Start timing a stage; `Lap::record` adds the time since to it
+pub(crate) fn lap() -> Lap {
+    Lap(RECORDING.get().map(|_| now_ms()))
+}
+
+impl Lap {
+    pub(crate) fn record(self, stage: Stage) {
+        let Some(started) = self.0 else { return };
+        let Some(mut timings) = RECORDING.get() else { return };
+        let elapsed = (now_ms() - started) as f32;
+        match stage {
+            Stage::Decode => timings.decode_ms += elapsed,
+            Stage::Reference => timings.reference_ms += elapsed,
+            Stage::Skeleton => timings.skeleton_ms += elapsed,
+            Stage::DistanceTransform => timings.distance_transform_ms += elapsed,
+            Stage::Metrics => timings.metrics_ms += elapsed,
+        }
+        RECORDING.set(Some(timings));
+    }
+}
+
+/// Run `f`, adding the time it takes to `stage`
+pub(crate) fn time<T>(stage: Stage, f: impl FnOnce() -> T) -> T {
+    let lap = lap();
+    let value = f();
+    lap.record(stage);
+    value
+}
+
+fn round_ms(ms: f64) -> f32 {
+    ((ms * 100.0).round() / 100.0) as f32
+}
+
+fn now_ms() -> f64 {
+    CLOCK.with_borrow(|clock| clock.as_ref().map(|clock| clock.now_ms())).unwrap_or_else(platform_now_ms)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn platform_now_ms() -> f64 {
+    static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+    EPOCH.get_or_init(std::time::Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+// performance.now() is there in windows, workers and Node alike
+#[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+fn platform_now_ms() -> f64 {
+    #[wasm_bindgen::prelude::wasm_bindgen]
+    extern "C" {
+        #[wasm_bindgen(js_namespace = performance, js_name = now)]
+        fn performance_now() -> f64;
+    }
+    performance_now()
+}
+
+// std::time::Instant panics on wasm32 without a JavaScript host to ask
+#[cfg(all(target_arch = "wasm32", not(feature = "wasm")))]
+fn platform_now_ms() -> f64 {
+    0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recording() {
+        assert!(lap().0.is_none());
+
+        let recording = Recording::start(None).unwrap();
+        assert!(Recording::start(None).is_none());
+        time(Stage::Skeleton, || std::thread::sleep(std::time::Duration::from_millis(5)));
+        time(Stage::Skeleton, || std::thread::sleep(std::time::Duration::from_millis(5)));
+        let timings = recording.finish();
+        assert!(timings.skeleton_ms >= 10.0);
+        assert!(timings.total_ms >= timings.skeleton_ms);
+        assert_eq!(timings.decode_ms, 0.0);
+
+        // Dropping a recording, e.g. on an early error return, stops it
+        drop(Recording::start(None).unwrap());
+        assert!(lap().0.is_none());
+    }
+
+    #[test]
+    fn test_injected_clock() {
+        // A clock moving 2 ms each time it is read
+        struct Ticking(Cell<f64>);
+        impl Clock for Ticking {
+            fn now_ms(&self) -> f64 {
+                self.0.set(self.0.get() + 2.0);
+                self.0.get()
+            }
+        }
+
+        let recording = Recording::start(Some(Rc::new(Ticking(Cell::new(0.0))))).unwrap();
+        time(Stage::Decode, || ());
+        time(Stage::Metrics, || ());
+        let timings = recording.finish();
+        assert_eq!((timings.decode_ms, timings.metrics_ms, timings.total_ms), (2.0, 2.0, 10.0));
+
+        // The clock goes with its recording
+        assert!(CLOCK.with_borrow(Option::is_none));
+    }
+}
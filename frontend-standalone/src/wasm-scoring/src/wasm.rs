@@ -0,0 +1,1580 @@
+//! wasm-bindgen exports
+//!
+//! Thin adapters from the JavaScript API to the Rust API in the crate root:
+//! they parse `JsValue` arguments, call the Rust function and convert the
+//! result and errors back. Nothing here does scoring work of its own.
+
+use crate::{AgeBand, AgeBandBundle, CancelToken, Clock, Font, RandomSource, ReferenceHandle, ReferencePack, ScoredDrawing, ScoringEngine, ScoringError, ScoringOptions, ScoringResult, Stroke, StrokeEvent};
+#[cfg(feature = "coaching")]
+use crate::LiveScorer;
+#[cfg(feature = "session-analytics")]
+use crate::SessionAttempt;
+#[cfg(feature = "shapes")]
+use crate::ReferenceShape;
+use crate::reference::cached_reference;
+use crate::scoring::{REFERENCE_SIZE, score_against_reference};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+// Types of the option and result objects, in place of `any`
+#[wasm_bindgen(typescript_custom_section)]
+const TYPES: &str = include_str!("../types.d.ts");
+
+// Starts rayon's Web Worker pool; JavaScript must await it before scoring
+#[cfg(all(feature = "parallel", target_arch = "wasm32"))]
+pub use wasm_bindgen_rayon::init_thread_pool;
+
+#[wasm_bindgen(start)]
+pub fn init() {
+    #[cfg(feature = "console_error_panic_hook")]
+    console_error_panic_hook::set_once();
+}
+
+#[wasm_bindgen]
+pub struct WasmScoringResult {
+    inner: ScoringResult,
+    reference_image: Vec<u8>,
+}
+
+impl From<ScoredDrawing> for WasmScoringResult {
+    fn from(scored: ScoredDrawing) -> Self {
+        Self { inner: scored.result, reference_image: scored.reference_image }
+    }
+}
+
+#[wasm_bindgen]
+impl WasmScoringResult {
+    #[wasm_bindgen(getter)]
+    pub fn score(&self) -> u8 {
+        self.inner.score
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn stars(&self) -> u8 {
+        self.inner.stars
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn feedback(&self) -> String {
+        self.inner.feedback.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn feedback_code(&self) -> Option<String> {
+        self.inner.feedback_code.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn scribble(&self) -> bool {
+        self.inner.scribble
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn suspected_trace(&self) -> bool {
+        self.inner.suspected_trace
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn clipped(&self) -> bool {
+        self.inner.clipped
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn coverage(&self) -> f32 {
+        self.inner.coverage
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn accuracy(&self) -> f32 {
+        self.inner.accuracy
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn similarity(&self) -> f32 {
+        self.inner.similarity
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn skeleton_coverage(&self) -> f32 {
+        self.inner.skeleton_coverage
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn structure(&self) -> f32 {
+        self.inner.structure
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn legibility(&self) -> f32 {
+        self.inner.legibility
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn moment_similarity(&self) -> f32 {
+        self.inner.moment_similarity
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn hausdorff_95(&self) -> f32 {
+        self.inner.hausdorff_95
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn worst_region(&self) -> Option<String> {
+        self.inner.worst_region.clone()
+    }
+
+    #[wasm_bindgen(getter, unchecked_return_type = "StrokeGap[]")]
+    pub fn gaps(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.gaps).unwrap_or(JsValue::NULL)
+    }
+
+    #[wasm_bindgen(getter, unchecked_return_type = "boolean[]")]
+    pub fn loop_closed(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.loop_closed).unwrap_or(JsValue::NULL)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn dot_present(&self) -> Option<bool> {
+        self.inner.dot_present
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn crossbar_present(&self) -> Option<bool> {
+        self.inner.crossbar_present
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tail_present(&self) -> Option<bool> {
+        self.inner.tail_present
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn order_score(&self) -> Option<f32> {
+        self.inner.order_score
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn expected_strokes(&self) -> Option<u32> {
+        self.inner.expected_strokes
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn actual_strokes(&self) -> Option<u32> {
+        self.inner.actual_strokes
+    }
+
+    /// Array of `{ stroke, template_stroke, start_ok, direction_ok }`
+    #[wasm_bindgen(getter, unchecked_return_type = "StrokeDirection[]")]
+    pub fn stroke_directions(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.stroke_directions).unwrap_or(JsValue::NULL)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn duration_ms(&self) -> Option<f64> {
+        self.inner.duration_ms
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn time_penalty(&self) -> f32 {
+        self.inner.time_penalty
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pressure_consistency(&self) -> Option<f32> {
+        self.inner.pressure_consistency
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn reference_glyph(&self) -> Option<String> {
+        self.inner.reference_glyph.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn contrast_gain(&self) -> f32 {
+        self.inner.contrast_gain
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn stray_marks(&self) -> u32 {
+        self.inner.stray_marks
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn drawing_hash(&self) -> String {
+        self.inner.drawing_hash.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn hints(&self) -> Vec<String> {
+        self.inner.hints.clone()
+    }
+
+    #[wasm_bindgen(getter, unchecked_return_type = "ScaffoldLevel")]
+    pub fn scaffold(&self) -> String {
+        self.inner.scaffold.as_str().to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn low_score_streak(&self) -> u32 {
+        self.inner.low_score_streak
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn confidence(&self) -> f32 {
+        self.inner.confidence
+    }
+
+    /// Component points, penalties, caps and thresholds behind the score
+    #[wasm_bindgen(getter, unchecked_return_type = "ScoreExplanation")]
+    pub fn explanation(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.inner.explanation).unwrap_or(JsValue::NULL)
+    }
+
+    /// Milliseconds per stage, when scored with `with_timings`
+    #[wasm_bindgen(getter, unchecked_return_type = "StageTimings | null")]
+    pub fn timings(&self) -> JsValue {
+        self.inner.timings.and_then(|timings| serde_wasm_bindgen::to_value(&timings).ok()).unwrap_or(JsValue::NULL)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn reference_image(&self) -> Vec<u8> {
+        self.reference_image.clone()
+    }
+
+    /// Every metric and flag as a plain object, for storage and analytics
+    ///
+    /// Named `toJSON`, so `JSON.stringify(result)` serializes the result
+    /// instead of an empty object. Missing values are `null`; the reference
+    /// image is left out.
+    #[wasm_bindgen(js_name = toJSON, unchecked_return_type = "ScoringResult")]
+    pub fn to_json(&self) -> Result<JsValue, JsValue> {
+        result_object(&self.inner)
+    }
+}
+
+/// A result as a plain object with the Rust field names, `null` for missing
+/// values
+fn result_object(result: &ScoringResult) -> Result<JsValue, JsValue> {
+    result.serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Score a user's drawing against a reference character
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing (JPEG and WebP with the
+///   `jpeg` and `webp` features)
+/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating the reference
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_drawing(
+    image_data: &[u8],
+    character: &str,
+    font_data: &[u8],
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    crate::score_drawing(image_data, char, &load_font(font_data)?, None, &ScoringOptions::default())
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// Score a user's drawing with custom scoring options
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating the reference
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_drawing_with_options(
+    image_data: &[u8],
+    character: &str,
+    font_data: &[u8],
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    crate::score_drawing(image_data, char, &load_font(font_data)?, None, &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// Score a user's drawing without blocking the page for the whole call
+///
+/// Returns a Promise. With `chunked`, the work is split into loading the
+/// font, preparing the reference and scoring the drawing, with a return to
+/// the event loop between them so the page can paint and handle input; the
+/// reference is the slow part the first time a character is scored. For
+/// pages that can't move scoring into a Worker.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating the reference
+/// * `strokes` - Strokes as for `score_drawing_with_strokes`, or `null`
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+/// * `chunked` - Yield to the event loop between stages (default `true`)
+/// * `signal` - An `AbortSignal`; once aborted, the Promise rejects with an
+///   `Error` named `Cancelled` at the next stage
+///
+/// # Returns
+/// A Promise of a ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub async fn score_drawing_async(
+    image_data: Vec<u8>,
+    character: String,
+    font_data: Vec<u8>,
+    #[wasm_bindgen(unchecked_param_type = "Stroke[] | null | undefined")] strokes: JsValue,
+    #[wasm_bindgen(unchecked_param_type = "ScoringOptions | null | undefined")] options: JsValue,
+    chunked: Option<bool>,
+    #[wasm_bindgen(unchecked_param_type = "AbortSignal | null | undefined")] signal: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let strokes: Option<Vec<Stroke>> = serde_wasm_bindgen::from_value(strokes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+    let options = parse_options(options)?;
+    let chunked = chunked.unwrap_or(true);
+
+    let cancelled = || if aborted(&signal) { Err(js_error(ScoringError::Cancelled)) } else { Ok(()) };
+
+    cancelled()?;
+    let font = load_font(&font_data)?;
+    if chunked {
+        yield_to_event_loop().await;
+    }
+
+    cancelled()?;
+    let variation = options.reference_variation(char);
+    let reference = cached_reference(&variation, &font, &options, REFERENCE_SIZE).map_err(js_error)?;
+    if chunked {
+        yield_to_event_loop().await;
+    }
+
+    cancelled()?;
+    score_against_reference(&image_data, char, &variation, &reference, strokes.as_deref(), &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(callback: &js_sys::Function, delay_ms: i32);
+}
+
+/// Resolve on a fresh task, after the browser has had a chance to paint
+///
+/// A resolved Promise would only queue a microtask, which runs before the
+/// page renders, so this goes through `setTimeout`, a global on windows,
+/// workers and Node alike.
+async fn yield_to_event_loop() {
+    let tick = js_sys::Promise::new(&mut |resolve, _| set_timeout(&resolve, 0));
+    // setTimeout never rejects
+    let _ = wasm_bindgen_futures::JsFuture::from(tick).await;
+}
+
+/// Whether `signal`, an `AbortSignal` or anything with an `aborted` flag,
+/// was aborted
+fn aborted(signal: &JsValue) -> bool {
+    signal.is_object() && js_sys::Reflect::get(signal, &JsValue::from_str("aborted")).is_ok_and(|aborted| aborted.is_truthy())
+}
+
+/// Score a user's drawing together with the strokes used to draw it
+///
+/// Stroke data enables metrics about how the character was formed, such
+/// as stroke order and direction. Strokes carrying pressure are re-rendered
+/// with variable pen width and scored in place of the image.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating the reference
+/// * `strokes` - Array of `{ points: [{ x, y, t?, pressure? }], erase?, width? }` in drawing order,
+///   in the image's pixel coordinates (or mapped there by `options.stroke_transform`)
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_drawing_with_strokes(
+    image_data: &[u8],
+    character: &str,
+    font_data: &[u8],
+    #[wasm_bindgen(unchecked_param_type = "Stroke[]")] strokes: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let strokes: Vec<Stroke> = serde_wasm_bindgen::from_value(strokes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+    let options = parse_options(options)?;
+
+    crate::score_drawing(image_data, char, &load_font(font_data)?, Some(&strokes), &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// Score a drawing from its event log, counting only the ink left visible
+///
+/// Replays strokes, undos, redos and clears, then scores the remaining
+/// strokes re-rendered on a canvas the size of the image, so undone or
+/// erased strokes that still show faintly in a flattened export are ignored.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing (sets the canvas size)
+/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating the reference
+/// * `events` - Array of `{ type: "stroke", points, erase?, width? }`, `{ type: "undo" }`,
+///   `{ type: "redo" }` or `{ type: "clear" }` in the order they happened
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_drawing_with_events(
+    image_data: &[u8],
+    character: &str,
+    font_data: &[u8],
+    #[wasm_bindgen(unchecked_param_type = "StrokeEvent[]")] events: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let events: Vec<StrokeEvent> = serde_wasm_bindgen::from_value(events)
+        .map_err(|e| JsValue::from_str(&format!("Invalid event log: {}", e)))?;
+    let options = parse_options(options)?;
+
+    crate::score_drawing_events(image_data, char, &load_font(font_data)?, &events, &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// Get a named options preset to pass to the scoring functions
+///
+/// # Arguments
+/// * `name` - "freehand" (the default) or "tracing" for trace-over exercises
+///
+/// # Returns
+/// A `ScoringOptions` object that can be adjusted before use
+#[wasm_bindgen(unchecked_return_type = "ScoringOptions")]
+pub fn scoring_preset(name: &str) -> Result<JsValue, JsValue> {
+    let options = ScoringOptions::preset(name)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown scoring preset: {}", name)))?;
+
+    serde_wasm_bindgen::to_value(&options)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize options: {}", e)))
+}
+
+/// Names of the built-in handwriting styles, for `options.style`
+///
+/// Empty when the engine is built without the `shapes` feature.
+#[wasm_bindgen]
+pub fn handwriting_styles() -> Vec<String> {
+    crate::handwriting_styles().into_iter().map(str::to_string).collect()
+}
+
+fn load_font(font_data: &[u8]) -> Result<Font, JsValue> {
+    Font::from_bytes(font_data).map_err(js_error)
+}
+
+/// Errors cross to JavaScript as strings, except a missing glyph, which is
+/// an `Error` named `GlyphMissing` so the app can fall back to another font,
+/// and cancellation, an `Error` named `Cancelled`
+fn js_error(error: ScoringError) -> JsValue {
+    match error {
+        ScoringError::GlyphMissing(_) | ScoringError::Cancelled => {
+            let js_error = js_sys::Error::new(&error.to_string());
+            js_error.set_name(if error == ScoringError::Cancelled { "Cancelled" } else { "GlyphMissing" });
+            js_error.into()
+        }
+        ScoringError::Invalid(message) => JsValue::from_str(&message),
+    }
+}
+
+pub(crate) fn parse_options(options: JsValue) -> Result<ScoringOptions, JsValue> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(ScoringOptions::default());
+    }
+
+    let value: serde_json::Value = serde_wasm_bindgen::from_value(options)
+        .map_err(|e| JsValue::from_str(&format!("Invalid scoring options: {}", e)))?;
+    ScoringOptions::from_json(value).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Get the built-in configuration bundle for an age band
+///
+/// Pass `{ age_band: "5-6" }` as options to score with a bundle; any other
+/// fields given override the bundle's values.
+///
+/// # Arguments
+/// * `age_band` - "3-4", "5-6" or "7-8"
+///
+/// # Returns
+/// `{ age_band, label, rationale, options }`, where `options` is
+/// the full `ScoringOptions` object the bundle applies
+#[wasm_bindgen(unchecked_return_type = "AgeBandBundle")]
+pub fn age_band_bundle(age_band: &str) -> Result<JsValue, JsValue> {
+    let band: AgeBand = serde_json::from_value(serde_json::Value::from(age_band))
+        .map_err(|_| JsValue::from_str(&format!("Unknown age band: {}", age_band)))?;
+
+    serde_wasm_bindgen::to_value(&band.bundle())
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize age band: {}", e)))
+}
+
+/// List all built-in age band bundles, youngest first
+#[wasm_bindgen(unchecked_return_type = "AgeBandBundle[]")]
+pub fn age_band_bundles() -> Result<JsValue, JsValue> {
+    let bundles: Vec<AgeBandBundle> = AgeBand::ALL.iter().map(|band| band.bundle()).collect();
+
+    serde_wasm_bindgen::to_value(&bundles)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize age bands: {}", e)))
+}
+
+/// Pick the font to render a character with from a fallback chain
+///
+/// Mixed exercises (Latin, Greek, digits) rarely fit in one font file. Pass
+/// the fonts in order of preference and use the returned one as `font_data`
+/// for scoring, reference images, hints and live scoring.
+///
+/// # Arguments
+/// * `fonts` - Array of font bytes (`Uint8Array`), most preferred first
+/// * `character` - The character to render
+/// * `options` - A `ScoringOptions` object; with `practice` set, the chosen
+///   variant glyph is looked up instead of the character itself
+///
+/// # Returns
+/// Index into `fonts` of the first font that has the glyph
+#[wasm_bindgen]
+pub fn select_font(fonts: js_sys::Array, character: &str, #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue) -> Result<u32, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+    let fonts = fonts.iter()
+        .map(|font| load_font(&js_sys::Uint8Array::new(&font).to_vec()))
+        .collect::<Result<Vec<Font>, JsValue>>()?;
+    let fonts: Vec<&Font> = fonts.iter().collect();
+
+    crate::select_font(&fonts, char, &options)
+        .map(|index| index as u32)
+        .map_err(js_error)
+}
+
+/// Generate a reference image for a character
+///
+/// # Arguments
+/// * `character` - The character to render
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes
+/// * `size` - Output image size (width and height)
+///
+/// # Returns
+/// PNG image bytes
+#[wasm_bindgen]
+pub fn generate_reference_image(
+    character: &str,
+    font_data: &[u8],
+    size: u32,
+) -> Result<Vec<u8>, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    crate::generate_reference_image(char, &load_font(font_data)?, size, &ScoringOptions::default())
+        .map_err(js_error)
+}
+
+/// Generate the reference image a drawing will be scored against
+///
+/// With `practice` options set, this renders the same varied reference that
+/// scoring with the same options uses, so it can be shown as the overlay.
+///
+/// # Arguments
+/// * `character` - The character to render
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes
+/// * `size` - Output image size (width and height)
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// PNG image bytes
+#[wasm_bindgen]
+pub fn generate_reference_image_with_options(
+    character: &str,
+    font_data: &[u8],
+    size: u32,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    crate::generate_reference_image(char, &load_font(font_data)?, size, &options)
+        .map_err(js_error)
+}
+
+/// Generate the reference image at any width and height
+///
+/// The letter is drawn in a square as wide as the shorter side, placed by
+/// `options.guide.anchor` (`"center"` by default, or `"top"`, `"bottom"`,
+/// `"left"`, `"right"`, `"top_left"`, `"top_right"`, `"bottom_left"`,
+/// `"bottom_right"`).
+///
+/// # Arguments
+/// * `character` - The character to render
+/// * `font` - A `WasmFont`
+/// * `width`, `height` - Output image size
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// PNG image bytes
+#[wasm_bindgen]
+pub fn generate_sized_reference_image(
+    character: &str,
+    font: &WasmFont,
+    width: u32,
+    height: u32,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    crate::generate_sized_reference_image(char, &font.inner, width, height, &options)
+        .map_err(js_error)
+}
+
+/// Drop the cached references for recently scored letters
+///
+/// Scoring keeps the prepared references of the last few letters so
+/// repeated attempts skip rendering them; call this to free that memory,
+/// e.g. when leaving a lesson.
+#[wasm_bindgen]
+pub fn clear_reference_cache() {
+    crate::clear_reference_cache();
+}
+
+/// Log scoring to the console at "off" (the default), "info", "debug" or "trace"
+///
+/// "info" logs each call's drawing size and outcome, "debug" adds ink pixel
+/// counts, registration, skeleton sizes, component scores and reference
+/// cache hits, and "trace" every skeletonization and distance transform.
+/// Only in builds with the `tracing` feature.
+#[cfg(feature = "tracing")]
+#[wasm_bindgen]
+pub fn set_log_level(#[wasm_bindgen(unchecked_param_type = "LogLevel")] level: &str) -> Result<(), JsValue> {
+    let level = serde_json::from_value(serde_json::Value::from(level))
+        .map_err(|_| JsValue::from_str(&format!("Unknown log level: {}", level)))?;
+    crate::set_log_level(level);
+    Ok(())
+}
+
+/// Generate a stroke-order guide: the letter in light gray with each
+/// stroke drawn as an arrow from a numbered start dot
+///
+/// # Arguments
+/// * `character` - The character to render
+/// * `font` - A `WasmFont`
+/// * `size` - Output image size (width and height)
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// PNG image bytes
+#[cfg(feature = "guides")]
+#[wasm_bindgen]
+pub fn generate_stroke_order_image(
+    character: &str,
+    font: &WasmFont,
+    size: u32,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    crate::generate_stroke_order_image(char, &font.inner, size, &options)
+        .map_err(js_error)
+}
+
+/// Generate frames drawing the letter stroke by stroke, for demonstration
+/// animations
+///
+/// # Arguments
+/// * `character` - The character to animate
+/// * `font` - A `WasmFont`
+/// * `size` - Frame size (width and height)
+/// * `frame_count` - Number of frames, 2 to 240; the first shows the letter
+///   in light gray, the last the finished letter
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// Array of PNG image bytes, one per frame
+#[cfg(feature = "guides")]
+#[wasm_bindgen]
+pub fn generate_stroke_animation(
+    character: &str,
+    font: &WasmFont,
+    size: u32,
+    frame_count: u32,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<js_sys::Array, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    let frames = crate::generate_stroke_animation(char, &font.inner, size, frame_count, &options)
+        .map_err(js_error)?;
+    Ok(frames.iter().map(|png| js_sys::Uint8Array::from(png.as_slice())).collect())
+}
+
+/// Generate the frames of `generate_stroke_animation` side by side in one
+/// PNG spritesheet, `size * frame_count` pixels wide
+#[cfg(feature = "guides")]
+#[wasm_bindgen]
+pub fn generate_stroke_animation_sheet(
+    character: &str,
+    font: &WasmFont,
+    size: u32,
+    frame_count: u32,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    crate::generate_stroke_animation_sheet(char, &font.inner, size, frame_count, &options)
+        .map_err(js_error)
+}
+
+/// Generate a printable practice worksheet
+///
+/// Each row practices one character, cycling through `characters`: a model
+/// letter, then copies to trace (faded, or dotted or dashed when
+/// `options.guide` asks for it), on topline, midline and baseline rules.
+///
+/// # Arguments
+/// * `characters` - Characters to practice, one per row
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes
+/// * `rows` - Number of rows, 1 to 20
+/// * `cols` - Letters per row, 2 to 16
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// PNG image bytes
+#[cfg(feature = "guides")]
+#[wasm_bindgen]
+pub fn generate_worksheet(
+    characters: &str,
+    font_data: &[u8],
+    rows: u32,
+    cols: u32,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let options = parse_options(options)?;
+
+    crate::generate_worksheet(characters, &load_font(font_data)?, rows, cols, &options)
+        .map_err(js_error)
+}
+
+/// A font loaded once for reuse across calls
+///
+/// The byte-taking functions copy the font into wasm memory and unpack it
+/// on every call. Create one `WasmFont` per font and pass it to
+/// `score_drawing_with_font` and `generate_reference_image_with_font`
+/// instead when scoring many attempts.
+#[wasm_bindgen]
+pub struct WasmFont {
+    inner: Font,
+}
+
+#[wasm_bindgen]
+impl WasmFont {
+    /// Load a font from TTF, OTF, WOFF or WOFF2 bytes
+    #[wasm_bindgen(constructor)]
+    pub fn new(font_data: &[u8]) -> Result<WasmFont, JsValue> {
+        load_font(font_data).map(|inner| WasmFont { inner })
+    }
+
+    /// Whether the font or one of its fallbacks has a glyph for the character
+    pub fn has_glyph(&self, character: &str) -> bool {
+        character.chars().next().is_some_and(|c| self.inner.has_glyph(c))
+    }
+
+    /// Fall back on `font` for characters this font and its earlier
+    /// fallbacks lack, in references and scoring alike
+    pub fn add_fallback(&mut self, font: &WasmFont) {
+        self.inner = self.inner.clone().with_fallbacks([font.inner.clone()]);
+    }
+}
+
+/// Score a user's drawing with a loaded font
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+/// * `font` - A `WasmFont` to generate the reference with
+/// * `strokes` - Strokes as for `score_drawing_with_strokes`, or `null`
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_drawing_with_font(
+    image_data: &[u8],
+    character: &str,
+    font: &WasmFont,
+    #[wasm_bindgen(unchecked_optional_param_type = "Stroke[] | null")] strokes: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let strokes: Option<Vec<Stroke>> = serde_wasm_bindgen::from_value(strokes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+    let options = parse_options(options)?;
+
+    crate::score_drawing(image_data, char, &font.inner, strokes.as_deref(), &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// Score a user's drawing with a loaded font, returning a plain object
+///
+/// Takes the same arguments as `score_drawing_with_font`. The result is the
+/// object `toJSON` gives plus `reference_image` as a `Uint8Array`, so it can
+/// go into framework state, IndexedDB or `postMessage` as it is.
+#[wasm_bindgen(unchecked_return_type = "ScoredDrawing")]
+pub fn score_drawing_js(
+    image_data: &[u8],
+    character: &str,
+    font: &WasmFont,
+    #[wasm_bindgen(unchecked_optional_param_type = "Stroke[] | null")] strokes: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let strokes: Option<Vec<Stroke>> = serde_wasm_bindgen::from_value(strokes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+    let options = parse_options(options)?;
+
+    let scored = crate::score_drawing(image_data, char, &font.inner, strokes.as_deref(), &options).map_err(js_error)?;
+    let object = result_object(&scored.result)?;
+    let reference_image = js_sys::Uint8Array::from(scored.reference_image.as_slice());
+    js_sys::Reflect::set(&object, &JsValue::from_str("reference_image"), &reference_image)?;
+    Ok(object)
+}
+
+/// Score several drawings with one loaded font and options
+///
+/// With the `parallel` build and `initThreadPool` awaited, the drawings are
+/// scored on Web Workers; otherwise one after another.
+///
+/// # Arguments
+/// * `images` - Array of PNG image bytes (`Uint8Array`), one per drawing
+/// * `characters` - The character each drawing should be, in the same order
+/// * `font` - A `WasmFont` to generate the references with
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// An array holding, for each drawing, a ScoringResult or the error scoring it
+#[wasm_bindgen]
+pub fn score_drawings(
+    images: js_sys::Array,
+    characters: &str,
+    font: &WasmFont,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<js_sys::Array, JsValue> {
+    let images: Vec<Vec<u8>> = images.iter().map(|image| js_sys::Uint8Array::new(&image).to_vec()).collect();
+    let characters: Vec<char> = characters.chars().collect();
+    if images.len() != characters.len() {
+        return Err(JsValue::from_str(&format!(
+            "Got {} images but {} characters", images.len(), characters.len(),
+        )));
+    }
+    let options = parse_options(options)?;
+
+    let drawings: Vec<(&[u8], char)> = images.iter().map(Vec::as_slice).zip(characters).collect();
+    Ok(crate::score_drawings(&drawings, &font.inner, &options)
+        .into_iter()
+        .map(|result| match result {
+            Ok(scored) => JsValue::from(WasmScoringResult::from(scored)),
+            Err(error) => js_error(error),
+        })
+        .collect())
+}
+
+/// Generate the reference image a drawing will be scored against, with a
+/// loaded font
+///
+/// # Arguments
+/// * `character` - The character to render
+/// * `font` - A `WasmFont`
+/// * `size` - Output image size (width and height)
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// PNG image bytes
+#[wasm_bindgen]
+pub fn generate_reference_image_with_font(
+    character: &str,
+    font: &WasmFont,
+    size: u32,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<Vec<u8>, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    crate::generate_reference_image(char, &font.inner, size, &options)
+        .map_err(js_error)
+}
+
+#[wasm_bindgen]
+impl ScoringEngine {
+    /// Create an engine for scoring many attempts with one font
+    ///
+    /// Keeps the font, the options and the pixel buffers scoring fills, so
+    /// back-to-back calls neither reload the font nor reallocate (and grow
+    /// wasm memory). Call `free()` when done to release them.
+    ///
+    /// # Arguments
+    /// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating references
+    /// * `options` - A `ScoringOptions` object; missing fields use their defaults
+    #[wasm_bindgen(constructor)]
+    pub fn new(font_data: &[u8], #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue) -> Result<ScoringEngine, JsValue> {
+        let options = parse_options(options)?;
+        Ok(Self::with_options(load_font(font_data)?, options))
+    }
+
+    /// Replace the options used for the following attempts
+    #[wasm_bindgen(js_name = set_options)]
+    pub fn js_set_options(&mut self, #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue) -> Result<(), JsValue> {
+        self.set_options(parse_options(options)?);
+        Ok(())
+    }
+
+    /// Draw each following attempt's practice variation from `next`, a
+    /// function returning numbers in [0, 1) like `Math.random`, instead of
+    /// from `practice.seed`; `null` restores the seed
+    #[wasm_bindgen(js_name = set_random_source)]
+    pub fn js_set_random_source(&mut self, next: Option<js_sys::Function>) {
+        self.set_random_source(next.map(|next| Box::new(JsRandomSource(next)) as Box<dyn RandomSource>));
+    }
+
+    /// Time the stages of `with_timings` with `now`, a function returning
+    /// milliseconds like `performance.now`; `null` restores `performance.now`
+    #[wasm_bindgen(js_name = set_clock)]
+    pub fn js_set_clock(&mut self, now: Option<js_sys::Function>) {
+        self.set_clock(now.map(|now| Box::new(JsClock(now)) as Box<dyn Clock>));
+    }
+
+    /// Reject following attempts with an `Error` named `Cancelled` once
+    /// `token` is cancelled. The token stays cancelled, so give the next
+    /// attempt a fresh one.
+    #[wasm_bindgen(js_name = set_cancel_token)]
+    pub fn js_set_cancel_token(&mut self, token: &CancelToken) {
+        self.set_cancel_token(Some(token.clone()));
+    }
+
+    /// Stop checking the token given to `set_cancel_token`
+    pub fn clear_cancel_token(&mut self) {
+        self.set_cancel_token(None);
+    }
+
+    /// Score a user's drawing
+    ///
+    /// # Arguments
+    /// * `image_data` - PNG image bytes of the user's drawing
+    /// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+    /// * `strokes` - Strokes as for `score_drawing_with_strokes`, or `null`
+    ///
+    /// # Returns
+    /// A ScoringResult containing the score, stars, and detailed metrics
+    #[wasm_bindgen(js_name = score)]
+    pub fn js_score(&mut self, image_data: &[u8], character: &str, #[wasm_bindgen(unchecked_optional_param_type = "Stroke[] | null")] strokes: JsValue) -> Result<WasmScoringResult, JsValue> {
+        let char = character.chars().next()
+            .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+        let strokes: Option<Vec<Stroke>> = serde_wasm_bindgen::from_value(strokes)
+            .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+
+        self.score(image_data, char, strokes.as_deref())
+            .map(WasmScoringResult::from)
+            .map_err(js_error)
+    }
+}
+
+#[wasm_bindgen]
+impl CancelToken {
+    /// A token not yet cancelled, for `ScoringEngine.set_cancel_token`
+    #[wasm_bindgen(constructor)]
+    pub fn js_new() -> CancelToken {
+        CancelToken::new()
+    }
+
+    /// Ask attempts holding this token to stop
+    #[wasm_bindgen(js_name = cancel)]
+    pub fn js_cancel(&self) {
+        self.cancel();
+    }
+
+    #[wasm_bindgen(getter, js_name = cancelled)]
+    pub fn js_cancelled(&self) -> bool {
+        self.is_cancelled()
+    }
+}
+
+/// A JavaScript function returning numbers in [0, 1), spread over `u64`
+struct JsRandomSource(js_sys::Function);
+
+impl RandomSource for JsRandomSource {
+    fn next_u64(&mut self) -> u64 {
+        let unit = self.0.call0(&JsValue::NULL).ok().and_then(|value| value.as_f64()).unwrap_or(0.0);
+        (unit.clamp(0.0, 1.0) * u64::MAX as f64) as u64
+    }
+}
+
+/// A JavaScript function returning milliseconds
+struct JsClock(js_sys::Function);
+
+impl Clock for JsClock {
+    fn now_ms(&self) -> f64 {
+        self.0.call0(&JsValue::NULL).ok().and_then(|value| value.as_f64()).unwrap_or(0.0)
+    }
+}
+
+/// Render and prepare references for a character set into a pack
+///
+/// Ship the pack in place of the font and score with
+/// `score_drawing_with_pack`; only the font's glyphs as unvaried
+/// references are kept, so practice variation still needs the font.
+///
+/// # Arguments
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes
+/// * `charset` - The characters to include, e.g. "abc...XYZ0123456789"
+/// * `options` - A `ScoringOptions` object; only `font_axes` is used
+///
+/// # Returns
+/// The pack bytes
+#[wasm_bindgen]
+pub fn build_reference_pack(font_data: &[u8], charset: &str, #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue) -> Result<Vec<u8>, JsValue> {
+    let options = parse_options(options)?;
+
+    crate::build_reference_pack(&load_font(font_data)?, charset, &options)
+        .map_err(js_error)
+}
+
+/// References loaded from a pack made by `build_reference_pack`
+#[wasm_bindgen]
+pub struct WasmReferencePack {
+    inner: ReferencePack,
+}
+
+#[wasm_bindgen]
+impl WasmReferencePack {
+    /// The characters in the pack, as one string
+    #[wasm_bindgen(getter)]
+    pub fn characters(&self) -> String {
+        self.inner.characters().collect()
+    }
+
+    /// Whether the pack has a reference for the character
+    pub fn contains(&self, character: &str) -> bool {
+        character.chars().next().is_some_and(|c| self.inner.contains(c))
+    }
+
+    /// PNG bytes of the reference for a character, or `undefined`
+    pub fn reference_image(&self, character: &str) -> Option<Vec<u8>> {
+        let char = character.chars().next()?;
+        self.inner.reference_image(char).map(<[u8]>::to_vec)
+    }
+}
+
+/// Load a reference pack
+///
+/// # Arguments
+/// * `bytes` - Pack bytes from `build_reference_pack`
+#[wasm_bindgen]
+pub fn load_reference_pack(bytes: &[u8]) -> Result<WasmReferencePack, JsValue> {
+    crate::load_reference_pack(bytes)
+        .map(|inner| WasmReferencePack { inner })
+        .map_err(js_error)
+}
+
+/// Score a user's drawing against a reference from a pack
+///
+/// A character missing from the pack throws a `GlyphMissing` error.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+/// * `character` - The character that was drawn (e.g., 'A', 'a', '5')
+/// * `pack` - A `WasmReferencePack` from `load_reference_pack`
+/// * `strokes` - Strokes as for `score_drawing_with_strokes`, or `null`
+/// * `options` - A `ScoringOptions` object; `practice` is not supported
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_drawing_with_pack(
+    image_data: &[u8],
+    character: &str,
+    pack: &WasmReferencePack,
+    #[wasm_bindgen(unchecked_optional_param_type = "Stroke[] | null")] strokes: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let strokes: Option<Vec<Stroke>> = serde_wasm_bindgen::from_value(strokes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+    let options = parse_options(options)?;
+
+    crate::score_drawing_with_pack(image_data, char, &pack.inner, strokes.as_deref(), &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// A prepared reference for one character
+///
+/// `serialize()` gives bytes to keep (e.g. in IndexedDB) and
+/// `WasmReferenceHandle.deserialize(bytes)` restores them on a later page
+/// load without the font or the preparation work.
+#[wasm_bindgen]
+pub struct WasmReferenceHandle {
+    inner: ReferenceHandle,
+}
+
+#[wasm_bindgen]
+impl WasmReferenceHandle {
+    /// Render and prepare the reference for a character
+    ///
+    /// # Arguments
+    /// * `character` - The character to prepare
+    /// * `font` - A `WasmFont`
+    /// * `options` - A `ScoringOptions` object; `font_axes` and `practice` choose the reference
+    pub fn prepare(character: &str, font: &WasmFont, #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue) -> Result<WasmReferenceHandle, JsValue> {
+        let char = character.chars().next()
+            .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+        let options = parse_options(options)?;
+
+        ReferenceHandle::prepare(char, &font.inner, &options)
+            .map(|inner| WasmReferenceHandle { inner })
+            .map_err(js_error)
+    }
+
+    /// Prepare an image, such as a school's handwriting model, as the
+    /// reference for a character
+    ///
+    /// # Arguments
+    /// * `character` - The character the image shows
+    /// * `template_png` - PNG image bytes, dark ink on a light background
+    pub fn from_template(character: &str, template_png: &[u8]) -> Result<WasmReferenceHandle, JsValue> {
+        let char = character.chars().next()
+            .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+        ReferenceHandle::from_template(char, template_png)
+            .map(|inner| WasmReferenceHandle { inner })
+            .map_err(js_error)
+    }
+
+    /// Prepare an SVG path as the reference for a character
+    ///
+    /// # Arguments
+    /// * `character` - The character the shape stands for; it picks the stroke template and letter checks
+    /// * `path` - SVG path data (the `d` attribute)
+    /// * `stroke_width` - Pen width in path units to draw the path as a centerline, or `undefined` to fill it
+    #[cfg(feature = "shapes")]
+    pub fn from_svg_path(character: &str, path: &str, stroke_width: Option<f32>) -> Result<WasmReferenceHandle, JsValue> {
+        let char = character.chars().next()
+            .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+        ReferenceShape::from_svg_path(path, stroke_width)
+            .and_then(|shape| ReferenceHandle::from_shape(char, &shape))
+            .map(|inner| WasmReferenceHandle { inner })
+            .map_err(js_error)
+    }
+
+    /// Restore a reference from `serialize()` bytes
+    pub fn deserialize(bytes: &[u8]) -> Result<WasmReferenceHandle, JsValue> {
+        ReferenceHandle::deserialize(bytes)
+            .map(|inner| WasmReferenceHandle { inner })
+            .map_err(js_error)
+    }
+
+    /// Encode the prepared reference for storage
+    pub fn serialize(&self) -> Vec<u8> {
+        self.inner.serialize()
+    }
+
+    /// The character this reference is for
+    #[wasm_bindgen(getter)]
+    pub fn character(&self) -> String {
+        self.inner.character().to_string()
+    }
+
+    /// PNG bytes of the reference
+    pub fn reference_image(&self) -> Vec<u8> {
+        self.inner.reference_image().to_vec()
+    }
+}
+
+/// Render SVG path data as a reference image
+///
+/// # Arguments
+/// * `path` - SVG path data (the `d` attribute)
+/// * `stroke_width` - Pen width in path units, or `undefined` to fill the path
+/// * `size` - Size of the output image (width and height)
+///
+/// # Returns
+/// PNG image bytes
+#[cfg(feature = "shapes")]
+#[wasm_bindgen]
+pub fn generate_svg_path_reference_image(path: &str, stroke_width: Option<f32>, size: u32) -> Result<Vec<u8>, JsValue> {
+    ReferenceShape::from_svg_path(path, stroke_width)
+        .and_then(|shape| crate::generate_shape_reference_image(&shape, size))
+        .map_err(js_error)
+}
+
+/// Score a user's drawing against an image of the letter
+///
+/// For repeated attempts, prepare the template once with
+/// `WasmReferenceHandle.from_template` and use `score_drawing_with_reference`.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+/// * `character` - The character that was drawn
+/// * `template_png` - PNG image bytes of the reference letter, dark ink on a light background
+/// * `strokes` - Strokes as for `score_drawing_with_strokes`, or `null`
+/// * `options` - A `ScoringOptions` object; `font_axes` and `practice` don't apply
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_against_template(
+    image_data: &[u8],
+    character: &str,
+    template_png: &[u8],
+    #[wasm_bindgen(unchecked_optional_param_type = "Stroke[] | null")] strokes: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let strokes: Option<Vec<Stroke>> = serde_wasm_bindgen::from_value(strokes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+    let options = parse_options(options)?;
+
+    crate::score_against_template(image_data, char, template_png, strokes.as_deref(), &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// Score a user's drawing against a prepared reference
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+/// * `reference` - A `WasmReferenceHandle` for the character that was drawn
+/// * `strokes` - Strokes as for `score_drawing_with_strokes`, or `null`
+/// * `options` - A `ScoringOptions` object; `font_axes` and `practice` were applied when preparing
+///
+/// # Returns
+/// A ScoringResult containing the score, stars, and detailed metrics
+#[wasm_bindgen]
+pub fn score_drawing_with_reference(
+    image_data: &[u8],
+    reference: &WasmReferenceHandle,
+    #[wasm_bindgen(unchecked_optional_param_type = "Stroke[] | null")] strokes: JsValue,
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<WasmScoringResult, JsValue> {
+    let strokes: Option<Vec<Stroke>> = serde_wasm_bindgen::from_value(strokes)
+        .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+    let options = parse_options(options)?;
+
+    crate::score_drawing_with_reference(image_data, &reference.inner, strokes.as_deref(), &options)
+        .map(WasmScoringResult::from)
+        .map_err(js_error)
+}
+
+/// Suggest which part of the letter to draw next on a partial drawing
+///
+/// The drawing must be made over the reference as displayed, since it is
+/// compared in the reference's frame.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the partial drawing
+/// * `character` - The character being drawn
+/// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating the reference
+/// * `options` - A `ScoringOptions` object; missing fields use their defaults
+///
+/// # Returns
+/// `{ points: [[x, y]], start: [x, y], template_stroke }` in fractions (0-1)
+/// of the reference image size, or `null` once the letter is complete
+#[cfg(feature = "coaching")]
+#[wasm_bindgen]
+pub fn suggest_next_stroke(
+    image_data: &[u8],
+    character: &str,
+    font_data: &[u8],
+    #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+) -> Result<JsValue, JsValue> {
+    let char = character.chars().next()
+        .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+    let options = parse_options(options)?;
+
+    let next = crate::suggest_next_stroke(image_data, char, &load_font(font_data)?, &options)
+        .map_err(js_error)?;
+
+    serde_wasm_bindgen::to_value(&next)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize hint: {}", e)))
+}
+
+/// Check whether a drawing can be scored, without running the scoring
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+///
+/// # Returns
+/// `{ scoreable, issues, message, ink_pixels }`, where `issues` holds
+/// `"empty"`, `"too_little_ink"`, `"faint"` or `"cut_off"` and `message` is a
+/// prompt for the learner about the first of them
+#[wasm_bindgen(unchecked_return_type = "DrawingQuality")]
+pub fn check_drawing_quality(image_data: &[u8]) -> Result<JsValue, JsValue> {
+    let quality = crate::check_drawing_quality(image_data).map_err(js_error)?;
+    serde_wasm_bindgen::to_value(&quality)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize drawing quality: {}", e)))
+}
+
+/// Extract the centerline of a drawing as a graph
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+///
+/// # Returns
+/// `{ nodes, edges, components, loops }` in the image's pixels: `nodes` are
+/// `{ x, y, kind }` with `kind` `"endpoint"`, `"junction"` or `"loop"`, and
+/// `edges` are `{ from, to, points, length }` with `points` a polyline of
+/// `[x, y]` pairs between the two nodes
+#[wasm_bindgen(unchecked_return_type = "DrawingGraph")]
+pub fn extract_skeleton_graph(image_data: &[u8]) -> Result<JsValue, JsValue> {
+    let graph = crate::extract_skeleton_graph(image_data).map_err(js_error)?;
+    serde_wasm_bindgen::to_value(&graph)
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize skeleton graph: {}", e)))
+}
+
+/// Estimate the legibility of a drawing without any reference
+///
+/// Useful for free-write mode, where there is no target character.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+///
+/// # Returns
+/// A legibility score from 0 to 100
+#[wasm_bindgen]
+pub fn estimate_legibility(image_data: &[u8]) -> Result<f32, JsValue> {
+    crate::estimate_legibility(image_data)
+        .map_err(js_error)
+}
+
+/// Perceptual hash of a drawing
+///
+/// Hashes the drawing after the same cropping and normalization used for
+/// scoring, so it matches `drawing_hash` in scoring results (unless stray
+/// marks were left out there). Identical drawings hash the same; similar
+/// ones differ in few bits, see `hash_distance`.
+///
+/// # Arguments
+/// * `image_data` - PNG image bytes of the user's drawing
+///
+/// # Returns
+/// A 256-bit hash as 64 hex digits
+#[wasm_bindgen]
+pub fn hash_drawing(image_data: &[u8]) -> Result<String, JsValue> {
+    crate::hash_drawing(image_data)
+        .map_err(js_error)
+}
+
+/// Number of differing bits between two drawing hashes
+///
+/// Useful for clustering similar attempts; 0 means the normalized
+/// drawings are practically identical.
+#[wasm_bindgen]
+pub fn hash_distance(a: &str, b: &str) -> Result<u32, JsValue> {
+    crate::hash_distance(a, b)
+        .map_err(js_error)
+}
+
+/// Summarize the attempts of a practice session
+///
+/// # Arguments
+/// * `attempts` - Array of `{ character, strokes, score }` objects, oldest
+///   first; strokes use the same format as `score_drawing_with_strokes`
+///
+/// # Returns
+/// A `SessionSummary` with the attempt count, a handedness estimate
+/// (`handedness.suggest_left_handed_mode` says whether to offer left-handed
+/// mode) and quality/fluency trends (`fatigue.suggestion` is set when the
+/// learner seems tired)
+#[cfg(feature = "session-analytics")]
+#[wasm_bindgen]
+pub fn summarize_session(attempts: JsValue) -> Result<JsValue, JsValue> {
+    let attempts: Vec<SessionAttempt> = serde_wasm_bindgen::from_value(attempts)
+        .map_err(|e| JsValue::from_str(&format!("Invalid session attempts: {}", e)))?;
+
+    serde_wasm_bindgen::to_value(&crate::summarize_session(&attempts))
+        .map_err(|e| JsValue::from_str(&format!("Failed to serialize session summary: {}", e)))
+}
+
+#[cfg(feature = "coaching")]
+#[wasm_bindgen]
+impl LiveScorer {
+    /// Create a live scorer for a character
+    ///
+    /// # Arguments
+    /// * `character` - The character being drawn
+    /// * `font_data` - TTF, OTF, WOFF or WOFF2 font bytes to use for generating the reference
+    /// * `canvas_width`, `canvas_height` - Size of the drawing canvas in pixels
+    /// * `options` - A `ScoringOptions` object; missing fields use their defaults
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        character: &str,
+        font_data: &[u8],
+        canvas_width: u32,
+        canvas_height: u32,
+        #[wasm_bindgen(unchecked_optional_param_type = "ScoringOptions | null")] options: JsValue,
+    ) -> Result<LiveScorer, JsValue> {
+        let char = character.chars().next()
+            .ok_or_else(|| JsValue::from_str("Empty character string"))?;
+
+        let options = parse_options(options)?;
+
+        Self::with_options(char, &load_font(font_data)?, canvas_width, canvas_height, options)
+            .map_err(js_error)
+    }
+
+    /// Add a finished stroke: `{ points: [{ x, y, t?, pressure? }], erase?, width? }`
+    pub fn add_stroke(&mut self, #[wasm_bindgen(unchecked_param_type = "Stroke")] stroke: JsValue) -> Result<(), JsValue> {
+        let stroke: Stroke = serde_wasm_bindgen::from_value(stroke)
+            .map_err(|e| JsValue::from_str(&format!("Invalid stroke data: {}", e)))?;
+        self.push_stroke(stroke);
+        Ok(())
+    }
+
+    /// The largest part of the letter not drawn yet, for pulsing that region
+    ///
+    /// Returns `{ points: [[x, y]], start: [x, y], template_stroke }` in
+    /// fractions (0-1) of the reference image, or `null` once every part
+    /// is drawn.
+    #[wasm_bindgen(js_name = next_hint)]
+    pub fn js_next_hint(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.next_hint()).unwrap_or(JsValue::NULL)
+    }
+
+    /// Reference segments as `[[x, y]]` polylines in fractions (0-1) of the
+    /// reference image, for lighting up parts of the letter
+    #[wasm_bindgen(js_name = segments)]
+    pub fn js_segments(&self) -> JsValue {
+        serde_wasm_bindgen::to_value(&self.segments()).unwrap_or(JsValue::NULL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgeBand, PracticeOptions, RulingOptions, StrokeDirection, StrokeGap, StrokePoint, StrokeTransform};
+
+    const TYPES: &str = include_str!("../types.d.ts");
+
+    /// Field names of an interface in types.d.ts, sorted
+    fn interface_fields(name: &str) -> Vec<String> {
+        let start = TYPES.find(&format!("export interface {} ", name))
+            .unwrap_or_else(|| panic!("types.d.ts has no interface {}", name));
+        let body = &TYPES[start..];
+        let body = &body[body.find('{').unwrap() + 1..body.find("\n}").unwrap()];
+        let mut fields: Vec<String> = body.lines()
+            .filter_map(|line| {
+                let line = line.trim();
+                let name: String = line.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+                let rest = &line[name.len()..];
+                (!name.is_empty() && (rest.starts_with(':') || rest.starts_with("?:"))).then_some(name)
+            })
+            .collect();
+        fields.sort();
+        fields
+    }
+
+    fn serialized_fields(value: impl Serialize) -> Vec<String> {
+        let mut fields: Vec<String> = serde_json::to_value(value).unwrap()
+            .as_object().unwrap()
+            .keys().cloned().collect();
+        fields.sort();
+        fields
+    }
+
+    #[test]
+    fn test_typescript_types_match_rust_fields() {
+        let options = ScoringOptions::default();
+        assert_eq!(interface_fields("ScoringOptions"), serialized_fields(&options));
+        assert_eq!(interface_fields("ScoreWeights"), serialized_fields(&options.weights));
+        assert_eq!(interface_fields("StarThresholds"), serialized_fields(&options.stars));
+        assert_eq!(interface_fields("TimeOptions"), serialized_fields(&options.time));
+        assert_eq!(interface_fields("ScaffoldOptions"), serialized_fields(&options.scaffold));
+        assert_eq!(interface_fields("GuideOptions"), serialized_fields(&options.guide));
+        assert_eq!(interface_fields("InputLimits"), serialized_fields(&options.input_limits));
+        assert_eq!(interface_fields("ThinningLimits"), serialized_fields(options.input_limits.thinning));
+        assert_eq!(interface_fields("StrokeTransform"), serialized_fields(StrokeTransform::default()));
+        assert_eq!(interface_fields("PracticeOptions"), serialized_fields(PracticeOptions::default()));
+        assert_eq!(interface_fields("RulingOptions"), serialized_fields(RulingOptions::default()));
+        assert_eq!(interface_fields("AgeBandBundle"), serialized_fields(AgeBand::Ages5To6.bundle()));
+
+        let point = StrokePoint { x: 0.0, y: 0.0, t: None, pressure: None };
+        assert_eq!(interface_fields("StrokePoint"), serialized_fields(point));
+        assert_eq!(interface_fields("Stroke"), serialized_fields(Stroke::default()));
+        let gap = StrokeGap { x: 0.0, y: 0.0, length: 0.0, region: String::new() };
+        assert_eq!(interface_fields("StrokeGap"), serialized_fields(gap));
+        let direction = StrokeDirection { stroke: 0, template_stroke: None, start_ok: true, direction_ok: true };
+        assert_eq!(interface_fields("StrokeDirection"), serialized_fields(direction));
+
+        let font = Font::from_bytes(include_bytes!("../../../public/fonts/Fredoka-Regular.ttf")).unwrap();
+        let png = crate::generate_reference_image('a', &font, 200, &options).unwrap();
+        let scored = crate::score_drawing(&png, 'a', &font, None, &options).unwrap();
+        assert_eq!(interface_fields("ScoringResult"), serialized_fields(&scored.result));
+        let explanation = &scored.result.explanation;
+        assert_eq!(interface_fields("ScoreExplanation"), serialized_fields(explanation));
+        assert_eq!(interface_fields("ScoreContribution"), serialized_fields(&explanation.coverage));
+        assert_eq!(interface_fields("ScoreCap"), serialized_fields(crate::ScoreCap::new("scribble", 29)));
+
+        let timed = ScoringOptions { with_timings: true, ..Default::default() };
+        let timings = crate::score_drawing(&png, 'a', &font, None, &timed).unwrap().result.timings.unwrap();
+        assert_eq!(interface_fields("StageTimings"), serialized_fields(timings));
+
+        let quality = crate::check_drawing_quality(&png).unwrap();
+        assert_eq!(interface_fields("DrawingQuality"), serialized_fields(quality));
+
+        let graph = crate::extract_skeleton_graph(&png).unwrap();
+        assert_eq!(interface_fields("DrawingGraph"), serialized_fields(&graph));
+        assert_eq!(interface_fields("GraphNode"), serialized_fields(&graph.nodes[0]));
+        assert_eq!(interface_fields("GraphEdge"), serialized_fields(&graph.edges[0]));
+    }
+}
@@ -0,0 +1,773 @@
+//! WOFF and WOFF2 font input
+//!
+//! Web apps ship fonts compressed as WOFF or WOFF2. The glyph rasterizer
+//! only reads plain sfnt (TTF/OTF) data, so compressed fonts are unpacked
+//! back into an sfnt first. WOFF compresses each table with zlib; WOFF2
+//! compresses all tables as one Brotli stream and may also transform the
+//! `glyf`, `loca` and `hmtx` tables, which are rebuilt here.
+
+use std::borrow::Cow;
+
+const WOFF_SIGNATURE: &[u8; 4] = b"wOFF";
+const WOFF2_SIGNATURE: &[u8; 4] = b"wOF2";
+
+// Decompressed fonts larger than this, counting all tables together, are
+// rejected rather than unpacked
+#[cfg(feature = "woff")]
+const MAX_SFNT_SIZE: usize = 32 * 1024 * 1024;
+// Most tables an sfnt directory can describe: its searchRange fields are
+// 16 times the table count in a u16
+#[cfg(feature = "woff")]
+const MAX_TABLES: u16 = 4095;
+
+/// Plain sfnt data for a font given as TTF, OTF, WOFF or WOFF2
+pub(crate) fn sfnt_data(font_data: &[u8]) -> Result<Cow<'_, [u8]>, String> {
+    match font_data.get(..4) {
+        Some(signature) if signature == WOFF_SIGNATURE => decode_woff(font_data).map(Cow::Owned),
+        Some(signature) if signature == WOFF2_SIGNATURE => decode_woff2(font_data).map(Cow::Owned),
+        _ => Ok(Cow::Borrowed(font_data)),
+    }
+}
+
+#[cfg(not(feature = "woff"))]
+fn decode_woff(_font_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("WOFF fonts need the woff feature".to_string())
+}
+
+#[cfg(not(feature = "woff"))]
+fn decode_woff2(_font_data: &[u8]) -> Result<Vec<u8>, String> {
+    Err("WOFF2 fonts need the woff feature".to_string())
+}
+
+#[cfg(feature = "woff")]
+pub(crate) use decode::{decode_woff, decode_woff2};
+
+#[cfg(feature = "woff")]
+mod decode {
+    use super::{MAX_SFNT_SIZE, MAX_TABLES};
+    use std::io::Read;
+
+    // Tags of the WOFF2 known-table index, in order
+    const KNOWN_TAGS: [&[u8; 4]; 63] = [
+        b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post",
+        b"cvt ", b"fpgm", b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT",
+        b"EBLC", b"gasp", b"hdmx", b"kern", b"LTSH", b"PCLT", b"VDMX", b"vhea",
+        b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC", b"JSTF", b"MATH",
+        b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+        b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar",
+        b"gvar", b"hsty", b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop",
+        b"trak", b"Zapf", b"Silf", b"Glat", b"Gloc", b"Feat", b"Sill",
+    ];
+
+    // Composite glyph component flags
+    const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+    const WE_HAVE_A_SCALE: u16 = 0x0008;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+    const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+    const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+    // Simple glyph point flags
+    const ON_CURVE_POINT: u8 = 0x01;
+    const OVERLAP_SIMPLE: u8 = 0x40;
+
+    /// Big-endian reader over a byte slice
+    struct Reader<'a> {
+        data: &'a [u8],
+        pos: usize,
+    }
+
+    impl<'a> Reader<'a> {
+        fn new(data: &'a [u8]) -> Self {
+            Self { data, pos: 0 }
+        }
+
+        fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+            let end = self.pos.checked_add(n).filter(|&end| end <= self.data.len())
+                .ok_or("Truncated font data")?;
+            let bytes = &self.data[self.pos..end];
+            self.pos = end;
+            Ok(bytes)
+        }
+
+        fn u8(&mut self) -> Result<u8, String> {
+            Ok(self.bytes(1)?[0])
+        }
+
+        fn u16(&mut self) -> Result<u16, String> {
+            let b = self.bytes(2)?;
+            Ok(u16::from_be_bytes([b[0], b[1]]))
+        }
+
+        fn i16(&mut self) -> Result<i16, String> {
+            Ok(self.u16()? as i16)
+        }
+
+        fn u32(&mut self) -> Result<u32, String> {
+            let b = self.bytes(4)?;
+            Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        }
+
+        /// WOFF2 variable-length UIntBase128
+        fn base128(&mut self) -> Result<u32, String> {
+            let mut value = 0u32;
+            for i in 0..5 {
+                let byte = self.u8()?;
+                if i == 0 && byte == 0x80 {
+                    return Err("Invalid WOFF2 number".to_string());
+                }
+                if value & 0xFE00_0000 != 0 {
+                    return Err("Invalid WOFF2 number".to_string());
+                }
+                value = (value << 7) | (byte & 0x7F) as u32;
+                if byte & 0x80 == 0 {
+                    return Ok(value);
+                }
+            }
+            Err("Invalid WOFF2 number".to_string())
+        }
+
+        /// WOFF2 variable-length 255UInt16
+        fn u255(&mut self) -> Result<u16, String> {
+            Ok(match self.u8()? {
+                253 => self.u16()?,
+                254 => self.u8()? as u16 + 506,
+                255 => self.u8()? as u16 + 253,
+                code => code as u16,
+            })
+        }
+    }
+
+    /// Unpack a WOFF font
+    pub(crate) fn decode_woff(data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut header = Reader::new(data);
+        header.bytes(4)?;
+        let flavor = header.u32()?;
+        header.u32()?;
+        let num_tables = table_count(header.u16()?)?;
+        header.bytes(30)?;
+
+        // The whole directory is checked before anything is decompressed
+        let mut directory = Vec::with_capacity(num_tables as usize);
+        let mut total = 0usize;
+        for _ in 0..num_tables {
+            let tag: [u8; 4] = header.bytes(4)?.try_into().unwrap();
+            let offset = header.u32()? as usize;
+            let compressed_length = header.u32()? as usize;
+            let length = header.u32()? as usize;
+            header.u32()?;
+
+            let stored = offset.checked_add(compressed_length)
+                .and_then(|end| data.get(offset..end))
+                .ok_or("Truncated WOFF table")?;
+            total = add_table_size(total, length)?;
+            directory.push((tag, stored, length));
+        }
+
+        let mut tables = Vec::with_capacity(directory.len());
+        for (tag, stored, length) in directory {
+            let table = if stored.len() < length {
+                miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(stored, length)
+                    .map_err(|e| format!("Failed to decompress WOFF table: {:?}", e.status))?
+            } else {
+                stored.to_vec()
+            };
+            if table.len() != length {
+                return Err("WOFF table has the wrong length".to_string());
+            }
+            tables.push((tag, table));
+        }
+
+        Ok(build_sfnt(flavor, tables))
+    }
+
+    /// One entry of the WOFF2 table directory
+    struct Woff2Table {
+        tag: [u8; 4],
+        transformed: bool,
+        length: usize,
+        stored_length: usize,
+    }
+
+    /// Unpack a WOFF2 font, undoing table transforms
+    pub(crate) fn decode_woff2(data: &[u8]) -> Result<Vec<u8>, String> {
+        let mut header = Reader::new(data);
+        header.bytes(4)?;
+        let flavor = header.u32()?;
+        if &flavor.to_be_bytes() == b"ttcf" {
+            return Err("WOFF2 font collections are not supported".to_string());
+        }
+        header.u32()?;
+        let num_tables = table_count(header.u16()?)?;
+        header.u16()?;
+        let sfnt_size = header.u32()? as usize;
+        let compressed_size = header.u32()? as usize;
+        header.bytes(24)?;
+
+        let mut directory = Vec::with_capacity(num_tables as usize);
+        let mut total = 0usize;
+        for _ in 0..num_tables {
+            let flags = header.u8()?;
+            let tag = match flags & 0x3F {
+                63 => header.bytes(4)?.try_into().unwrap(),
+                index => *KNOWN_TAGS[index as usize],
+            };
+            let version = flags >> 6;
+            // glyf and loca are transformed unless version 3; others only
+            // when the version is nonzero
+            let transformed = if &tag == b"glyf" || &tag == b"loca" { version != 3 } else { version != 0 };
+            let length = header.base128()? as usize;
+            let stored_length = if transformed { header.base128()? as usize } else { length };
+            total = add_table_size(total, length.max(stored_length))?;
+            directory.push(Woff2Table { tag, transformed, length, stored_length });
+        }
+
+        let compressed = header.bytes(compressed_size)?;
+        let limit = sfnt_size.clamp(1, MAX_SFNT_SIZE);
+        let mut stream = Vec::new();
+        brotli_decompressor::Decompressor::new(compressed, 4096)
+            .take(limit as u64 * 2)
+            .read_to_end(&mut stream)
+            .map_err(|e| format!("Failed to decompress WOFF2 data: {}", e))?;
+
+        let mut stored = Reader::new(&stream);
+        let mut tables: Vec<([u8; 4], Vec<u8>)> = Vec::with_capacity(directory.len());
+        let mut glyf = None;
+        let mut hmtx = None;
+        for entry in &directory {
+            let bytes = stored.bytes(entry.stored_length)?;
+            match &entry.tag {
+                b"glyf" if entry.transformed => glyf = Some(reconstruct_glyf(bytes)?),
+                b"loca" if entry.transformed => {}
+                b"hmtx" if entry.transformed => hmtx = Some(bytes),
+                _ if entry.transformed => {
+                    return Err(format!("Unknown WOFF2 transform of {}", String::from_utf8_lossy(&entry.tag)));
+                }
+                _ => {
+                    if bytes.len() != entry.length {
+                        return Err("WOFF2 table has the wrong length".to_string());
+                    }
+                }
+            }
+            tables.push((entry.tag, bytes.to_vec()));
+        }
+
+        if let Some(glyphs) = &glyf {
+            for (tag, table) in tables.iter_mut() {
+                match &*tag {
+                    b"glyf" => *table = glyphs.glyf.clone(),
+                    b"loca" => *table = glyphs.loca.clone(),
+                    // The rebuilt loca always uses long offsets
+                    b"head" if table.len() >= 52 => table[50..52].copy_from_slice(&1u16.to_be_bytes()),
+                    _ => {}
+                }
+            }
+        }
+        if let Some(transformed) = hmtx {
+            let glyphs = glyf.as_ref().ok_or("WOFF2 hmtx transform needs glyf")?;
+            let hhea = tables.iter().find(|(tag, _)| tag == b"hhea").ok_or("WOFF2 font has no hhea")?;
+            let metrics = Reader::new(hhea.1.get(34..).unwrap_or_default()).u16()? as usize;
+            let rebuilt = reconstruct_hmtx(transformed, metrics, &glyphs.x_mins)?;
+            if let Some((_, table)) = tables.iter_mut().find(|(tag, _)| tag == b"hmtx") {
+                *table = rebuilt;
+            }
+        }
+
+        // Rebuilt tables can outgrow the lengths the directory declared
+        tables.iter().try_fold(0, |total, (_, table)| add_table_size(total, table.len()))?;
+        Ok(build_sfnt(flavor, tables))
+    }
+
+    /// A header's table count, if an sfnt can hold that many tables
+    fn table_count(num_tables: u16) -> Result<u16, String> {
+        if num_tables == 0 || num_tables > MAX_TABLES {
+            return Err(format!("Invalid font table count {}", num_tables));
+        }
+        Ok(num_tables)
+    }
+
+    /// Running size of the unpacked tables, failing past `MAX_SFNT_SIZE`
+    fn add_table_size(total: usize, length: usize) -> Result<usize, String> {
+        total.checked_add(length).filter(|&total| total <= MAX_SFNT_SIZE)
+            .ok_or_else(|| "Font is too large once decompressed".to_string())
+    }
+
+    /// glyf and loca rebuilt from the WOFF2 transform
+    pub(super) struct Glyphs {
+        pub(super) glyf: Vec<u8>,
+        pub(super) loca: Vec<u8>,
+        /// Left edge of each glyph, for the hmtx transform
+        pub(super) x_mins: Vec<i16>,
+    }
+
+    pub(super) fn reconstruct_glyf(data: &[u8]) -> Result<Glyphs, String> {
+        let mut header = Reader::new(data);
+        header.u16()?;
+        let option_flags = header.u16()?;
+        let num_glyphs = header.u16()? as usize;
+        header.u16()?;
+        let mut sizes = [0usize; 7];
+        for size in sizes.iter_mut() {
+            *size = header.u32()? as usize;
+        }
+
+        let mut streams = Vec::with_capacity(7);
+        for size in sizes {
+            streams.push(Reader::new(header.bytes(size)?));
+        }
+        let overlap_bitmap = if option_flags & 1 != 0 { Some(header.bytes(num_glyphs.div_ceil(8))?) } else { None };
+        let [mut n_contours, mut n_points, mut flags, mut glyph_stream, mut composite, mut bbox, mut instructions]: [Reader; 7] =
+            streams.try_into().map_err(|_| "Invalid WOFF2 glyf table")?;
+
+        let bbox_bitmap = bbox.bytes(num_glyphs.div_ceil(32) * 4)?;
+        let has_bit = |bitmap: &[u8], i: usize| bitmap[i >> 3] & (0x80 >> (i & 7)) != 0;
+
+        let mut glyf = Vec::new();
+        let mut loca = Vec::with_capacity((num_glyphs + 1) * 4);
+        let mut x_mins = vec![0i16; num_glyphs];
+
+        for (glyph, x_min) in x_mins.iter_mut().enumerate() {
+            loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+            let contours = n_contours.i16()?;
+            let explicit_bbox = if has_bit(bbox_bitmap, glyph) {
+                Some([bbox.i16()?, bbox.i16()?, bbox.i16()?, bbox.i16()?])
+            } else {
+                None
+            };
+
+            if contours == 0 {
+                continue;
+            } else if contours < 0 {
+                // Composite: components are stored as in glyf
+                let bounds = explicit_bbox.ok_or("WOFF2 composite glyph without a bounding box")?;
+                let start = composite.pos;
+                let mut has_instructions = false;
+                loop {
+                    let component_flags = composite.u16()?;
+                    composite.u16()?;
+                    let mut skip = if component_flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+                    if component_flags & WE_HAVE_A_SCALE != 0 {
+                        skip += 2;
+                    } else if component_flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                        skip += 4;
+                    } else if component_flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                        skip += 8;
+                    }
+                    composite.bytes(skip)?;
+                    has_instructions |= component_flags & WE_HAVE_INSTRUCTIONS != 0;
+                    if component_flags & MORE_COMPONENTS == 0 {
+                        break;
+                    }
+                }
+                let components = &composite.data[start..composite.pos];
+
+                glyf.extend_from_slice(&(-1i16).to_be_bytes());
+                for value in bounds {
+                    glyf.extend_from_slice(&value.to_be_bytes());
+                }
+                glyf.extend_from_slice(components);
+                if has_instructions {
+                    let length = glyph_stream.u255()? as usize;
+                    glyf.extend_from_slice(&(length as u16).to_be_bytes());
+                    glyf.extend_from_slice(instructions.bytes(length)?);
+                }
+                *x_min = bounds[0];
+            } else {
+                let mut end_points = Vec::with_capacity(contours as usize);
+                let mut total = 0usize;
+                for _ in 0..contours {
+                    total += n_points.u255()? as usize;
+                    end_points.push(total.checked_sub(1).ok_or("WOFF2 contour without points")?);
+                }
+
+                // Every point takes a byte of the flag stream, so a count
+                // past its end is corrupt, however large it claims to be
+                if total > flags.data.len() - flags.pos {
+                    return Err("WOFF2 glyph has more points than flags".to_string());
+                }
+                let mut points = Vec::with_capacity(total);
+                let (mut x, mut y) = (0i32, 0i32);
+                for _ in 0..total {
+                    let flag = flags.u8()?;
+                    let (dx, dy) = decode_triplet(flag & 0x7F, &mut glyph_stream)?;
+                    x += dx;
+                    y += dy;
+                    points.push((x, y, flag & 0x80 == 0));
+                }
+                let instruction_length = glyph_stream.u255()? as usize;
+
+                let bounds = explicit_bbox.unwrap_or_else(|| {
+                    let (mut min_x, mut min_y, mut max_x, mut max_y) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+                    for &(x, y, _) in &points {
+                        min_x = min_x.min(x);
+                        min_y = min_y.min(y);
+                        max_x = max_x.max(x);
+                        max_y = max_y.max(y);
+                    }
+                    [min_x as i16, min_y as i16, max_x as i16, max_y as i16]
+                });
+
+                glyf.extend_from_slice(&contours.to_be_bytes());
+                for value in bounds {
+                    glyf.extend_from_slice(&value.to_be_bytes());
+                }
+                for end in end_points {
+                    glyf.extend_from_slice(&(end as u16).to_be_bytes());
+                }
+                glyf.extend_from_slice(&(instruction_length as u16).to_be_bytes());
+                glyf.extend_from_slice(instructions.bytes(instruction_length)?);
+
+                // Flags without short-vector bits, so every delta is a word
+                let overlap = overlap_bitmap.is_some_and(|bitmap| has_bit(bitmap, glyph));
+                for (i, &(_, _, on_curve)) in points.iter().enumerate() {
+                    let mut flag = if on_curve { ON_CURVE_POINT } else { 0 };
+                    if i == 0 && overlap {
+                        flag |= OVERLAP_SIMPLE;
+                    }
+                    glyf.push(flag);
+                }
+                let mut previous = 0i32;
+                for &(x, _, _) in &points {
+                    glyf.extend_from_slice(&((x - previous) as i16).to_be_bytes());
+                    previous = x;
+                }
+                previous = 0;
+                for &(_, y, _) in &points {
+                    glyf.extend_from_slice(&((y - previous) as i16).to_be_bytes());
+                    previous = y;
+                }
+                *x_min = bounds[0];
+            }
+
+            while glyf.len() % 4 != 0 {
+                glyf.push(0);
+            }
+            if glyf.len() > MAX_SFNT_SIZE {
+                return Err("Font is too large once decompressed".to_string());
+            }
+        }
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+
+        Ok(Glyphs { glyf, loca, x_mins })
+    }
+
+    /// Point delta encoded by a WOFF2 triplet flag
+    fn decode_triplet(flag: u8, stream: &mut Reader) -> Result<(i32, i32), String> {
+        let with_sign = |flag: u8, value: i32| if flag & 1 != 0 { value } else { -value };
+        let flag_value = flag as i32;
+        Ok(match flag {
+            0..=9 => (0, with_sign(flag, ((flag_value & 14) << 7) + stream.u8()? as i32)),
+            10..=19 => (with_sign(flag, (((flag_value - 10) & 14) << 7) + stream.u8()? as i32), 0),
+            20..=83 => {
+                let b0 = flag_value - 20;
+                let b1 = stream.u8()? as i32;
+                (
+                    with_sign(flag, 1 + (b0 & 0x30) + (b1 >> 4)),
+                    with_sign(flag >> 1, 1 + ((b0 & 0x0C) << 2) + (b1 & 0x0F)),
+                )
+            }
+            84..=119 => {
+                let b0 = flag_value - 84;
+                let b = stream.bytes(2)?;
+                (
+                    with_sign(flag, 1 + ((b0 / 12) << 8) + b[0] as i32),
+                    with_sign(flag >> 1, 1 + (((b0 % 12) >> 2) << 8) + b[1] as i32),
+                )
+            }
+            120..=123 => {
+                let b = stream.bytes(3)?;
+                (
+                    with_sign(flag, ((b[0] as i32) << 4) + (b[1] as i32 >> 4)),
+                    with_sign(flag >> 1, ((b[1] as i32 & 0x0F) << 8) + b[2] as i32),
+                )
+            }
+            _ => {
+                let b = stream.bytes(4)?;
+                (
+                    with_sign(flag, ((b[0] as i32) << 8) + b[1] as i32),
+                    with_sign(flag >> 1, ((b[2] as i32) << 8) + b[3] as i32),
+                )
+            }
+        })
+    }
+
+    /// hmtx rebuilt from the WOFF2 transform, taking omitted side bearings
+    /// from the glyph bounding boxes
+    fn reconstruct_hmtx(data: &[u8], metrics: usize, x_mins: &[i16]) -> Result<Vec<u8>, String> {
+        let mut stream = Reader::new(data);
+        let flags = stream.u8()?;
+        let mut advances = Vec::with_capacity(metrics);
+        for _ in 0..metrics {
+            advances.push(stream.u16()?);
+        }
+
+        let mut table = Vec::with_capacity(x_mins.len() * 4);
+        for (glyph, &x_min) in x_mins.iter().enumerate() {
+            let proportional = glyph < metrics;
+            let omitted = if proportional { flags & 1 != 0 } else { flags & 2 != 0 };
+            let side_bearing = if omitted { x_min } else { stream.i16()? };
+            if proportional {
+                table.extend_from_slice(&advances[glyph].to_be_bytes());
+            }
+            table.extend_from_slice(&side_bearing.to_be_bytes());
+        }
+        Ok(table)
+    }
+
+    /// Assemble tables into an sfnt file
+    fn build_sfnt(flavor: u32, mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+        tables.sort_by_key(|table| table.0);
+
+        let num_tables = tables.len() as u16;
+        let entry_selector = 15 - num_tables.max(1).leading_zeros() as u16;
+        let search_range = (1u16 << entry_selector) * 16;
+
+        let mut sfnt = Vec::new();
+        sfnt.extend_from_slice(&flavor.to_be_bytes());
+        for value in [num_tables, search_range, entry_selector, num_tables * 16 - search_range] {
+            sfnt.extend_from_slice(&value.to_be_bytes());
+        }
+
+        let mut offset = 12 + tables.len() * 16;
+        for (tag, table) in &tables {
+            let checksum = table.chunks(4).fold(0u32, |sum, chunk| {
+                let mut word = [0u8; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                sum.wrapping_add(u32::from_be_bytes(word))
+            });
+            sfnt.extend_from_slice(tag);
+            sfnt.extend_from_slice(&checksum.to_be_bytes());
+            sfnt.extend_from_slice(&(offset as u32).to_be_bytes());
+            sfnt.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len().div_ceil(4) * 4;
+        }
+        for (_, table) in &tables {
+            sfnt.extend_from_slice(table);
+            sfnt.resize(sfnt.len().div_ceil(4) * 4, 0);
+        }
+        sfnt
+    }
+}
+
+#[cfg(all(test, feature = "woff"))]
+mod tests {
+    use super::*;
+
+    const TEST_FONT: &[u8] = include_bytes!("../../../public/fonts/Fredoka-Regular.ttf");
+
+    /// Wrap a TTF as WOFF, compressing every other table
+    fn to_woff(ttf: &[u8]) -> Vec<u8> {
+        let be16 = |at: usize| u16::from_be_bytes([ttf[at], ttf[at + 1]]) as usize;
+        let be32 = |at: usize| u32::from_be_bytes(ttf[at..at + 4].try_into().unwrap()) as usize;
+        let num_tables = be16(4);
+
+        let mut directory = Vec::new();
+        let mut body = Vec::new();
+        let data_start = 44 + num_tables * 20;
+        for i in 0..num_tables {
+            let record = 12 + i * 16;
+            let table = &ttf[be32(record + 8)..be32(record + 8) + be32(record + 12)];
+            let stored = if i % 2 == 0 { miniz_oxide::deflate::compress_to_vec_zlib(table, 6) } else { table.to_vec() };
+            let stored = if stored.len() < table.len() { stored } else { table.to_vec() };
+
+            directory.extend_from_slice(&ttf[record..record + 4]);
+            directory.extend_from_slice(&((data_start + body.len()) as u32).to_be_bytes());
+            directory.extend_from_slice(&(stored.len() as u32).to_be_bytes());
+            directory.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            directory.extend_from_slice(&ttf[record + 4..record + 8]);
+            body.extend_from_slice(&stored);
+            body.resize(body.len().div_ceil(4) * 4, 0);
+        }
+
+        let mut woff = b"wOFF".to_vec();
+        woff.extend_from_slice(&ttf[0..4]);
+        woff.extend_from_slice(&((data_start + body.len()) as u32).to_be_bytes());
+        woff.extend_from_slice(&(num_tables as u16).to_be_bytes());
+        woff.extend_from_slice(&[0; 2]);
+        woff.extend_from_slice(&(ttf.len() as u32).to_be_bytes());
+        woff.extend_from_slice(&[0; 24]);
+        woff.extend_from_slice(&directory);
+        woff.extend_from_slice(&body);
+        woff
+    }
+
+    #[test]
+    fn test_sfnt_data_passes_plain_fonts_through() {
+        assert!(matches!(sfnt_data(TEST_FONT).unwrap(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_decode_woff() {
+        let woff = to_woff(TEST_FONT);
+        assert!(woff.len() < TEST_FONT.len());
+
+        let sfnt = sfnt_data(&woff).unwrap();
+        let original = ab_glyph::FontRef::try_from_slice(TEST_FONT).unwrap();
+        let decoded = ab_glyph::FontRef::try_from_slice(&sfnt).unwrap();
+        use ab_glyph::Font;
+        for character in ['a', 'g', 'Q'] {
+            let outline = |font: &ab_glyph::FontRef| font.outline(font.glyph_id(character)).unwrap().curves.len();
+            assert_eq!(outline(&original), outline(&decoded));
+        }
+    }
+
+    /// Wrap data in a Brotli stream of uncompressed meta-blocks
+    fn brotli_stored(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let (mut bits, mut count) = (0u32, 0u32);
+        let mut push = |out: &mut Vec<u8>, value: u32, width: u32, align: bool| {
+            bits |= value << count;
+            count += width;
+            if align {
+                count = count.div_ceil(8) * 8;
+            }
+            while count >= 8 {
+                out.push(bits as u8);
+                bits >>= 8;
+                count -= 8;
+            }
+        };
+        // Window bits 16
+        push(&mut out, 0, 1, false);
+        for chunk in data.chunks(65536) {
+            // Not last, four length nibbles, length - 1, uncompressed
+            push(&mut out, 0, 3, false);
+            push(&mut out, chunk.len() as u32 - 1, 16, false);
+            push(&mut out, 1, 1, true);
+            out.extend_from_slice(chunk);
+        }
+        // Last and empty
+        push(&mut out, 3, 2, true);
+        out
+    }
+
+    /// Wrap a TTF as WOFF2 without table transforms
+    fn to_woff2(ttf: &[u8]) -> Vec<u8> {
+        let be16 = |at: usize| u16::from_be_bytes([ttf[at], ttf[at + 1]]) as usize;
+        let be32 = |at: usize| u32::from_be_bytes(ttf[at..at + 4].try_into().unwrap()) as usize;
+        let num_tables = be16(4);
+
+        let mut directory = Vec::new();
+        let mut stream = Vec::new();
+        for i in 0..num_tables {
+            let record = 12 + i * 16;
+            let tag = &ttf[record..record + 4];
+            let length = be32(record + 12);
+            // Version 3 marks glyf and loca as untransformed
+            let version = if tag == b"glyf" || tag == b"loca" { 3 << 6 } else { 0 };
+            directory.push(63 | version);
+            directory.extend_from_slice(tag);
+            let mut digits = vec![(length & 0x7F) as u8];
+            let mut rest = length >> 7;
+            while rest > 0 {
+                digits.push((rest & 0x7F) as u8 | 0x80);
+                rest >>= 7;
+            }
+            directory.extend(digits.iter().rev());
+            stream.extend_from_slice(&ttf[be32(record + 8)..be32(record + 8) + length]);
+        }
+        let compressed = brotli_stored(&stream);
+
+        let mut woff2 = b"wOF2".to_vec();
+        woff2.extend_from_slice(&ttf[0..4]);
+        woff2.extend_from_slice(&((48 + directory.len() + compressed.len()) as u32).to_be_bytes());
+        woff2.extend_from_slice(&(num_tables as u16).to_be_bytes());
+        woff2.extend_from_slice(&[0; 2]);
+        woff2.extend_from_slice(&(ttf.len() as u32).to_be_bytes());
+        woff2.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+        woff2.extend_from_slice(&[0; 24]);
+        woff2.extend_from_slice(&directory);
+        woff2.extend_from_slice(&compressed);
+        woff2
+    }
+
+    #[test]
+    fn test_decode_woff2() {
+        let woff2 = to_woff2(TEST_FONT);
+        let sfnt = sfnt_data(&woff2).unwrap();
+        let original = ab_glyph::FontRef::try_from_slice(TEST_FONT).unwrap();
+        let decoded = ab_glyph::FontRef::try_from_slice(&sfnt).unwrap();
+        use ab_glyph::Font;
+        for character in ['a', 'g', 'Q'] {
+            let outline = |font: &ab_glyph::FontRef| font.outline(font.glyph_id(character)).unwrap().curves.len();
+            assert_eq!(outline(&original), outline(&decoded));
+        }
+    }
+
+    #[test]
+    fn test_reconstruct_glyf() {
+        // Two glyphs: an empty one and a 100-unit square
+        let streams: [&[u8]; 7] = [
+            &[0, 0, 0, 1],
+            &[4],
+            &[0, 11, 1, 10],
+            &[0, 100, 100, 100, 0],
+            &[],
+            &[0; 4],
+            &[],
+        ];
+        let mut transformed = vec![0, 0, 0, 0, 0, 2, 0, 0];
+        for stream in streams {
+            transformed.extend_from_slice(&(stream.len() as u32).to_be_bytes());
+        }
+        for stream in streams {
+            transformed.extend_from_slice(stream);
+        }
+
+        let glyphs = decode::reconstruct_glyf(&transformed).unwrap();
+        let square: Vec<u8> = [
+            &[0, 1, 0, 0, 0, 0, 0, 100, 0, 100][..],
+            &[0, 3, 0, 0],
+            &[1, 1, 1, 1],
+            &[0, 0, 0, 100, 0, 0, 0xFF, 0x9C],
+            &[0, 0, 0, 0, 0, 100, 0, 0],
+            &[0, 0],
+        ].concat();
+        assert_eq!(glyphs.glyf, square);
+        assert_eq!(glyphs.loca, [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 36]);
+        assert_eq!(glyphs.x_mins, [0, 0]);
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_table_counts() {
+        // A WOFF and a WOFF2 header claiming no tables
+        let mut woff = b"wOFF\0\x01\0\0".to_vec();
+        woff.extend_from_slice(&[0; 36]);
+        assert_eq!(sfnt_data(&woff).unwrap_err(), "Invalid font table count 0");
+        let mut woff2 = b"wOF2\0\x01\0\0".to_vec();
+        woff2.extend_from_slice(&[0; 40]);
+        assert_eq!(sfnt_data(&woff2).unwrap_err(), "Invalid font table count 0");
+
+        woff[12..14].copy_from_slice(&4096u16.to_be_bytes());
+        assert_eq!(sfnt_data(&woff).unwrap_err(), "Invalid font table count 4096");
+    }
+
+    #[test]
+    fn test_decode_rejects_oversized_data() {
+        // A glyph claiming 65535 points with four flag bytes to read them from
+        let streams: [&[u8]; 7] = [&[0, 1], &[253, 0xFF, 0xFF], &[0; 4], &[], &[], &[0; 4], &[]];
+        let mut transformed = vec![0, 0, 0, 0, 0, 1, 0, 0];
+        for stream in streams {
+            transformed.extend_from_slice(&(stream.len() as u32).to_be_bytes());
+        }
+        for stream in streams {
+            transformed.extend_from_slice(stream);
+        }
+        assert!(decode::reconstruct_glyf(&transformed).is_err());
+
+        // Tables each under the limit that add up to more than it
+        let mut woff = to_woff(TEST_FONT);
+        let num_tables = u16::from_be_bytes([woff[12], woff[13]]) as usize;
+        for i in 0..num_tables {
+            let length = 44 + i * 20 + 12;
+            woff[length..length + 4].copy_from_slice(&(MAX_SFNT_SIZE as u32 / 2).to_be_bytes());
+        }
+        assert_eq!(sfnt_data(&woff).unwrap_err(), "Font is too large once decompressed");
+    }
+
+    #[test]
+    fn test_decode_rejects_truncated_data() {
+        let woff = to_woff(TEST_FONT);
+        assert!(sfnt_data(&woff[..woff.len() / 2]).is_err());
+        assert!(sfnt_data(b"wOF2\0\0\0\0").is_err());
+    }
+}
@@ -0,0 +1,323 @@
+// TypeScript types for the objects the JavaScript API takes and returns.
+//
+// Kept by hand next to the Rust types they describe (ScoringOptions in
+// src/options.rs, ScoringResult in src/lib.rs, ...); wasm.rs appends this
+// file to the generated .d.ts, and a test in wasm.rs fails when a field is
+// added to or removed from the Rust side without updating it here.
+
+/** Options controlling how a drawing is scored; every field is optional */
+export interface ScoringOptions {
+  accuracy_tolerance?: number
+  accuracy_falloff?: number
+  mode?: ScoringMode
+  similarity_method?: SimilarityMethod
+  /** Packs and handles keep the algorithm they were built with */
+  thinning?: ThinningAlgorithm
+  weights?: ScoreWeights
+  min_skeleton_coverage?: number
+  /** Per-character overrides of `min_skeleton_coverage` */
+  min_skeleton_coverage_overrides?: Record<string, number>
+  stroke_transform?: StrokeTransform | null
+  stroke_width?: number
+  rasterize_strokes?: boolean
+  stroke_smoothing?: number
+  /** Pixels, 0-8; larger values are treated as 8 */
+  edge_smoothing?: number
+  auto_contrast?: boolean
+  speck_area?: number
+  exclude_stray_marks?: boolean
+  threshold?: ThresholdMethod
+  /** RGB color showing through transparent parts of the drawing */
+  background?: [number, number, number]
+  ink?: InkColor
+  photo?: boolean
+  /** Corners of the worksheet's box in image pixels, clockwise from the top left */
+  photo_corners?: [[number, number], [number, number], [number, number], [number, number]] | null
+  duration_ms?: number | null
+  time?: TimeOptions
+  practice?: PracticeOptions | null
+  stars?: StarThresholds
+  extra_strokes_allowed?: number
+  /** The age bundle supplies every field not given */
+  age_band?: AgeBand | null
+  scaffold?: ScaffoldOptions
+  /** Variable font axis values by tag, e.g. `{ wght: 600 }` */
+  font_axes?: Record<string, number>
+  /** Handwriting style, one of `handwriting_styles()` */
+  style?: string | null
+  guide?: GuideOptions
+  reference_font_size?: number
+  reference_stroke_width?: number | null
+  normalize_padding?: number
+  reference_supersampling?: number
+  frame_size?: number
+  pyramid?: boolean
+  input_limits?: InputLimits
+  with_timings?: boolean
+}
+
+export type ScoringMode = 'freehand' | 'tracing'
+export type SimilarityMethod = 'skeleton' | 'ssim' | 'blended' | 'shape_context'
+export type ThinningAlgorithm = 'zhang_suen' | 'guo_hall'
+export type ThresholdMethod = 'otsu' | 'fixed'
+export type InkColor = 'any' | 'dark'
+export type AgeBand = '3-4' | '5-6' | '7-8'
+
+export interface ScoreWeights {
+  coverage?: number
+  accuracy?: number
+  similarity?: number
+  structure?: number
+  legibility?: number
+}
+
+export interface StarThresholds {
+  five?: number
+  four?: number
+  three?: number
+  two?: number
+}
+
+export interface TimeOptions {
+  min_duration_ms?: number
+  max_penalty?: number
+}
+
+export interface StrokeTransform {
+  scale?: number
+  /** Degrees, clockwise on screen */
+  rotation?: number
+  offset_x?: number
+  offset_y?: number
+}
+
+export interface PracticeOptions {
+  seed?: number
+  size_jitter?: number
+  offset_jitter?: number
+  /** Alternative glyphs per character, e.g. `{ a: ['ɑ'] }` */
+  variants?: Record<string, string[]>
+}
+
+export interface ScaffoldOptions {
+  /** Scores of the previous attempts at this letter, oldest first */
+  recent_scores?: number[]
+  low_score?: number | null
+  attempts_per_level?: number
+}
+
+export interface GuideOptions {
+  line?: 'solid' | 'dotted' | 'dashed'
+  size?: number
+  /** At least a quarter of `size` and 1 pixel, as is `dash_length` */
+  spacing?: number
+  dash_length?: number
+  ruling?: RulingOptions | null
+  anchor?: ReferenceAnchor
+}
+
+export type ReferenceAnchor =
+  | 'center' | 'top' | 'bottom' | 'left' | 'right'
+  | 'top_left' | 'top_right' | 'bottom_left' | 'bottom_right'
+
+export interface RulingOptions {
+  topline_color?: string
+  midline_color?: string
+  baseline_color?: string
+  dashed_midline?: boolean
+  thickness?: number
+}
+
+export interface InputLimits {
+  max_width?: number
+  max_height?: number
+  max_bytes?: number
+  /** Work thinning the drawing may do */
+  thinning?: ThinningLimits
+}
+
+export interface ThinningLimits {
+  max_iterations?: number
+  /** Pixels examined, summed over every pass */
+  max_pixel_visits?: number
+}
+
+/** A sampled pointer position, in the image's pixels unless `stroke_transform` maps it */
+export interface StrokePoint {
+  x: number
+  y: number
+  /** Timestamp in milliseconds */
+  t?: number | null
+  /** Pen pressure in [0, 1] */
+  pressure?: number | null
+}
+
+export interface Stroke {
+  points: StrokePoint[]
+  /** Eraser stroke */
+  erase?: boolean
+  width?: number | null
+}
+
+export type StrokeEvent =
+  | ({ type: 'stroke' } & Stroke)
+  | { type: 'undo' }
+  | { type: 'redo' }
+  | { type: 'clear' }
+
+/** A scoring result as a plain object, from `toJSON()` or `score_drawing_js` */
+export interface ScoringResult {
+  score: number
+  stars: number
+  feedback: string
+  feedback_code: string | null
+  scribble: boolean
+  suspected_trace: boolean
+  /** The letter ran off an edge of the canvas */
+  clipped: boolean
+  coverage: number
+  accuracy: number
+  similarity: number
+  skeleton_coverage: number
+  structure: number
+  legibility: number
+  moment_similarity: number
+  hausdorff_95: number
+  worst_region: string | null
+  gaps: StrokeGap[]
+  loop_closed: boolean[]
+  dot_present: boolean | null
+  crossbar_present: boolean | null
+  tail_present: boolean | null
+  order_score: number | null
+  expected_strokes: number | null
+  actual_strokes: number | null
+  stroke_directions: StrokeDirection[]
+  duration_ms: number | null
+  time_penalty: number
+  pressure_consistency: number | null
+  reference_glyph: string | null
+  contrast_gain: number
+  stray_marks: number
+  drawing_hash: string
+  hints: string[]
+  scaffold: ScaffoldLevel
+  low_score_streak: number
+  /** How far the score can be trusted, 0-100 */
+  confidence: number
+  explanation: ScoreExplanation
+  /** Set when scored with `with_timings` */
+  timings: StageTimings | null
+}
+
+/** How the score was put together; component points minus penalties give the score before caps */
+export interface ScoreExplanation {
+  coverage: ScoreContribution
+  accuracy: ScoreContribution
+  similarity: ScoreContribution
+  structure: ScoreContribution
+  legibility: ScoreContribution
+  weighted_score: number
+  time_penalty: number
+  stray_mark_penalty: number
+  missing_feature_penalty: number
+  caps: ScoreCap[]
+  stars: Required<StarThresholds>
+  min_skeleton_coverage: number
+}
+
+export interface ScoreContribution {
+  /** Component score, 0-100 */
+  value: number
+  /** Fraction of all the weights */
+  weight: number
+  points: number
+}
+
+export interface ScoreCap {
+  reason: 'min_skeleton_coverage' | 'canvas_filled' | 'scribble'
+  max_score: number
+}
+
+/**
+ * Milliseconds spent in each stage; skeletonization and distance transforms
+ * count wherever they run, so they overlap the reference and metrics stages
+ */
+export interface StageTimings {
+  decode_ms: number
+  reference_ms: number
+  skeleton_ms: number
+  distance_transform_ms: number
+  metrics_ms: number
+  total_ms: number
+}
+
+/** `score_drawing_js`'s result, with the reference the drawing was compared against */
+export interface ScoredDrawing extends ScoringResult {
+  /** PNG bytes */
+  reference_image: Uint8Array
+}
+
+/** A break between parts of the drawing that the reference joins */
+export interface StrokeGap {
+  /** Midpoint as fractions (0-1) of the normalized frame */
+  x: number
+  y: number
+  length: number
+  region: string
+}
+
+export interface StrokeDirection {
+  stroke: number
+  template_stroke: number | null
+  start_ok: boolean
+  direction_ok: boolean
+}
+
+export type ScaffoldLevel = 'none' | 'show_animation' | 'show_arrows' | 'tracing' | 'shrink_canvas'
+
+/** Whether a drawing can be scored, from `check_drawing_quality` */
+export interface DrawingQuality {
+  scoreable: boolean
+  /** Most serious first */
+  issues: DrawingIssue[]
+  /** Prompt for the learner about the first issue */
+  message?: string | null
+  ink_pixels: number
+}
+
+export type DrawingIssue = 'empty' | 'too_little_ink' | 'faint' | 'cut_off'
+
+/** Centerline of a drawing from `extract_skeleton_graph`, in the image's pixels */
+export interface DrawingGraph {
+  nodes: GraphNode[]
+  edges: GraphEdge[]
+  components: number
+  loops: number
+}
+
+export interface GraphNode {
+  x: number
+  y: number
+  /** `loop` anchors a closed stroke with no other node on it */
+  kind: 'endpoint' | 'junction' | 'loop'
+}
+
+export interface GraphEdge {
+  /** Indices into `nodes`; not the direction the stroke was drawn in */
+  from: number
+  to: number
+  points: [number, number][]
+  length: number
+}
+
+/** Verbosity for `set_log_level`, in builds with the `tracing` feature */
+export type LogLevel = 'off' | 'info' | 'debug' | 'trace'
+
+/** A built-in age bundle, from `age_band_bundle` */
+export interface AgeBandBundle {
+  age_band: AgeBand
+  label: string
+  rationale: string
+  options: ScoringOptions
+}
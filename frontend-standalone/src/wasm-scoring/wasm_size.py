@@ -0,0 +1,95 @@
+#!/usr/bin/env python3
+"""Measure the scoring module's WebAssembly size per cargo feature.
+
+Builds the core (`--no-default-features --features wasm`) and the core plus
+each optional feature for wasm32-unknown-unknown in release, and reports
+what each feature adds. Sizes leave out custom sections (symbol names and
+wasm-bindgen's descriptors), which the shipped module doesn't carry, and
+are before wasm-opt, which takes a little more off. With `--check` the
+script fails when the core or a feature is over its budget.
+
+    python3 wasm_size.py [--check]
+"""
+
+import gzip
+import os
+import subprocess
+import sys
+from pathlib import Path
+
+CRATE = Path(__file__).resolve().parent
+MODULE = CRATE / "target/wasm32-unknown-unknown/release/learning_letters_scoring.wasm"
+
+# Budgets in KB: the core's size, and what each feature may add to it.
+# Each is the measured size with some headroom for toolchain drift; raise
+# one only with a note in the PR saying why
+CORE_BUDGET = 2700
+FEATURE_BUDGETS = {
+    "console_error_panic_hook": 5,
+    "stroke-templates": 85,
+    "coaching": 40,
+    "session-analytics": 25,
+    "woff": 260,
+    "shapes": 45,
+    "guides": 35,
+}
+
+
+def build(features):
+    env = dict(os.environ, CARGO_PROFILE_RELEASE_STRIP="true")
+    subprocess.run(
+        ["cargo", "build", "--release", "--target", "wasm32-unknown-unknown",
+         "--no-default-features", "--features", ",".join(["wasm", *features])],
+        cwd=CRATE, env=env, check=True,
+    )
+    return MODULE.read_bytes()
+
+
+def leb128(data, at):
+    value = shift = 0
+    while True:
+        byte = data[at]
+        at += 1
+        value |= (byte & 0x7F) << shift
+        shift += 7
+        if byte < 0x80:
+            return value, at
+
+
+def without_custom_sections(module):
+    """The module with its custom sections (id 0) dropped"""
+    kept, at = bytearray(module[:8]), 8
+    while at < len(module):
+        length, start = leb128(module, at + 1)
+        if module[at] != 0:
+            kept += module[at:start + length]
+        at = start + length
+    return bytes(kept)
+
+
+def measure(features):
+    module = without_custom_sections(build(features))
+    return len(module), len(gzip.compress(module, 9))
+
+
+def main():
+    check = "--check" in sys.argv[1:]
+    core, core_gzip = measure([])
+    rows = [("(core)", core, core_gzip, CORE_BUDGET)]
+    for feature, budget in FEATURE_BUDGETS.items():
+        size, size_gzip = measure([feature])
+        rows.append((feature, size - core, size_gzip - core_gzip, budget))
+
+    over = []
+    print(f"{'':26}{'KB':>8}{'gzip KB':>10}{'budget':>8}")
+    for name, size, size_gzip, budget in rows:
+        sign = "" if name == "(core)" else "+"
+        print(f"{name:26}{sign + format(size / 1024, '.0f'):>8}{sign + format(size_gzip / 1024, '.0f'):>10}{budget:>8}")
+        if size > budget * 1024:
+            over.append(name)
+    if check and over:
+        sys.exit(f"Over budget: {', '.join(over)}")
+
+
+if __name__ == "__main__":
+    main()